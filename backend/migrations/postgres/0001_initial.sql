@@ -0,0 +1,52 @@
+-- PostgreSQL版の初期スキーマ（SQLite版 0001_initial.sql と同じ構造）
+-- タイムスタンプはSQLiteと同じ 'YYYY-MM-DD HH:MM:SS' 形式のテキストで保持する
+CREATE OR REPLACE FUNCTION md_wiki_timestamp() RETURNS TEXT AS $$
+    SELECT to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')
+$$ LANGUAGE SQL STABLE;
+
+CREATE OR REPLACE FUNCTION md_wiki_touch_updated_at() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.updated_at := md_wiki_timestamp();
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TABLE IF NOT EXISTS users (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL UNIQUE,
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL CONSTRAINT users_role_check CHECK (role IN ('Admin', 'Editor', 'Viewer')),
+    created_at TEXT DEFAULT md_wiki_timestamp(),
+    updated_at TEXT DEFAULT md_wiki_timestamp()
+);
+
+CREATE TABLE IF NOT EXISTS documents (
+    id BIGSERIAL PRIMARY KEY,
+    title TEXT NOT NULL,
+    path TEXT NOT NULL UNIQUE,
+    created_by BIGINT NOT NULL,
+    created_at TEXT DEFAULT md_wiki_timestamp(),
+    updated_at TEXT DEFAULT md_wiki_timestamp(),
+    CONSTRAINT documents_created_by_fkey FOREIGN KEY (created_by) REFERENCES users(id)
+);
+
+CREATE TABLE IF NOT EXISTS tags (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS document_tags (
+    document_id BIGINT NOT NULL,
+    tag_id BIGINT NOT NULL,
+    PRIMARY KEY (document_id, tag_id),
+    FOREIGN KEY (document_id) REFERENCES documents(id) ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE TRIGGER update_user_timestamp
+    BEFORE UPDATE ON users
+    FOR EACH ROW EXECUTE FUNCTION md_wiki_touch_updated_at();
+
+CREATE TRIGGER update_document_timestamp
+    BEFORE UPDATE ON documents
+    FOR EACH ROW EXECUTE FUNCTION md_wiki_touch_updated_at();
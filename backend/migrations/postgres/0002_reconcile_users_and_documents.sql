@@ -0,0 +1,13 @@
+-- Users table: lowercase roles to match `Role` serialization and add email
+ALTER TABLE users DROP CONSTRAINT users_role_check;
+UPDATE users SET role = lower(role);
+ALTER TABLE users ADD CONSTRAINT users_role_check CHECK (role IN ('admin', 'editor', 'viewer'));
+ALTER TABLE users ADD COLUMN email TEXT UNIQUE;
+
+-- Documents table: documents are keyed by filename, title and author are optional
+ALTER TABLE documents RENAME COLUMN path TO filename;
+ALTER TABLE documents ALTER COLUMN title DROP NOT NULL;
+ALTER TABLE documents ALTER COLUMN created_by DROP NOT NULL;
+ALTER TABLE documents DROP CONSTRAINT documents_created_by_fkey;
+ALTER TABLE documents ADD CONSTRAINT documents_created_by_fkey
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL;
@@ -0,0 +1,9 @@
+-- Tags table: descriptions, colors, hierarchy (`infra/k8s` under `infra`) and synonyms
+ALTER TABLE tags
+    ADD COLUMN description TEXT,
+    ADD COLUMN color TEXT,
+    ADD COLUMN parent_id BIGINT REFERENCES tags(id),
+    ADD COLUMN synonym_of BIGINT REFERENCES tags(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS idx_tags_parent_id ON tags(parent_id);
+CREATE INDEX IF NOT EXISTS idx_tags_synonym_of ON tags(synonym_of);
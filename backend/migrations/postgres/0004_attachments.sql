@@ -0,0 +1,16 @@
+-- Attachments: file contents live content-addressed in the git repository (attachments/<hash>),
+-- rows record which document they belong to and how they were uploaded
+CREATE TABLE IF NOT EXISTS attachments (
+    id BIGSERIAL PRIMARY KEY,
+    document_filename TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    uploaded_by BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    created_at TEXT DEFAULT md_wiki_timestamp(),
+    UNIQUE (document_filename, hash, filename)
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_document ON attachments(document_filename);
+CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(hash);
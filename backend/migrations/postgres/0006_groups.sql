@@ -0,0 +1,22 @@
+-- User groups: a group can grant a role to all of its members and be named in document ACLs
+CREATE TABLE IF NOT EXISTS groups (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    role TEXT CHECK (role IN ('viewer', 'editor', 'admin')),
+    created_at TEXT DEFAULT md_wiki_timestamp(),
+    updated_at TEXT DEFAULT md_wiki_timestamp()
+);
+
+CREATE TABLE IF NOT EXISTS group_members (
+    group_id BIGINT NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT DEFAULT md_wiki_timestamp(),
+    PRIMARY KEY (group_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_group_members_user ON group_members(user_id);
+
+CREATE TRIGGER update_group_timestamp
+    BEFORE UPDATE ON groups
+    FOR EACH ROW EXECUTE FUNCTION md_wiki_touch_updated_at();
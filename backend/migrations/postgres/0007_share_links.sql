@@ -0,0 +1,15 @@
+-- Share links: signed, expiring links to a single document (optionally a fixed version)
+-- the link token itself is not stored; rows exist so links can be listed and revoked
+CREATE TABLE IF NOT EXISTS share_links (
+    id BIGSERIAL PRIMARY KEY,
+    document_filename TEXT NOT NULL,
+    commit_id TEXT,
+    permission TEXT NOT NULL CHECK (permission IN ('read', 'write')),
+    created_by BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TEXT NOT NULL,
+    revoked_at TEXT,
+    created_at TEXT DEFAULT md_wiki_timestamp()
+);
+
+CREATE INDEX IF NOT EXISTS idx_share_links_document ON share_links(document_filename);
+CREATE INDEX IF NOT EXISTS idx_share_links_created_by ON share_links(created_by);
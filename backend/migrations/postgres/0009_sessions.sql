@@ -0,0 +1,14 @@
+-- Login sessions: each session holds the hash of its current refresh token
+-- the token is replaced on every refresh; revoked or expired sessions cannot be refreshed
+CREATE TABLE IF NOT EXISTS sessions (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    refresh_token_hash TEXT NOT NULL UNIQUE,
+    user_agent TEXT,
+    expires_at TEXT NOT NULL,
+    last_used_at TEXT,
+    revoked_at TEXT,
+    created_at TEXT DEFAULT md_wiki_timestamp()
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions(user_id);
@@ -0,0 +1,12 @@
+-- Accounts at external identity providers (OpenID Connect) linked to local users
+-- users created through single sign-on have an empty password_hash and cannot log in with a password
+CREATE TABLE IF NOT EXISTS user_identities (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    issuer TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    created_at TEXT DEFAULT md_wiki_timestamp(),
+    UNIQUE (issuer, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_user ON user_identities(user_id);
@@ -0,0 +1,17 @@
+-- Optional TOTP two-factor authentication
+-- totp_secret is set when enrollment starts and totp_enabled_at once the first code is confirmed
+-- totp_last_step is the last accepted time step, so that a code cannot be used twice
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_secret TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_enabled_at TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS totp_last_step BIGINT;
+
+-- One-time recovery codes for when the authenticator is lost (only hashes are stored)
+CREATE TABLE IF NOT EXISTS recovery_codes (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TEXT,
+    created_at TEXT DEFAULT md_wiki_timestamp()
+);
+
+CREATE INDEX IF NOT EXISTS idx_recovery_codes_user ON recovery_codes(user_id);
@@ -0,0 +1,23 @@
+-- Failed login attempts, kept as an audit record
+CREATE TABLE IF NOT EXISTS login_failures (
+    id BIGSERIAL PRIMARY KEY,
+    username TEXT NOT NULL,
+    ip_address TEXT,
+    user_agent TEXT,
+    reason TEXT NOT NULL,
+    created_at TEXT DEFAULT md_wiki_timestamp()
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_failures_username ON login_failures(username);
+CREATE INDEX IF NOT EXISTS idx_login_failures_ip ON login_failures(ip_address);
+
+-- Consecutive failures per username or IP address, and until when further attempts are refused
+-- rows are not tied to users so that unknown usernames are throttled the same way
+CREATE TABLE IF NOT EXISTS login_throttles (
+    scope TEXT NOT NULL CHECK (scope IN ('username', 'ip')),
+    identifier TEXT NOT NULL,
+    failures BIGINT NOT NULL DEFAULT 0,
+    locked_until TEXT NOT NULL,
+    last_failure_at TEXT NOT NULL,
+    PRIMARY KEY (scope, identifier)
+);
@@ -0,0 +1,16 @@
+-- Invitations: an admin invites someone by username and/or email with a role
+-- the single-use token itself is not stored, only its hash
+CREATE TABLE IF NOT EXISTS invitations (
+    id BIGSERIAL PRIMARY KEY,
+    token_hash TEXT NOT NULL UNIQUE,
+    username TEXT,
+    email TEXT,
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor', 'admin')),
+    created_by BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TEXT NOT NULL,
+    accepted_at TEXT,
+    -- the user created when the invitation was accepted
+    user_id BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    revoked_at TEXT,
+    created_at TEXT DEFAULT md_wiki_timestamp()
+);
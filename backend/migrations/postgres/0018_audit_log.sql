@@ -0,0 +1,36 @@
+-- Audit log of security and content events (logins, role and permission changes, document changes)
+-- actor is a snapshot of the username so entries stay readable after the user is deleted,
+-- and rows are not tied to users for the same reason
+CREATE TABLE IF NOT EXISTS audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    action TEXT NOT NULL,
+    actor_id BIGINT,
+    actor TEXT,
+    ip_address TEXT,
+    user_agent TEXT,
+    target_type TEXT NOT NULL,
+    target TEXT NOT NULL,
+    before_summary TEXT,
+    after_summary TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_action ON audit_log(action);
+CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON audit_log(actor);
+CREATE INDEX IF NOT EXISTS idx_audit_log_target ON audit_log(target_type, target);
+CREATE INDEX IF NOT EXISTS idx_audit_log_created_at ON audit_log(created_at);
+
+-- The log is append-only: entries can never be changed or removed
+CREATE OR REPLACE FUNCTION md_wiki_audit_log_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_no_modify
+    BEFORE UPDATE OR DELETE ON audit_log
+    FOR EACH ROW EXECUTE FUNCTION md_wiki_audit_log_append_only();
+
+CREATE TRIGGER audit_log_no_truncate
+    BEFORE TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION md_wiki_audit_log_append_only();
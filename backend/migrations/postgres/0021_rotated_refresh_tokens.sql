@@ -0,0 +1,10 @@
+-- Refresh tokens that have already been replaced by a newer one
+-- presenting one of these again means the token was copied, so the whole session is revoked
+-- rows are no longer needed once the token they replaced would have expired
+CREATE TABLE IF NOT EXISTS rotated_refresh_tokens (
+    token_hash TEXT PRIMARY KEY,
+    session_id BIGINT NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    expires_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_rotated_refresh_tokens_expires_at ON rotated_refresh_tokens(expires_at);
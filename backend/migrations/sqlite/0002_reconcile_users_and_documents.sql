@@ -0,0 +1,47 @@
+-- Users table: lowercase roles to match `Role` serialization and add email
+CREATE TABLE users_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    username TEXT NOT NULL UNIQUE,
+    email TEXT UNIQUE,
+    password_hash TEXT NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('admin', 'editor', 'viewer')),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+INSERT INTO users_new (id, username, password_hash, role, created_at, updated_at)
+    SELECT id, username, password_hash, lower(role), created_at, updated_at FROM users;
+
+DROP TABLE users;
+ALTER TABLE users_new RENAME TO users;
+
+CREATE TRIGGER IF NOT EXISTS update_user_timestamp
+    AFTER UPDATE ON users
+BEGIN
+    UPDATE users SET updated_at = CURRENT_TIMESTAMP
+    WHERE id = NEW.id;
+END;
+
+-- Documents table: documents are keyed by filename, title and author are optional
+CREATE TABLE documents_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    filename TEXT NOT NULL UNIQUE,
+    title TEXT,
+    created_by INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+INSERT INTO documents_new (id, filename, title, created_by, created_at, updated_at)
+    SELECT id, path, title, created_by, created_at, updated_at FROM documents;
+
+DROP TABLE documents;
+ALTER TABLE documents_new RENAME TO documents;
+
+CREATE TRIGGER IF NOT EXISTS update_document_timestamp
+    AFTER UPDATE ON documents
+BEGIN
+    UPDATE documents SET updated_at = CURRENT_TIMESTAMP
+    WHERE id = NEW.id;
+END;
@@ -0,0 +1,20 @@
+-- Tags table: descriptions, colors, hierarchy (`infra/k8s` under `infra`) and synonyms
+CREATE TABLE tags_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    description TEXT,
+    color TEXT,
+    parent_id INTEGER,
+    synonym_of INTEGER,
+    FOREIGN KEY (parent_id) REFERENCES tags(id),
+    FOREIGN KEY (synonym_of) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+INSERT INTO tags_new (id, name)
+    SELECT id, name FROM tags;
+
+DROP TABLE tags;
+ALTER TABLE tags_new RENAME TO tags;
+
+CREATE INDEX IF NOT EXISTS idx_tags_parent_id ON tags(parent_id);
+CREATE INDEX IF NOT EXISTS idx_tags_synonym_of ON tags(synonym_of);
@@ -0,0 +1,17 @@
+-- Attachments: file contents live content-addressed in the git repository (attachments/<hash>),
+-- rows record which document they belong to and how they were uploaded
+CREATE TABLE IF NOT EXISTS attachments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    document_filename TEXT NOT NULL,
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    hash TEXT NOT NULL,
+    uploaded_by INTEGER,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (document_filename, hash, filename),
+    FOREIGN KEY (uploaded_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_document ON attachments(document_filename);
+CREATE INDEX IF NOT EXISTS idx_attachments_hash ON attachments(hash);
@@ -0,0 +1,15 @@
+-- Access control lists for documents and folders
+-- path is a document name (guide/setup) or a folder name with a trailing slash (guide/);
+-- the nearest path that has entries decides access, paths without any entries are open to every user
+CREATE TABLE IF NOT EXISTS acl_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT NOT NULL,
+    principal_type TEXT NOT NULL CHECK (principal_type IN ('user', 'group')),
+    principal_id INTEGER NOT NULL,
+    permission TEXT NOT NULL CHECK (permission IN ('read', 'write', 'admin')),
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (path, principal_type, principal_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_acl_entries_path ON acl_entries(path);
+CREATE INDEX IF NOT EXISTS idx_acl_entries_principal ON acl_entries(principal_type, principal_id);
@@ -0,0 +1,16 @@
+-- Share links: signed, expiring links to a single document (optionally a fixed version)
+-- the link token itself is not stored; rows exist so links can be listed and revoked
+CREATE TABLE IF NOT EXISTS share_links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    document_filename TEXT NOT NULL,
+    commit_id TEXT,
+    permission TEXT NOT NULL CHECK (permission IN ('read', 'write')),
+    created_by INTEGER,
+    expires_at TEXT NOT NULL,
+    revoked_at TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_share_links_document ON share_links(document_filename);
+CREATE INDEX IF NOT EXISTS idx_share_links_created_by ON share_links(created_by);
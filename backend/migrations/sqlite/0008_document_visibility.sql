@@ -0,0 +1,3 @@
+-- Per-document visibility for anonymous readers
+-- NULL follows the wiki-wide WIKI_MODE setting
+ALTER TABLE documents ADD COLUMN visibility TEXT CHECK (visibility IN ('public', 'private'));
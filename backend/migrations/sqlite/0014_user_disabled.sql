@@ -0,0 +1,2 @@
+-- Disabled accounts cannot log in but keep their data (documents, attachments, share links)
+ALTER TABLE users ADD COLUMN disabled_at TEXT;
@@ -0,0 +1,7 @@
+-- One-time token for creating the first admin on a fresh database
+-- tokens are issued at startup and by the `setup-token` command; all are removed once setup completes
+CREATE TABLE IF NOT EXISTS setup_tokens (
+    token_hash TEXT PRIMARY KEY,
+    expires_at TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
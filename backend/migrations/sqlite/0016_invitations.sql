@@ -0,0 +1,18 @@
+-- Invitations: an admin invites someone by username and/or email with a role
+-- the single-use token itself is not stored, only its hash
+CREATE TABLE IF NOT EXISTS invitations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    token_hash TEXT NOT NULL UNIQUE,
+    username TEXT,
+    email TEXT,
+    role TEXT NOT NULL CHECK (role IN ('viewer', 'editor', 'admin')),
+    created_by INTEGER,
+    expires_at TEXT NOT NULL,
+    accepted_at TEXT,
+    -- the user created when the invitation was accepted
+    user_id INTEGER,
+    revoked_at TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (created_by) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL
+);
@@ -0,0 +1,5 @@
+-- Indexes for the keyset-paginated document list
+-- Each index ends with filename, which breaks ties between equal sort values
+CREATE INDEX IF NOT EXISTS idx_documents_updated_at ON documents (updated_at, filename);
+CREATE INDEX IF NOT EXISTS idx_documents_created_at ON documents (created_at, filename);
+CREATE INDEX IF NOT EXISTS idx_documents_title ON documents (LOWER(COALESCE(title, filename)), filename);
@@ -0,0 +1,11 @@
+-- Second-step login tokens that have already been exchanged for a session
+-- each token carries a random nonce, and a nonce can only be recorded once
+-- rows are no longer needed once the token has expired
+CREATE TABLE IF NOT EXISTS used_mfa_tokens (
+    nonce TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    expires_at TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_used_mfa_tokens_expires_at ON used_mfa_tokens(expires_at);
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::auth::Claims;
+use crate::db::{AclEntry, DbManager};
+use crate::error::AppError;
+use crate::models::acl::{Permission, PrincipalType, Visibility};
+use crate::models::user::{Role, User};
+use crate::AppState;
+
+// ACL を評価する対象のユーザー
+#[derive(Debug, Clone)]
+pub struct Subject {
+    // 未ログインなら None
+    pub user_id: Option<i64>,
+    pub group_ids: Vec<i64>,
+    // ACLが設定されていないドキュメントの権限はロールで決まる
+    // 管理者はACLに関係なくすべてのドキュメントを操作できる
+    pub role: Role,
+}
+
+impl Subject {
+    // トークンのユーザーと、その所属グループから作る
+    pub async fn load(db: Option<&DbManager>, claims: Option<&Claims>) -> Result<Self, AppError> {
+        let claims = match claims {
+            Some(claims) => claims,
+            None => return Ok(Self::anonymous()),
+        };
+
+        let group_ids = match db {
+            Some(db) => db.list_user_groups(claims.sub).await?.into_iter().map(|group| group.id).collect(),
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            user_id: Some(claims.sub),
+            group_ids,
+            role: Role::from_str(&claims.role).unwrap_or(Role::Viewer),
+        })
+    }
+
+    // ログインしていないユーザーの権限を確かめる場合（共有リンクの作成者など）
+    // ロールは現在のロール（グループを含む）を使う
+    pub async fn for_user(db: &DbManager, user: &User) -> Result<Self, AppError> {
+        let group_ids = db.list_user_groups(user.id).await?.into_iter().map(|group| group.id).collect();
+        Ok(Self {
+            user_id: Some(user.id),
+            group_ids,
+            role: db.effective_role(user.id, user.role).await?,
+        })
+    }
+
+    pub fn anonymous() -> Self {
+        Self {
+            user_id: None,
+            group_ids: Vec::new(),
+            role: Role::Viewer,
+        }
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.user_id.is_none()
+    }
+
+    fn matches(&self, entry: &AclEntry) -> bool {
+        match entry.principal_type {
+            PrincipalType::User => self.user_id == Some(entry.principal_id),
+            PrincipalType::Group => self.group_ids.contains(&entry.principal_id),
+        }
+    }
+}
+
+// フォルダのACLのパス（`guide/setup` → `guide/setup/`）
+pub fn folder_path(folder: &str) -> String {
+    format!("{}/", folder.trim_matches('/'))
+}
+
+// ACLを探すパスを近い順に並べる
+// ドキュメント `a/b/c` なら `a/b/c`, `a/b/`, `a/`、フォルダ `a/b/` なら `a/b/`, `a/`
+pub fn lookup_paths(path: &str) -> Vec<String> {
+    let mut paths = vec![path.to_string()];
+    let mut rest = path.trim_end_matches('/');
+    while let Some(pos) = rest.rfind('/') {
+        rest = &rest[..pos];
+        paths.push(format!("{}/", rest));
+    }
+    paths
+}
+
+// 読み込んだACLをパスごとにまとめたもの
+#[derive(Debug, Default)]
+pub struct AccessControl {
+    entries: HashMap<String, Vec<AclEntry>>,
+    // 公開設定を明示したドキュメントと、それ以外に適用するウィキ全体の設定
+    visibility: HashMap<String, Visibility>,
+    wiki_mode: Visibility,
+}
+
+impl AccessControl {
+    pub fn new(entries: Vec<AclEntry>) -> Self {
+        let mut by_path: HashMap<String, Vec<AclEntry>> = HashMap::new();
+        for entry in entries {
+            by_path.entry(entry.path.clone()).or_default().push(entry);
+        }
+        Self {
+            entries: by_path,
+            ..Self::default()
+        }
+    }
+
+    // 未ログインのユーザーの判定に使う公開設定を付ける
+    pub fn with_visibility(mut self, wiki_mode: Visibility, visibility: HashMap<String, Visibility>) -> Self {
+        self.wiki_mode = wiki_mode;
+        self.visibility = visibility;
+        self
+    }
+
+    pub fn visibility(&self, name: &str) -> Visibility {
+        self.visibility.get(name).copied().unwrap_or(self.wiki_mode)
+    }
+
+    // どのパスにもACLが設定されていないか
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // 権限を決めるACLが設定されたパス（自身か最も近い上位のフォルダ）
+    pub fn effective_path(&self, path: &str) -> Option<String> {
+        lookup_paths(path).into_iter().find(|p| self.entries.contains_key(p))
+    }
+
+    // ドキュメント（またはフォルダ）に対するユーザーの権限
+    // ACLが設定された最も近いパスの設定だけで決まる
+    // どこにも設定がなければ、編集者は書き込みまで、閲覧者は閲覧だけができる（ACLの変更は管理者のみ）
+    // 未ログインのユーザーは、ACLで制限されていない公開ドキュメントを読むことだけができる
+    pub fn permission(&self, subject: &Subject, path: &str) -> Option<Permission> {
+        if subject.is_admin() {
+            return Some(Permission::Admin);
+        }
+        if subject.is_anonymous() {
+            let public = self.effective_path(path).is_none() && self.visibility(path) == Visibility::Public;
+            return public.then_some(Permission::Read);
+        }
+
+        match self.effective_path(path) {
+            Some(effective) => self.entries[&effective]
+                .iter()
+                .filter(|entry| subject.matches(entry))
+                .map(|entry| entry.permission)
+                .max(),
+            None if subject.role.can_edit() => Some(Permission::Write),
+            None => Some(Permission::Read),
+        }
+    }
+
+    pub fn allows(&self, subject: &Subject, path: &str, required: Permission) -> bool {
+        self.permission(subject, path).is_some_and(|permission| permission >= required)
+    }
+}
+
+// パスの権限判定に必要なACLだけを読み込む
+pub async fn load(db: &DbManager, path: &str) -> Result<AccessControl, AppError> {
+    Ok(AccessControl::new(db.get_acl_entries(&lookup_paths(path)).await?))
+}
+
+// すべてのACLと公開設定を読み込む（一覧・検索用）
+// データベースがない場合はACLによる制限をしない
+pub async fn load_all(state: &AppState) -> Result<AccessControl, AppError> {
+    match &state.db_manager {
+        Some(db) => Ok(AccessControl::new(db.list_acl_entries().await?)
+            .with_visibility(state.config.wiki_mode, db.list_document_visibility().await?)),
+        None => Ok(AccessControl::default().with_visibility(state.config.wiki_mode, HashMap::new())),
+    }
+}
+
+// ドキュメントに対して必要な権限があるか確認する
+// 未ログインで権限がない場合はログインを求める
+pub async fn authorize(
+    state: &AppState,
+    claims: Option<&Claims>,
+    name: &str,
+    required: Permission,
+) -> Result<(), AppError> {
+    let db = state.db_manager.as_ref();
+    let mut access = match db {
+        Some(db) => load(db, name).await?,
+        None => AccessControl::default(),
+    };
+
+    if claims.is_none() {
+        let mut visibility = HashMap::new();
+        if let Some(db) = db {
+            if let Some(v) = db.get_document_visibility(name).await? {
+                visibility.insert(name.to_string(), v);
+            }
+        }
+        access = access.with_visibility(state.config.wiki_mode, visibility);
+    }
+
+    if access.allows(&Subject::load(db, claims).await?, name, required) {
+        Ok(())
+    } else if claims.is_none() {
+        Err(AppError::Auth("Authentication required".to_string()))
+    } else {
+        Err(AppError::Forbidden(format!("{} permission is required for {}", required, name)))
+    }
+}
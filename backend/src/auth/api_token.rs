@@ -0,0 +1,47 @@
+use crate::auth::session::{generate_secret_token, hash_secret_token};
+use crate::auth::Claims;
+use crate::error::AppError;
+use crate::AppState;
+
+// 個人用APIトークンの接頭辞（ログインのJWTと区別する）
+pub const API_TOKEN_PREFIX: &str = "mdw_";
+
+pub fn generate_api_token() -> String {
+    format!("{}{}", API_TOKEN_PREFIX, generate_secret_token())
+}
+
+pub fn is_api_token(token: &str) -> bool {
+    token.starts_with(API_TOKEN_PREFIX)
+}
+
+// APIトークンを検証し、ログインと同じ形の Claims にする
+// ロールはユーザーの現在のロール（グループを含む）とトークンのスコープの弱い方になる
+pub async fn verify_api_token(state: &AppState, token: &str) -> Result<Claims, AppError> {
+    let db = state
+        .db_manager
+        .as_ref()
+        .ok_or_else(|| AppError::Internal("Database not initialized".to_string()))?;
+
+    let api_token = db
+        .get_api_token_by_hash(&hash_secret_token(token))
+        .await?
+        .filter(|api_token| api_token.is_active())
+        .ok_or_else(|| AppError::Auth("Invalid or expired API token".to_string()))?;
+
+    let user = db
+        .get_user_by_id(api_token.user_id)
+        .await?
+        .filter(|user| !user.is_disabled())
+        .ok_or_else(|| AppError::Auth("Invalid or expired API token".to_string()))?;
+    let role = db.effective_role(user.id, user.role).await?.min(api_token.scope.max_role());
+
+    db.touch_api_token(api_token.id).await?;
+
+    Ok(Claims {
+        sub: user.id,
+        exp: api_token.expires_at_timestamp() as usize,
+        role: role.to_string(),
+        sid: None,
+        api_token: Some(api_token.id),
+    })
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::TokenService;
+use crate::error::AppError;
+
+// 添付ファイルの署名付きURL用のトークンであることを示す `aud`
+const ATTACHMENT_AUDIENCE: &str = "md-wiki-attachment";
+
+// 署名付きURLの有効期間（分）
+// `<img>` は Authorization ヘッダを送れないため、閲覧できるユーザーにだけ短い期間のURLを渡す
+pub const ATTACHMENT_URL_MINUTES: i64 = 15;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttachmentClaims {
+    pub att: i64, // attachments.id
+    pub aud: String,
+    pub exp: usize,
+}
+
+pub fn create_attachment_token(tokens: &TokenService, attachment_id: i64) -> Result<String, AppError> {
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(ATTACHMENT_URL_MINUTES);
+    let claims = AttachmentClaims {
+        att: attachment_id,
+        aud: ATTACHMENT_AUDIENCE.to_string(),
+        exp: expires_at.timestamp() as usize,
+    };
+
+    tokens.sign(&claims)
+}
+
+pub fn verify_attachment_token(tokens: &TokenService, token: &str) -> Result<AttachmentClaims, AppError> {
+    tokens
+        .verify(token, Some(ATTACHMENT_AUDIENCE))
+        .map_err(|e| AppError::Auth(format!("Invalid attachment link: {}", e)))
+}
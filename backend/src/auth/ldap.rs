@@ -0,0 +1,212 @@
+use async_trait::async_trait;
+use ldap3::{dn_escape, ldap_escape, Ldap, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::auth::provider::{map_role, parse_role_mapping, AuthProvider, ExternalIdentity};
+use crate::config::LdapConfig;
+use crate::error::AppError;
+use crate::models::user::Role;
+
+// 接続からグループの検索までにかけられる時間
+const LDAP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// LDAP の結果コード
+const RC_NO_SUCH_OBJECT: u32 = 32;
+const RC_INVALID_CREDENTIALS: u32 = 49;
+
+fn config_error(message: String) -> AppError {
+    AppError::Internal(format!("Invalid LDAP configuration: {}", message))
+}
+
+fn ldap_error(error: ldap3::LdapError) -> AppError {
+    AppError::Internal(format!("LDAP error: {}", error))
+}
+
+fn first_value(entry: &SearchEntry, attribute: &str) -> Option<String> {
+    entry
+        .attrs
+        .get(attribute)
+        .and_then(|values| values.first())
+        .filter(|value| !value.is_empty())
+        .cloned()
+}
+
+// LDAP のバインドでパスワードを確認する
+// ユーザーはサービスアカウント（または匿名）で検索するか、DN のテンプレートから決める
+#[derive(Debug)]
+pub struct LdapProvider {
+    config: LdapConfig,
+    role_mapping: Vec<(String, Role)>,
+    default_role: Role,
+}
+
+impl LdapProvider {
+    pub fn from_config(config: &LdapConfig) -> Result<Self, AppError> {
+        if config.user_dn_template.is_none() && config.user_base_dn.is_empty() {
+            return Err(config_error("LDAP_USER_BASE_DN or LDAP_USER_DN_TEMPLATE is required".to_string()));
+        }
+        if config.bind_dn.is_some() != config.bind_password.is_some() {
+            return Err(config_error("LDAP_BIND_DN and LDAP_BIND_PASSWORD must be set together".to_string()));
+        }
+
+        let role_mapping = parse_role_mapping(&config.role_mapping, "LDAP_ROLE_MAPPING").map_err(config_error)?;
+        let default_role = Role::from_str(&config.default_role)
+            .map_err(|e| config_error(format!("LDAP_DEFAULT_ROLE: {}", e)))?;
+
+        Ok(Self {
+            config: config.clone(),
+            role_mapping,
+            default_role,
+        })
+    }
+
+    async fn connect(&self) -> Result<Ldap, AppError> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(LDAP_TIMEOUT)
+            .set_starttls(self.config.starttls);
+        let (conn, ldap) = LdapConnAsync::with_settings(settings, &self.config.url)
+            .await
+            .map_err(ldap_error)?;
+        ldap3::drive!(conn);
+        Ok(ldap)
+    }
+
+    // サービスアカウントでバインドする（設定していなければ匿名のまま）
+    async fn bind_service_account(&self, ldap: &mut Ldap) -> Result<(), AppError> {
+        if let (Some(dn), Some(password)) = (&self.config.bind_dn, &self.config.bind_password) {
+            ldap.simple_bind(dn, password)
+                .await
+                .and_then(|result| result.success())
+                .map_err(ldap_error)?;
+        }
+        Ok(())
+    }
+
+    // ユーザーのエントリ。見つからないか、1件に決まらなければ None
+    async fn find_user(&self, ldap: &mut Ldap, username: &str) -> Result<Option<SearchEntry>, AppError> {
+        let attributes = vec![self.config.username_attribute.as_str(), self.config.email_attribute.as_str()];
+
+        let (base, scope, filter) = match &self.config.user_dn_template {
+            Some(template) => (
+                template.replace("{username}", &dn_escape(username)),
+                Scope::Base,
+                "(objectClass=*)".to_string(),
+            ),
+            None => (
+                self.config.user_base_dn.clone(),
+                Scope::Subtree,
+                self.config.user_filter.replace("{username}", &ldap_escape(username)),
+            ),
+        };
+
+        let (mut entries, _) = match ldap.search(&base, scope, &filter, attributes).await.map_err(ldap_error)?.success() {
+            Ok(result) => result,
+            // DN のテンプレートで存在しないエントリを指した場合（noSuchObject）
+            Err(ldap3::LdapError::LdapResult { result }) if result.rc == RC_NO_SUCH_OBJECT => return Ok(None),
+            Err(e) => return Err(ldap_error(e)),
+        };
+        if entries.len() != 1 {
+            return Ok(None);
+        }
+        Ok(entries.pop().map(SearchEntry::construct))
+    }
+
+    async fn group_names(&self, ldap: &mut Ldap, user_dn: &str, username: &str) -> Result<Vec<String>, AppError> {
+        let base = match &self.config.group_base_dn {
+            Some(base) => base,
+            None => return Ok(Vec::new()),
+        };
+        let filter = self
+            .config
+            .group_filter
+            .replace("{dn}", &ldap_escape(user_dn))
+            .replace("{username}", &ldap_escape(username));
+
+        let (entries, _) = ldap
+            .search(base, Scope::Subtree, &filter, vec![self.config.group_name_attribute.as_str()])
+            .await
+            .and_then(|result| result.success())
+            .map_err(ldap_error)?;
+
+        Ok(entries
+            .into_iter()
+            .map(SearchEntry::construct)
+            .filter_map(|entry| first_value(&entry, &self.config.group_name_attribute))
+            .collect())
+    }
+
+    // パスワードが正しくなければ false
+    async fn bind_user(&self, ldap: &mut Ldap, dn: &str, password: &str) -> Result<bool, AppError> {
+        let result = ldap.simple_bind(dn, password).await.map_err(ldap_error)?;
+        if result.rc == RC_INVALID_CREDENTIALS {
+            return Ok(false);
+        }
+        result.success().map_err(ldap_error)?;
+        Ok(true)
+    }
+
+    async fn authenticate_with(&self, ldap: &mut Ldap, username: &str, password: &str) -> Result<Option<ExternalIdentity>, AppError> {
+        let entry = match &self.config.user_dn_template {
+            // DN のテンプレートを使う場合は、本人でバインドしてからエントリを読む
+            Some(template) => {
+                let dn = template.replace("{username}", &dn_escape(username));
+                if !self.bind_user(ldap, &dn, password).await? {
+                    return Ok(None);
+                }
+                match self.find_user(ldap, username).await? {
+                    Some(entry) => entry,
+                    None => return Ok(None),
+                }
+            }
+            None => {
+                self.bind_service_account(ldap).await?;
+                let entry = match self.find_user(ldap, username).await? {
+                    Some(entry) => entry,
+                    None => return Ok(None),
+                };
+                if !self.bind_user(ldap, &entry.dn, password).await? {
+                    return Ok(None);
+                }
+                entry
+            }
+        };
+
+        // グループはサービスアカウントの権限で調べる（本人には読めない場合がある）
+        self.bind_service_account(ldap).await?;
+        let groups = self.group_names(ldap, &entry.dn, username).await?;
+
+        Ok(Some(ExternalIdentity {
+            issuer: self.config.url.clone(),
+            username: first_value(&entry, &self.config.username_attribute).unwrap_or_else(|| username.to_string()),
+            email: first_value(&entry, &self.config.email_attribute),
+            role: map_role(&self.role_mapping, &groups, self.default_role),
+            sync_role: !self.role_mapping.is_empty(),
+            subject: entry.dn,
+        }))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for LdapProvider {
+    fn name(&self) -> &'static str {
+        "ldap"
+    }
+
+    async fn authenticate(&self, username: &str, password: &str) -> Result<Option<ExternalIdentity>, AppError> {
+        // 空のパスワードでのバインドは匿名バインドとして成功してしまうため、先に拒否する
+        if username.is_empty() || password.is_empty() {
+            return Ok(None);
+        }
+
+        let result = tokio::time::timeout(LDAP_TIMEOUT, async {
+            let mut ldap = self.connect().await?;
+            let result = self.authenticate_with(&mut ldap, username, password).await;
+            let _ = ldap.unbind().await;
+            result
+        })
+        .await;
+
+        result.unwrap_or_else(|_| Err(AppError::Internal("LDAP server did not respond in time".to_string())))
+    }
+}
@@ -13,27 +13,14 @@ use crate::auth;
 use std::str::FromStr;
 
 pub async fn require_auth(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     mut req: Request<axum::body::Body>,
     next: Next,
 ) -> Result<Response, AppError> {
-    let auth_header = req.headers()
-        .get(axum::http::header::AUTHORIZATION)
-        .and_then(|header| header.to_str().ok());
-
-    let auth_header = if let Some(auth_header) = auth_header {
-        auth_header
-    } else {
-        return Err(AppError::Auth("Missing authorization header".to_string()));
-    };
-
-    if let Some(token) = auth_header.strip_prefix("Bearer ") {
-        let claims = auth::verify_token(token)?;
-        req.extensions_mut().insert(claims);
-        Ok(next.run(req).await)
-    } else {
-        Err(AppError::Auth("Invalid authorization header format".to_string()))
-    }
+    let token = auth::bearer_token(req.headers())?;
+    let claims = auth::authenticate(&state, token).await?;
+    req.extensions_mut().insert(claims);
+    Ok(next.run(req).await)
 }
 
 pub async fn require_role(
@@ -46,9 +33,39 @@ pub async fn require_role(
 
     let claims_role = Role::from_str(&claims.role).map_err(|_| AppError::Auth("Invalid role in token".to_string()))?;
 
-    if claims_role >= required_role {
+    if claims_role.includes(required_role) {
         Ok(next.run(req).await)
     } else {
-        Err(AppError::Auth("Insufficient permissions".to_string()))
+        Err(AppError::Forbidden(format!("{} role or higher is required", required_role)))
+    }
+}
+
+// ドキュメントやタグを変更するルート用（編集者以上）
+pub async fn require_editor(
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_role(req, next, Role::Editor).await
+}
+
+// ユーザー管理などのルート用（管理者のみ）
+pub async fn require_admin(
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    require_role(req, next, Role::Admin).await
+}
+
+// 未ログインでも公開ドキュメントを閲覧できるルート用
+// トークンがあれば検証し、なければ匿名のまま通す（権限はハンドラでACLと公開設定から判断する）
+pub async fn optional_auth(
+    state: State<AppState>,
+    req: Request<axum::body::Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    if req.headers().contains_key(axum::http::header::AUTHORIZATION) {
+        require_auth(state, req, next).await
+    } else {
+        Ok(next.run(req).await)
     }
 }
\ No newline at end of file
@@ -1,49 +1,102 @@
 use serde::{Deserialize, Serialize};
-use jsonwebtoken::{encode, EncodingKey, Header};
 use crate::error::AppError;
+use crate::AppState;
 use async_trait::async_trait;
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
 
-
+pub mod acl;
+pub mod attachment;
+pub mod api_token;
+pub mod ldap;
 pub mod middleware;
+pub mod oidc;
+pub mod provider;
+pub mod session;
+pub mod setup;
+pub mod share;
+pub mod throttle;
+pub mod tokens;
+pub mod totp;
+
+pub use tokens::TokenService;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Claims {
     pub sub: i64,  // user id
     pub exp: usize,  // expiration time
     pub role: String,
+    // ログインしたセッション（sessions.id）。ログアウトで取り消す対象になる
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<i64>,
+    // 個人用APIトークンで認証した場合はそのID（JWTには含めない）
+    #[serde(skip)]
+    pub api_token: Option<i64>,
 }
 
 #[async_trait]
-impl<S> FromRequestParts<S> for Claims
-where
-    S: Send + Sync,
-{
+impl FromRequestParts<AppState> for Claims {
     type Rejection = AppError;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        let auth_header = parts.headers
-            .get(axum::http::header::AUTHORIZATION)
-            .and_then(|header| header.to_str().ok());
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        // 認証ミドルウェアで検証済みならそれを使う
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(claims.clone());
+        }
 
-        let auth_header = if let Some(auth_header) = auth_header {
-            auth_header
-        } else {
-            return Err(AppError::Auth("Missing authorization header".to_string()));
-        };
+        let token = bearer_token(&parts.headers)?;
+        authenticate(state, token).await
+    }
+}
 
-        if let Some(token) = auth_header.strip_prefix("Bearer ") {
-            verify_token(token)
-        } else {
-            Err(AppError::Auth("Invalid authorization header format".to_string()))
-        }
+// Authorization ヘッダから Bearer トークンを取り出す
+pub fn bearer_token(headers: &axum::http::HeaderMap) -> Result<&str, AppError> {
+    let auth_header = headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .ok_or_else(|| AppError::Auth("Missing authorization header".to_string()))?;
+
+    auth_header
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| AppError::Auth("Invalid authorization header format".to_string()))
+}
+
+// ログインで発行したJWTか、個人用APIトークンを検証する
+pub async fn authenticate(state: &AppState, token: &str) -> Result<Claims, AppError> {
+    if api_token::is_api_token(token) {
+        api_token::verify_api_token(state, token).await
+    } else {
+        let mut claims = verify_token(&state.tokens, token)?;
+        verify_login(state, &mut claims).await?;
+        Ok(claims)
     }
 }
 
-pub fn create_token(user_id: i64, role: &str) -> Result<String, AppError> {
+// アクセストークンを発行したセッションが取り消されておらず、ユーザーが無効にされていないか
+// ログアウトやセッションの取り消し、ユーザーの無効化を、アクセストークンの期限を待たずに反映する
+// ロールもトークンに入れたものではなく現在のものを使い、降格をすぐに反映する
+async fn verify_login(state: &AppState, claims: &mut Claims) -> Result<(), AppError> {
+    let Some(db) = state.db_manager.as_ref() else {
+        return Ok(());
+    };
+    let sid = claims.sid.ok_or_else(|| AppError::Auth("Token has no session".to_string()))?;
+    db.get_session(sid)
+        .await?
+        .filter(|session| session.user_id == claims.sub && session.is_active())
+        .ok_or_else(|| AppError::Auth("Session has been revoked or expired".to_string()))?;
+    let user = db
+        .get_user_by_id(claims.sub)
+        .await?
+        .filter(|user| !user.is_disabled())
+        .ok_or_else(|| AppError::Auth("User is disabled or no longer exists".to_string()))?;
+    claims.role = db.effective_role(user.id, user.role).await?.to_string();
+    Ok(())
+}
+
+// アクセストークンの有効期間は短くし、リフレッシュトークンで更新する
+pub fn create_token(tokens: &TokenService, user_id: i64, role: &str, session_id: i64) -> Result<String, AppError> {
     let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(24))
+        .checked_add_signed(chrono::Duration::minutes(session::ACCESS_TOKEN_MINUTES))
         .expect("valid timestamp")
         .timestamp() as usize;
 
@@ -51,20 +104,15 @@ pub fn create_token(user_id: i64, role: &str) -> Result<String, AppError> {
         sub: user_id,
         exp: expiration,
         role: role.to_string(),
+        sid: Some(session_id),
+        api_token: None,
     };
 
-    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-    let key = EncodingKey::from_secret(secret.as_bytes());
-
-    encode(&Header::default(), &claims, &key)
-        .map_err(|e| AppError::Internal(format!("Failed to create token: {}", e)))
+    tokens.sign(&claims)
 }
 
-pub fn verify_token(token: &str) -> Result<Claims, AppError> {
-    let secret = std::env::var("JWT_SECRET").unwrap_or_else(|_| "your-secret-key".to_string());
-    let key = jsonwebtoken::DecodingKey::from_secret(secret.as_bytes());
-
-    jsonwebtoken::decode::<Claims>(token, &key, &jsonwebtoken::Validation::default())
-        .map(|data| data.claims)
+pub fn verify_token(tokens: &TokenService, token: &str) -> Result<Claims, AppError> {
+    tokens
+        .verify(token, None)
         .map_err(|e| AppError::Auth(format!("Invalid token: {}", e)))
-} 
\ No newline at end of file
+}
\ No newline at end of file
@@ -0,0 +1,328 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use crate::auth::provider::{map_role, parse_role_mapping, ExternalIdentity};
+use crate::auth::session::generate_secret_token;
+use crate::config::OidcConfig;
+use crate::error::AppError;
+use crate::models::user::Role;
+
+// ログインを始めてからプロバイダから戻ってくるまでの猶予
+const PENDING_LOGIN_TTL: Duration = Duration::from_secs(10 * 60);
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// ディスカバリ（/.well-known/openid-configuration）で取得するプロバイダの情報
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+// 認可リクエストごとに、戻ってきたときの検証に使う値を覚えておく
+struct PendingLogin {
+    code_verifier: String,
+    nonce: String,
+    created_at: Instant,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+}
+
+// OpenID Connect の認可コードフロー（PKCE つき）でログインする
+// プロバイダの情報と署名鍵は初めて使うときに取得し、未知の鍵が使われたら取り直す
+pub struct OidcClient {
+    config: OidcConfig,
+    role_mapping: Vec<(String, Role)>,
+    default_role: Role,
+    http: reqwest::Client,
+    metadata: RwLock<Option<ProviderMetadata>>,
+    jwks: RwLock<JwkSet>,
+    pending: Mutex<HashMap<String, PendingLogin>>,
+}
+
+impl fmt::Debug for OidcClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OidcClient")
+            .field("issuer_url", &self.config.issuer_url)
+            .field("client_id", &self.config.client_id)
+            .field("redirect_url", &self.config.redirect_url)
+            .finish()
+    }
+}
+
+fn config_error(message: String) -> AppError {
+    AppError::Internal(format!("Invalid OpenID Connect configuration: {}", message))
+}
+
+fn provider_error(message: String) -> AppError {
+    AppError::Internal(format!("OpenID Connect provider error: {}", message))
+}
+
+// PKCE の code_challenge（S256）
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+// `realm_access.roles` のようなドット区切りのパスでクレームを取り出す
+// 値が配列なら文字列の要素をすべて返す
+fn claim_values(claims: &Map<String, Value>, path: &str) -> Vec<String> {
+    let mut parts = path.split('.');
+    let mut value = parts.next().and_then(|key| claims.get(key));
+    for key in parts {
+        value = value.and_then(|value| value.get(key));
+    }
+
+    match value {
+        Some(Value::String(value)) => vec![value.clone()],
+        Some(Value::Array(values)) => values
+            .iter()
+            .filter_map(|value| value.as_str().map(str::to_string))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn claim_string(claims: &Map<String, Value>, name: &str) -> Option<String> {
+    claims
+        .get(name)
+        .and_then(Value::as_str)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+impl OidcClient {
+    pub fn from_config(config: &OidcConfig) -> Result<Self, AppError> {
+        if config.client_id.is_empty() {
+            return Err(config_error("OIDC_CLIENT_ID is required".to_string()));
+        }
+        if config.redirect_url.is_empty() {
+            return Err(config_error("OIDC_REDIRECT_URL is required".to_string()));
+        }
+
+        let role_mapping = parse_role_mapping(&config.role_mapping, "OIDC_ROLE_MAPPING").map_err(config_error)?;
+
+        let default_role = Role::from_str(&config.default_role)
+            .map_err(|e| config_error(format!("OIDC_DEFAULT_ROLE: {}", e)))?;
+
+        let http = reqwest::Client::builder()
+            .timeout(HTTP_TIMEOUT)
+            .build()
+            .map_err(|e| config_error(e.to_string()))?;
+
+        Ok(Self {
+            config: config.clone(),
+            role_mapping,
+            default_role,
+            http,
+            metadata: RwLock::new(None),
+            jwks: RwLock::new(JwkSet { keys: Vec::new() }),
+            pending: Mutex::new(HashMap::new()),
+        })
+    }
+
+    async fn metadata(&self) -> Result<ProviderMetadata, AppError> {
+        if let Some(metadata) = self.metadata.read().await.as_ref() {
+            return Ok(metadata.clone());
+        }
+
+        let issuer = self.config.issuer_url.trim_end_matches('/');
+        let metadata: ProviderMetadata = self
+            .http
+            .get(format!("{}/.well-known/openid-configuration", issuer))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| provider_error(format!("discovery failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| provider_error(format!("invalid discovery document: {}", e)))?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            return Err(provider_error(format!(
+                "discovery document is for issuer {}, expected {}",
+                metadata.issuer, self.config.issuer_url
+            )));
+        }
+
+        *self.metadata.write().await = Some(metadata.clone());
+        Ok(metadata)
+    }
+
+    async fn refresh_jwks(&self, jwks_uri: &str) -> Result<(), AppError> {
+        let jwks: JwkSet = self
+            .http
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| provider_error(format!("failed to fetch signing keys: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| provider_error(format!("invalid signing keys: {}", e)))?;
+
+        *self.jwks.write().await = jwks;
+        Ok(())
+    }
+
+    async fn decoding_key(&self, kid: Option<&str>) -> Option<DecodingKey> {
+        let jwks = self.jwks.read().await;
+        let jwk = match kid {
+            Some(kid) => jwks.find(kid),
+            // `kid` がなければ、鍵が1つだけの場合にそれを使う
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }?;
+        DecodingKey::from_jwk(jwk).ok()
+    }
+
+    // プロバイダの認可エンドポイントのURLを作る
+    pub async fn authorization_url(&self) -> Result<String, AppError> {
+        let metadata = self.metadata().await?;
+
+        let state = generate_secret_token();
+        let nonce = generate_secret_token();
+        let code_verifier = generate_secret_token();
+        let challenge = code_challenge(&code_verifier);
+
+        {
+            let mut pending = self.pending.lock();
+            pending.retain(|_, login| login.created_at.elapsed() < PENDING_LOGIN_TTL);
+            pending.insert(
+                state.clone(),
+                PendingLogin {
+                    code_verifier,
+                    nonce: nonce.clone(),
+                    created_at: Instant::now(),
+                },
+            );
+        }
+
+        let url = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", self.config.client_id.as_str()),
+                ("redirect_uri", self.config.redirect_url.as_str()),
+                ("scope", self.config.scopes.as_str()),
+                ("state", state.as_str()),
+                ("nonce", nonce.as_str()),
+                ("code_challenge", challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| provider_error(format!("invalid authorization endpoint: {}", e)))?;
+
+        Ok(url.to_string())
+    }
+
+    // プロバイダから戻ってきた認可コードをトークンに交換し、ID トークンを検証する
+    pub async fn exchange_code(&self, code: &str, state: &str) -> Result<ExternalIdentity, AppError> {
+        // state は1回しか使えない
+        let pending = self
+            .pending
+            .lock()
+            .remove(state)
+            .filter(|login| login.created_at.elapsed() < PENDING_LOGIN_TTL)
+            .ok_or_else(|| AppError::Auth("Invalid or expired login state".to_string()))?;
+
+        let metadata = self.metadata().await?;
+
+        let mut request = self.http.post(&metadata.token_endpoint).form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", pending.code_verifier.as_str()),
+        ]);
+        if let Some(secret) = &self.config.client_secret {
+            request = request.basic_auth(&self.config.client_id, Some(secret));
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| provider_error(format!("token request failed: {}", e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::Auth(format!("Token request was rejected ({}): {}", status, body)));
+        }
+
+        let id_token = response
+            .json::<TokenResponse>()
+            .await
+            .map_err(|e| provider_error(format!("invalid token response: {}", e)))?
+            .id_token
+            .ok_or_else(|| provider_error("token response has no id_token".to_string()))?;
+
+        let claims = self.verify_id_token(&metadata, &id_token).await?;
+        if claims.get("nonce").and_then(Value::as_str) != Some(pending.nonce.as_str()) {
+            return Err(AppError::Auth("ID token nonce does not match".to_string()));
+        }
+
+        Ok(self.identity(&metadata, &claims))
+    }
+
+    async fn verify_id_token(&self, metadata: &ProviderMetadata, id_token: &str) -> Result<Map<String, Value>, AppError> {
+        let header = decode_header(id_token).map_err(|e| AppError::Auth(format!("Invalid ID token: {}", e)))?;
+        // クライアントシークレットで署名する方式は受け付けない
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(AppError::Auth(format!("Unsupported ID token algorithm {:?}", header.alg)));
+        }
+
+        let key = match self.decoding_key(header.kid.as_deref()).await {
+            Some(key) => key,
+            None => {
+                self.refresh_jwks(&metadata.jwks_uri).await?;
+                self.decoding_key(header.kid.as_deref())
+                    .await
+                    .ok_or_else(|| AppError::Auth("ID token is signed with an unknown key".to_string()))?
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        decode::<Map<String, Value>>(id_token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| AppError::Auth(format!("Invalid ID token: {}", e)))
+    }
+
+    fn identity(&self, metadata: &ProviderMetadata, claims: &Map<String, Value>) -> ExternalIdentity {
+        let subject = claim_string(claims, "sub").unwrap_or_default();
+        let email = claim_string(claims, "email");
+        let username = claim_string(claims, &self.config.username_claim)
+            .or_else(|| email.clone())
+            .unwrap_or_else(|| subject.clone());
+
+        let role = map_role(&self.role_mapping, &claim_values(claims, &self.config.role_claim), self.default_role);
+
+        ExternalIdentity {
+            issuer: metadata.issuer.clone(),
+            subject,
+            username,
+            email,
+            role,
+            sync_role: !self.role_mapping.is_empty(),
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use async_trait::async_trait;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::AppError;
+use crate::models::user::Role;
+
+// 外部の認証基盤（OpenID Connect、LDAP など）で確認したユーザー
+// 発行者と subject の組でローカルのユーザーに紐づける
+#[derive(Debug)]
+pub struct ExternalIdentity {
+    pub issuer: String,
+    pub subject: String,
+    pub username: String,
+    pub email: Option<String>,
+    pub role: Role,
+    // ロールの対応を設定している場合は、ログインのたびに既存のユーザーのロールも合わせる
+    pub sync_role: bool,
+}
+
+// ユーザー名とパスワードを確認する外部の認証基盤
+// ローカルのパスワードを持たないユーザーのログインは `DbManager::authenticate_user` から順に問い合わせる
+#[async_trait]
+pub trait AuthProvider: Send + Sync + fmt::Debug {
+    // ログイン方法の一覧に出す名前
+    fn name(&self) -> &'static str;
+
+    // 認証できなければ None。認証基盤に接続できないなどの場合はエラー
+    async fn authenticate(&self, username: &str, password: &str) -> Result<Option<ExternalIdentity>, AppError>;
+}
+
+// `値=ロール` 形式のロールの対応を読み込む（`setting` はエラーメッセージ用の設定名）
+pub fn parse_role_mapping(entries: &[String], setting: &str) -> Result<Vec<(String, Role)>, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let (value, role) = entry
+                .rsplit_once('=')
+                .ok_or_else(|| format!("{} entry {} must be value=role", setting, entry))?;
+            let role = Role::from_str(role.trim()).map_err(|e| format!("{}: {}", setting, e))?;
+            Ok((value.trim().to_string(), role))
+        })
+        .collect()
+}
+
+// 当てはまる値のうち最も強いロール（なければ既定のロール）
+pub fn map_role(mapping: &[(String, Role)], values: &[String], default_role: Role) -> Role {
+    mapping
+        .iter()
+        .filter(|(value, _)| values.contains(value))
+        .map(|(_, role)| *role)
+        .max()
+        .unwrap_or(default_role)
+}
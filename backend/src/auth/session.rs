@@ -0,0 +1,24 @@
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+// アクセストークンの有効期間（分）
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+
+// リフレッシュトークンの有効期間（日）。更新するたびにそこから延長される
+pub const REFRESH_TOKEN_DAYS: i64 = 30;
+
+// ランダムなトークンを作る（リフレッシュトークン・APIトークン用）
+// トークン自体は保存せず、ハッシュだけをデータベースに保存する
+pub fn generate_secret_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn hash_secret_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+pub fn refresh_token_expiry() -> chrono::DateTime<chrono::Utc> {
+    chrono::Utc::now() + chrono::Duration::days(REFRESH_TOKEN_DAYS)
+}
@@ -0,0 +1,19 @@
+use crate::auth::session::{generate_secret_token, hash_secret_token};
+use crate::db::DbManager;
+use crate::error::AppError;
+
+// 初期設定のトークンの有効期間（時間）
+pub const SETUP_TOKEN_HOURS: i64 = 24;
+
+// ユーザーが1人もいなければ、最初の管理者を作成するためのトークンを発行する（既にいれば None）
+// サーバーの起動時と `md-wiki-backend setup-token` で使う
+pub async fn issue_setup_token(db: &DbManager) -> Result<Option<String>, AppError> {
+    if db.count_users().await? > 0 {
+        return Ok(None);
+    }
+
+    let token = generate_secret_token();
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(SETUP_TOKEN_HOURS);
+    db.add_setup_token(&hash_secret_token(&token), expires_at).await?;
+    Ok(Some(token))
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+use crate::auth::TokenService;
+use crate::db::ShareLink;
+use crate::error::AppError;
+
+// 共有リンク用のトークンであることを示す `aud`
+// ログイン用のトークンには `aud` がないため、互いに取り違えても検証に失敗する
+const SHARE_AUDIENCE: &str = "md-wiki-share";
+
+// 共有リンクのトークン
+// 権限や期限は share_links の行にも保存し、取り消しはそちらで確認する
+// `doc` は発行時の名前で、名前の変更後は share_links の行の方が正しい
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ShareClaims {
+    pub sid: i64,  // share_links.id
+    pub doc: String,
+    pub aud: String,
+    pub exp: usize,
+}
+
+pub fn create_share_token(
+    tokens: &TokenService,
+    link: &ShareLink,
+    expires_at: chrono::DateTime<chrono::Utc>,
+) -> Result<String, AppError> {
+    let claims = ShareClaims {
+        sid: link.id,
+        doc: link.document.clone(),
+        aud: SHARE_AUDIENCE.to_string(),
+        exp: expires_at.timestamp() as usize,
+    };
+
+    tokens.sign(&claims)
+}
+
+pub fn verify_share_token(tokens: &TokenService, token: &str) -> Result<ShareClaims, AppError> {
+    tokens
+        .verify(token, Some(SHARE_AUDIENCE))
+        .map_err(|e| AppError::Auth(format!("Invalid share link: {}", e)))
+}
@@ -0,0 +1,112 @@
+use chrono::Duration;
+
+use crate::config::Config;
+
+// 最初の失敗の後に待たせる時間（秒）。失敗するたびに倍にする
+const INITIAL_DELAY_SECS: i64 = 1;
+
+// ユーザー名ごと、またはIPアドレスごとの連続失敗の扱い
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottlePolicy {
+    // この回数続けて失敗したらロックする（0 なら制限しない）
+    max_failures: u32,
+    lockout: Duration,
+    // 上限に達する前も、失敗するたびに待ち時間を延ばすか
+    backoff: bool,
+}
+
+impl ThrottlePolicy {
+    pub fn new(max_failures: u32, lockout_minutes: i64, backoff: bool) -> Self {
+        Self {
+            max_failures,
+            lockout: Duration::minutes(lockout_minutes),
+            backoff,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.max_failures > 0
+    }
+
+    // `failures` 回続けて失敗した後、次に試せるまでの時間
+    // 上限の回数まではロックの時間を超えない範囲で倍々に延ばし（`backoff` の場合）、上限に達したらロックする
+    pub fn delay(&self, failures: u32) -> Duration {
+        if self.locks_out(failures) {
+            return self.lockout;
+        }
+        if !self.backoff {
+            return Duration::zero();
+        }
+        let exponent = failures.saturating_sub(1).min(20);
+        Duration::seconds(INITIAL_DELAY_SECS << exponent).min(self.lockout)
+    }
+
+    // 上限の回数に達してロックするか
+    pub fn locks_out(&self, failures: u32) -> bool {
+        failures >= self.max_failures
+    }
+
+    // 最後の失敗からこの時間が経てば、失敗の回数を数え直す
+    pub fn window(&self) -> Duration {
+        self.lockout
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LoginThrottle {
+    pub username: ThrottlePolicy,
+    pub ip: ThrottlePolicy,
+}
+
+impl LoginThrottle {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            username: ThrottlePolicy::new(config.login_max_failures, config.login_lockout_minutes, true),
+            // 同じIPアドレスを共有する利用者を巻き込まないよう、IPアドレスは上限に達するまで待たせない
+            ip: ThrottlePolicy::new(config.login_max_failures_per_ip, config.login_lockout_minutes, false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_until_the_lockout_is_reached() {
+        let policy = ThrottlePolicy::new(5, 15, true);
+        let delays: Vec<i64> = (1..5).map(|failures| policy.delay(failures).num_seconds()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 8]);
+        assert!(!policy.locks_out(4));
+
+        assert!(policy.locks_out(5));
+        assert_eq!(policy.delay(5), Duration::minutes(15));
+        assert_eq!(policy.delay(50), Duration::minutes(15));
+    }
+
+    #[test]
+    fn delay_never_exceeds_the_lockout() {
+        let policy = ThrottlePolicy::new(100, 1, true);
+        assert_eq!(policy.delay(6).num_seconds(), 32);
+        assert_eq!(policy.delay(7), Duration::minutes(1));
+        // 大きな回数でもあふれない
+        assert_eq!(policy.delay(99), Duration::minutes(1));
+    }
+
+    #[test]
+    fn policies_without_backoff_only_lock() {
+        let policy = ThrottlePolicy::new(3, 15, false);
+        assert_eq!(policy.delay(1), Duration::zero());
+        assert_eq!(policy.delay(2), Duration::zero());
+        assert_eq!(policy.delay(3), Duration::minutes(15));
+        assert_eq!(policy.window(), Duration::minutes(15));
+    }
+
+    #[test]
+    fn zero_max_failures_disables_the_policy() {
+        let policy = ThrottlePolicy::new(0, 15, true);
+        assert!(!policy.enabled());
+        assert!(ThrottlePolicy::new(1, 15, true).enabled());
+        assert!(ThrottlePolicy::new(1, 15, true).locks_out(1));
+    }
+}
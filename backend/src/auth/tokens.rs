@@ -0,0 +1,250 @@
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::config::{Config, Environment, DEFAULT_JWT_SECRET};
+use crate::error::AppError;
+
+// ログイン・共有リンクのトークンの署名と検証
+// 署名は現在の鍵だけで行い、ヘッダの `kid` で検証に使う鍵を選ぶ
+// 以前の鍵を検証用に残しておけば、発行済みのトークンを無効にせずに鍵を切り替えられる
+pub struct TokenService {
+    key_id: String,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_keys: HashMap<String, (Algorithm, DecodingKey)>,
+}
+
+impl fmt::Debug for TokenService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenService")
+            .field("key_id", &self.key_id)
+            .field("algorithm", &self.algorithm)
+            .field("verification_keys", &self.decoding_keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+fn key_error(message: String) -> AppError {
+    AppError::Internal(format!("Invalid JWT key configuration: {}", message))
+}
+
+fn parse_algorithm(name: &str) -> Result<Algorithm, AppError> {
+    match name.to_uppercase().as_str() {
+        "HS256" => Ok(Algorithm::HS256),
+        "RS256" => Ok(Algorithm::RS256),
+        "EDDSA" => Ok(Algorithm::EdDSA),
+        _ => Err(key_error(format!("unsupported algorithm {} (use HS256, RS256 or EdDSA)", name))),
+    }
+}
+
+fn read_pem(path: Option<&Path>, name: &str) -> Result<Vec<u8>, AppError> {
+    let path = path.ok_or_else(|| key_error(format!("{} is required for this algorithm", name)))?;
+    std::fs::read(path).map_err(|e| key_error(format!("failed to read {}: {}", path.display(), e)))
+}
+
+fn decoding_key(algorithm: Algorithm, pem: &[u8]) -> Result<DecodingKey, jsonwebtoken::errors::Error> {
+    match algorithm {
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(pem),
+        _ => DecodingKey::from_rsa_pem(pem),
+    }
+}
+
+// `kid:アルゴリズム:値` 形式の検証用の鍵
+// 値は HS256 なら秘密鍵、RS256 / EdDSA なら公開鍵のファイル
+fn previous_key(spec: &str) -> Result<(String, Algorithm, DecodingKey), AppError> {
+    let mut parts = spec.splitn(3, ':');
+    let (kid, algorithm, value) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(kid), Some(algorithm), Some(value)) if !kid.is_empty() && !value.is_empty() => (kid, algorithm, value),
+        _ => return Err(key_error(format!("JWT_PREVIOUS_KEYS entry {} must be kid:algorithm:key", spec))),
+    };
+
+    let algorithm = parse_algorithm(algorithm)?;
+    let key = match algorithm {
+        Algorithm::HS256 => DecodingKey::from_secret(value.as_bytes()),
+        _ => {
+            let pem = read_pem(Some(Path::new(value)), "public key file")?;
+            decoding_key(algorithm, &pem).map_err(|e| key_error(format!("public key for {}: {}", kid, e)))?
+        }
+    };
+    Ok((kid.to_string(), algorithm, key))
+}
+
+impl TokenService {
+    // 設定から鍵を読み込む
+    // 開発環境以外では既定の秘密鍵での起動を拒否する
+    pub fn from_config(config: &Config) -> Result<Self, AppError> {
+        let algorithm = parse_algorithm(&config.jwt_algorithm)?;
+
+        let (encoding_key, current_key) = match algorithm {
+            Algorithm::HS256 => {
+                if config.jwt_secret.is_empty() {
+                    return Err(key_error("JWT_SECRET must not be empty".to_string()));
+                }
+                if config.jwt_secret == DEFAULT_JWT_SECRET {
+                    if config.environment != Environment::Development {
+                        return Err(key_error(
+                            "JWT_SECRET is not set; refusing to use the default secret outside development (set APP_ENV=development to allow it)".to_string(),
+                        ));
+                    }
+                    tracing::warn!("Using the default JWT secret; set JWT_SECRET before deploying");
+                }
+                (
+                    EncodingKey::from_secret(config.jwt_secret.as_bytes()),
+                    DecodingKey::from_secret(config.jwt_secret.as_bytes()),
+                )
+            }
+            _ => {
+                let private_pem = read_pem(config.jwt_private_key_file.as_deref(), "JWT_PRIVATE_KEY_FILE")?;
+                let public_pem = read_pem(config.jwt_public_key_file.as_deref(), "JWT_PUBLIC_KEY_FILE")?;
+                let encoding_key = match algorithm {
+                    Algorithm::EdDSA => EncodingKey::from_ed_pem(&private_pem),
+                    _ => EncodingKey::from_rsa_pem(&private_pem),
+                }
+                .map_err(|e| key_error(format!("private key: {}", e)))?;
+                let decoding_key = decoding_key(algorithm, &public_pem)
+                    .map_err(|e| key_error(format!("public key: {}", e)))?;
+                (encoding_key, decoding_key)
+            }
+        };
+
+        let mut decoding_keys = HashMap::new();
+        for spec in &config.jwt_previous_keys {
+            let (kid, algorithm, key) = previous_key(spec)?;
+            if kid == config.jwt_key_id {
+                return Err(key_error(format!("JWT_PREVIOUS_KEYS must not reuse the current key id {}", kid)));
+            }
+            decoding_keys.insert(kid, (algorithm, key));
+        }
+        decoding_keys.insert(config.jwt_key_id.clone(), (algorithm, current_key));
+
+        Ok(Self {
+            key_id: config.jwt_key_id.clone(),
+            algorithm,
+            encoding_key,
+            decoding_keys,
+        })
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, AppError> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = Some(self.key_id.clone());
+
+        encode(&header, claims, &self.encoding_key)
+            .map_err(|e| AppError::Internal(format!("Failed to create token: {}", e)))
+    }
+
+    // `audience` を指定した場合は `aud` が一致するトークンだけを、
+    // 指定しない場合は `aud` のないトークンだけを受け付ける
+    pub fn verify<T: DeserializeOwned>(&self, token: &str, audience: Option<&str>) -> Result<T, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        // `kid` のないトークンは現在の鍵で検証する
+        let kid = header.kid.as_deref().unwrap_or(&self.key_id);
+        let (algorithm, key) = self
+            .decoding_keys
+            .get(kid)
+            .ok_or_else(|| jsonwebtoken::errors::Error::from(jsonwebtoken::errors::ErrorKind::InvalidSignature))?;
+
+        let mut validation = Validation::new(*algorithm);
+        if let Some(audience) = audience {
+            validation.set_audience(&[audience]);
+            // `aud` のないトークンも検証に失敗させる
+            validation.required_spec_claims.insert("aud".to_string());
+        }
+
+        let claims = decode::<serde_json::Value>(token, key, &validation)?.claims;
+        // 共有リンクなど用途を限ったトークンをログインに使えないよう、ここでも `aud` がないことを確かめる
+        if audience.is_none() && claims.get("aud").is_some() {
+            return Err(ErrorKind::InvalidAudience.into());
+        }
+        Ok(serde_json::from_value(claims)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct TestClaims {
+        sub: String,
+        exp: i64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aud: Option<String>,
+    }
+
+    fn claims(aud: Option<&str>) -> TestClaims {
+        TestClaims {
+            sub: "1".to_string(),
+            exp: chrono::Utc::now().timestamp() + 3600,
+            aud: aud.map(str::to_string),
+        }
+    }
+
+    fn service(key_id: &str, secret: &str, previous_keys: &[&str]) -> TokenService {
+        let mut config = Config::from_env();
+        config.environment = Environment::Production;
+        config.jwt_algorithm = "HS256".to_string();
+        config.jwt_key_id = key_id.to_string();
+        config.jwt_secret = secret.to_string();
+        config.jwt_previous_keys = previous_keys.iter().map(|key| key.to_string()).collect();
+        TokenService::from_config(&config).unwrap()
+    }
+
+    #[test]
+    fn tokens_signed_with_a_previous_key_still_verify() {
+        let old = service("2024", "old-secret", &[]);
+        let token = old.sign(&claims(None)).unwrap();
+
+        let rotated = service("2025", "new-secret", &["2024:HS256:old-secret"]);
+        assert_eq!(rotated.verify::<TestClaims>(&token, None).unwrap().sub, "1");
+        let fresh = rotated.sign(&claims(None)).unwrap();
+        assert!(rotated.verify::<TestClaims>(&fresh, None).is_ok());
+
+        // 以前の鍵を外すと検証できなくなる
+        let dropped = service("2025", "new-secret", &[]);
+        assert!(dropped.verify::<TestClaims>(&token, None).is_err());
+    }
+
+    #[test]
+    fn unknown_key_ids_are_rejected() {
+        let tokens = service("current", "secret", &[]);
+        // 同じ秘密鍵でも、知らない `kid` のトークンは受け付けない
+        let other = service("unknown", "secret", &[]);
+        let token = other.sign(&claims(None)).unwrap();
+        let error = tokens.verify::<TestClaims>(&token, None).unwrap_err();
+        assert_eq!(*error.kind(), ErrorKind::InvalidSignature);
+    }
+
+    #[test]
+    fn audience_must_match_or_be_absent() {
+        let tokens = service("current", "secret", &[]);
+        let shared = tokens.sign(&claims(Some("share"))).unwrap();
+        let login = tokens.sign(&claims(None)).unwrap();
+
+        assert!(tokens.verify::<TestClaims>(&shared, Some("share")).is_ok());
+        assert!(tokens.verify::<TestClaims>(&shared, Some("other")).is_err());
+        let error = tokens.verify::<TestClaims>(&shared, None).unwrap_err();
+        assert_eq!(*error.kind(), ErrorKind::InvalidAudience);
+        assert!(tokens.verify::<TestClaims>(&login, Some("share")).is_err());
+        assert!(tokens.verify::<TestClaims>(&login, None).is_ok());
+    }
+
+    #[test]
+    fn default_secret_is_refused_outside_development() {
+        let mut config = Config::from_env();
+        config.jwt_algorithm = "HS256".to_string();
+        config.jwt_secret = DEFAULT_JWT_SECRET.to_string();
+        config.jwt_previous_keys = Vec::new();
+
+        config.environment = Environment::Production;
+        assert!(TokenService::from_config(&config).is_err());
+        config.environment = Environment::Development;
+        assert!(TokenService::from_config(&config).is_ok());
+    }
+}
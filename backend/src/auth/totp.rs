@@ -0,0 +1,183 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::auth::session::hash_secret_token;
+use crate::auth::TokenService;
+use crate::error::AppError;
+
+// 認証アプリに表示される発行者名
+const TOTP_ISSUER: &str = "MD-Wiki";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECS: u64 = 30;
+// 端末の時計のずれを考慮して、前後何ステップのコードまで受け付けるか
+const TOTP_SKEW_STEPS: i64 = 1;
+// 秘密鍵の長さ（バイト）。RFC 4226 の推奨値
+const TOTP_SECRET_BYTES: usize = 20;
+
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+// ログインの2段階目までの猶予（分）
+pub const MFA_TOKEN_MINUTES: i64 = 5;
+
+// 2段階目のログイン用のトークンであることを示す `aud`
+// ログイン用のトークンには `aud` がないため、APIの認証には使えない
+const MFA_AUDIENCE: &str = "md-wiki-mfa";
+
+fn totp(secret: &str, username: &str) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| AppError::Internal(format!("Invalid TOTP secret: {:?}", e)))?;
+    // 発行者名・アカウント名に `:` は使えない
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        username.replace(':', "_"),
+    )
+    .map_err(|e| AppError::Internal(format!("Invalid TOTP secret: {}", e)))
+}
+
+// 新しい秘密鍵（Base32）
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; TOTP_SECRET_BYTES];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+// 認証アプリに登録するための `otpauth://` のURI（QRコードにして表示する）
+pub fn provisioning_uri(secret: &str, username: &str) -> Result<String, AppError> {
+    Ok(totp(secret, username)?.get_url())
+}
+
+// コードが正しければ、その時間ステップを返す
+pub fn verify_code(secret: &str, code: &str) -> Result<Option<i64>, AppError> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let totp = totp(secret, "")?;
+    let now = chrono::Utc::now().timestamp();
+    let current_step = now / TOTP_STEP_SECS as i64;
+
+    Ok((-TOTP_SKEW_STEPS..=TOTP_SKEW_STEPS)
+        .map(|offset| current_step + offset)
+        .find(|step| *step >= 0 && totp.check(code, *step as u64 * TOTP_STEP_SECS)))
+}
+
+// リカバリーコード（`xxxxx-xxxxx` 形式）を作る
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rand::rngs::OsRng.fill_bytes(&mut bytes);
+            let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{}-{}", &hex[..5], &hex[5..])
+        })
+        .collect()
+}
+
+// 区切りや大文字小文字の違いを無視してハッシュにする
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    hash_secret_token(&normalized)
+}
+
+fn generate_nonce() -> String {
+    let mut bytes = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MfaPurpose {
+    // 登録済みのコードを確認する
+    Verify,
+    // 必須になっているがまだ登録していないため、登録してからログインする
+    Setup,
+}
+
+// パスワードを確認した後、2段階目のログインに使うトークン
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MfaClaims {
+    pub sub: i64,  // user id
+    pub purpose: MfaPurpose,
+    pub aud: String,
+    pub exp: usize,
+    // 1回だけ使えるようにするための乱数（使用済みのものは DB に記録する）
+    pub nonce: String,
+}
+
+pub fn create_mfa_token(tokens: &TokenService, user_id: i64, purpose: MfaPurpose) -> Result<String, AppError> {
+    let expiration = chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::minutes(MFA_TOKEN_MINUTES))
+        .expect("valid timestamp")
+        .timestamp() as usize;
+
+    let claims = MfaClaims {
+        sub: user_id,
+        purpose,
+        aud: MFA_AUDIENCE.to_string(),
+        exp: expiration,
+        nonce: generate_nonce(),
+    };
+
+    tokens.sign(&claims)
+}
+
+pub fn verify_mfa_token(tokens: &TokenService, token: &str) -> Result<MfaClaims, AppError> {
+    tokens
+        .verify(token, Some(MFA_AUDIENCE))
+        .map_err(|e| AppError::Auth(format!("Invalid or expired login: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_at(secret: &str, step: i64) -> String {
+        totp(secret, "").unwrap().generate(step as u64 * TOTP_STEP_SECS)
+    }
+
+    #[test]
+    fn codes_within_the_allowed_skew_return_their_step() {
+        let secret = generate_secret();
+        let current_step = chrono::Utc::now().timestamp() / TOTP_STEP_SECS as i64;
+
+        // ステップの境目をまたいでも結果が変わらないよう、前後のステップのコードで確かめる
+        let step = verify_code(&secret, &code_at(&secret, current_step)).unwrap().unwrap();
+        assert!((current_step - 1..=current_step + 1).contains(&step));
+        let step = verify_code(&secret, &format!(" {} ", code_at(&secret, current_step + 1))).unwrap();
+        assert!(step.is_some());
+
+        assert_eq!(verify_code(&secret, &code_at(&secret, current_step - 10)).unwrap(), None);
+        assert_eq!(verify_code(&generate_secret(), &code_at(&secret, current_step)).unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_codes_are_rejected() {
+        let secret = generate_secret();
+        for code in ["", "12345", "1234567", "12345a", "１２３４５６"] {
+            assert_eq!(verify_code(&secret, code).unwrap(), None);
+        }
+        assert!(verify_code("not base32!", "123456").is_err());
+    }
+
+    #[test]
+    fn recovery_codes_ignore_separators_and_case() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| code.len() == 11 && code.as_bytes()[5] == b'-'));
+        assert_eq!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[0].replace('-', " ").to_uppercase()));
+        assert_ne!(hash_recovery_code(&codes[0]), hash_recovery_code(&codes[1]));
+    }
+}
@@ -1,36 +1,417 @@
 use std::path::PathBuf;
 
+use crate::models::acl::Visibility;
+
+// メタデータを保存するデータベースの種類
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatabaseBackend {
+    Sqlite,
+    Postgres,
+}
+
+impl DatabaseBackend {
+    // DATABASE_URL のスキームから判定する（スキームのないパスはSQLiteのファイルとして扱う）
+    pub fn from_url(url: &str) -> Self {
+        if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+            DatabaseBackend::Postgres
+        } else {
+            DatabaseBackend::Sqlite
+        }
+    }
+}
+
+// 実行環境
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Environment {
+    Development,
+    Production,
+}
+
+impl Environment {
+    // APP_ENV が未設定の場合は、デバッグビルドを開発環境とみなす
+    pub fn from_env_value(value: Option<&str>) -> Self {
+        match value.map(|v| v.to_lowercase()) {
+            Some(v) if v == "development" || v == "dev" => Environment::Development,
+            Some(_) => Environment::Production,
+            None if cfg!(debug_assertions) => Environment::Development,
+            None => Environment::Production,
+        }
+    }
+}
+
+// ログインしていない利用者の登録（/api/auth/register）の扱い
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegistrationMode {
+    // 登録を受け付けない（ユーザーは管理者が作成する）
+    Off,
+    // 閲覧者としてだけ登録できる
+    Viewer,
+    // 招待されたユーザーだけが登録できる
+    Invite,
+}
+
+impl RegistrationMode {
+    // 未設定や不明な値は、登録を受け付けない側に倒す
+    pub fn from_env_value(value: Option<&str>) -> Self {
+        match value.map(|v| v.to_lowercase()).as_deref() {
+            Some("viewer") => RegistrationMode::Viewer,
+            Some("invite") | Some("invite-only") => RegistrationMode::Invite,
+            _ => RegistrationMode::Off,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RegistrationMode::Off => "off",
+            RegistrationMode::Viewer => "viewer",
+            RegistrationMode::Invite => "invite",
+        }
+    }
+}
+
+// OpenID Connect によるシングルサインオンの設定（OIDC_ISSUER_URL を設定すると有効になる）
+#[derive(Clone, Debug)]
+pub struct OidcConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    // プロバイダから戻ってくるURL（プロバイダに登録したものと同じにする）
+    pub redirect_url: String,
+    pub scopes: String,
+    // ユーザー名に使うクレーム（なければメールアドレス、subject の順に使う）
+    pub username_claim: String,
+    // ロールの判定に使うクレーム（`realm_access.roles` のようにドットで入れ子を指定できる）
+    pub role_claim: String,
+    // `クレームの値=ロール` をカンマ区切りで指定する。空ならロールを同期しない
+    pub role_mapping: Vec<String>,
+    // どの値にも当てはまらない場合のロール
+    pub default_role: String,
+}
+
+impl OidcConfig {
+    fn from_env() -> Option<Self> {
+        let issuer_url = std::env::var("OIDC_ISSUER_URL").ok().filter(|url| !url.is_empty())?;
+        let env_or = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+
+        Some(Self {
+            issuer_url,
+            client_id: env_or("OIDC_CLIENT_ID", ""),
+            client_secret: std::env::var("OIDC_CLIENT_SECRET").ok().filter(|secret| !secret.is_empty()),
+            redirect_url: env_or("OIDC_REDIRECT_URL", ""),
+            scopes: env_or("OIDC_SCOPES", "openid profile email"),
+            username_claim: env_or("OIDC_USERNAME_CLAIM", "preferred_username"),
+            role_claim: env_or("OIDC_ROLE_CLAIM", "groups"),
+            role_mapping: list_env("OIDC_ROLE_MAPPING"),
+            default_role: env_or("OIDC_DEFAULT_ROLE", "viewer"),
+        })
+    }
+}
+
+// LDAP でのパスワード認証の設定（LDAP_URL を設定すると有効になる）
+#[derive(Clone, Debug)]
+pub struct LdapConfig {
+    pub url: String,
+    pub starttls: bool,
+    // ユーザーの検索に使うアカウント（省略した場合は匿名で検索する）
+    pub bind_dn: Option<String>,
+    pub bind_password: Option<String>,
+    // `uid={username},ou=people,dc=example,dc=com` のように指定すると、検索せずにこのDNでバインドする
+    pub user_dn_template: Option<String>,
+    pub user_base_dn: String,
+    // `{username}` はエスケープしたユーザー名に置き換わる
+    pub user_filter: String,
+    pub username_attribute: String,
+    pub email_attribute: String,
+    // グループの検索（省略した場合はグループを調べない）
+    // フィルタの `{dn}` はユーザーのDN、`{username}` はユーザー名に置き換わる
+    pub group_base_dn: Option<String>,
+    pub group_filter: String,
+    pub group_name_attribute: String,
+    // `グループ名=ロール` をカンマ区切りで指定する。空ならロールを同期しない
+    pub role_mapping: Vec<String>,
+    pub default_role: String,
+}
+
+impl LdapConfig {
+    fn from_env() -> Option<Self> {
+        let url = std::env::var("LDAP_URL").ok().filter(|url| !url.is_empty())?;
+        let env_or = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let env_opt = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        Some(Self {
+            url,
+            starttls: env_opt("LDAP_STARTTLS").is_some_and(|value| matches!(value.to_lowercase().as_str(), "true" | "1" | "yes" | "on")),
+            bind_dn: env_opt("LDAP_BIND_DN"),
+            bind_password: env_opt("LDAP_BIND_PASSWORD"),
+            user_dn_template: env_opt("LDAP_USER_DN_TEMPLATE"),
+            user_base_dn: env_or("LDAP_USER_BASE_DN", ""),
+            user_filter: env_or("LDAP_USER_FILTER", "(uid={username})"),
+            username_attribute: env_or("LDAP_USERNAME_ATTRIBUTE", "uid"),
+            email_attribute: env_or("LDAP_EMAIL_ATTRIBUTE", "mail"),
+            group_base_dn: env_opt("LDAP_GROUP_BASE_DN"),
+            group_filter: env_or("LDAP_GROUP_FILTER", "(|(member={dn})(uniqueMember={dn})(memberUid={username}))"),
+            group_name_attribute: env_or("LDAP_GROUP_NAME_ATTRIBUTE", "cn"),
+            role_mapping: list_env("LDAP_ROLE_MAPPING"),
+            default_role: env_or("LDAP_DEFAULT_ROLE", "viewer"),
+        })
+    }
+}
+
+// カンマ区切りの環境変数
+fn list_env(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|entry| entry.trim().to_string())
+                .filter(|entry| !entry.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// SMTPサーバーとの接続の暗号化
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpTls {
+    // 平文で接続してから STARTTLS で暗号化する（ポート587）
+    StartTls,
+    // 最初から TLS で接続する（ポート465）
+    Tls,
+    // 暗号化しない（ローカルのテスト用サーバーなど）
+    None,
+}
+
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub tls: SmtpTls,
+}
+
+// メールの送り方
+#[derive(Clone, Debug)]
+pub enum MailTransport {
+    // ログに出すだけ（開発用）
+    Log,
+    // ディレクトリに .eml ファイルとして保存する（開発・テスト用）
+    File(PathBuf),
+    Smtp(SmtpConfig),
+}
+
+#[derive(Clone, Debug)]
+pub struct MailConfig {
+    // 差出人（`MD-Wiki <wiki@example.com>` の形式も使える）
+    pub from: String,
+    pub transport: MailTransport,
+}
+
+impl MailConfig {
+    // MAIL_TRANSPORT を指定しなければ、SMTP_HOST があれば SMTP、なければログに出す
+    fn from_env() -> Self {
+        let env_or = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let env_opt = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        let smtp_host = env_opt("SMTP_HOST");
+        let transport = match std::env::var("MAIL_TRANSPORT").ok().map(|v| v.to_lowercase()).as_deref() {
+            Some("log") => MailTransport::Log,
+            Some("file") => MailTransport::File(PathBuf::from(env_or("MAIL_DIR", "./storage/mail"))),
+            Some("smtp") => MailTransport::Smtp(SmtpConfig::from_env(smtp_host.unwrap_or_default())),
+            _ => match smtp_host {
+                Some(host) => MailTransport::Smtp(SmtpConfig::from_env(host)),
+                None => MailTransport::Log,
+            },
+        };
+
+        Self {
+            from: env_or("MAIL_FROM", "MD-Wiki <noreply@localhost>"),
+            transport,
+        }
+    }
+}
+
+impl SmtpConfig {
+    fn from_env(host: String) -> Self {
+        let env_opt = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+
+        let tls = match env_opt("SMTP_TLS").map(|v| v.to_lowercase()).as_deref() {
+            Some("tls") => SmtpTls::Tls,
+            Some("none") => SmtpTls::None,
+            _ => SmtpTls::StartTls,
+        };
+        let default_port = match tls {
+            SmtpTls::Tls => 465,
+            SmtpTls::StartTls | SmtpTls::None => 587,
+        };
+
+        Self {
+            host,
+            port: env_opt("SMTP_PORT").and_then(|s| s.parse().ok()).unwrap_or(default_port),
+            username: env_opt("SMTP_USERNAME"),
+            password: env_opt("SMTP_PASSWORD"),
+            tls,
+        }
+    }
+}
+
+// JWT_SECRET を設定しなかった場合の秘密鍵（開発環境でのみ使える）
+pub const DEFAULT_JWT_SECRET: &str = "your-secret-key";
+
 #[derive(Clone, Debug)]
 pub struct Config {
+    pub environment: Environment,
     pub database_url: String,
+    pub database_backend: DatabaseBackend,
     pub markdown_dir: PathBuf,
     pub jwt_secret: String,
+    // トークンの署名方式（HS256 / RS256 / EdDSA）
+    pub jwt_algorithm: String,
+    // 署名に使う鍵のID（トークンのヘッダの `kid`）
+    pub jwt_key_id: String,
+    // RS256 / EdDSA の鍵（PEM形式のファイル）
+    pub jwt_private_key_file: Option<PathBuf>,
+    pub jwt_public_key_file: Option<PathBuf>,
+    // 検証だけに使う以前の鍵（`kid:アルゴリズム:秘密鍵または公開鍵のファイル` をカンマ区切りで指定）
+    pub jwt_previous_keys: Vec<String>,
     pub server_port: u16,
+    pub max_attachment_size: usize,
+    pub cache_dir: PathBuf,
+    pub wiki_mode: Visibility,
+    // ローカルのパスワードでのログイン・登録を許可するか（シングルサインオンや LDAP だけにする場合は false）
+    pub password_login: bool,
+    // ログインしていない利用者の登録（REGISTRATION_MODE）
+    pub registration_mode: RegistrationMode,
+    // 管理者のロールのユーザーに二要素認証（TOTP）を必須にするか
+    pub totp_required_for_admins: bool,
+    // ログインを続けて失敗できる回数（ユーザー名ごと・IPアドレスごと。0 なら制限しない）
+    pub login_max_failures: u32,
+    pub login_max_failures_per_ip: u32,
+    // 上限に達したときにロックする時間（分）。最後の失敗からこの時間が経つと回数もリセットされる
+    pub login_lockout_minutes: i64,
+    // リバースプロキシが付ける X-Forwarded-For をクライアントのIPアドレスとして使うか
+    pub trust_proxy_headers: bool,
+    // 利用者がブラウザで開くURL（メールに書くリンクに使う）
+    pub public_url: String,
+    pub mail: MailConfig,
+    pub oidc: Option<OidcConfig>,
+    pub ldap: Option<LdapConfig>,
 }
 
 impl Config {
     pub fn from_env() -> Self {
         let database_url = std::env::var("DATABASE_URL")
             .unwrap_or_else(|_| String::from("md_wiki.db"));
+        let database_backend = DatabaseBackend::from_url(&database_url);
         
         let markdown_dir = PathBuf::from(
             std::env::var("MARKDOWN_DIR")
                 .unwrap_or_else(|_| String::from("storage/markdown_files")),
         );
 
+        let environment = Environment::from_env_value(std::env::var("APP_ENV").ok().as_deref());
+
         let jwt_secret = std::env::var("JWT_SECRET")
-            .unwrap_or_else(|_| String::from("your-secret-key"));
+            .unwrap_or_else(|_| String::from(DEFAULT_JWT_SECRET));
+
+        let jwt_algorithm = std::env::var("JWT_ALGORITHM")
+            .unwrap_or_else(|_| String::from("HS256"));
+
+        let jwt_key_id = std::env::var("JWT_KEY_ID")
+            .unwrap_or_else(|_| String::from("default"));
+
+        let jwt_private_key_file = std::env::var("JWT_PRIVATE_KEY_FILE").ok().map(PathBuf::from);
+        let jwt_public_key_file = std::env::var("JWT_PUBLIC_KEY_FILE").ok().map(PathBuf::from);
+
+        let jwt_previous_keys = list_env("JWT_PREVIOUS_KEYS");
 
         let server_port = std::env::var("SERVER_PORT")
             .ok()
             .and_then(|p| p.parse().ok())
             .unwrap_or(3000);
 
+        // 添付ファイルの最大サイズ（バイト）
+        let max_attachment_size = std::env::var("MAX_ATTACHMENT_SIZE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10 * 1024 * 1024);
+
+        // 縮小画像などのキャッシュの保存先（削除しても再生成される）
+        let cache_dir = PathBuf::from(
+            std::env::var("CACHE_DIR")
+                .unwrap_or_else(|_| String::from("storage/cache")),
+        );
+
+        // ウィキ全体の公開設定（public なら未ログインでも閲覧できる。ドキュメントごとに上書きできる）
+        let wiki_mode = std::env::var("WIKI_MODE")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+
+        let password_login = std::env::var("PASSWORD_LOGIN")
+            .map(|value| !matches!(value.to_lowercase().as_str(), "false" | "0" | "no" | "off"))
+            .unwrap_or(true);
+
+        let registration_mode = RegistrationMode::from_env_value(std::env::var("REGISTRATION_MODE").ok().as_deref());
+
+        let totp_required_for_admins = std::env::var("TOTP_REQUIRED_FOR_ADMINS")
+            .is_ok_and(|value| matches!(value.to_lowercase().as_str(), "true" | "1" | "yes" | "on"));
+
+        let login_max_failures = std::env::var("LOGIN_MAX_FAILURES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(5);
+
+        let login_max_failures_per_ip = std::env::var("LOGIN_MAX_FAILURES_PER_IP")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(20);
+
+        let login_lockout_minutes = std::env::var("LOGIN_LOCKOUT_MINUTES")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .filter(|minutes| *minutes > 0)
+            .unwrap_or(15);
+
+        let trust_proxy_headers = std::env::var("TRUST_PROXY_HEADERS")
+            .is_ok_and(|value| matches!(value.to_lowercase().as_str(), "true" | "1" | "yes" | "on"));
+
+        let public_url = std::env::var("PUBLIC_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|| format!("http://localhost:{}", server_port));
+        let mail = MailConfig::from_env();
+
+        let oidc = OidcConfig::from_env();
+        let ldap = LdapConfig::from_env();
+
         Self {
+            environment,
             database_url,
+            database_backend,
             markdown_dir,
             jwt_secret,
+            jwt_algorithm,
+            jwt_key_id,
+            jwt_private_key_file,
+            jwt_public_key_file,
+            jwt_previous_keys,
             server_port,
+            max_attachment_size,
+            cache_dir,
+            wiki_mode,
+            password_login,
+            registration_mode,
+            totp_required_for_admins,
+            login_max_failures,
+            login_max_failures_per_ip,
+            login_lockout_minutes,
+            trust_proxy_headers,
+            public_url,
+            mail,
+            oidc,
+            ldap,
         }
     }
-} 
\ No newline at end of file
+} 
\ No newline at end of file
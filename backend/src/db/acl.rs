@@ -0,0 +1,99 @@
+use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use crate::models::acl::{Permission, PrincipalType};
+use super::DbManager;
+use std::str::FromStr;
+
+// 表示用に相手の名前（ユーザー名・グループ名）も合わせて取り出す
+const ACL_COLUMNS: &str =
+    "a.path, a.principal_type, a.principal_id, COALESCE(u.username, g.name), a.permission";
+const ACL_FROM: &str =
+    "acl_entries a
+     LEFT JOIN users u ON a.principal_type = 'user' AND u.id = a.principal_id
+     LEFT JOIN groups g ON a.principal_type = 'group' AND g.id = a.principal_id";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AclEntry {
+    pub path: String,
+    pub principal_type: PrincipalType,
+    pub principal_id: i64,
+    pub principal: Option<String>,
+    pub permission: Permission,
+}
+
+impl AclEntry {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        let principal_type: String = row.try_get(1)?;
+        let permission: String = row.try_get(4)?;
+        Ok(Self {
+            path: row.try_get(0)?,
+            principal_type: PrincipalType::from_str(&principal_type).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            principal_id: row.try_get(2)?,
+            principal: row.try_get(3)?,
+            permission: Permission::from_str(&permission).map_err(|e| sqlx::Error::Decode(e.into()))?,
+        })
+    }
+}
+
+impl DbManager {
+    // すべての ACL を取得する（一覧・検索で各ドキュメントの権限をまとめて判定するため）
+    pub async fn list_acl_entries(&self) -> Result<Vec<AclEntry>, AppError> {
+        let rows = sqlx::query(&format!("SELECT {} FROM {} ORDER BY a.path, a.id", ACL_COLUMNS, ACL_FROM))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(AclEntry::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 指定したパスに設定された ACL を取得する
+    pub async fn get_acl_entries(&self, paths: &[String]) -> Result<Vec<AclEntry>, AppError> {
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let placeholders: Vec<String> = (1..=paths.len()).map(|i| format!("${}", i)).collect();
+        let sql = format!(
+            "SELECT {} FROM {} WHERE a.path IN ({}) ORDER BY a.path, a.id",
+            ACL_COLUMNS,
+            ACL_FROM,
+            placeholders.join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for path in paths {
+            query = query.bind(path);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(AclEntry::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // パスの ACL を置き換える（空にすると上位のフォルダの設定を継承する）
+    pub async fn set_acl_entries(
+        &self,
+        path: &str,
+        entries: &[(PrincipalType, i64, Permission)],
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM acl_entries WHERE path = $1")
+            .bind(path)
+            .execute(&mut *tx)
+            .await?;
+
+        for (principal_type, principal_id, permission) in entries {
+            sqlx::query(
+                "INSERT INTO acl_entries (path, principal_type, principal_id, permission) VALUES ($1, $2, $3, $4)",
+            )
+            .bind(path)
+            .bind(principal_type.to_string())
+            .bind(*principal_id)
+            .bind(permission.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
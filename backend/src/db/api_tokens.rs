@@ -0,0 +1,124 @@
+use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use crate::models::user::TokenScope;
+use super::{format_timestamp, parse_timestamp, DbManager};
+use std::str::FromStr;
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+// トークンのハッシュは読み出さない
+const API_TOKEN_COLUMNS: &str =
+    "id, user_id, name, scope, expires_at, last_used_at, revoked_at, CAST(created_at AS TEXT)";
+
+// 最終利用日時はこの間隔（秒）より細かくは更新しない
+const LAST_USED_INTERVAL_SECS: i64 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub scope: TokenScope,
+    pub expires_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub created_at: String,
+}
+
+impl ApiToken {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        let scope: String = row.try_get(3)?;
+        Ok(Self {
+            id: row.try_get(0)?,
+            user_id: row.try_get(1)?,
+            name: row.try_get(2)?,
+            scope: TokenScope::from_str(&scope).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            expires_at: row.try_get(4)?,
+            last_used_at: row.try_get(5)?,
+            revoked_at: row.try_get(6)?,
+            created_at: row.try_get(7)?,
+        })
+    }
+
+    // 取り消されておらず、期限内か
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > format_timestamp(chrono::Utc::now())
+    }
+
+    // 有効期限（UNIX時間）
+    pub fn expires_at_timestamp(&self) -> i64 {
+        parse_timestamp(&self.expires_at).map_or(0, |time| time.timestamp())
+    }
+}
+
+impl DbManager {
+    pub async fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        token_hash: &str,
+        scope: TokenScope,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<ApiToken, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO api_tokens (user_id, name, token_hash, scope, expires_at)
+             VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+            API_TOKEN_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(name)
+        .bind(token_hash)
+        .bind(scope.to_string())
+        .bind(format_timestamp(expires_at))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ApiToken::from_row(&row)?)
+    }
+
+    pub async fn get_api_token_by_hash(&self, token_hash: &str) -> Result<Option<ApiToken>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM api_tokens WHERE token_hash = $1", API_TOKEN_COLUMNS))
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(ApiToken::from_row).transpose()?)
+    }
+
+    // ユーザーのトークンの一覧（取り消したものを除く、新しい順）
+    pub async fn list_user_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM api_tokens WHERE user_id = $1 AND revoked_at IS NULL ORDER BY id DESC",
+            API_TOKEN_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(ApiToken::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // ユーザーのトークンを取り消す（該当するトークンがなければ false）
+    pub async fn revoke_api_token(&self, id: i64, user_id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE api_tokens SET revoked_at = $1 WHERE id = $2 AND user_id = $3 AND revoked_at IS NULL")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 最終利用日時を記録する（リクエストごとに書き込まないよう間隔を空ける）
+    pub async fn touch_api_token(&self, id: i64) -> Result<(), AppError> {
+        let now = chrono::Utc::now();
+        sqlx::query(
+            "UPDATE api_tokens SET last_used_at = $1
+             WHERE id = $2 AND (last_used_at IS NULL OR last_used_at < $3)",
+        )
+        .bind(format_timestamp(now))
+        .bind(id)
+        .bind(format_timestamp(now - chrono::Duration::seconds(LAST_USED_INTERVAL_SECS)))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
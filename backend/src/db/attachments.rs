@@ -0,0 +1,100 @@
+use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use super::DbManager;
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+const ATTACHMENT_COLUMNS: &str =
+    "id, document_filename, filename, content_type, size, hash, uploaded_by, CAST(created_at AS TEXT)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i64,
+    pub document: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub hash: String,
+    pub uploaded_by: Option<i64>,
+    pub created_at: String,
+}
+
+// 登録する添付ファイルの情報（内容はストアに保存済みであること）
+#[derive(Debug, Clone)]
+pub struct NewAttachment {
+    pub document: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+    pub hash: String,
+    pub uploaded_by: Option<i64>,
+}
+
+impl Attachment {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            document: row.try_get(1)?,
+            filename: row.try_get(2)?,
+            content_type: row.try_get(3)?,
+            size: row.try_get(4)?,
+            hash: row.try_get(5)?,
+            uploaded_by: row.try_get(6)?,
+            created_at: row.try_get(7)?,
+        })
+    }
+}
+
+impl DbManager {
+    // 添付ファイルを登録する
+    // 同じドキュメントに同じ名前・内容のファイルが登録済みなら既存のものを返す
+    pub async fn create_attachment(&self, attachment: NewAttachment) -> Result<Attachment, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO attachments (document_filename, filename, content_type, size, hash, uploaded_by)
+             VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT DO NOTHING",
+        )
+        .bind(&attachment.document)
+        .bind(&attachment.filename)
+        .bind(&attachment.content_type)
+        .bind(attachment.size)
+        .bind(&attachment.hash)
+        .bind(attachment.uploaded_by)
+        .execute(&mut *tx)
+        .await?;
+
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM attachments WHERE document_filename = $1 AND hash = $2 AND filename = $3",
+            ATTACHMENT_COLUMNS
+        ))
+        .bind(&attachment.document)
+        .bind(&attachment.hash)
+        .bind(&attachment.filename)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Attachment::from_row(&row)?)
+    }
+
+    pub async fn get_attachment(&self, id: i64) -> Result<Option<Attachment>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM attachments WHERE id = $1", ATTACHMENT_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Attachment::from_row).transpose()?)
+    }
+
+    pub async fn list_document_attachments(&self, document: &str) -> Result<Vec<Attachment>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM attachments WHERE document_filename = $1 ORDER BY id",
+            ATTACHMENT_COLUMNS
+        ))
+        .bind(document)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(Attachment::from_row).collect::<Result<_, _>>()?)
+    }
+}
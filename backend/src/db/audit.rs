@@ -0,0 +1,138 @@
+use serde::Serialize;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use super::{format_timestamp, DbManager};
+
+const AUDIT_COLUMNS: &str =
+    "id, action, actor_id, actor, ip_address, user_agent, target_type, target, before_summary, after_summary, created_at";
+
+// 監査ログの記録
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub action: String,
+    pub actor_id: Option<i64>,
+    // 記録した時点のユーザー名（ログインの失敗では入力されたユーザー名）
+    pub actor: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub target_type: String,
+    pub target: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: String,
+}
+
+impl AuditEntry {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            action: row.try_get(1)?,
+            actor_id: row.try_get(2)?,
+            actor: row.try_get(3)?,
+            ip_address: row.try_get(4)?,
+            user_agent: row.try_get(5)?,
+            target_type: row.try_get(6)?,
+            target: row.try_get(7)?,
+            before: row.try_get(8)?,
+            after: row.try_get(9)?,
+            created_at: row.try_get(10)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct NewAuditEntry<'a> {
+    pub action: &'a str,
+    pub actor_id: Option<i64>,
+    pub actor: Option<&'a str>,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub target_type: &'a str,
+    pub target: &'a str,
+    pub before: Option<&'a str>,
+    pub after: Option<&'a str>,
+}
+
+// 監査ログの絞り込み条件（指定したものすべてに一致する記録）
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    // `document.*` のように `.*` で終わる場合は前方一致
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target: Option<String>,
+    pub ip_address: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    // この日時を含まない
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl DbManager {
+    // 監査ログに追記する（記録は変更・削除できない）
+    pub async fn record_audit(&self, entry: &NewAuditEntry<'_>) -> Result<(), AppError> {
+        sqlx::query(
+            "INSERT INTO audit_log
+             (action, actor_id, actor, ip_address, user_agent, target_type, target, before_summary, after_summary, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+        )
+        .bind(entry.action)
+        .bind(entry.actor_id)
+        .bind(entry.actor)
+        .bind(entry.ip_address)
+        .bind(entry.user_agent)
+        .bind(entry.target_type)
+        .bind(entry.target)
+        .bind(entry.before)
+        .bind(entry.after)
+        .bind(format_timestamp(chrono::Utc::now()))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 監査ログ（新しい順）。`before` を指定した場合はそのIDより前のもの
+    pub async fn list_audit_entries(
+        &self,
+        filter: &AuditFilter,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<AuditEntry>, AppError> {
+        let (action, action_prefix) = match filter.action.as_deref() {
+            Some(action) => match action.strip_suffix('*') {
+                Some(prefix) => (None, Some(format!("{}%", prefix))),
+                None => (Some(action), None),
+            },
+            None => (None, None),
+        };
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM audit_log
+             WHERE ($1 IS NULL OR actor = $1)
+               AND ($2 IS NULL OR action = $2)
+               AND ($3 IS NULL OR action LIKE $3)
+               AND ($4 IS NULL OR target_type = $4)
+               AND ($5 IS NULL OR target = $5)
+               AND ($6 IS NULL OR ip_address = $6)
+               AND ($7 IS NULL OR created_at >= $7)
+               AND ($8 IS NULL OR created_at < $8)
+               AND ($9 IS NULL OR id < $9)
+             ORDER BY id DESC LIMIT $10",
+            AUDIT_COLUMNS
+        ))
+        .bind(filter.actor.as_deref())
+        .bind(action)
+        .bind(action_prefix)
+        .bind(filter.target_type.as_deref())
+        .bind(filter.target.as_deref())
+        .bind(filter.ip_address.as_deref())
+        .bind(filter.since.map(format_timestamp))
+        .bind(filter.until.map(format_timestamp))
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(AuditEntry::from_row).collect::<Result<_, _>>()?)
+    }
+}
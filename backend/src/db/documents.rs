@@ -1,7 +1,16 @@
-use rusqlite::{params, OptionalExtension, Result as RusqliteResult};
 use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::{AnyPool, Row};
 use crate::error::AppError;
-use super::DbManager;
+use crate::models::acl::Visibility;
+use crate::storage::StoredDocument;
+use super::tags::resolve_tag_id;
+use super::{format_timestamp, DbManager};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+const DOCUMENT_COLUMNS: &str = "id, filename, title, CAST(created_at AS TEXT), CAST(updated_at AS TEXT), visibility";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentMeta {
@@ -11,134 +20,369 @@ pub struct DocumentMeta {
     pub created_at: String,
     pub updated_at: String,
     pub tags: Vec<String>,
+    // 未設定ならウィキ全体の設定に従う
+    pub visibility: Option<Visibility>,
+}
+
+// ドキュメント一覧の並び順に使う列
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentSort {
+    Title,
+    #[default]
+    UpdatedAt,
+    CreatedAt,
+}
+
+impl DocumentSort {
+    // 並び順に使う式（タイトル未設定のドキュメントはファイル名で並べる）
+    fn expression(self) -> &'static str {
+        match self {
+            DocumentSort::Title => "LOWER(COALESCE(title, filename))",
+            DocumentSort::UpdatedAt => "updated_at",
+            DocumentSort::CreatedAt => "created_at",
+        }
+    }
+}
+
+// ドキュメント一覧の1回分の取得条件
+// `after` は前ページ最後のドキュメントの (並び順の値, ファイル名)
+#[derive(Debug, Clone, Default)]
+pub struct DocumentListQuery {
+    pub folder: Option<String>,
+    pub tag: Option<String>,
+    pub sort: DocumentSort,
+    pub descending: bool,
+    pub after: Option<(String, String)>,
+    pub limit: usize,
+}
+
+fn parse_visibility(visibility: Option<String>) -> Result<Option<Visibility>, sqlx::Error> {
+    visibility.as_deref().map(Visibility::from_str).transpose().map_err(|e| sqlx::Error::Decode(e.into()))
+}
+
+impl DocumentMeta {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            filename: row.try_get(1)?,
+            title: row.try_get(2)?,
+            created_at: row.try_get(3)?,
+            updated_at: row.try_get(4)?,
+            tags: Vec::new(),
+            visibility: parse_visibility(row.try_get(5)?)?,
+        })
+    }
+}
+
+// ドキュメントに付いているタグ名を取得する
+async fn document_tags(pool: &AnyPool, document_id: i64) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT t.name FROM tags t JOIN document_tags dt ON t.id = dt.tag_id WHERE dt.document_id = $1 ORDER BY t.name")
+        .bind(document_id)
+        .fetch_all(pool)
+        .await
 }
 
 impl DbManager {
     pub async fn get_document_metadata(&self, filename: &str) -> Result<Option<DocumentMeta>, AppError> {
-        let filename_clone = filename.to_string();
-        self.conn.call(move |conn| {
-            let mut doc_meta: Option<DocumentMeta> = conn.query_row(
-                "SELECT id, filename, title, created_at, updated_at FROM documents WHERE filename = ?",
-                params![filename_clone],
-                |row| {
-                    Ok(DocumentMeta {
-                        id: row.get(0)?,
-                        filename: row.get(1)?,
-                        title: row.get(2)?,
-                        created_at: row.get(3)?,
-                        updated_at: row.get(4)?,
-                        tags: Vec::new(),
-                    })
-                },
-            ).optional()?;
-
-            if let Some(ref mut doc) = doc_meta {
-                let mut stmt = conn.prepare("SELECT t.name FROM tags t JOIN document_tags dt ON t.id = dt.tag_id WHERE dt.document_id = ?")?;
-                let tags = stmt.query_map(params![doc.id], |row| row.get(0))?.collect::<RusqliteResult<Vec<String>>>()?;
-                doc.tags = tags;
-            }
+        let row = sqlx::query(&format!("SELECT {} FROM documents WHERE filename = $1", DOCUMENT_COLUMNS))
+            .bind(filename)
+            .fetch_optional(&self.pool)
+            .await?;
 
-            Ok(doc_meta)
-        }).await.map_err(|e| AppError::Database(e.to_string()))
+        match row {
+            Some(row) => {
+                let mut doc = DocumentMeta::from_row(&row)?;
+                doc.tags = document_tags(&self.pool, doc.id).await?;
+                Ok(Some(doc))
+            }
+            None => Ok(None),
+        }
     }
 
     pub async fn create_document_metadata(&self, filename: &str, title: Option<&str>) -> Result<i64, AppError> {
-        let filename_clone = filename.to_string();
-        let title_clone = title.map(|s| s.to_string());
-        self.conn.call(move |conn| {
-            conn.execute(
-                "INSERT INTO documents (filename, title) VALUES (?, ?)",
-                params![filename_clone, title_clone],
-            )?;
-            Ok(conn.last_insert_rowid())
-        }).await.map_err(|e| AppError::Database(e.to_string()))
+        let id = sqlx::query_scalar("INSERT INTO documents (filename, title) VALUES ($1, $2) RETURNING id")
+            .bind(filename)
+            .bind(title)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
     }
 
+    // updated_at はトリガーで更新される
     pub async fn update_document_metadata(&self, filename: &str, title: Option<&str>) -> Result<bool, AppError> {
-        let filename_clone = filename.to_string();
-        let title_clone = title.map(|s| s.to_string());
-        self.conn.call(move |conn| {
-            let rows = conn.execute(
-                "UPDATE documents SET title = ?, updated_at = CURRENT_TIMESTAMP WHERE filename = ?",
-                params![title_clone, filename_clone],
-            )?;
-            Ok(rows > 0)
-        }).await.map_err(|e| AppError::Database(e.to_string()))
+        let result = sqlx::query("UPDATE documents SET title = $1 WHERE filename = $2")
+            .bind(title)
+            .bind(filename)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
     }
 
     pub async fn delete_document_metadata(&self, filename: &str) -> Result<bool, AppError> {
-        let filename_clone = filename.to_string();
-        self.conn.call(move |conn| {
-            let tx = conn.transaction()?;
-            
-            let document_id: Option<i64> = tx.query_row("SELECT id FROM documents WHERE filename = ?", params![filename_clone], |row| row.get(0)).optional()?;
-            
-            if let Some(id) = document_id {
-                tx.execute("DELETE FROM document_tags WHERE document_id = ?", params![id])?;
-                tx.execute("DELETE FROM documents WHERE id = ?", params![id])?;
-                tx.commit()?;
-                Ok(true)
-            } else {
-                Ok(false)
-            }
-        }).await.map_err(|e| AppError::Database(e.to_string()))
+        let mut tx = self.pool.begin().await?;
+
+        let document_id: Option<i64> = sqlx::query_scalar("SELECT id FROM documents WHERE filename = $1")
+            .bind(filename)
+            .fetch_optional(&mut *tx)
+            .await?;
+
+        // タグの付与は外部キーの ON DELETE CASCADE で削除される
+        if let Some(id) = document_id {
+            sqlx::query("DELETE FROM documents WHERE id = $1").bind(id).execute(&mut *tx).await?;
+            tx.commit().await?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    // ドキュメントの名前の変更に合わせて、メタデータ・添付ファイル・ACL・共有リンクを移す
+    // 変更先の名前に残っていた古いメタデータやACLは、移すものに置き換える
+    pub async fn rename_document_metadata(&self, from: &str, to: &str) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let stale_id: Option<i64> = sqlx::query_scalar("SELECT id FROM documents WHERE filename = $1")
+            .bind(to)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(id) = stale_id {
+            sqlx::query("DELETE FROM document_tags WHERE document_id = $1").bind(id).execute(&mut *tx).await?;
+            sqlx::query("DELETE FROM documents WHERE id = $1").bind(id).execute(&mut *tx).await?;
+        }
+        sqlx::query("UPDATE documents SET filename = $1 WHERE filename = $2")
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM acl_entries WHERE path = $1").bind(to).execute(&mut *tx).await?;
+        sqlx::query("UPDATE acl_entries SET path = $1 WHERE path = $2")
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+
+        // 同じ添付ファイルが変更先にも登録されていれば、そちらを残す
+        sqlx::query(
+            "UPDATE attachments SET document_filename = $1
+             WHERE document_filename = $2
+               AND NOT EXISTS (
+                   SELECT 1 FROM attachments other
+                   WHERE other.document_filename = $1 AND other.hash = attachments.hash AND other.filename = attachments.filename
+               )",
+        )
+        .bind(to)
+        .bind(from)
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM attachments WHERE document_filename = $1").bind(from).execute(&mut *tx).await?;
+
+        sqlx::query("UPDATE share_links SET document_filename = $1 WHERE document_filename = $2")
+            .bind(to)
+            .bind(from)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
     }
 
     pub async fn list_documents(&self) -> Result<Vec<DocumentMeta>, AppError> {
-        self.conn.call(move |conn| {
-            let mut stmt = conn.prepare("SELECT id, filename, title, created_at, updated_at FROM documents")?;
-            
-            let docs_iter = stmt.query_map([], |row| {
-                Ok(DocumentMeta {
-                    id: row.get(0)?,
-                    filename: row.get(1)?,
-                    title: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                    tags: Vec::new(),
-                })
-            })?;
-
-            let mut documents = Vec::new();
-            for doc_result in docs_iter {
-                let mut doc = doc_result?;
-                let mut tag_stmt = conn.prepare("SELECT t.name FROM tags t JOIN document_tags dt ON t.id = dt.tag_id WHERE dt.document_id = ?")?;
-                let tags = tag_stmt.query_map(params![doc.id], |row| row.get(0))?.collect::<RusqliteResult<Vec<String>>>()?;
-                doc.tags = tags;
-                documents.push(doc);
-            }
-            
-            Ok(documents)
-        }).await.map_err(|e| AppError::Database(e.to_string()))
+        let rows = sqlx::query(&format!("SELECT {} FROM documents", DOCUMENT_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut documents = Vec::new();
+        for row in &rows {
+            let mut doc = DocumentMeta::from_row(row)?;
+            doc.tags = document_tags(&self.pool, doc.id).await?;
+            documents.push(doc);
+        }
+
+        Ok(documents)
+    }
+
+    // 並び順とカーソルをSQLで扱い、条件に合うドキュメントを `limit` 件まで取得する
+    // 返す文字列は並び順の値で、次のページのカーソルに使う
+    // タグは同義語を正規タグに解決し、子孫タグ（`infra` なら `infra/k8s`）が付いたドキュメントも含める
+    pub async fn list_documents_page(&self, query: &DocumentListQuery) -> Result<Vec<(String, DocumentMeta)>, AppError> {
+        let mut conn = self.pool.acquire().await?;
+
+        let tag_id = match &query.tag {
+            Some(tag) => match resolve_tag_id(&mut conn, tag).await? {
+                Some(id) => Some(id),
+                None => return Ok(Vec::new()),
+            },
+            None => None,
+        };
+
+        let sort = query.sort.expression();
+        let (compare, direction) = if query.descending { ("<", "DESC") } else { (">", "ASC") };
+        let mut conditions = Vec::new();
+        let mut placeholder = 0;
+        let mut next = || {
+            placeholder += 1;
+            format!("${}", placeholder)
+        };
+
+        let mut sql = String::new();
+        if tag_id.is_some() {
+            let tag = next();
+            sql.push_str(&format!(
+                "WITH RECURSIVE tag_tree(id) AS (
+                     SELECT CAST({} AS BIGINT)
+                     UNION
+                     SELECT t.id FROM tags t JOIN tag_tree tree ON t.parent_id = tree.id
+                 ) ",
+                tag
+            ));
+            conditions.push("id IN (SELECT dt.document_id FROM document_tags dt WHERE dt.tag_id IN (SELECT id FROM tag_tree))".to_string());
+        }
+        if query.folder.is_some() {
+            let prefix = next();
+            conditions.push(format!("SUBSTR(filename, 1, LENGTH({p})) = {p}", p = prefix));
+        }
+        if query.after.is_some() {
+            let (value, filename) = (next(), next());
+            conditions.push(format!(
+                "({sort} {c} {v} OR ({sort} = {v} AND filename {c} {f}))",
+                sort = sort,
+                c = compare,
+                v = value,
+                f = filename
+            ));
+        }
+        let limit = next();
+
+        sql.push_str(&format!("SELECT {}, CAST({} AS TEXT) FROM documents", DOCUMENT_COLUMNS, sort));
+        if !conditions.is_empty() {
+            sql.push_str(&format!(" WHERE {}", conditions.join(" AND ")));
+        }
+        sql.push_str(&format!(" ORDER BY {s} {d}, filename {d} LIMIT {l}", s = sort, d = direction, l = limit));
+
+        let mut statement = sqlx::query(&sql);
+        if let Some(tag_id) = tag_id {
+            statement = statement.bind(tag_id);
+        }
+        if let Some(folder) = &query.folder {
+            statement = statement.bind(format!("{}/", folder));
+        }
+        if let Some((value, filename)) = &query.after {
+            statement = statement.bind(value).bind(filename);
+        }
+        let rows = statement.bind(query.limit as i64).fetch_all(&mut *conn).await?;
+        // SQLiteの接続は1本のため、タグを取得する前に返す
+        drop(conn);
+
+        let mut documents = Vec::new();
+        for row in &rows {
+            let mut doc = DocumentMeta::from_row(row)?;
+            doc.tags = document_tags(&self.pool, doc.id).await?;
+            documents.push((row.try_get(6)?, doc));
+        }
+
+        Ok(documents)
+    }
+
+    // ドキュメントの書き込みを一覧の索引に反映する（メタデータがなければ作る）
+    pub async fn touch_document(&self, filename: &str) -> Result<(), AppError> {
+        let now = format_timestamp(chrono::Utc::now());
+        sqlx::query(
+            "INSERT INTO documents (filename, created_at, updated_at) VALUES ($1, $2, $2)
+             ON CONFLICT (filename) DO UPDATE SET updated_at = $2",
+        )
+        .bind(filename)
+        .bind(&now)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ストアにあってメタデータのないドキュメントを一覧の索引に加える（起動時に呼ぶ）
+    // 日時はストアのものを使う
+    pub async fn sync_documents(&self, stored: &[StoredDocument]) -> Result<usize, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let now = format_timestamp(chrono::Utc::now());
+        let mut added = 0;
+        for doc in stored {
+            let created_at = if doc.created_at.is_empty() { &now } else { &doc.created_at };
+            let updated_at = if doc.updated_at.is_empty() { &now } else { &doc.updated_at };
+            let result = sqlx::query(
+                "INSERT INTO documents (filename, created_at, updated_at) VALUES ($1, $2, $3) ON CONFLICT (filename) DO NOTHING",
+            )
+            .bind(&doc.name)
+            .bind(created_at)
+            .bind(updated_at)
+            .execute(&mut *tx)
+            .await?;
+            added += result.rows_affected() as usize;
+        }
+
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    pub async fn list_recent_documents_meta(&self, limit: u32, offset: u32) -> Result<Vec<DocumentMeta>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM documents ORDER BY updated_at DESC, id DESC LIMIT $1 OFFSET $2",
+            DOCUMENT_COLUMNS
+        ))
+            .bind(i64::from(limit))
+            .bind(i64::from(offset))
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut documents = Vec::new();
+        for row in &rows {
+            let mut doc = DocumentMeta::from_row(row)?;
+            doc.tags = document_tags(&self.pool, doc.id).await?;
+            documents.push(doc);
+        }
+
+        Ok(documents)
     }
 
-    pub async fn list_recent_documents_meta(&self, limit: u32) -> Result<Vec<DocumentMeta>, AppError> {
-        self.conn.call(move |conn| {
-            let mut stmt = conn.prepare(
-                "SELECT id, filename, title, created_at, updated_at FROM documents ORDER BY updated_at DESC LIMIT ?"
-            )?;
-            
-            let docs_iter = stmt.query_map(params![limit], |row| {
-                Ok(DocumentMeta {
-                    id: row.get(0)?,
-                    filename: row.get(1)?,
-                    title: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                    tags: Vec::new(),
-                })
-            })?;
-
-            let mut documents = Vec::new();
-            for doc_result in docs_iter {
-                let mut doc = doc_result?;
-                let mut tag_stmt = conn.prepare("SELECT t.name FROM tags t JOIN document_tags dt ON t.id = dt.tag_id WHERE dt.document_id = ?")?;
-                let tags = tag_stmt.query_map(params![doc.id], |row| row.get(0))?.collect::<RusqliteResult<Vec<String>>>()?;
-                doc.tags = tags;
-                documents.push(doc);
+    // 公開設定を変更する（メタデータがなければ作る）
+    pub async fn set_document_visibility(&self, filename: &str, visibility: Option<Visibility>) -> Result<(), AppError> {
+        let visibility = visibility.map(|v| v.to_string());
+        let result = sqlx::query("UPDATE documents SET visibility = $1 WHERE filename = $2")
+            .bind(&visibility)
+            .bind(filename)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            sqlx::query("INSERT INTO documents (filename, visibility) VALUES ($1, $2)")
+                .bind(filename)
+                .bind(&visibility)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn get_document_visibility(&self, filename: &str) -> Result<Option<Visibility>, AppError> {
+        let visibility: Option<Option<String>> = sqlx::query_scalar("SELECT visibility FROM documents WHERE filename = $1")
+            .bind(filename)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(parse_visibility(visibility.flatten())?)
+    }
+
+    // 公開設定が明示されているドキュメントの一覧
+    pub async fn list_document_visibility(&self) -> Result<HashMap<String, Visibility>, AppError> {
+        let rows = sqlx::query("SELECT filename, visibility FROM documents WHERE visibility IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut visibility = HashMap::new();
+        for row in &rows {
+            if let Some(v) = parse_visibility(row.try_get(1)?)? {
+                visibility.insert(row.try_get(0)?, v);
             }
-            
-            Ok(documents)
-        }).await.map_err(|e| AppError::Database(e.to_string()))
+        }
+        Ok(visibility)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,155 @@
+use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use crate::models::user::Role;
+use super::DbManager;
+use std::str::FromStr;
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+const GROUP_COLUMNS: &str =
+    "id, name, description, role, CAST(created_at AS TEXT), CAST(updated_at AS TEXT)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    // メンバー全員に与えるロール
+    pub role: Option<Role>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Group {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        let role: Option<String> = row.try_get(3)?;
+        Ok(Self {
+            id: row.try_get(0)?,
+            name: row.try_get(1)?,
+            description: row.try_get(2)?,
+            role: role.as_deref().map(Role::from_str).transpose().map_err(|e| sqlx::Error::Decode(e.into()))?,
+            created_at: row.try_get(4)?,
+            updated_at: row.try_get(5)?,
+        })
+    }
+}
+
+impl DbManager {
+    pub async fn create_group(&self, name: &str, description: Option<&str>, role: Option<Role>) -> Result<Group, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO groups (name, description, role) VALUES ($1, $2, $3) RETURNING {}",
+            GROUP_COLUMNS
+        ))
+        .bind(name)
+        .bind(description)
+        .bind(role.map(|r| r.to_string()))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Group::from_row(&row)?)
+    }
+
+    pub async fn get_group(&self, name: &str) -> Result<Option<Group>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM groups WHERE name = $1", GROUP_COLUMNS))
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Group::from_row).transpose()?)
+    }
+
+    pub async fn list_groups(&self) -> Result<Vec<Group>, AppError> {
+        let rows = sqlx::query(&format!("SELECT {} FROM groups ORDER BY name", GROUP_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(Group::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 説明とロールを置き換える
+    pub async fn update_group(&self, name: &str, description: Option<&str>, role: Option<Role>) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE groups SET description = $1, role = $2 WHERE name = $3")
+            .bind(description)
+            .bind(role.map(|r| r.to_string()))
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // グループとそのメンバー・ACLの設定を削除する
+    pub async fn delete_group(&self, name: &str) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let group_id: Option<i64> = sqlx::query_scalar("SELECT id FROM groups WHERE name = $1")
+            .bind(name)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let group_id = match group_id {
+            Some(id) => id,
+            None => return Ok(false),
+        };
+
+        sqlx::query("DELETE FROM acl_entries WHERE principal_type = 'group' AND principal_id = $1")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM group_members WHERE group_id = $1")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM groups WHERE id = $1")
+            .bind(group_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    pub async fn add_group_member(&self, group_id: i64, user_id: i64) -> Result<(), AppError> {
+        sqlx::query("INSERT INTO group_members (group_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn remove_group_member(&self, group_id: i64, user_id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM group_members WHERE group_id = $1 AND user_id = $2")
+            .bind(group_id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // グループのメンバーのユーザー名
+    pub async fn list_group_members(&self, group_id: i64) -> Result<Vec<String>, AppError> {
+        let members = sqlx::query_scalar(
+            "SELECT u.username FROM group_members m JOIN users u ON u.id = m.user_id
+             WHERE m.group_id = $1 ORDER BY u.username",
+        )
+        .bind(group_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    // ユーザーが所属するグループ
+    pub async fn list_user_groups(&self, user_id: i64) -> Result<Vec<Group>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM groups WHERE id IN (SELECT group_id FROM group_members WHERE user_id = $1) ORDER BY name",
+            GROUP_COLUMNS
+        ))
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(Group::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // ユーザー自身のロールと所属グループのロールのうち最も強いもの
+    pub async fn effective_role(&self, user_id: i64, role: Role) -> Result<Role, AppError> {
+        let groups = self.list_user_groups(user_id).await?;
+        Ok(groups.into_iter().filter_map(|group| group.role).fold(role, Role::max))
+    }
+}
@@ -0,0 +1,132 @@
+use crate::auth::provider::ExternalIdentity;
+use crate::error::AppError;
+use crate::models::user::{Role, User};
+use super::{format_timestamp, DbManager, NewAuditEntry};
+
+// 同じユーザー名のユーザーがいる場合に試す連番の上限
+const MAX_USERNAME_SUFFIX: u32 = 100;
+
+impl DbManager {
+    // 外部のアカウント（発行者と subject の組）に紐づいたユーザー
+    pub async fn get_user_by_identity(&self, issuer: &str, subject: &str) -> Result<Option<User>, AppError> {
+        let user_id: Option<i64> = sqlx::query_scalar("SELECT user_id FROM user_identities WHERE issuer = $1 AND subject = $2")
+            .bind(issuer)
+            .bind(subject)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        match user_id {
+            Some(user_id) => self.get_user_by_id(user_id).await,
+            None => Ok(None),
+        }
+    }
+
+    pub async fn email_exists(&self, email: &str) -> Result<bool, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count > 0)
+    }
+
+    // 外部の認証基盤で確認したユーザーを返す。初めてのログインならユーザーを作る
+    // 既存のローカルユーザーとはユーザー名やメールアドレスが同じでも紐づけない
+    pub async fn provision_external_user(&self, identity: &ExternalIdentity) -> Result<User, AppError> {
+        if let Some(mut user) = self.get_user_by_identity(&identity.issuer, &identity.subject).await? {
+            if identity.sync_role && user.role != identity.role {
+                self.update_user_role(&user.username, &identity.role.to_string()).await?;
+                let (before, after) = (user.role.to_string(), identity.role.to_string());
+                self.audit_provisioning(&user, "user.role_change", Some(&before), &after, &identity.issuer).await;
+                user.role = identity.role;
+            }
+            return Ok(user);
+        }
+
+        let username = self.available_username(&identity.username).await?;
+        // メールアドレスは一意なので、他のユーザーが使っていれば保存しない
+        let email = match identity.email.as_deref() {
+            Some(email) if !self.email_exists(email).await? => Some(email),
+            _ => None,
+        };
+
+        let user = self
+            .create_external_user(&username, email, identity.role, &identity.issuer, &identity.subject)
+            .await?;
+        self.audit_provisioning(&user, "user.create", None, &format!("role {}", user.role), &identity.issuer)
+            .await;
+        Ok(user)
+    }
+
+    // 外部の認証基盤でのログインによるユーザーの作成とロールの変更を監査ログに残す
+    // ログインしたユーザー自身を操作したユーザーとし、どの認証基盤のグループに従ったかを残す
+    // ユーザーは既に作成・更新しているため、記録に失敗してもログインは失敗にせず、エラーをログに残す
+    async fn audit_provisioning(&self, user: &User, action: &str, before: Option<&str>, after: &str, issuer: &str) {
+        let recorded = self.record_audit(&NewAuditEntry {
+            action,
+            actor_id: Some(user.id),
+            actor: Some(&user.username),
+            ip_address: None,
+            user_agent: None,
+            target_type: "user",
+            target: &user.username,
+            before,
+            after: Some(&format!("{} (from {})", after, issuer)),
+        })
+        .await;
+        if let Err(e) = recorded {
+            tracing::error!("Failed to record audit event {} for user {}: {}", action, user.username, e);
+        }
+    }
+
+    // ユーザー名が使われていれば `名前-2`、`名前-3` … を試す
+    async fn available_username(&self, base: &str) -> Result<String, AppError> {
+        if self.get_user_by_username(base).await?.is_none() {
+            return Ok(base.to_string());
+        }
+        for suffix in 2..=MAX_USERNAME_SUFFIX {
+            let candidate = format!("{}-{}", base, suffix);
+            if self.get_user_by_username(&candidate).await?.is_none() {
+                return Ok(candidate);
+            }
+        }
+        Err(AppError::Internal(format!("No available username for {}", base)))
+    }
+
+    // 外部の認証基盤で初めてログインしたユーザーを作り、外部のアカウントと紐づける
+    // パスワードは設定しない（空のハッシュはどのパスワードとも一致しない）
+    pub async fn create_external_user(
+        &self,
+        username: &str,
+        email: Option<&str>,
+        role: Role,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<User, AppError> {
+        let now = format_timestamp(chrono::Utc::now());
+        let mut tx = self.pool.begin().await?;
+
+        let user_id: i64 = sqlx::query_scalar(
+            "INSERT INTO users (username, password_hash, role, email, created_at, updated_at) VALUES ($1, '', $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(username)
+        .bind(role.to_string())
+        .bind(email)
+        .bind(&now)
+        .bind(&now)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query("INSERT INTO user_identities (user_id, issuer, subject) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(issuer)
+            .bind(subject)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        self.get_user_by_id(user_id)
+            .await?
+            .ok_or_else(|| AppError::Internal("Created user not found".to_string()))
+    }
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use crate::models::user::Role;
+use super::users::insert_user;
+use super::{format_timestamp, DbManager};
+use std::str::FromStr;
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+const INVITATION_COLUMNS: &str =
+    "id, username, email, role, created_by, expires_at, accepted_at, user_id, revoked_at, CAST(created_at AS TEXT)";
+
+// 招待（トークンそのものは保存しない）
+#[derive(Debug, Clone, Serialize)]
+pub struct Invitation {
+    pub id: i64,
+    // 指定した場合は、このユーザー名でしか登録できない
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub role: Role,
+    pub created_by: Option<i64>,
+    pub expires_at: String,
+    pub accepted_at: Option<String>,
+    // 招待を受けて作成されたユーザー
+    pub user_id: Option<i64>,
+    pub revoked_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewInvitation {
+    pub token_hash: String,
+    pub username: Option<String>,
+    pub email: Option<String>,
+    pub role: Role,
+    pub created_by: Option<i64>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl Invitation {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        let role: String = row.try_get(3)?;
+        Ok(Self {
+            id: row.try_get(0)?,
+            username: row.try_get(1)?,
+            email: row.try_get(2)?,
+            role: Role::from_str(&role).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            created_by: row.try_get(4)?,
+            expires_at: row.try_get(5)?,
+            accepted_at: row.try_get(6)?,
+            user_id: row.try_get(7)?,
+            revoked_at: row.try_get(8)?,
+            created_at: row.try_get(9)?,
+        })
+    }
+
+    // まだ使われておらず、取り消されておらず、期限内か
+    pub fn is_active(&self) -> bool {
+        let now = format_timestamp(chrono::Utc::now());
+        self.accepted_at.is_none() && self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+impl DbManager {
+    pub async fn create_invitation(&self, invitation: NewInvitation) -> Result<Invitation, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO invitations (token_hash, username, email, role, created_by, expires_at)
+             VALUES ($1, $2, $3, $4, $5, $6) RETURNING {}",
+            INVITATION_COLUMNS
+        ))
+        .bind(&invitation.token_hash)
+        .bind(&invitation.username)
+        .bind(&invitation.email)
+        .bind(invitation.role.to_string())
+        .bind(invitation.created_by)
+        .bind(format_timestamp(invitation.expires_at))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Invitation::from_row(&row)?)
+    }
+
+    pub async fn get_invitation_by_token(&self, token_hash: &str) -> Result<Option<Invitation>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM invitations WHERE token_hash = $1", INVITATION_COLUMNS))
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Invitation::from_row).transpose()?)
+    }
+
+    // 招待の一覧（新しい順）。`pending_only` なら使われていない有効なものだけ
+    pub async fn list_invitations(&self, pending_only: bool) -> Result<Vec<Invitation>, AppError> {
+        let rows = if pending_only {
+            sqlx::query(&format!(
+                "SELECT {} FROM invitations
+                 WHERE accepted_at IS NULL AND revoked_at IS NULL AND expires_at > $1
+                 ORDER BY id DESC",
+                INVITATION_COLUMNS
+            ))
+            .bind(format_timestamp(chrono::Utc::now()))
+            .fetch_all(&self.pool)
+            .await?
+        } else {
+            sqlx::query(&format!("SELECT {} FROM invitations ORDER BY id DESC", INVITATION_COLUMNS))
+                .fetch_all(&self.pool)
+                .await?
+        };
+        Ok(rows.iter().map(Invitation::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 使われていない招待を取り消す（該当するものがなければ None）
+    pub async fn revoke_invitation(&self, id: i64) -> Result<Option<Invitation>, AppError> {
+        let row = sqlx::query(&format!(
+            "UPDATE invitations SET revoked_at = $1 WHERE id = $2 AND accepted_at IS NULL AND revoked_at IS NULL RETURNING {}",
+            INVITATION_COLUMNS
+        ))
+        .bind(format_timestamp(chrono::Utc::now()))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.as_ref().map(Invitation::from_row).transpose()?)
+    }
+
+    // 招待を使ってユーザーを作成する。招待が使えなくなっていれば None
+    pub async fn accept_invitation(
+        &self,
+        invitation: &Invitation,
+        username: &str,
+        password: &str,
+        email: Option<&str>,
+    ) -> Result<Option<i64>, AppError> {
+        let now = format_timestamp(chrono::Utc::now());
+        let mut tx = self.pool.begin().await?;
+
+        // 先に使用済みにすることで、同時に使われても1回しか成功しないようにする
+        let claimed = sqlx::query(
+            "UPDATE invitations SET accepted_at = $1
+             WHERE id = $2 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > $1",
+        )
+        .bind(&now)
+        .bind(invitation.id)
+        .execute(&mut *tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let user_id = insert_user(&mut tx, username, password, email, invitation.role).await?;
+        sqlx::query("UPDATE invitations SET user_id = $1 WHERE id = $2")
+            .bind(user_id)
+            .bind(invitation.id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+}
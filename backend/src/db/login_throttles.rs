@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use sqlx::any::AnyRow;
+use sqlx::{AnyConnection, Row};
+use crate::auth::throttle::{LoginThrottle, ThrottlePolicy};
+use crate::error::AppError;
+use super::{format_timestamp, parse_timestamp, DbManager};
+
+const LOGIN_FAILURE_COLUMNS: &str = "id, username, ip_address, user_agent, reason, CAST(created_at AS TEXT)";
+const LOGIN_THROTTLE_COLUMNS: &str = "scope, identifier, failures, locked_until, last_failure_at";
+
+// ログインの失敗を数える単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThrottleScope {
+    Username,
+    Ip,
+}
+
+impl ThrottleScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleScope::Username => "username",
+            ThrottleScope::Ip => "ip",
+        }
+    }
+}
+
+// 失敗したログインの記録
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginFailure {
+    pub id: i64,
+    pub username: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub reason: String,
+    pub created_at: String,
+}
+
+impl LoginFailure {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            username: row.try_get(1)?,
+            ip_address: row.try_get(2)?,
+            user_agent: row.try_get(3)?,
+            reason: row.try_get(4)?,
+            created_at: row.try_get(5)?,
+        })
+    }
+}
+
+// ロック中のユーザー名・IPアドレス
+#[derive(Debug, Clone, Serialize)]
+pub struct LoginLockout {
+    pub scope: String,
+    pub identifier: String,
+    pub failures: i64,
+    pub locked_until: String,
+    pub last_failure_at: String,
+}
+
+impl LoginLockout {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            scope: row.try_get(0)?,
+            identifier: row.try_get(1)?,
+            failures: row.try_get(2)?,
+            locked_until: row.try_get(3)?,
+            last_failure_at: row.try_get(4)?,
+        })
+    }
+}
+
+// ログインの失敗の内容（ユーザー名は存在しないものも含めて、入力されたまま記録する）
+#[derive(Debug, Clone, Copy)]
+pub struct FailedLogin<'a> {
+    pub username: &'a str,
+    pub ip_address: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub reason: &'a str,
+}
+
+// 連続失敗の回数を増やし、次に試せる日時を延ばす（トランザクションの中で使う）
+// 上限の回数に達してロックした場合は true
+async fn bump_throttle(
+    conn: &mut AnyConnection,
+    scope: ThrottleScope,
+    identifier: &str,
+    policy: &ThrottlePolicy,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<bool, AppError> {
+    let previous: Option<(i64, String)> = sqlx::query_as(
+        "SELECT failures, last_failure_at FROM login_throttles WHERE scope = $1 AND identifier = $2",
+    )
+    .bind(scope.as_str())
+    .bind(identifier)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    // 前の失敗から時間が経っていれば数え直す
+    let failures = match previous {
+        Some((failures, last_failure_at))
+            if parse_timestamp(&last_failure_at).is_some_and(|time| time > now - policy.window()) =>
+        {
+            failures + 1
+        }
+        _ => 1,
+    };
+    let failures_u32 = u32::try_from(failures).unwrap_or(u32::MAX);
+    let locked_until = now + policy.delay(failures_u32);
+
+    sqlx::query(
+        "INSERT INTO login_throttles (scope, identifier, failures, locked_until, last_failure_at)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (scope, identifier) DO UPDATE
+         SET failures = excluded.failures, locked_until = excluded.locked_until, last_failure_at = excluded.last_failure_at",
+    )
+    .bind(scope.as_str())
+    .bind(identifier)
+    .bind(failures)
+    .bind(format_timestamp(locked_until))
+    .bind(format_timestamp(now))
+    .execute(&mut *conn)
+    .await?;
+    Ok(policy.locks_out(failures_u32))
+}
+
+impl DbManager {
+    // まだ試せなければ、試せるようになる日時
+    pub async fn login_locked_until(
+        &self,
+        scope: ThrottleScope,
+        identifier: &str,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, AppError> {
+        let locked_until: Option<String> = sqlx::query_scalar(
+            "SELECT locked_until FROM login_throttles WHERE scope = $1 AND identifier = $2 AND locked_until > $3",
+        )
+        .bind(scope.as_str())
+        .bind(identifier)
+        .bind(format_timestamp(chrono::Utc::now()))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(locked_until.as_deref().and_then(parse_timestamp))
+    }
+
+    // 失敗を記録する。`throttle` を渡した場合は、ユーザー名とIPアドレスの連続失敗の回数も増やす
+    // この失敗でロックしたユーザー名・IPアドレスを返す
+    pub async fn record_login_failure(
+        &self,
+        failure: &FailedLogin<'_>,
+        throttle: Option<&LoginThrottle>,
+    ) -> Result<Vec<ThrottleScope>, AppError> {
+        let now = chrono::Utc::now();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO login_failures (username, ip_address, user_agent, reason, created_at)
+             VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(failure.username)
+        .bind(failure.ip_address)
+        .bind(failure.user_agent)
+        .bind(failure.reason)
+        .bind(format_timestamp(now))
+        .execute(&mut *tx)
+        .await?;
+
+        let mut locked = Vec::new();
+        if let Some(throttle) = throttle {
+            if throttle.username.enabled()
+                && bump_throttle(&mut tx, ThrottleScope::Username, failure.username, &throttle.username, now).await?
+            {
+                locked.push(ThrottleScope::Username);
+            }
+            if let (Some(ip_address), true) = (failure.ip_address, throttle.ip.enabled()) {
+                if bump_throttle(&mut tx, ThrottleScope::Ip, ip_address, &throttle.ip, now).await? {
+                    locked.push(ThrottleScope::Ip);
+                }
+            }
+        }
+
+        tx.commit().await?;
+        Ok(locked)
+    }
+
+    // 連続失敗の回数とロックを解除する（該当するものがなければ false）
+    pub async fn reset_login_throttle(&self, scope: ThrottleScope, identifier: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM login_throttles WHERE scope = $1 AND identifier = $2")
+            .bind(scope.as_str())
+            .bind(identifier)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 失敗の記録（新しい順）。`before` を指定した場合はそのIDより前のもの
+    pub async fn list_login_failures(
+        &self,
+        username: Option<&str>,
+        ip_address: Option<&str>,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<LoginFailure>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM login_failures
+             WHERE ($1 IS NULL OR username = $1) AND ($2 IS NULL OR ip_address = $2) AND ($3 IS NULL OR id < $3)
+             ORDER BY id DESC LIMIT $4",
+            LOGIN_FAILURE_COLUMNS
+        ))
+        .bind(username)
+        .bind(ip_address)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(LoginFailure::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 現在ロックしているユーザー名・IPアドレス（解除が遅い順）
+    pub async fn list_login_lockouts(&self) -> Result<Vec<LoginLockout>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM login_throttles WHERE locked_until > $1 ORDER BY locked_until DESC",
+            LOGIN_THROTTLE_COLUMNS
+        ))
+        .bind(format_timestamp(chrono::Utc::now()))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(LoginLockout::from_row).collect::<Result<_, _>>()?)
+    }
+}
@@ -0,0 +1,274 @@
+use sqlx::{Connection, Row};
+use crate::config::DatabaseBackend;
+use crate::error::AppError;
+use super::DbManager;
+
+// 番号付きマイグレーション
+// 適用済みのマイグレーションは書き換えず、スキーマ変更は必ず新しい番号で追加する
+// SQLite と PostgreSQL で同じ番号・名前のファイルを用意する
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sqlite: &'static str,
+    pub postgres: &'static str,
+    // SQLite でテーブルを作り直す（新しいテーブルに移して古いものを削除する）か
+    // 古いテーブルを削除すると参照する行まで連鎖して削除されるため、その間だけ外部キー制約を止める
+    pub rebuilds_tables: bool,
+}
+
+impl Migration {
+    fn sql(&self, backend: DatabaseBackend) -> &'static str {
+        match backend {
+            DatabaseBackend::Sqlite => self.sqlite,
+            DatabaseBackend::Postgres => self.postgres,
+        }
+    }
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial",
+        sqlite: include_str!("../../migrations/sqlite/0001_initial.sql"),
+        postgres: include_str!("../../migrations/postgres/0001_initial.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 2,
+        name: "reconcile_users_and_documents",
+        sqlite: include_str!("../../migrations/sqlite/0002_reconcile_users_and_documents.sql"),
+        postgres: include_str!("../../migrations/postgres/0002_reconcile_users_and_documents.sql"),
+        rebuilds_tables: true,
+    },
+    Migration {
+        version: 3,
+        name: "tag_details_and_hierarchy",
+        sqlite: include_str!("../../migrations/sqlite/0003_tag_details_and_hierarchy.sql"),
+        postgres: include_str!("../../migrations/postgres/0003_tag_details_and_hierarchy.sql"),
+        rebuilds_tables: true,
+    },
+    Migration {
+        version: 4,
+        name: "attachments",
+        sqlite: include_str!("../../migrations/sqlite/0004_attachments.sql"),
+        postgres: include_str!("../../migrations/postgres/0004_attachments.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 5,
+        name: "document_acl",
+        sqlite: include_str!("../../migrations/sqlite/0005_document_acl.sql"),
+        postgres: include_str!("../../migrations/postgres/0005_document_acl.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 6,
+        name: "groups",
+        sqlite: include_str!("../../migrations/sqlite/0006_groups.sql"),
+        postgres: include_str!("../../migrations/postgres/0006_groups.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 7,
+        name: "share_links",
+        sqlite: include_str!("../../migrations/sqlite/0007_share_links.sql"),
+        postgres: include_str!("../../migrations/postgres/0007_share_links.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 8,
+        name: "document_visibility",
+        sqlite: include_str!("../../migrations/sqlite/0008_document_visibility.sql"),
+        postgres: include_str!("../../migrations/postgres/0008_document_visibility.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 9,
+        name: "sessions",
+        sqlite: include_str!("../../migrations/sqlite/0009_sessions.sql"),
+        postgres: include_str!("../../migrations/postgres/0009_sessions.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 10,
+        name: "api_tokens",
+        sqlite: include_str!("../../migrations/sqlite/0010_api_tokens.sql"),
+        postgres: include_str!("../../migrations/postgres/0010_api_tokens.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 11,
+        name: "user_identities",
+        sqlite: include_str!("../../migrations/sqlite/0011_user_identities.sql"),
+        postgres: include_str!("../../migrations/postgres/0011_user_identities.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 12,
+        name: "totp",
+        sqlite: include_str!("../../migrations/sqlite/0012_totp.sql"),
+        postgres: include_str!("../../migrations/postgres/0012_totp.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 13,
+        name: "login_throttling",
+        sqlite: include_str!("../../migrations/sqlite/0013_login_throttling.sql"),
+        postgres: include_str!("../../migrations/postgres/0013_login_throttling.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 14,
+        name: "user_disabled",
+        sqlite: include_str!("../../migrations/sqlite/0014_user_disabled.sql"),
+        postgres: include_str!("../../migrations/postgres/0014_user_disabled.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 15,
+        name: "setup_tokens",
+        sqlite: include_str!("../../migrations/sqlite/0015_setup_tokens.sql"),
+        postgres: include_str!("../../migrations/postgres/0015_setup_tokens.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 16,
+        name: "invitations",
+        sqlite: include_str!("../../migrations/sqlite/0016_invitations.sql"),
+        postgres: include_str!("../../migrations/postgres/0016_invitations.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 17,
+        name: "password_resets",
+        sqlite: include_str!("../../migrations/sqlite/0017_password_resets.sql"),
+        postgres: include_str!("../../migrations/postgres/0017_password_resets.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 18,
+        name: "audit_log",
+        sqlite: include_str!("../../migrations/sqlite/0018_audit_log.sql"),
+        postgres: include_str!("../../migrations/postgres/0018_audit_log.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 19,
+        name: "document_list_indexes",
+        sqlite: include_str!("../../migrations/sqlite/0019_document_list_indexes.sql"),
+        postgres: include_str!("../../migrations/postgres/0019_document_list_indexes.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 20,
+        name: "used_mfa_tokens",
+        sqlite: include_str!("../../migrations/sqlite/0020_used_mfa_tokens.sql"),
+        postgres: include_str!("../../migrations/postgres/0020_used_mfa_tokens.sql"),
+        rebuilds_tables: false,
+    },
+    Migration {
+        version: 21,
+        name: "rotated_refresh_tokens",
+        sqlite: include_str!("../../migrations/sqlite/0021_rotated_refresh_tokens.sql"),
+        postgres: include_str!("../../migrations/postgres/0021_rotated_refresh_tokens.sql"),
+        rebuilds_tables: false,
+    },
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+// 適用済みのマイグレーションがこのバイナリの知っているものと一致するか確認する
+fn check_applied(applied: &[(i64, String)]) -> Result<(), AppError> {
+    for (version, name) in applied {
+        match MIGRATIONS.iter().find(|m| m.version == *version) {
+            Some(migration) if migration.name == name => {}
+            Some(migration) => {
+                return Err(AppError::Database(format!(
+                    "Migration {} is recorded as '{}' but this build expects '{}'",
+                    version, name, migration.name
+                )));
+            }
+            None => {
+                return Err(AppError::Database(format!(
+                    "Database schema version {} is newer than this build supports ({}); refusing to start",
+                    version,
+                    latest_version()
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl DbManager {
+    pub async fn schema_version(&self) -> Result<i64, AppError> {
+        let version = sqlx::query_scalar("SELECT COALESCE(MAX(version), 0) FROM schema_version")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(version)
+    }
+
+    // 未適用のマイグレーションを順番に適用する
+    // 各マイグレーションは記録と合わせて1つのトランザクションで実行される
+    pub async fn migrate(&self) -> Result<(), AppError> {
+        let read_applied = async {
+            sqlx::raw_sql(
+                "CREATE TABLE IF NOT EXISTS schema_version (
+                    version BIGINT PRIMARY KEY,
+                    name TEXT NOT NULL,
+                    applied_at TEXT DEFAULT CURRENT_TIMESTAMP
+                );",
+            )
+            .execute(&self.pool)
+            .await?;
+
+            sqlx::query("SELECT version, name FROM schema_version ORDER BY version")
+                .fetch_all(&self.pool)
+                .await?
+                .iter()
+                .map(|row| Ok((row.try_get(0)?, row.try_get(1)?)))
+                .collect::<Result<Vec<(i64, String)>, sqlx::Error>>()
+        };
+        let applied = read_applied
+            .await
+            .map_err(|e| AppError::Database(format!("Failed to read schema version: {}", e)))?;
+
+        check_applied(&applied)?;
+
+        for migration in MIGRATIONS {
+            if applied.iter().any(|(version, _)| *version == migration.version) {
+                continue;
+            }
+
+            tracing::info!("Applying database migration {:04}_{}", migration.version, migration.name);
+            let mut conn = self.pool.acquire().await?;
+            // SQLite ではトランザクションの中で外部キー制約を切り替えられないため、その前後で切り替える
+            let pause_foreign_keys = self.backend == DatabaseBackend::Sqlite && migration.rebuilds_tables;
+            if pause_foreign_keys {
+                sqlx::raw_sql("PRAGMA foreign_keys = OFF").execute(&mut *conn).await?;
+            }
+            let apply = async {
+                let mut tx = conn.begin().await?;
+                sqlx::raw_sql(migration.sql(self.backend)).execute(&mut *tx).await?;
+                sqlx::query("INSERT INTO schema_version (version, name) VALUES ($1, $2)")
+                    .bind(migration.version)
+                    .bind(migration.name)
+                    .execute(&mut *tx)
+                    .await?;
+                tx.commit().await
+            };
+            let applied = apply.await;
+            // 失敗した場合も、接続を制約が止まったまま戻さない
+            if pause_foreign_keys {
+                sqlx::raw_sql("PRAGMA foreign_keys = ON").execute(&mut *conn).await?;
+            }
+            applied.map_err(|e| AppError::Database(format!(
+                "Failed to apply migration {:04}_{}: {}", migration.version, migration.name, e
+            )))?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,36 +1,147 @@
-use std::path::Path;
-use tokio_rusqlite::Connection;
+use sqlx::any::{AnyPoolOptions, install_default_drivers};
+use sqlx::{AnyPool, Executor};
+use std::sync::Arc;
+use crate::auth::provider::AuthProvider;
+use crate::config::DatabaseBackend;
 use crate::error::AppError;
 
+pub mod acl;
+pub mod api_tokens;
+pub mod attachments;
+pub mod audit;
 pub mod documents;
+pub mod groups;
+pub mod identities;
+pub mod invitations;
+pub mod login_throttles;
+pub mod migrations;
+pub mod password_resets;
+pub mod sessions;
+pub mod setup;
+pub mod shares;
 pub mod users;
 pub mod tags;
+pub mod totp;
 
+// メタデータ用のデータベース（SQLite または PostgreSQL）
+// クエリは両方で動く共通のSQL（`$1` 形式のプレースホルダ）で書く
 #[derive(Clone, Debug)]
 pub struct DbManager {
-    conn: Connection,
+    pool: AnyPool,
+    backend: DatabaseBackend,
+    // ローカルのパスワードでログインできるか
+    local_passwords: bool,
+    // ローカルのパスワードを持たないユーザーの認証に使う外部の認証基盤（LDAP など）
+    auth_providers: Vec<Arc<dyn AuthProvider>>,
+}
+
+// アプリ側で書き込む日時は `created_at` と同じ `YYYY-MM-DD HH:MM:SS`（UTC）で保存する
+// 文字列のまま比較しても日時の順になる
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn format_timestamp(time: chrono::DateTime<chrono::Utc>) -> String {
+    time.format(TIMESTAMP_FORMAT).to_string()
+}
+
+fn parse_timestamp(value: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+// SQLiteのパス指定を sqlx の接続URLに変換する
+fn sqlite_url(database_url: &str) -> String {
+    let url = if database_url.starts_with("sqlite:") {
+        database_url.to_string()
+    } else {
+        format!("sqlite://{}", database_url)
+    };
+
+    if url.contains('?') {
+        url
+    } else {
+        format!("{}?mode=rwc", url)
+    }
 }
 
 impl DbManager {
-    pub async fn new(db_path: &Path) -> Result<Self, AppError> {
-        let conn = Connection::open(db_path)
-            .await
-            .map_err(|e| AppError::Database(format!("Failed to open database: {}", e)))?;
+    pub async fn new(database_url: &str, backend: DatabaseBackend) -> Result<Self, AppError> {
+        install_default_drivers();
 
-        Ok(Self { conn })
+        let pool = match backend {
+            DatabaseBackend::Sqlite => {
+                // SQLiteは書き込みが直列化されるため接続は1本にする
+                // 削除の連鎖を PostgreSQL と揃えるため、外部キー制約を有効にする
+                // （テーブルを作り直すマイグレーションの間だけ止める）
+                AnyPoolOptions::new()
+                    .max_connections(1)
+                    .after_connect(|conn, _| Box::pin(async move {
+                        conn.execute("PRAGMA foreign_keys = ON").await?;
+                        Ok(())
+                    }))
+                    .connect(&sqlite_url(database_url))
+                    .await
+            }
+            DatabaseBackend::Postgres => {
+                AnyPoolOptions::new()
+                    .max_connections(10)
+                    .connect(database_url)
+                    .await
+            }
+        }
+        .map_err(|e| AppError::Database(format!("Failed to open database: {}", e)))?;
+
+        Ok(Self {
+            pool,
+            backend,
+            local_passwords: true,
+            auth_providers: Vec::new(),
+        })
+    }
+
+    // `authenticate_user` で使う認証方法を設定する
+    pub fn with_auth_providers(mut self, local_passwords: bool, providers: Vec<Arc<dyn AuthProvider>>) -> Self {
+        self.local_passwords = local_passwords;
+        self.auth_providers = providers;
+        self
+    }
+
+    pub fn auth_provider_names(&self) -> Vec<&'static str> {
+        self.auth_providers.iter().map(|provider| provider.name()).collect()
     }
 
+    pub fn backend(&self) -> DatabaseBackend {
+        self.backend
+    }
+
+    // スキーマを最新バージョンまでマイグレーションし、起動可能な状態か確認する
     pub async fn init(&self) -> Result<(), AppError> {
-        self.conn
-            .call(|conn| {
-                conn.execute_batch(include_str!("schema.sql"))?;
-                Ok(())
-            })
-            .await
-            .map_err(|e| AppError::Database(format!("Failed to initialize database: {}", e)))
+        self.migrate().await?;
+
+        let version = self.schema_version().await?;
+        if version != migrations::latest_version() {
+            return Err(AppError::Database(format!(
+                "Database schema is at version {} but version {} is required",
+                version,
+                migrations::latest_version()
+            )));
+        }
+
+        tracing::info!("Database schema is at version {} ({:?})", version, self.backend);
+        Ok(())
     }
 }
 
 pub use crate::models::user::User;
-pub use documents::{DocumentMeta, self as document_ops};
-pub use tags::{Tag, self as tag_ops}; 
\ No newline at end of file
+pub use acl::AclEntry;
+pub use api_tokens::ApiToken;
+pub use attachments::{Attachment, NewAttachment};
+pub use audit::{AuditEntry, AuditFilter, NewAuditEntry};
+pub use documents::{DocumentListQuery, DocumentMeta, DocumentSort, self as document_ops};
+pub use groups::Group;
+pub use invitations::{Invitation, NewInvitation};
+pub use login_throttles::{FailedLogin, LoginFailure, LoginLockout, ThrottleScope};
+pub use sessions::Session;
+pub use shares::{ShareLink, NewShareLink};
+pub use tags::{Tag, TagChange, self as tag_ops};
+pub use totp::TotpState;
@@ -0,0 +1,99 @@
+use crate::error::AppError;
+use super::{format_timestamp, parse_timestamp, DbManager};
+
+impl DbManager {
+    // パスワードの再設定のトークンを保存する（まだ使っていない以前のトークンは使えなくなる）
+    pub async fn create_password_reset(
+        &self,
+        user_id: i64,
+        token_hash: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM password_resets WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO password_resets (user_id, token_hash, expires_at, created_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(format_timestamp(expires_at))
+        .bind(format_timestamp(chrono::Utc::now()))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // 最後にパスワードの再設定を申し込んだ日時
+    pub async fn last_password_reset_at(&self, user_id: i64) -> Result<Option<chrono::DateTime<chrono::Utc>>, AppError> {
+        let created_at: Option<String> =
+            sqlx::query_scalar("SELECT MAX(created_at) FROM password_resets WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_one(&self.pool)
+                .await?;
+        Ok(created_at.as_deref().and_then(parse_timestamp))
+    }
+
+    // 使える（未使用で期限内の）トークンなら、そのユーザーのID
+    pub async fn password_reset_user(&self, token_hash: &str) -> Result<Option<i64>, AppError> {
+        let user_id = sqlx::query_scalar(
+            "SELECT user_id FROM password_resets WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2",
+        )
+        .bind(token_hash)
+        .bind(format_timestamp(chrono::Utc::now()))
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(user_id)
+    }
+
+    // トークンを使ってパスワードを変更し、ユーザーのすべてのセッションと個人用APIトークンを取り消す
+    // トークンが使えなくなっていれば false
+    pub async fn complete_password_reset(
+        &self,
+        token_hash: &str,
+        user_id: i64,
+        password_hash: &str,
+    ) -> Result<bool, AppError> {
+        let now = format_timestamp(chrono::Utc::now());
+        let mut tx = self.pool.begin().await?;
+
+        // 先に使用済みにすることで、同時に使われても1回しか成功しないようにする
+        let claimed = sqlx::query(
+            "UPDATE password_resets SET used_at = $1
+             WHERE token_hash = $2 AND user_id = $3 AND used_at IS NULL AND expires_at > $1",
+        )
+        .bind(&now)
+        .bind(token_hash)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(password_hash)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE sessions SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL")
+            .bind(&now)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        // 漏れたパスワードで作られたかもしれない個人用APIトークンも取り消す
+        sqlx::query("UPDATE api_tokens SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL")
+            .bind(&now)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+}
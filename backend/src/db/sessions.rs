@@ -0,0 +1,193 @@
+use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use super::{format_timestamp, DbManager};
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+// リフレッシュトークンのハッシュは読み出さない
+const SESSION_COLUMNS: &str =
+    "id, user_id, user_agent, expires_at, last_used_at, revoked_at, CAST(created_at AS TEXT)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: i64,
+    pub user_id: i64,
+    pub user_agent: Option<String>,
+    pub expires_at: String,
+    pub last_used_at: Option<String>,
+    pub revoked_at: Option<String>,
+    pub created_at: String,
+}
+
+impl Session {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            user_id: row.try_get(1)?,
+            user_agent: row.try_get(2)?,
+            expires_at: row.try_get(3)?,
+            last_used_at: row.try_get(4)?,
+            revoked_at: row.try_get(5)?,
+            created_at: row.try_get(6)?,
+        })
+    }
+
+    // 取り消されておらず、期限内か
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > format_timestamp(chrono::Utc::now())
+    }
+}
+
+impl DbManager {
+    pub async fn create_session(
+        &self,
+        user_id: i64,
+        refresh_token_hash: &str,
+        user_agent: Option<&str>,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Session, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO sessions (user_id, refresh_token_hash, user_agent, expires_at)
+             VALUES ($1, $2, $3, $4) RETURNING {}",
+            SESSION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(refresh_token_hash)
+        .bind(user_agent)
+        .bind(format_timestamp(expires_at))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Session::from_row(&row)?)
+    }
+
+    pub async fn get_session(&self, id: i64) -> Result<Option<Session>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM sessions WHERE id = $1", SESSION_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Session::from_row).transpose()?)
+    }
+
+    pub async fn get_session_by_token(&self, refresh_token_hash: &str) -> Result<Option<Session>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM sessions WHERE refresh_token_hash = $1", SESSION_COLUMNS))
+            .bind(refresh_token_hash)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Session::from_row).transpose()?)
+    }
+
+    // リフレッシュトークンを新しいものに置き換え、期限を延ばす
+    // 古いトークンは使用済みとして残し、再び使われたら検出できるようにする
+    // 同じトークンで同時に更新された場合は片方だけが成功する
+    pub async fn rotate_session_token(
+        &self,
+        id: i64,
+        old_hash: &str,
+        new_hash: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<bool, AppError> {
+        let now = format_timestamp(chrono::Utc::now());
+        let mut tx = self.pool.begin().await?;
+
+        let old_expires_at: Option<String> = sqlx::query_scalar(
+            "SELECT expires_at FROM sessions WHERE id = $1 AND refresh_token_hash = $2 AND revoked_at IS NULL",
+        )
+        .bind(id)
+        .bind(old_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+        let Some(old_expires_at) = old_expires_at else {
+            return Ok(false);
+        };
+
+        let result = sqlx::query(
+            "UPDATE sessions SET refresh_token_hash = $1, expires_at = $2, last_used_at = $3
+             WHERE id = $4 AND refresh_token_hash = $5 AND revoked_at IS NULL",
+        )
+        .bind(new_hash)
+        .bind(format_timestamp(expires_at))
+        .bind(&now)
+        .bind(id)
+        .bind(old_hash)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // 期限が切れたトークンはそもそも受け付けないため、その記録は削除する
+        sqlx::query("DELETE FROM rotated_refresh_tokens WHERE expires_at <= $1")
+            .bind(&now)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO rotated_refresh_tokens (token_hash, session_id, expires_at) VALUES ($1, $2, $3)")
+            .bind(old_hash)
+            .bind(id)
+            .bind(old_expires_at)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // すでに置き換えたリフレッシュトークンなら、そのセッション
+    pub async fn get_session_by_rotated_token(&self, token_hash: &str) -> Result<Option<Session>, AppError> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM sessions WHERE id = (SELECT session_id FROM rotated_refresh_tokens WHERE token_hash = $1)",
+            SESSION_COLUMNS
+        ))
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.as_ref().map(Session::from_row).transpose()?)
+    }
+
+    // ユーザーの有効なセッション（新しい順）
+    pub async fn list_user_sessions(&self, user_id: i64) -> Result<Vec<Session>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM sessions
+             WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > $2
+             ORDER BY id DESC",
+            SESSION_COLUMNS
+        ))
+        .bind(user_id)
+        .bind(format_timestamp(chrono::Utc::now()))
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(Session::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // ユーザーのセッションを1つ取り消す（該当するセッションがなければ false）
+    pub async fn revoke_session(&self, id: i64, user_id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE sessions SET revoked_at = $1 WHERE id = $2 AND user_id = $3 AND revoked_at IS NULL")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 指定したセッション以外のユーザーのセッションを取り消し、取り消した数を返す
+    pub async fn revoke_other_sessions(&self, user_id: i64, keep: i64) -> Result<u64, AppError> {
+        let result = sqlx::query("UPDATE sessions SET revoked_at = $1 WHERE user_id = $2 AND id <> $3 AND revoked_at IS NULL")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .bind(user_id)
+            .bind(keep)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // ユーザーのすべてのセッションを取り消し、取り消した数を返す
+    pub async fn revoke_user_sessions(&self, user_id: i64) -> Result<u64, AppError> {
+        let result = sqlx::query("UPDATE sessions SET revoked_at = $1 WHERE user_id = $2 AND revoked_at IS NULL")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
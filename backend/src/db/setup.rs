@@ -0,0 +1,76 @@
+use crate::error::AppError;
+use crate::models::user::Role;
+use super::users::insert_user;
+use super::{format_timestamp, DbManager};
+
+impl DbManager {
+    // 初期設定のトークンを追加する（起動時とコマンドで発行したものは、どれも期限まで使える）
+    pub async fn add_setup_token(
+        &self,
+        token_hash: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM setup_tokens WHERE expires_at <= $1")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("INSERT INTO setup_tokens (token_hash, expires_at) VALUES ($1, $2)")
+            .bind(token_hash)
+            .bind(format_timestamp(expires_at))
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // 期限内の初期設定のトークンがあるか
+    pub async fn has_setup_token(&self) -> Result<bool, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM setup_tokens WHERE expires_at > $1")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count > 0)
+    }
+
+    // 初期設定のトークンを使って最初の管理者を作成する
+    // トークンが正しくないか期限切れ、または既にユーザーがいる場合は None
+    pub async fn complete_setup(
+        &self,
+        token_hash: &str,
+        username: &str,
+        password: &str,
+        email: Option<&str>,
+    ) -> Result<Option<i64>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        // 先にトークンを消すことで、同時に使われても1回しか成功しないようにする
+        let consumed = sqlx::query("DELETE FROM setup_tokens WHERE token_hash = $1 AND expires_at > $2")
+            .bind(token_hash)
+            .bind(format_timestamp(chrono::Utc::now()))
+            .execute(&mut *tx)
+            .await?;
+        if consumed.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&mut *tx)
+            .await?;
+        if users > 0 {
+            tx.commit().await?;
+            return Ok(None);
+        }
+
+        let user_id = insert_user(&mut tx, username, password, email, Role::Admin).await?;
+        // 他に発行したトークンも使えなくする
+        sqlx::query("DELETE FROM setup_tokens")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+}
@@ -0,0 +1,111 @@
+use serde::{Serialize, Deserialize};
+use sqlx::any::AnyRow;
+use sqlx::Row;
+use crate::error::AppError;
+use crate::models::acl::Permission;
+use super::{format_timestamp, DbManager};
+use std::str::FromStr;
+
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+const SHARE_LINK_COLUMNS: &str =
+    "id, document_filename, commit_id, permission, created_by, expires_at, revoked_at, CAST(created_at AS TEXT)";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: i64,
+    pub document: String,
+    // 特定のバージョンを共有する場合のコミットID
+    pub commit_id: Option<String>,
+    pub permission: Permission,
+    pub created_by: Option<i64>,
+    pub expires_at: String,
+    pub revoked_at: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewShareLink {
+    pub document: String,
+    pub commit_id: Option<String>,
+    pub permission: Permission,
+    pub created_by: Option<i64>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl ShareLink {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        let permission: String = row.try_get(3)?;
+        Ok(Self {
+            id: row.try_get(0)?,
+            document: row.try_get(1)?,
+            commit_id: row.try_get(2)?,
+            permission: Permission::from_str(&permission).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            created_by: row.try_get(4)?,
+            expires_at: row.try_get(5)?,
+            revoked_at: row.try_get(6)?,
+            created_at: row.try_get(7)?,
+        })
+    }
+
+    // 取り消されておらず、期限内か
+    pub fn is_active(&self) -> bool {
+        let now = format_timestamp(chrono::Utc::now());
+        self.revoked_at.is_none() && self.expires_at > now
+    }
+}
+
+impl DbManager {
+    pub async fn create_share_link(&self, link: NewShareLink) -> Result<ShareLink, AppError> {
+        let row = sqlx::query(&format!(
+            "INSERT INTO share_links (document_filename, commit_id, permission, created_by, expires_at)
+             VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+            SHARE_LINK_COLUMNS
+        ))
+        .bind(&link.document)
+        .bind(&link.commit_id)
+        .bind(link.permission.to_string())
+        .bind(link.created_by)
+        .bind(format_timestamp(link.expires_at))
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(ShareLink::from_row(&row)?)
+    }
+
+    pub async fn get_share_link(&self, id: i64) -> Result<Option<ShareLink>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM share_links WHERE id = $1", SHARE_LINK_COLUMNS))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(ShareLink::from_row).transpose()?)
+    }
+
+    // 共有リンクの一覧（新しい順）
+    // ドキュメントや作成者で絞り込める
+    pub async fn list_share_links(
+        &self,
+        document: Option<&str>,
+        created_by: Option<i64>,
+    ) -> Result<Vec<ShareLink>, AppError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM share_links
+             WHERE ($1 IS NULL OR document_filename = $1) AND ($2 IS NULL OR created_by = $2)
+             ORDER BY id DESC",
+            SHARE_LINK_COLUMNS
+        ))
+        .bind(document)
+        .bind(created_by)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.iter().map(ShareLink::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 共有リンクを取り消す（取り消し済みなら false）
+    pub async fn revoke_share_link(&self, id: i64) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE share_links SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
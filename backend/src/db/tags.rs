@@ -1,4 +1,5 @@
-use rusqlite::{params, OptionalExtension, Result as RusqliteResult};
+use sqlx::any::AnyRow;
+use sqlx::{AnyConnection, Row};
 use crate::error::AppError;
 use super::DbManager;
 
@@ -6,88 +7,412 @@ use super::DbManager;
 pub struct Tag {
     pub id: i64,
     pub name: String,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    pub usage_count: i64,
+    pub parent: Option<String>,
+    pub synonym_of: Option<String>,
+}
+
+// タグ操作の結果（存在しない・他のタグと衝突する場合を区別する）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagChange {
+    Applied,
+    NotFound,
+    Conflict,
+}
+
+const TAG_COLUMNS: &str =
+    "t.id, t.name, t.description, t.color, (SELECT COUNT(*) FROM document_tags dt WHERE dt.tag_id = t.id), \
+     (SELECT p.name FROM tags p WHERE p.id = t.parent_id), (SELECT s.name FROM tags s WHERE s.id = t.synonym_of)";
+
+impl Tag {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get(0)?,
+            name: row.try_get(1)?,
+            description: row.try_get(2)?,
+            color: row.try_get(3)?,
+            usage_count: row.try_get(4)?,
+            parent: row.try_get(5)?,
+            synonym_of: row.try_get(6)?,
+        })
+    }
+}
+
+async fn find_tag_id(conn: &mut AnyConnection, name: &str) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM tags WHERE name = $1")
+        .bind(name)
+        .fetch_optional(conn)
+        .await
+}
+
+// タグ名を正規のタグIDに解決する（同義語の場合は正規タグのIDを返す）
+pub(super) async fn resolve_tag_id(conn: &mut AnyConnection, name: &str) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar("SELECT COALESCE(synonym_of, id) FROM tags WHERE name = $1")
+        .bind(name)
+        .fetch_optional(conn)
+        .await
+}
+
+// タグ名からIDを取得し、存在しなければ作成する
+// `infra/k8s` のような階層タグは、親タグ `infra` も合わせて作成して紐付ける
+// 新しく作成したタグの名前は `created` に追加する
+async fn get_or_create_tag_id(conn: &mut AnyConnection, name: &str, created: &mut Vec<String>) -> Result<i64, sqlx::Error> {
+    if let Some(id) = resolve_tag_id(&mut *conn, name).await? {
+        return Ok(id);
+    }
+
+    // 根から順に、存在しない祖先タグを作成していく
+    let mut parent_id: Option<i64> = None;
+    let ends = name.match_indices('/').map(|(i, _)| i).chain(std::iter::once(name.len()));
+    for end in ends {
+        let prefix = &name[..end];
+        let id = match resolve_tag_id(&mut *conn, prefix).await? {
+            Some(id) => id,
+            None => {
+                let id = sqlx::query_scalar("INSERT INTO tags (name, parent_id) VALUES ($1, $2) RETURNING id")
+                    .bind(prefix)
+                    .bind(parent_id)
+                    .fetch_one(&mut *conn)
+                    .await?;
+                created.push(prefix.to_string());
+                id
+            }
+        };
+        parent_id = Some(id);
+    }
+
+    Ok(parent_id.expect("tag name has at least one segment"))
+}
+
+async fn has_child_tags(conn: &mut AnyConnection, tag_id: i64) -> Result<bool, sqlx::Error> {
+    let children: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tags WHERE parent_id = $1")
+        .bind(tag_id)
+        .fetch_one(conn)
+        .await?;
+    Ok(children > 0)
+}
+
+// from_id のタグ付けと同義語を to_id に付け替える
+async fn move_tag_usage(conn: &mut AnyConnection, from_id: i64, to_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO document_tags (document_id, tag_id) SELECT document_id, $1 FROM document_tags WHERE tag_id = $2
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(to_id)
+    .bind(from_id)
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query("DELETE FROM document_tags WHERE tag_id = $1").bind(from_id).execute(&mut *conn).await?;
+    sqlx::query("UPDATE tags SET synonym_of = $1 WHERE synonym_of = $2")
+        .bind(to_id)
+        .bind(from_id)
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
+}
+
+async fn tag_document(conn: &mut AnyConnection, document_id: i64, tag_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO document_tags (document_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(document_id)
+        .bind(tag_id)
+        .execute(conn)
+        .await?;
+    Ok(())
 }
 
 impl DbManager {
     pub async fn create_tag(&self, name: &str) -> Result<i64, AppError> {
-        let name_clone = name.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<i64, rusqlite::Error> {
-            conn.execute("INSERT INTO tags (name) VALUES (?)", params![name_clone])?;
-            Ok(conn.last_insert_rowid())
-        }).await.map_err(AppError::from)
+        let id = sqlx::query_scalar("INSERT INTO tags (name) VALUES ($1) RETURNING id")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
     }
 
     pub async fn get_tag_by_name(&self, name: &str) -> Result<Option<Tag>, AppError> {
-        let name_clone = name.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<Option<Tag>, rusqlite::Error> {
-            Ok(conn.query_row(
-                "SELECT id, name FROM tags WHERE name = ?",
-                params![name_clone],
-                |row| {
-                    Ok(Tag {
-                        id: row.get(0)?,
-                        name: row.get(1)?,
-                    })
-                },
-            ).optional()?)
-        }).await.map_err(AppError::from)
+        let row = sqlx::query(&format!("SELECT {} FROM tags t WHERE t.name = $1", TAG_COLUMNS))
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(Tag::from_row).transpose()?)
     }
 
     pub async fn list_tags(&self) -> Result<Vec<Tag>, AppError> {
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<Vec<Tag>, rusqlite::Error> {
-            let mut stmt = conn.prepare("SELECT id, name FROM tags")?;
-            let tags_iter = stmt.query_map([], |row| {
-                Ok(Tag {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                })
-            })?;
-            tags_iter.collect()
-        }).await.map_err(AppError::from)
+        let rows = sqlx::query(&format!("SELECT {} FROM tags t ORDER BY t.name", TAG_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(Tag::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 名前順で `after` より後のタグを `limit` 件まで取得する
+    pub async fn list_tags_page(&self, after: Option<&str>, limit: usize) -> Result<Vec<Tag>, AppError> {
+        let rows = match after {
+            Some(after) => {
+                sqlx::query(&format!("SELECT {} FROM tags t WHERE t.name > $1 ORDER BY t.name LIMIT $2", TAG_COLUMNS))
+                    .bind(after)
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+            None => {
+                sqlx::query(&format!("SELECT {} FROM tags t ORDER BY t.name LIMIT $1", TAG_COLUMNS))
+                    .bind(limit as i64)
+                    .fetch_all(&self.pool)
+                    .await?
+            }
+        };
+        Ok(rows.iter().map(Tag::from_row).collect::<Result<_, _>>()?)
+    }
+
+    // 指定したタグが付いたドキュメントを (タグID, ファイル名) の組で取得する
+    pub async fn list_tagged_documents(&self, tag_ids: &[i64]) -> Result<Vec<(i64, String)>, AppError> {
+        if tag_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let placeholders: Vec<String> = (1..=tag_ids.len()).map(|i| format!("${}", i)).collect();
+        let sql = format!(
+            "SELECT dt.tag_id, d.filename FROM document_tags dt JOIN documents d ON d.id = dt.document_id
+             WHERE dt.tag_id IN ({})",
+            placeholders.join(", ")
+        );
+        let mut query = sqlx::query(&sql);
+        for id in tag_ids {
+            query = query.bind(*id);
+        }
+
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.iter().map(|row| Ok((row.try_get(0)?, row.try_get(1)?))).collect::<Result<_, sqlx::Error>>()?)
     }
 
     pub async fn add_tag_to_document(&self, document_id: i64, tag_name: &str) -> Result<bool, AppError> {
-        let tag_name_clone = tag_name.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<bool, rusqlite::Error> {
-            let tx = conn.transaction()?;
-            
-            let tag_id = match tx.query_row("SELECT id FROM tags WHERE name = ?", params![&tag_name_clone], |row| row.get::<_, i64>(0)).optional()? {
-                Some(id) => id,
-                None => {
-                    tx.execute("INSERT INTO tags (name) VALUES (?)", params![&tag_name_clone])?;
-                    tx.last_insert_rowid()
-                }
-            };
-            
-            tx.execute("INSERT OR IGNORE INTO document_tags (document_id, tag_id) VALUES (?, ?)", params![document_id, tag_id])?;
-            tx.commit()?;
-            Ok(true)
-        }).await.map_err(AppError::from)
+        let mut tx = self.pool.begin().await?;
+
+        let tag_id = get_or_create_tag_id(&mut tx, tag_name, &mut Vec::new()).await?;
+        tag_document(&mut tx, document_id, tag_id).await?;
+
+        tx.commit().await?;
+        Ok(true)
     }
 
     pub async fn remove_tag_from_document(&self, document_id: i64, tag_name: &str) -> Result<bool, AppError> {
-        let tag_name_clone = tag_name.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<bool, rusqlite::Error> {
-            if let Some(tag_id) = conn.query_row("SELECT id FROM tags WHERE name = ?", params![&tag_name_clone], |row| row.get::<_, i64>(0)).optional()? {
-                let rows_affected = conn.execute("DELETE FROM document_tags WHERE document_id = ? AND tag_id = ?", params![document_id, tag_id])?;
-                Ok(rows_affected > 0)
-            } else {
-                Ok(false)
+        let mut conn = self.pool.acquire().await?;
+
+        if let Some(tag_id) = resolve_tag_id(&mut conn, tag_name).await? {
+            let result = sqlx::query("DELETE FROM document_tags WHERE document_id = $1 AND tag_id = $2")
+                .bind(document_id)
+                .bind(tag_id)
+                .execute(&mut *conn)
+                .await?;
+            Ok(result.rows_affected() > 0)
+        } else {
+            Ok(false)
+        }
+    }
+
+    // ドキュメントのタグを指定されたリストで置き換え、新しく作成したタグの名前を返す
+    pub async fn set_document_tags(&self, document_id: i64, tag_names: Vec<String>) -> Result<Vec<String>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM document_tags WHERE document_id = $1").bind(document_id).execute(&mut *tx).await?;
+        let mut created = Vec::new();
+        for name in &tag_names {
+            let tag_id = get_or_create_tag_id(&mut tx, name, &mut created).await?;
+            tag_document(&mut tx, document_id, tag_id).await?;
+        }
+
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    // タグ名を変更する。子タグの名前と親子関係も合わせて付け替える
+    pub async fn rename_tag(&self, old_name: &str, new_name: &str) -> Result<TagChange, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let tag_id = match find_tag_id(&mut tx, old_name).await? {
+            Some(id) => id,
+            None => return Ok(TagChange::NotFound),
+        };
+
+        let old_prefix = format!("{}/", old_name);
+        if find_tag_id(&mut tx, new_name).await?.is_some() || new_name.starts_with(&old_prefix) {
+            return Ok(TagChange::Conflict);
+        }
+
+        let parent_id = match new_name.rsplit_once('/') {
+            Some((parent, _)) => Some(get_or_create_tag_id(&mut tx, parent, &mut Vec::new()).await?),
+            None => None,
+        };
+        sqlx::query("UPDATE tags SET name = $1, parent_id = $2 WHERE id = $3")
+            .bind(new_name)
+            .bind(parent_id)
+            .bind(tag_id)
+            .execute(&mut *tx)
+            .await?;
+
+        // substr の位置引数はPostgreSQLでは integer 型である必要がある
+        let old_len = old_name.chars().count() as i32;
+        sqlx::query("UPDATE tags SET name = $1 || substr(name, $2) WHERE substr(name, 1, $3) = $4")
+            .bind(new_name)
+            .bind(old_len + 1)
+            .bind(old_len + 1)
+            .bind(&old_prefix)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(TagChange::Applied)
+    }
+
+    // source のタグ付けと同義語をすべて target に移し、source を削除する
+    pub async fn merge_tags(&self, source: &str, target: &str) -> Result<TagChange, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let source_id = match find_tag_id(&mut tx, source).await? {
+            Some(id) => id,
+            None => return Ok(TagChange::NotFound),
+        };
+        // 子タグを持つタグは名前の階層が崩れるため統合できない
+        if has_child_tags(&mut tx, source_id).await? {
+            return Ok(TagChange::Conflict);
+        }
+        let target_id = get_or_create_tag_id(&mut tx, target, &mut Vec::new()).await?;
+        if target_id == source_id {
+            return Ok(TagChange::Conflict);
+        }
+
+        move_tag_usage(&mut tx, source_id, target_id).await?;
+        sqlx::query("DELETE FROM tags WHERE id = $1").bind(source_id).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+        Ok(TagChange::Applied)
+    }
+
+    // synonym を canonical の同義語として登録する
+    // 既存のタグを同義語にする場合、そのタグ付けは正規タグに移される
+    pub async fn add_tag_synonym(&self, canonical: &str, synonym: &str) -> Result<TagChange, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let canonical_id = match resolve_tag_id(&mut tx, canonical).await? {
+            Some(id) => id,
+            None => return Ok(TagChange::NotFound),
+        };
+
+        match find_tag_id(&mut tx, synonym).await? {
+            Some(synonym_id) => {
+                if synonym_id == canonical_id || has_child_tags(&mut tx, synonym_id).await? {
+                    return Ok(TagChange::Conflict);
+                }
+                move_tag_usage(&mut tx, synonym_id, canonical_id).await?;
+                sqlx::query("UPDATE tags SET synonym_of = $1 WHERE id = $2")
+                    .bind(canonical_id)
+                    .bind(synonym_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            None => {
+                sqlx::query("INSERT INTO tags (name, synonym_of) VALUES ($1, $2)")
+                    .bind(synonym)
+                    .bind(canonical_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(TagChange::Applied)
+    }
+
+    pub async fn remove_tag_synonym(&self, canonical: &str, synonym: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM tags WHERE name = $1 AND synonym_of = (SELECT id FROM tags WHERE name = $2)")
+            .bind(synonym)
+            .bind(canonical)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn update_tag_details(&self, name: &str, description: Option<String>, color: Option<String>) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE tags SET description = $1, color = $2 WHERE name = $3")
+            .bind(description)
+            .bind(color)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // どのドキュメントにも使われておらず子タグも持たないタグのみ削除する（同義語も合わせて削除）
+    pub async fn delete_tag_if_unused(&self, name: &str) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let tag_id: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM tags WHERE name = $1
+             AND NOT EXISTS (SELECT 1 FROM document_tags dt WHERE dt.tag_id = tags.id)
+             AND NOT EXISTS (SELECT 1 FROM tags c WHERE c.parent_id = tags.id)",
+        )
+        .bind(name)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        match tag_id {
+            Some(id) => {
+                sqlx::query("DELETE FROM tags WHERE synonym_of = $1").bind(id).execute(&mut *tx).await?;
+                sqlx::query("DELETE FROM tags WHERE id = $1").bind(id).execute(&mut *tx).await?;
+                tx.commit().await?;
+                Ok(true)
             }
-        }).await.map_err(AppError::from)
-    }
-
-    pub async fn get_documents_by_tag(&self, tag_name: &str) -> Result<Vec<String>, AppError> {
-        let tag_name_clone = tag_name.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<Vec<String>, rusqlite::Error> {
-            let mut stmt = conn.prepare(
-                "SELECT d.filename FROM documents d 
-                 JOIN document_tags dt ON d.id = dt.document_id 
-                 JOIN tags t ON dt.tag_id = t.id 
-                 WHERE t.name = ?"
-            )?;
-            let filenames_iter = stmt.query_map(params![tag_name_clone], |row| row.get(0))?;
-            filenames_iter.collect()
-        }).await.map_err(AppError::from)
-    }
-}
\ No newline at end of file
+            None => Ok(false),
+        }
+    }
+
+    // 未使用の葉タグをまとめて削除し、削除したタグの名前を返す（同義語は正規タグが消えた場合のみ削除）
+    pub async fn delete_unused_tags(&self) -> Result<Vec<String>, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let deleted: Vec<String> = sqlx::query_scalar(
+            "DELETE FROM tags WHERE synonym_of IS NULL
+             AND NOT EXISTS (SELECT 1 FROM document_tags dt WHERE dt.tag_id = tags.id)
+             AND NOT EXISTS (SELECT 1 FROM tags c WHERE c.parent_id = tags.id)
+             RETURNING name",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query("DELETE FROM tags WHERE synonym_of IS NOT NULL AND synonym_of NOT IN (SELECT id FROM tags)")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    // タグが付いたドキュメントを取得する
+    // 同義語は正規タグとして扱い、include_descendants が true なら子孫タグのドキュメントも含める
+    pub async fn get_documents_by_tag(&self, tag_name: &str, include_descendants: bool) -> Result<Vec<String>, AppError> {
+        let mut conn = self.pool.acquire().await?;
+
+        let tag_id = match resolve_tag_id(&mut conn, tag_name).await? {
+            Some(id) => id,
+            None => return Ok(Vec::new()),
+        };
+
+        let filenames = sqlx::query_scalar(
+            "WITH RECURSIVE tag_tree(id) AS (
+                 SELECT CAST($1 AS BIGINT)
+                 UNION
+                 SELECT t.id FROM tags t JOIN tag_tree tree ON t.parent_id = tree.id WHERE $2
+             )
+             SELECT DISTINCT d.filename FROM documents d
+             JOIN document_tags dt ON d.id = dt.document_id
+             WHERE dt.tag_id IN (SELECT id FROM tag_tree)
+             ORDER BY d.filename",
+        )
+        .bind(tag_id)
+        .bind(include_descendants)
+        .fetch_all(&mut *conn)
+        .await?;
+        Ok(filenames)
+    }
+}
@@ -0,0 +1,171 @@
+use sqlx::AnyConnection;
+use crate::error::AppError;
+use super::{format_timestamp, DbManager};
+
+// ユーザーの二要素認証（TOTP）の状態
+#[derive(Debug, Clone, Default)]
+pub struct TotpState {
+    // 登録を始めた時点で保存する（有効にする前は確認待ち）
+    pub secret: Option<String>,
+    pub enabled: bool,
+    // 最後に受け付けたコードの時間ステップ
+    pub last_step: Option<i64>,
+}
+
+// リカバリーコードを置き換える（トランザクションの中で使う）
+async fn replace_codes(conn: &mut AnyConnection, user_id: i64, code_hashes: &[String]) -> Result<(), AppError> {
+    sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for code_hash in code_hashes {
+        sqlx::query("INSERT INTO recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(code_hash)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+impl DbManager {
+    pub async fn get_totp_state(&self, user_id: i64) -> Result<TotpState, AppError> {
+        let row: Option<(Option<String>, Option<String>, Option<i64>)> =
+            sqlx::query_as("SELECT totp_secret, totp_enabled_at, totp_last_step FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row
+            .map(|(secret, enabled_at, last_step)| TotpState {
+                secret,
+                enabled: enabled_at.is_some(),
+                last_step,
+            })
+            .unwrap_or_default())
+    }
+
+    // 登録を始める（確認前の秘密鍵を保存する）。すでに有効なら false
+    pub async fn start_totp_enrollment(&self, user_id: i64, secret: &str) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE users SET totp_secret = $1, totp_last_step = NULL WHERE id = $2 AND totp_enabled_at IS NULL",
+        )
+        .bind(secret)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 最初のコードを確認できたら有効にし、リカバリーコードを保存する
+    // 登録を始めていないか、すでに有効なら false
+    pub async fn enable_totp(&self, user_id: i64, step: i64, code_hashes: &[String]) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            "UPDATE users SET totp_enabled_at = $1, totp_last_step = $2
+             WHERE id = $3 AND totp_secret IS NOT NULL AND totp_enabled_at IS NULL",
+        )
+        .bind(format_timestamp(chrono::Utc::now()))
+        .bind(step)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        replace_codes(&mut tx, user_id, code_hashes).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    // 二要素認証を解除し、リカバリーコードも削除する
+    pub async fn disable_totp(&self, user_id: i64) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // 受け付けたコードの時間ステップを記録する
+    // 同じか前のステップのコードがすでに使われていれば false（同じコードの再利用を防ぐ）
+    pub async fn record_totp_step(&self, user_id: i64, step: i64) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE users SET totp_last_step = $1
+             WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)",
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn replace_recovery_codes(&self, user_id: i64, code_hashes: &[String]) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        replace_codes(&mut tx, user_id, code_hashes).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    // リカバリーコードを使用済みにする（該当する未使用のコードがなければ false）
+    pub async fn use_recovery_code(&self, user_id: i64, code_hash: &str) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            "UPDATE recovery_codes SET used_at = $1 WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL",
+        )
+        .bind(format_timestamp(chrono::Utc::now()))
+        .bind(user_id)
+        .bind(code_hash)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 未使用のリカバリーコードの数
+    pub async fn count_recovery_codes(&self, user_id: i64) -> Result<i64, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM recovery_codes WHERE user_id = $1 AND used_at IS NULL")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    // 2段階目のログイン用のトークンを使用済みにする（すでに使われていれば false）
+    // 期限切れのトークンは検証で拒否されるため、その記録はここで合わせて削除する
+    pub async fn use_mfa_token(
+        &self,
+        nonce: &str,
+        user_id: i64,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM used_mfa_tokens WHERE expires_at <= $1")
+            .bind(format_timestamp(chrono::Utc::now()))
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query(
+            "INSERT INTO used_mfa_tokens (nonce, user_id, expires_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+        )
+        .bind(nonce)
+        .bind(user_id)
+        .bind(format_timestamp(expires_at))
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
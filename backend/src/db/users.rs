@@ -1,148 +1,203 @@
-use rusqlite::{params, OptionalExtension, Result as RusqliteResult, Row};
+use sqlx::any::AnyRow;
+use sqlx::{AnyConnection, Row};
 use crate::error::AppError;
 use crate::models::user::{User, Role, hash_password, verify_password};
-use super::DbManager;
+use super::{format_timestamp, DbManager};
 use std::str::FromStr;
 
+// SQLiteの DATETIME 列は Any ドライバで読めないため、テキストとして取り出す
+const USER_COLUMNS: &str =
+    "id, username, password_hash, role, email, disabled_at, CAST(created_at AS TEXT), CAST(updated_at AS TEXT)";
+
 impl User {
-    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+    fn from_row(row: &AnyRow) -> Result<Self, sqlx::Error> {
         Ok(Self {
-            id: row.get(0)?,
-            username: row.get(1)?,
-            password_hash: row.get(2)?,
-            role: Role::from_str(&row.get::<_, String>(3)?).unwrap_or(Role::Viewer),
-            created_at: row.get(4)?,
-            updated_at: row.get(5)?,
+            id: row.try_get(0)?,
+            username: row.try_get(1)?,
+            password_hash: row.try_get(2)?,
+            role: Role::from_str(&row.try_get::<String, _>(3)?).unwrap_or(Role::Viewer),
+            email: row.try_get(4)?,
+            disabled_at: row.try_get(5)?,
+            created_at: row.try_get(6)?,
+            updated_at: row.try_get(7)?,
         })
     }
 }
 
+// ユーザーを追加する（トランザクションの中で使えるよう、接続を受け取る）
+pub(super) async fn insert_user(
+    conn: &mut AnyConnection,
+    username: &str,
+    password: &str,
+    email: Option<&str>,
+    role: Role,
+) -> Result<i64, AppError> {
+    let password_hash = hash_password(password)?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let id = sqlx::query_scalar(
+        "INSERT INTO users (username, password_hash, role, email, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
+    )
+    .bind(username)
+    .bind(password_hash)
+    .bind(role.to_string())
+    .bind(email)
+    .bind(&now)
+    .bind(&now)
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(id)
+}
+
 impl DbManager {
-    pub async fn create_user(&self, username: &str, password: &str, email: &str, role: Role) -> Result<i64, AppError> {
-        let password_hash = hash_password(password)?;
-
-        let role_str = role.to_string();
-        let username = username.to_string();
-        let email = email.to_string();
-        
-        self.conn
-            .call(move |conn: &mut rusqlite::Connection| -> Result<i64, rusqlite::Error> {
-                conn.execute(
-                    "INSERT INTO users (username, password_hash, role, email, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-                    params![username, password_hash, role_str, email, chrono::Utc::now().to_rfc3339(), chrono::Utc::now().to_rfc3339()],
-                )?;
-                Ok(conn.last_insert_rowid())
-            })
-            .await.map_err(AppError::from)
+    pub async fn create_user(&self, username: &str, password: &str, email: Option<&str>, role: Role) -> Result<i64, AppError> {
+        let mut conn = self.pool.acquire().await?;
+        insert_user(&mut conn, username, password, email, role).await
+    }
+
+    pub async fn count_users(&self) -> Result<i64, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
     }
 
     pub async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, AppError> {
-        let username = username.to_string();
-        self.conn
-            .call(move |conn: &mut rusqlite::Connection| -> Result<Option<User>, rusqlite::Error> {
-                Ok(conn.query_row(
-                    "SELECT id, username, password_hash, role, created_at, updated_at FROM users WHERE username = ?1",
-                    params![username],
-                    User::from_row,
-                )
-                .optional()?)
-            })
-            .await.map_err(AppError::from)
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE username = $1", USER_COLUMNS))
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(User::from_row).transpose()?)
     }
 
     pub async fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, AppError> {
-        self.conn
-            .call(move |conn: &mut rusqlite::Connection| -> Result<Option<User>, rusqlite::Error> {
-                Ok(conn.query_row(
-                    "SELECT id, username, password_hash, role, created_at, updated_at FROM users WHERE id = ?1",
-                    params![user_id],
-                    User::from_row,
-                )
-                .optional()?)
-            })
-            .await.map_err(AppError::from)
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE id = $1", USER_COLUMNS))
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(User::from_row).transpose()?)
+    }
+
+    pub async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
+        let row = sqlx::query(&format!("SELECT {} FROM users WHERE email = $1", USER_COLUMNS))
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().map(User::from_row).transpose()?)
     }
 
     pub async fn get_password_hash(&self, username: &str) -> Result<Option<String>, AppError> {
-        let username = username.to_string();
-        self.conn
-            .call(move |conn: &mut rusqlite::Connection| -> Result<Option<String>, rusqlite::Error> {
-                Ok(conn.query_row(
-                    "SELECT password_hash FROM users WHERE username = ?1",
-                    params![username],
-                    |row| row.get(0),
-                )
-                .optional()?)
-            })
-            .await.map_err(AppError::from)
+        let hash = sqlx::query_scalar("SELECT password_hash FROM users WHERE username = $1")
+            .bind(username)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(hash)
     }
 
     pub async fn update_password(&self, user_id: i64, new_password_hash: &str) -> Result<(), AppError> {
-        let new_password_hash = new_password_hash.to_string();
-        self.conn
-            .call(move |conn: &mut rusqlite::Connection| -> Result<(), rusqlite::Error> {
-                Ok(conn.execute(
-                    "UPDATE users SET password_hash = ?1 WHERE id = ?2",
-                    params![new_password_hash, user_id],
-                )
-                .map(|_| ())?)
-            })
-            .await.map_err(AppError::from)
+        sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(new_password_hash)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     pub async fn get_all_users(&self) -> Result<Vec<User>, AppError> {
-        self.conn
-            .call(move |conn: &mut rusqlite::Connection| -> Result<Vec<User>, rusqlite::Error> {
-                let mut stmt = conn.prepare("SELECT id, username, password_hash, role, created_at, updated_at FROM users")?;
-                let users_iter = stmt.query_map([], User::from_row)?;
-                users_iter.collect()
-            })
-            .await.map_err(AppError::from)
+        self.list_users().await
     }
 
     pub async fn update_user_role(&self, username: &str, new_role: &str) -> Result<bool, AppError> {
-        let username_clone = username.to_string();
-        let new_role_clone = new_role.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<bool, rusqlite::Error> {
-            let rows = conn.execute(
-                "UPDATE users SET role = ? WHERE username = ?",
-                params![new_role_clone, username_clone],
-            )?;
-            Ok(rows > 0)
-        }).await.map_err(AppError::from)
-    }
-
-    pub async fn delete_user(&self, username: &str) -> Result<bool, AppError> {
-        let username_clone = username.to_string();
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<bool, rusqlite::Error> {
-            let rows = conn.execute(
-                "DELETE FROM users WHERE username = ?",
-                params![username_clone],
-            )?;
-            Ok(rows > 0)
-        }).await.map_err(AppError::from)
+        let result = sqlx::query("UPDATE users SET role = $1 WHERE username = $2")
+            .bind(new_role)
+            .bind(username)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 無効にする（または元に戻す）。該当するユーザーがいなければ false
+    pub async fn set_user_disabled(&self, user_id: i64, disabled: bool) -> Result<bool, AppError> {
+        let disabled_at = disabled.then(|| format_timestamp(chrono::Utc::now()));
+        let result = sqlx::query("UPDATE users SET disabled_at = $1 WHERE id = $2")
+            .bind(disabled_at)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 有効な管理者の数（グループによる管理者は含めない）
+    pub async fn count_active_admins(&self) -> Result<i64, AppError> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE role = 'admin' AND disabled_at IS NULL")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count)
+    }
+
+    // ユーザーを削除する
+    // ドキュメント・添付ファイル・共有リンクは削除しない。`reassign_to` を指定した場合は作成者をそのユーザーに付け替え、
+    // 指定しなければ作成者を空にする（外部キーの ON DELETE SET NULL と同じ結果になる）
+    pub async fn delete_user(&self, username: &str, reassign_to: Option<i64>) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+
+        for statement in [
+            "UPDATE documents SET created_by = $1 WHERE created_by IN (SELECT id FROM users WHERE username = $2)",
+            "UPDATE attachments SET uploaded_by = $1 WHERE uploaded_by IN (SELECT id FROM users WHERE username = $2)",
+            "UPDATE share_links SET created_by = $1 WHERE created_by IN (SELECT id FROM users WHERE username = $2)",
+            "UPDATE invitations SET created_by = $1 WHERE created_by IN (SELECT id FROM users WHERE username = $2)",
+        ] {
+            sqlx::query(statement)
+                .bind(reassign_to)
+                .bind(username)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // 削除したユーザーのIDが再利用されても権限を引き継がないよう、ACLからも取り除く（ACLは外部キーを持たない）
+        sqlx::query(
+            "DELETE FROM acl_entries WHERE principal_type = 'user'
+             AND principal_id IN (SELECT id FROM users WHERE username = $1)",
+        )
+        .bind(username)
+        .execute(&mut *tx)
+        .await?;
+
+        // セッション・APIトークン・グループの所属・リカバリーコードなどは外部キーの ON DELETE CASCADE で削除される
+        // 招待を受け入れたユーザーの記録は ON DELETE SET NULL で空になる
+        let result = sqlx::query("DELETE FROM users WHERE username = $1")
+            .bind(username)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
     }
 
     pub async fn list_users(&self) -> Result<Vec<User>, AppError> {
-        self.conn.call(move |conn: &mut rusqlite::Connection| -> Result<Vec<User>, rusqlite::Error> {
-            let mut stmt = conn.prepare("SELECT id, username, password_hash, role, created_at, updated_at FROM users")?;
-            let users_iter = stmt.query_map([], User::from_row)?;
-            users_iter.collect()
-        }).await.map_err(AppError::from)
+        let rows = sqlx::query(&format!("SELECT {} FROM users ORDER BY id", USER_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(User::from_row).collect::<Result<_, _>>()?)
     }
 
+    // ローカルのパスワードを持つユーザーはそのパスワードで確認する
+    // それ以外は外部の認証基盤に順に問い合わせ、認証できたらユーザーを作成（または更新）する
     pub async fn authenticate_user(&self, username: &str, password: &str) -> Result<Option<User>, AppError> {
-        let user = self.get_user_by_username(username).await?;
+        if self.local_passwords {
+            if let Some(user) = self.get_user_by_username(username).await? {
+                if !user.password_hash.is_empty() {
+                    return Ok(verify_password(&user.password_hash, password).then_some(user));
+                }
+            }
+        }
 
-        if let Some(user) = user {
-            if verify_password(&user.password_hash, password) {
-                Ok(Some(user))
-            } else {
-                Ok(None) // Invalid password
+        for provider in &self.auth_providers {
+            if let Some(identity) = provider.authenticate(username, password).await? {
+                return self.provision_external_user(&identity).await.map(Some);
             }
-        } else {
-            Ok(None) // User not found
         }
+        Ok(None)
     }
 
     pub async fn change_password(&self, user_id: i64, current_password: &str, new_password: &str) -> Result<(), AppError> {
@@ -156,4 +211,4 @@ impl DbManager {
 
         self.update_password(user_id, &new_password_hash).await
     }
-}
\ No newline at end of file
+}
@@ -10,6 +10,9 @@ use thiserror::Error;
 pub enum AppError {
     #[error("Authentication error: {0}")]
     Auth(String),
+
+    #[error("Permission denied: {0}")]
+    Forbidden(String),
     
     #[error("Database error: {0}")]
     Database(String),
@@ -22,14 +25,17 @@ pub enum AppError {
     
     #[error("Invalid input: {0}")]
     InvalidInput(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
     
     #[error("Internal server error: {0}")]
     Internal(String),
 }
 
 // エラーの変換処理
-impl From<rusqlite::Error> for AppError {
-    fn from(err: rusqlite::Error) -> Self {
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
         AppError::Database(err.to_string())
     }
 }
@@ -40,32 +46,39 @@ impl From<git2::Error> for AppError {
     }
 }
 
-impl From<tokio_rusqlite::Error> for AppError {
-    fn from(err: tokio_rusqlite::Error) -> Self {
-        AppError::Database(err.to_string())
-    }
-}
-
 impl From<std::io::Error> for AppError {
     fn from(err: std::io::Error) -> Self {
         AppError::Internal(err.to_string())
     }
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let status = match &self {
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
             AppError::Auth(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
             AppError::NotFound(_) => StatusCode::NOT_FOUND,
             AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+        }
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        <(StatusCode, Json<serde_json::Value>)>::from(self).into_response()
+    }
+}
 
+// ハンドラのエラー型 `(StatusCode, Json)` に `?` で変換できるようにする
+impl From<AppError> for (StatusCode, Json<serde_json::Value>) {
+    fn from(err: AppError) -> Self {
         let body = Json(json!({
-            "error": self.to_string()
+            "error": err.to_string()
         }));
 
-        (status, body).into_response()
+        (err.status_code(), body)
     }
 }
 
@@ -3,7 +3,6 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use parking_lot::Mutex;
 use serde::{Serialize, Deserialize};
-use chrono::DateTime;
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -80,6 +79,12 @@ impl GitRepository {
     pub fn get_file_history<P: AsRef<Path>>(&self, file_path: P) -> Result<Vec<CommitInfo>, GitError> {
         let path = file_path.as_ref();
         let repo = self.repo.lock();
+
+        // まだコミットがない場合は履歴なし
+        if repo.head().is_err() {
+            return Ok(Vec::new());
+        }
+
         let mut revwalk = repo.revwalk()?;
         revwalk.push_head()?;
         
@@ -87,38 +92,9 @@ impl GitRepository {
         for oid in revwalk {
             let oid = oid?;
             let commit = repo.find_commit(oid)?;
-            
-            if let Some(parent) = commit.parent(0).ok() {
-                let diff = repo.diff_tree_to_tree(
-                    Some(&parent.tree()?), 
-                    Some(&commit.tree()?), 
-                    None,
-                )?;
-
-                let mut found = false;
-                diff.foreach(
-                    &mut |delta, _| {
-                        if let Some(file) = delta.new_file().path() {
-                            if file == path {
-                                found = true;
-                                return false; // Stop iteration
-                            }
-                        }
-                        true
-                    },
-                    None,
-                    None,
-                    None,
-                )?;
 
-                if found {
-                    history.push(CommitInfo {
-                        id: commit.id().to_string(),
-                        author: commit.author().name().unwrap_or("Unknown").to_string(),
-                        message: commit.message().unwrap_or("").to_string(),
-                        timestamp: commit.time().seconds(),
-                    });
-                }
+            if Self::file_changed_in_commit(&repo, &commit, path)? {
+                history.push(CommitInfo::from_commit(&commit));
             }
         }
 
@@ -158,7 +134,7 @@ impl GitRepository {
             let commit = repo.find_commit(oid)?;
             
             // このコミットでファイルが変更されたかチェック
-            if self.file_changed_in_commit(&commit, relative_path)? {
+            if Self::file_changed_in_commit(&repo, &commit, relative_path)? {
                 commits.push(self.commit_to_info(&commit)?);
             }
         }
@@ -166,8 +142,9 @@ impl GitRepository {
         Ok(commits)
     }
     
-    // 特定のコミットでファイルが変更されたかチェック
-    fn file_changed_in_commit(&self, commit: &Commit, file_path: &Path) -> Result<bool, GitError> {
+    // 特定のコミットでファイルが変更（追加・更新・削除）されたかチェック
+    // 呼び出し側でリポジトリのロックを保持したまま使う
+    fn file_changed_in_commit(repo: &Repository, commit: &Commit, file_path: &Path) -> Result<bool, GitError> {
         if commit.parent_count() == 0 {
             // 最初のコミットの場合、ツリーからファイルを探す
             let tree = commit.tree()?;
@@ -178,30 +155,15 @@ impl GitRepository {
         let parent_tree = parent.tree()?;
         let commit_tree = commit.tree()?;
         
-        let repo = self.repo.lock();
         let diff = repo.diff_tree_to_tree(
             Some(&parent_tree),
             Some(&commit_tree),
             None,
         )?;
         
-        let mut found = false;
-        diff.foreach(
-            &mut |_delta, _progress| { true },
-            None,
-            None,
-            Some(&mut |diff_file, _binary, _| {
-                if let Some(diff_path) = diff_file.new_file().path() {
-                    if diff_path == file_path {
-                        found = true;
-                        return false;
-                    }
-                }
-                true
-            }),
-        )?;
-        
-        Ok(found)
+        Ok(diff.deltas().any(|delta| {
+            delta.new_file().path() == Some(file_path) || delta.old_file().path() == Some(file_path)
+        }))
     }
     
     // コミットオブジェクトをCommitInfo構造体に変換
@@ -218,8 +180,7 @@ impl GitRepository {
     }
     
     // 署名（コミット作者情報）を作成
-    fn get_signature(&self) -> Result<Signature<'_>, GitError> {
-        let repo = self.repo.lock();
+    fn get_signature(repo: &Repository) -> Result<Signature<'static>, GitError> {
         let config = repo.config()?;
         
         let name = config.get_string("user.name")
@@ -233,11 +194,11 @@ impl GitRepository {
             .unwrap_or_default()
             .as_secs() as i64;
             
-        Ok(Signature::new(&name, &email, &Time::new(now, 0))?)
+        Signature::new(&name, &email, &Time::new(now, 0))
     }
     
     // HEADコミットを取得
-    fn get_head_commit(repo: &Repository) -> Result<Commit, GitError> {
+    fn get_head_commit(repo: &Repository) -> Result<Commit<'_>, GitError> {
         let head = repo.head()?;
         let head_commit = head.peel_to_commit()?;
         Ok(head_commit)
@@ -265,7 +226,7 @@ impl GitRepository {
         let tree = repo.find_tree(tree_id)?;
         
         // コミット作成
-        let signature = self.get_signature()?;
+        let signature = GitRepository::get_signature(&repo)?;
         let parent_commit = GitRepository::get_head_commit(&repo)?;
         
         let commit_id = repo.commit(
@@ -280,6 +241,30 @@ impl GitRepository {
         Ok(commit_id.to_string())
     }
     
+    // ファイルの名前を変更してコミット（作業ツリーのファイルは呼び出し側で移動しておく）
+    pub fn rename_file(&self, from: &str, to: &str, message: &str, author: &str) -> Result<Oid, GitError> {
+        let repo = self.repo.lock();
+        let mut index = repo.index()?;
+        index.remove_path(Path::new(from))?;
+        index.add_path(Path::new(to))?;
+        index.write()?;
+
+        let tree_id = index.write_tree()?;
+        let tree = repo.find_tree(tree_id)?;
+
+        let signature = Signature::now(author, "user@example.com")?;
+        let parent_commit = GitRepository::get_head_commit(&repo)?;
+
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            message,
+            &tree,
+            &[&parent_commit],
+        )
+    }
+
     // リポジトリの全コミット履歴を取得
     pub fn get_repo_history(&self, limit: usize) -> Result<Vec<CommitInfo>, GitError> {
         let repo = self.repo.lock();
@@ -302,13 +287,6 @@ impl GitRepository {
     }
 }
 
-// Unixタイムスタンプをフォーマットする関数
-fn format_timestamp(timestamp: i64) -> String {
-    let dt = DateTime::from_timestamp(timestamp, 0)
-        .unwrap_or_else(|| DateTime::from_timestamp(0, 0).unwrap());
-    dt.format("%Y-%m-%d %H:%M:%S").to_string()
-}
-
 impl CommitInfo {
     pub fn from_commit(commit: &Commit) -> Self {
         let author = commit.author();
@@ -322,9 +300,3 @@ impl CommitInfo {
         }
     }
 }
-
-fn format_time(time: &Time) -> String {
-    let dt = chrono::DateTime::from_timestamp(time.seconds(), 0)
-        .unwrap_or(chrono::DateTime::from_timestamp(0, 0).unwrap());
-    dt.format("%Y-%m-%d %H:%M:%S").to_string()
-} 
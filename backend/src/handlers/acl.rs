@@ -0,0 +1,200 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{auth, storage, AppError, AppState};
+use crate::auth::acl::{self, AccessControl, Subject};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::db::{AclEntry, DbManager};
+use crate::models::acl::{Permission, PrincipalType};
+
+#[derive(Serialize)]
+pub struct AclResponse {
+    path: String,
+    entries: Vec<AclEntry>,
+    // 自身にACLがなく、上位のフォルダの設定が適用されている場合はそのパス
+    inherited_from: Option<String>,
+}
+
+// `user` か `group` のどちらか一方を指定する
+#[derive(Deserialize)]
+pub struct AclEntryRequest {
+    user: Option<String>,
+    group: Option<String>,
+    permission: Permission,
+}
+
+#[derive(Deserialize)]
+pub struct SetAclRequest {
+    entries: Vec<AclEntryRequest>,
+}
+
+// ドキュメントのACLを取得
+pub async fn get_document_acl(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Path(filename): Path<String>,
+) -> Result<Json<AclResponse>, (StatusCode, Json<serde_json::Value>)> {
+    storage::validate_name(&filename)?;
+    get_acl(&state, &claims, filename).await
+}
+
+// ドキュメントのACLを置き換える
+pub async fn set_document_acl(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(filename): Path<String>,
+    Json(request): Json<SetAclRequest>,
+) -> Result<Json<AclResponse>, (StatusCode, Json<serde_json::Value>)> {
+    storage::validate_name(&filename)?;
+    set_acl(&state, &claims, &audit, filename, request).await
+}
+
+// フォルダのACLを取得
+pub async fn get_folder_acl(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Path(folder): Path<String>,
+) -> Result<Json<AclResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let folder = folder.trim_matches('/');
+    storage::validate_name(folder)?;
+    get_acl(&state, &claims, acl::folder_path(folder)).await
+}
+
+// フォルダのACLを置き換える（配下のドキュメント・フォルダに継承される）
+pub async fn set_folder_acl(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(folder): Path<String>,
+    Json(request): Json<SetAclRequest>,
+) -> Result<Json<AclResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let folder = folder.trim_matches('/');
+    storage::validate_name(folder)?;
+    set_acl(&state, &claims, &audit, acl::folder_path(folder), request).await
+}
+
+async fn acl_response(db: &DbManager, path: String) -> Result<AclResponse, AppError> {
+    let access = acl::load(db, &path).await?;
+    let entries = db.get_acl_entries(std::slice::from_ref(&path)).await?;
+    let inherited_from = access.effective_path(&path).filter(|effective| *effective != path);
+
+    Ok(AclResponse {
+        path,
+        entries,
+        inherited_from,
+    })
+}
+
+async fn get_acl(
+    state: &AppState,
+    claims: &auth::Claims,
+    path: String,
+) -> Result<Json<AclResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(state)?;
+
+    acl::authorize(state, Some(claims), &path, Permission::Read).await?;
+    Ok(Json(acl_response(db, path).await?))
+}
+
+// 監査ログに残すACLの内容（空なら上位のフォルダから継承する）
+fn acl_summary(entries: &[AclEntry]) -> String {
+    if entries.is_empty() {
+        return "inherited".to_string();
+    }
+    entries
+        .iter()
+        .map(|entry| {
+            let principal = entry.principal.clone().unwrap_or_else(|| entry.principal_id.to_string());
+            format!("{}:{}={}", entry.principal_type, principal, entry.permission)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+async fn set_acl(
+    state: &AppState,
+    claims: &auth::Claims,
+    audit: &AuditContext,
+    path: String,
+    request: SetAclRequest,
+) -> Result<Json<AclResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(state)?;
+
+    acl::authorize(state, Some(claims), &path, Permission::Admin).await?;
+
+    // 同じ相手が複数回指定された場合は強い方の権限にまとめる
+    let mut permissions: BTreeMap<(PrincipalType, i64), (String, Permission)> = BTreeMap::new();
+    for entry in request.entries {
+        let (principal_type, id, name) = match (entry.user, entry.group) {
+            (Some(username), None) => {
+                let user = db
+                    .get_user_by_username(&username)
+                    .await?
+                    .ok_or_else(|| AppError::InvalidInput(format!("User {} not found", username)))?;
+                (PrincipalType::User, user.id, user.username)
+            }
+            (None, Some(name)) => {
+                let group = db
+                    .get_group(&name)
+                    .await?
+                    .ok_or_else(|| AppError::InvalidInput(format!("Group {} not found", name)))?;
+                (PrincipalType::Group, group.id, group.name)
+            }
+            _ => {
+                return Err(AppError::InvalidInput(
+                    "Each ACL entry needs either user or group".to_string(),
+                ).into());
+            }
+        };
+
+        let slot = permissions
+            .entry((principal_type, id))
+            .or_insert((name, entry.permission));
+        slot.1 = slot.1.max(entry.permission);
+    }
+
+    let entries: Vec<AclEntry> = permissions
+        .into_iter()
+        .map(|((principal_type, id), (name, permission))| AclEntry {
+            path: path.clone(),
+            principal_type,
+            principal_id: id,
+            principal: Some(name),
+            permission,
+        })
+        .collect();
+
+    // 設定したユーザー自身が管理できなくならないようにする（空にして継承に戻すのは可）
+    let subject = Subject::load(Some(db), Some(claims)).await?;
+    if !entries.is_empty() && !AccessControl::new(entries.clone()).allows(&subject, &path, Permission::Admin) {
+        return Err(AppError::InvalidInput(
+            "The ACL must keep admin permission for yourself".to_string(),
+        ).into());
+    }
+
+    let previous = db.get_acl_entries(std::slice::from_ref(&path)).await?;
+    let rows: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.principal_type, entry.principal_id, entry.permission))
+        .collect();
+    db.set_acl_entries(&path, &rows).await?;
+
+    let target_type = if path.ends_with('/') { "folder" } else { "document" };
+    audit
+        .record(
+            db,
+            AuditEvent::new("permission.acl", target_type, &path)
+                .before(acl_summary(&previous))
+                .after(acl_summary(&entries)),
+        )
+        .await;
+
+    Ok(Json(acl_response(db, path).await?))
+}
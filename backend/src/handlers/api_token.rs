@@ -0,0 +1,114 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::{auth, AppError, AppState};
+use crate::auth::api_token::generate_api_token;
+use crate::auth::session::hash_secret_token;
+use crate::db::ApiToken;
+use crate::handlers::database;
+use crate::models::user::{Role, TokenScope};
+
+// APIトークンの有効期間（日）
+const DEFAULT_API_TOKEN_DAYS: i64 = 90;
+const MAX_API_TOKEN_DAYS: i64 = 365;
+
+#[derive(Deserialize)]
+pub struct CreateApiTokenRequest {
+    name: String,
+    scope: TokenScope,
+    expires_in_days: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    api_token: ApiToken,
+    token: String,
+}
+
+#[derive(Serialize)]
+pub struct ApiTokenList {
+    tokens: Vec<ApiToken>,
+}
+
+// APIトークンで新しいトークンを作ったり取り消したりできないようにする
+fn require_login(claims: &auth::Claims) -> Result<(), AppError> {
+    if claims.api_token.is_some() {
+        return Err(AppError::Forbidden("API tokens cannot be used to manage API tokens".to_string()));
+    }
+    Ok(())
+}
+
+// 自分のAPIトークンの一覧
+pub async fn list_api_tokens(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<Json<ApiTokenList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    Ok(Json(ApiTokenList {
+        tokens: db.list_user_api_tokens(claims.sub).await?,
+    }))
+}
+
+// APIトークンを作成する（トークンはこのレスポンスでのみ返す）
+pub async fn create_api_token(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> Result<(StatusCode, Json<CreatedApiToken>), (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    require_login(&claims)?;
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Token name cannot be empty".to_string()).into());
+    }
+
+    let role = Role::from_str(&claims.role).map_err(AppError::Auth)?;
+    if !role.includes(request.scope.max_role()) {
+        return Err(AppError::Forbidden(format!(
+            "The {} scope requires the {} role",
+            request.scope,
+            request.scope.max_role()
+        ))
+        .into());
+    }
+
+    let days = request.expires_in_days.unwrap_or(DEFAULT_API_TOKEN_DAYS);
+    if !(1..=MAX_API_TOKEN_DAYS).contains(&days) {
+        return Err(AppError::InvalidInput(format!(
+            "expires_in_days must be between 1 and {}",
+            MAX_API_TOKEN_DAYS
+        ))
+        .into());
+    }
+
+    let token = generate_api_token();
+    let expires_at = chrono::Utc::now() + chrono::Duration::days(days);
+    let api_token = db
+        .create_api_token(claims.sub, name, &hash_secret_token(&token), request.scope, expires_at)
+        .await?;
+
+    Ok((StatusCode::CREATED, Json(CreatedApiToken { api_token, token })))
+}
+
+// 自分のAPIトークンを取り消す
+pub async fn revoke_api_token(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    require_login(&claims)?;
+
+    if db.revoke_api_token(id, claims.sub).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(AppError::NotFound(format!("API token {} not found", id)).into())
+    }
+}
@@ -0,0 +1,391 @@
+use axum::{
+    body::Body,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{auth, images, AppError, AppState};
+use crate::auth::acl;
+use crate::auth::attachment::{create_attachment_token, verify_attachment_token};
+use crate::db::{Attachment, NewAttachment};
+use crate::models::acl::Permission;
+
+#[derive(Deserialize)]
+pub struct ResizeQuery {
+    w: Option<u32>,
+    // 署名付きURLのトークン
+    token: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SignedQuery {
+    token: Option<String>,
+}
+
+// 添付ファイルと、ログインのトークンなしで取得できる署名付きURL
+#[derive(Serialize)]
+pub struct SignedAttachment {
+    #[serde(flatten)]
+    attachment: Attachment,
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct AttachmentList {
+    document: String,
+    attachments: Vec<SignedAttachment>,
+}
+
+// ファイル先頭のシグネチャから形式を判定する
+// 受け付けるのはページに埋め込む画像とPDFのみ（SVGはスクリプトを含められるため対象外）
+fn detect_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"\xff\xd8\xff") {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else {
+        None
+    }
+}
+
+// アップロードされたファイル名からディレクトリ部分と制御文字を取り除く
+fn sanitize_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base.chars().filter(|c| !c.is_control()).take(255).collect();
+    let cleaned = cleaned.trim().trim_start_matches('.').to_string();
+
+    if cleaned.is_empty() {
+        "attachment".to_string()
+    } else {
+        cleaned
+    }
+}
+
+// Content-Disposition 用にファイル名をパーセントエンコードする（RFC 5987）
+fn encode_filename(name: &str) -> String {
+    name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn error_response(status: StatusCode, message: String) -> (StatusCode, Json<serde_json::Value>) {
+    (status, Json(serde_json::json!({ "error": message })))
+}
+
+// 添付ファイルをアップロードする
+// multipart の `document`（ドキュメント名）と `file`（ファイル本体）を受け取る
+pub async fn upload_attachment(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<Attachment>), (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db,
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "Database not initialized" })),
+            ));
+        }
+    };
+
+    let max_size = state.config.max_attachment_size;
+    let mut document: Option<String> = None;
+    let mut file: Option<(String, Vec<u8>)> = None;
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return Err(error_response(e.status(), format!("Invalid multipart body: {}", e.body_text()))),
+        };
+
+        match field.name() {
+            Some("document") => {
+                let value = field.text().await
+                    .map_err(|e| error_response(e.status(), format!("Invalid document field: {}", e.body_text())))?;
+                document = Some(value.trim().to_string());
+            }
+            Some("file") => {
+                let filename = sanitize_filename(field.file_name().unwrap_or(""));
+                let data = field.bytes().await
+                    .map_err(|e| error_response(e.status(), format!("Failed to read file: {}", e.body_text())))?;
+                if data.len() > max_size {
+                    return Err(error_response(
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!("Attachment exceeds the maximum size of {} bytes", max_size),
+                    ));
+                }
+                file = Some((filename, data.to_vec()));
+            }
+            _ => {}
+        }
+    }
+
+    let document = document
+        .filter(|d| !d.is_empty())
+        .ok_or_else(|| error_response(StatusCode::BAD_REQUEST, "Missing document field".to_string()))?;
+    let (filename, data) = file
+        .ok_or_else(|| error_response(StatusCode::BAD_REQUEST, "Missing file field".to_string()))?;
+
+    acl::authorize(&state, Some(&claims), &document, Permission::Write).await?;
+
+    if data.is_empty() {
+        return Err(error_response(StatusCode::BAD_REQUEST, "Attachment is empty".to_string()));
+    }
+    let content_type = detect_content_type(&data).ok_or_else(|| {
+        error_response(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Only PNG, JPEG, GIF, WebP images and PDF files can be attached".to_string(),
+        )
+    })?;
+
+    // 位置情報などを含む EXIF は保存前に取り除く
+    let data = match tokio::task::spawn_blocking(move || images::strip_metadata(content_type, &data)).await {
+        Ok(Ok(data)) => data,
+        Ok(Err(AppError::InvalidInput(e))) => return Err(error_response(StatusCode::BAD_REQUEST, e)),
+        Ok(Err(e)) => return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+
+    match state.document_store.exists(&document).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Err(error_response(StatusCode::NOT_FOUND, format!("Document {} not found", document)));
+        }
+        Err(AppError::InvalidInput(e)) => return Err(error_response(StatusCode::BAD_REQUEST, e)),
+        Err(e) => return Err(error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+
+    let hash = format!("{:x}", Sha256::digest(&data));
+    let commit_message = format!("Add attachment {} to {}.md", filename, document);
+    if let Err(e) = state.document_store.write_attachment(&hash, &data, &commit_message).await {
+        return Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to store attachment: {}", e),
+        ));
+    }
+
+    let attachment = db
+        .create_attachment(NewAttachment {
+            document,
+            filename,
+            content_type: content_type.to_string(),
+            size: data.len() as i64,
+            hash,
+            uploaded_by: Some(claims.sub),
+        })
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save attachment: {}", e)))?;
+
+    Ok((StatusCode::CREATED, Json(attachment)))
+}
+
+// 添付ファイルを配信する
+// `?w=` を指定すると画像をその幅に縮小して返す
+pub async fn get_attachment(
+    State(state): State<AppState>,
+    claims: Option<auth::Claims>,
+    Path(id): Path<i64>,
+    Query(query): Query<ResizeQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    if let Some(width) = query.w {
+        if !(images::MIN_RESIZE_WIDTH..=images::MAX_RESIZE_WIDTH).contains(&width) {
+            return Err(error_response(
+                StatusCode::BAD_REQUEST,
+                format!("w must be between {} and {}", images::MIN_RESIZE_WIDTH, images::MAX_RESIZE_WIDTH),
+            ));
+        }
+    }
+
+    serve_attachment(&state, claims.as_ref(), query.token.as_deref(), id, query.w, &headers).await
+}
+
+// 添付画像のサムネイルを配信する
+pub async fn get_attachment_thumbnail(
+    State(state): State<AppState>,
+    claims: Option<auth::Claims>,
+    Path(id): Path<i64>,
+    Query(query): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    serve_attachment(&state, claims.as_ref(), query.token.as_deref(), id, Some(images::THUMBNAIL_WIDTH), &headers).await
+}
+
+// 縮小画像をキャッシュから取得し、なければ生成してキャッシュに保存する
+async fn resized_content(
+    state: &AppState,
+    attachment: &Attachment,
+    width: u32,
+) -> Result<Vec<u8>, (StatusCode, Json<serde_json::Value>)> {
+    let path = images::cache_path(&state.config.cache_dir, &attachment.hash, width);
+    if let Some(content) = images::read_cache(&path) {
+        return Ok(content);
+    }
+
+    let original = read_content(state, attachment).await?;
+    let content_type = attachment.content_type.clone();
+    let resized = tokio::task::spawn_blocking(move || images::resize(&content_type, &original, width))
+        .await
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to resize attachment: {}", e)))?;
+
+    // キャッシュに保存できなくても縮小画像は返す
+    if let Err(e) = images::write_cache(&path, &resized) {
+        tracing::warn!("Failed to cache resized attachment {}: {}", path.display(), e);
+    }
+    Ok(resized)
+}
+
+async fn read_content(
+    state: &AppState,
+    attachment: &Attachment,
+) -> Result<Vec<u8>, (StatusCode, Json<serde_json::Value>)> {
+    match state.document_store.read_attachment(&attachment.hash).await {
+        Ok(content) => Ok(content),
+        Err(AppError::NotFound(e)) => Err(error_response(StatusCode::NOT_FOUND, e)),
+        Err(e) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to read attachment: {}", e),
+        )),
+    }
+}
+
+// 内容はハッシュで識別され変更されないため、長期間キャッシュさせる
+// 添付先のドキュメントを読めるユーザーか、その添付ファイルの署名付きURLにだけ配信する
+async fn serve_attachment(
+    state: &AppState,
+    claims: Option<&auth::Claims>,
+    signed: Option<&str>,
+    id: i64,
+    width: Option<u32>,
+    headers: &HeaderMap,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db,
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "Database not initialized" })),
+            ));
+        }
+    };
+
+    let attachment = match db.get_attachment(id).await {
+        Ok(Some(attachment)) => attachment,
+        Ok(None) => {
+            return Err(error_response(StatusCode::NOT_FOUND, format!("Attachment {} not found", id)));
+        }
+        Err(e) => {
+            return Err(error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to get attachment: {}", e),
+            ));
+        }
+    };
+
+    match signed {
+        Some(token) => {
+            if verify_attachment_token(&state.tokens, token)?.att != attachment.id {
+                return Err(AppError::Auth("Attachment link is not valid for this attachment".to_string()).into());
+            }
+        }
+        None => acl::authorize(state, claims, &attachment.document, Permission::Read).await?,
+    }
+
+    if width.is_some() && !images::is_resizable(&attachment.content_type) {
+        return Err(error_response(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("{} attachments cannot be resized", attachment.content_type),
+        ));
+    }
+
+    let etag = match width {
+        Some(width) => format!("\"{}-w{}\"", attachment.hash, width),
+        None => format!("\"{}\"", attachment.hash),
+    };
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+
+    let builder = Response::builder()
+        .header(header::ETAG, &etag)
+        .header(header::CACHE_CONTROL, "private, max-age=31536000, immutable");
+
+    if not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+    }
+
+    let content = match width {
+        Some(width) => resized_content(state, &attachment, width).await?,
+        None => read_content(state, &attachment).await?,
+    };
+
+    let disposition = format!("inline; filename*=UTF-8''{}", encode_filename(&attachment.filename));
+    builder
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, &attachment.content_type)
+        .header(header::CONTENT_LENGTH, content.len())
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .header(header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))
+        .body(Body::from(content))
+        .map_err(|e| error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+// ドキュメントに添付されたファイルの一覧を取得する
+pub async fn list_document_attachments(
+    State(state): State<AppState>,
+    claims: Option<auth::Claims>,
+    Path(filename): Path<String>,
+) -> Result<Json<AttachmentList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db,
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": "Database not initialized" })),
+            ));
+        }
+    };
+
+    acl::authorize(&state, claims.as_ref(), &filename, Permission::Read).await?;
+
+    let attachments = db.list_document_attachments(&filename).await.map_err(|e| {
+        error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to list attachments: {}", e))
+    })?;
+
+    // ページに埋め込む画像は Authorization ヘッダを送れないため、署名付きURLを添える
+    let attachments = attachments
+        .into_iter()
+        .map(|attachment| {
+            let token = create_attachment_token(&state.tokens, attachment.id)?;
+            Ok(SignedAttachment {
+                url: format!("/api/attachments/{}?token={}", attachment.id, token),
+                attachment,
+            })
+        })
+        .collect::<Result<Vec<_>, AppError>>()?;
+
+    Ok(Json(AttachmentList {
+        document: filename,
+        attachments,
+    }))
+}
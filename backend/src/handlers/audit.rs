@@ -0,0 +1,309 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Query, State},
+    http::{header, request::Parts, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use crate::{auth, AppError, AppState};
+use crate::db::{AuditEntry, AuditFilter, DbManager, NewAuditEntry};
+use crate::handlers::{client_ip, database};
+use crate::handlers::pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+// エクスポートで一度に読み出す件数
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+// 監査ログに残すリクエストの情報（操作したユーザー・IPアドレス・User-Agent）
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    actor_id: Option<i64>,
+    actor: Option<String>,
+    ip_address: Option<String>,
+    user_agent: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        // 操作したユーザーは認証ミドルウェアで検証済みのものだけを使う
+        let actor_id = parts.extensions.get::<auth::Claims>().map(|claims| claims.sub);
+        let connect_info = parts.extensions.get::<ConnectInfo<SocketAddr>>();
+
+        Ok(Self {
+            actor_id,
+            actor: None,
+            ip_address: client_ip(state, &parts.headers, connect_info),
+            user_agent: parts
+                .headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+        })
+    }
+}
+
+impl AuditContext {
+    // ログインのように、まだトークンがない操作のユーザーを指定する
+    pub(crate) fn with_actor(&self, actor_id: Option<i64>, actor: &str) -> Self {
+        Self {
+            actor_id,
+            actor: Some(actor.to_string()),
+            ..self.clone()
+        }
+    }
+
+    pub(crate) fn ip_address(&self) -> Option<&str> {
+        self.ip_address.as_deref()
+    }
+
+    pub(crate) fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    // 操作は記録の前に済んでいるため、記録に失敗しても操作は失敗にせず、エラーをログに残す
+    pub(crate) async fn record(&self, db: &DbManager, event: AuditEvent<'_>) {
+        if let Err(e) = self.try_record(db, &event).await {
+            tracing::error!("Failed to record audit event {} for {} {}: {}", event.action, event.target_type, event.target, e);
+        }
+    }
+
+    async fn try_record(&self, db: &DbManager, event: &AuditEvent<'_>) -> Result<(), AppError> {
+        // ユーザーが後で削除・改名されても分かるよう、記録した時点のユーザー名を残す
+        let actor = match (&self.actor, self.actor_id) {
+            (Some(actor), _) => Some(actor.clone()),
+            (None, Some(actor_id)) => db.get_user_by_id(actor_id).await?.map(|user| user.username),
+            (None, None) => None,
+        };
+
+        db.record_audit(&NewAuditEntry {
+            action: event.action,
+            actor_id: self.actor_id,
+            actor: actor.as_deref(),
+            ip_address: self.ip_address.as_deref(),
+            user_agent: self.user_agent.as_deref(),
+            target_type: event.target_type,
+            target: event.target,
+            before: event.before.as_deref(),
+            after: event.after.as_deref(),
+        })
+        .await
+    }
+
+    // ドキュメントはデータベースがなくても扱えるため、その場合は記録しない
+    pub(crate) async fn record_document(&self, state: &AppState, event: AuditEvent<'_>) {
+        if let Some(db) = &state.db_manager {
+            self.record(db, event).await;
+        }
+    }
+}
+
+// 監査ログに記録する操作
+pub(crate) struct AuditEvent<'a> {
+    action: &'a str,
+    target_type: &'a str,
+    target: &'a str,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl<'a> AuditEvent<'a> {
+    pub(crate) fn new(action: &'a str, target_type: &'a str, target: &'a str) -> Self {
+        Self {
+            action,
+            target_type,
+            target,
+            before: None,
+            after: None,
+        }
+    }
+
+    // 変更前の状態の要約
+    pub(crate) fn before(mut self, summary: impl Into<String>) -> Self {
+        self.before = Some(summary.into());
+        self
+    }
+
+    // 変更後の状態の要約
+    pub(crate) fn after(mut self, summary: impl Into<String>) -> Self {
+        self.after = Some(summary.into());
+        self
+    }
+}
+
+// ドキュメントの本文の要約（本文そのものは監査ログに残さない）
+pub(crate) fn content_summary(content: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(content.as_bytes()));
+    format!("{} bytes, sha256 {}", content.len(), &hash[..12])
+}
+
+// 現在のドキュメントの本文の要約（ドキュメントがなければ None）
+pub(crate) async fn document_summary(state: &AppState, name: &str) -> Result<Option<String>, AppError> {
+    match state.document_store.read(name).await {
+        Ok(content) => Ok(Some(content_summary(&content))),
+        Err(AppError::NotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    actor: Option<String>,
+    action: Option<String>,
+    target_type: Option<String>,
+    target: Option<String>,
+    ip: Option<String>,
+    // `YYYY-MM-DD`、`YYYY-MM-DD HH:MM:SS`（UTC）または RFC 3339
+    since: Option<String>,
+    until: Option<String>,
+    // このIDより前の記録（前のページの最後の ID を指定する）
+    before: Option<i64>,
+    limit: Option<usize>,
+    // エクスポートの形式
+    format: Option<ExportFormat>,
+}
+
+#[derive(Serialize)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+fn parse_time(name: &str, value: &str) -> Result<chrono::DateTime<chrono::Utc>, AppError> {
+    let value = value.trim();
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").map(|time| time.and_utc()))
+        .or_else(|_| {
+            chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).expect("valid time").and_utc())
+        })
+        .map_err(|_| AppError::InvalidInput(format!("Invalid {}: {}", name, value)))
+}
+
+impl AuditQuery {
+    fn filter(&self) -> Result<AuditFilter, AppError> {
+        let non_empty = |value: &Option<String>| value.clone().filter(|value| !value.is_empty());
+        Ok(AuditFilter {
+            actor: non_empty(&self.actor),
+            action: non_empty(&self.action),
+            target_type: non_empty(&self.target_type),
+            target: non_empty(&self.target),
+            ip_address: non_empty(&self.ip),
+            since: self.since.as_deref().map(|value| parse_time("since", value)).transpose()?,
+            until: self.until.as_deref().map(|value| parse_time("until", value)).transpose()?,
+        })
+    }
+}
+
+// 監査ログを検索する（管理者用）
+pub async fn list_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<AuditLog>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let entries = db.list_audit_entries(&query.filter()?, query.before, limit as i64).await?;
+    Ok(Json(AuditLog { entries }))
+}
+
+// CSV の1項目
+// 表計算ソフトで開いたときに数式として実行されないよう、`=` などで始まる値の先頭には `'` を付ける
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn to_csv(entries: &[AuditEntry]) -> String {
+    let mut csv = String::from(
+        "id,created_at,action,actor_id,actor,ip_address,user_agent,target_type,target,before,after\r\n",
+    );
+    for entry in entries {
+        let fields = [
+            entry.id.to_string(),
+            entry.created_at.clone(),
+            entry.action.clone(),
+            entry.actor_id.map(|id| id.to_string()).unwrap_or_default(),
+            entry.actor.clone().unwrap_or_default(),
+            entry.ip_address.clone().unwrap_or_default(),
+            entry.user_agent.clone().unwrap_or_default(),
+            entry.target_type.clone(),
+            entry.target.clone(),
+            entry.before.clone().unwrap_or_default(),
+            entry.after.clone().unwrap_or_default(),
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push_str("\r\n");
+    }
+    csv
+}
+
+// 条件に一致する監査ログをすべて CSV か JSON でダウンロードする（管理者用）
+pub async fn export_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let filter = query.filter()?;
+
+    let mut entries = Vec::new();
+    let mut before = query.before;
+    loop {
+        let batch = db.list_audit_entries(&filter, before, EXPORT_BATCH_SIZE).await?;
+        let done = (batch.len() as i64) < EXPORT_BATCH_SIZE;
+        before = batch.last().map(|entry| entry.id);
+        entries.extend(batch);
+        if done {
+            break;
+        }
+    }
+
+    let (content_type, extension, body) = match query.format.unwrap_or_default() {
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv", to_csv(&entries)),
+        ExportFormat::Json => (
+            "application/json",
+            "json",
+            serde_json::to_string(&entries).map_err(|e| AppError::Internal(e.to_string()))?,
+        ),
+    };
+    let disposition = format!(
+        "attachment; filename=\"audit-log-{}.{}\"",
+        chrono::Utc::now().format("%Y%m%d%H%M%S"),
+        extension
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::from_str(&disposition).map_err(|e| AppError::Internal(e.to_string()))?,
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
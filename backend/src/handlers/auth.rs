@@ -5,17 +5,39 @@ use axum::{
 };
 
 
-use crate::AppState;
+use crate::{AppError, AppState};
+use crate::config::RegistrationMode;
 use crate::models::{LoginCredentials, UserRegistration, ChangePasswordRequest, User, Role};
+use crate::models::user::{validate_password, verify_password};
 use crate::auth;
+use crate::handlers::audit::AuditContext;
+use crate::handlers::database;
+use crate::handlers::lockout::LoginAttempt;
+use crate::handlers::session::start_session;
+use crate::handlers::totp::mfa_challenge;
 
 use std::str::FromStr;
 
-// ユーザー登録
+// ユーザー登録（REGISTRATION_MODE=viewer の場合だけ受け付ける）
+// 登録したユーザーは閲覧者になる。他のロールは管理者が変更する
 pub async fn register_user(
     State(state): State<AppState>,
     Json(registration): Json<UserRegistration>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    // ローカルのパスワードを止めている場合は、登録を受け付けない
+    if !state.config.password_login {
+        return Err(AppError::Forbidden("Password login is disabled".to_string()).into());
+    }
+    match state.config.registration_mode {
+        RegistrationMode::Viewer => {}
+        RegistrationMode::Invite => {
+            return Err(AppError::Forbidden("Registration requires an invitation".to_string()).into());
+        }
+        RegistrationMode::Off => {
+            return Err(AppError::Forbidden("Registration is disabled".to_string()).into());
+        }
+    }
+
     let db = match &state.db_manager {
         Some(db) => db.clone(),
         None => {
@@ -27,8 +49,13 @@ pub async fn register_user(
             ));
         }
     };
+
+    // 最初のユーザーは初期設定（/api/auth/setup）で管理者として作成する
+    if db.count_users().await? == 0 {
+        return Err(AppError::Forbidden("Initial setup has not been completed".to_string()).into());
+    }
     
-    // 役割の設定（指定がなければデフォルトでViewer）
+    // 役割の指定は閲覧者のみ許可する（自分で権限を上げられないように）
     let role = registration.role.as_deref().unwrap_or("viewer");
     let role_enum = Role::from_str(role).map_err(|e| (
         StatusCode::BAD_REQUEST,
@@ -36,12 +63,16 @@ pub async fn register_user(
             "error": format!("Invalid role: {}", e)
         })),
     ))?;
+    if role_enum != Role::Viewer {
+        return Err(AppError::Forbidden("Self-registration cannot choose a role".to_string()).into());
+    }
+    validate_password(&registration.password)?;
     
     // ユーザー作成
     match db.create_user(
         &registration.username,
         &registration.password,
-        &registration.email,
+        Some(&registration.email),
         role_enum,
     ).await {
         Ok(_) => Ok(StatusCode::CREATED),
@@ -69,6 +100,7 @@ pub async fn register_user(
 // ログイン
 pub async fn login(
     State(state): State<AppState>,
+    audit: AuditContext,
     Json(credentials): Json<LoginCredentials>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
     let db = match &state.db_manager {
@@ -83,24 +115,53 @@ pub async fn login(
         }
     };
     
+    // ローカルのパスワードを止めていて、LDAP などもなければパスワードでのログインを受け付けない
+    if !state.config.password_login && db.auth_provider_names().is_empty() {
+        return Err(AppError::Forbidden("Password login is disabled".to_string()).into());
+    }
+
+    // ローカルのパスワードだけで認証する場合は、初期設定が済むまでログインを受け付けない
+    // （LDAP などがあれば、認証基盤のユーザーを最初のユーザーとして作成できる）
+    if db.auth_provider_names().is_empty() && db.count_users().await? == 0 {
+        return Err(AppError::Forbidden("Initial setup has not been completed".to_string()).into());
+    }
+
+    // 同じユーザー名・IPアドレスで続けて失敗している場合は、しばらく受け付けない
+    let attempt = LoginAttempt::new(&audit, &credentials.username);
+    attempt.check_allowed(&db).await?;
+
     // ユーザー認証
     match db.authenticate_user(&credentials.username, &credentials.password).await {
         Ok(Some(user)) => {
-            // JWTトークン生成
-            match crate::auth::create_token(user.id, &user.role.to_string()) {
-                Ok(token) => {
-                    let user_without_hash = User {
-                        id: user.id,
-                        username: user.username,
-                        password_hash: "".to_string(), // パスワードハッシュは返さない
-                        role: user.role,
-                        created_at: user.created_at,
-                        updated_at: user.updated_at,
-                    };
-                    
+            // 無効にしたアカウントは、パスワードが正しくてもログインさせない
+            if user.is_disabled() {
+                attempt.rejected(&db, "disabled").await?;
+                return Err(AppError::Forbidden("This account has been disabled".to_string()).into());
+            }
+
+            // 所属グループのロールも含めた、最も強いロールをトークンに入れる
+            let role = db.effective_role(user.id, user.role).await.map_err(|e| (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": format!("Failed to resolve user role: {}", e)
+                })),
+            ))?;
+
+            // 二要素認証が必要なら、トークンは発行せず2段階目（/auth/login/mfa）に進ませる
+            if let Some(challenge) = mfa_challenge(&state, &db, &user, role).await? {
+                return Ok(Json(challenge));
+            }
+            attempt.succeeded(&db).await?;
+
+            // セッションを作成し、アクセストークンとリフレッシュトークンを発行
+            match start_session(&db, &state.tokens, &user, role, &audit, "password").await {
+                Ok(tokens) => {
+                    // パスワードハッシュはシリアライズされない
                     Ok(Json(serde_json::json!({
-                        "token": token,
-                        "user": user_without_hash
+                        "token": tokens.token,
+                        "refresh_token": tokens.refresh_token,
+                        "expires_in": tokens.expires_in,
+                        "user": user
                     })))
                 },
                 Err(e) => {
@@ -114,12 +175,15 @@ pub async fn login(
                 }
             }
         },
-        Ok(None) => Err((
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({
-                "error": "Invalid username or password"
-            })),
-        )),
+        Ok(None) => {
+            attempt.failed(&state, &db, "invalid_credentials").await?;
+            Err((
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({
+                    "error": "Invalid username or password"
+                })),
+            ))
+        },
         Err(e) => {
             eprintln!("Authentication error: {}", e);
             Err((
@@ -132,34 +196,38 @@ pub async fn login(
     }
 }
 
-// パスワード変更
+// 自分のパスワードを変更する
+// 現在のセッション以外はすべてログアウトさせる
 pub async fn change_password(
     State(state): State<AppState>,
-    Path(user_id): Path<i64>,
+    claims: auth::Claims,
     Json(request): Json<ChangePasswordRequest>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
-    let db = match &state.db_manager {
-        Some(db) => db.clone(),
-        None => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": "Database not initialized"
-                })),
-            ));
-        }
-    };
-    
-    // パスワード変更
-    match db.change_password(user_id, &request.current_password, &request.new_password).await {
-        Ok(()) => Ok(StatusCode::OK),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to change password: {}", e)
-            })),
-        )),
+    // APIトークンが漏れてもパスワードを変えられないよう、ログインしたセッションに限る
+    if claims.api_token.is_some() {
+        return Err(AppError::Forbidden("API tokens cannot change passwords".to_string()).into());
     }
+    let db = database(&state)?;
+
+    let user = db
+        .get_user_by_id(claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+    // 外部の認証基盤（LDAP・OIDC）のユーザーはそちらでパスワードを管理する
+    if user.password_hash.is_empty() || !state.config.password_login {
+        return Err(AppError::Forbidden("Password is managed by an external provider".to_string()).into());
+    }
+    if !verify_password(&user.password_hash, &request.current_password) {
+        return Err(AppError::Auth("Invalid current password".to_string()).into());
+    }
+    validate_password(&request.new_password)?;
+
+    db.change_password(user.id, &request.current_password, &request.new_password).await?;
+    match claims.sid {
+        Some(session_id) => db.revoke_other_sessions(user.id, session_id).await?,
+        None => db.revoke_user_sessions(user.id).await?,
+    };
+    Ok(StatusCode::OK)
 }
 
 // ユーザー一覧取得（管理者用）
@@ -180,21 +248,8 @@ pub async fn get_all_users(
     
     // すべてのユーザーを取得
     match db.get_all_users().await {
-        Ok(users) => {
-            // パスワードハッシュを除外
-            let users_without_hash: Vec<User> = users.into_iter().map(|user| {
-                User {
-                    id: user.id,
-                    username: user.username,
-                    password_hash: "".to_string(),
-                    role: user.role,
-                    created_at: user.created_at,
-                    updated_at: user.updated_at,
-                }
-            }).collect();
-            
-            Ok(Json(users_without_hash))
-        },
+        // パスワードハッシュはシリアライズされない
+        Ok(users) => Ok(Json(users)),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({
@@ -223,19 +278,8 @@ pub async fn get_user(
     
     // ユーザーを取得
     match db.get_user_by_id(user_id).await {
-        Ok(Some(user)) => {
-            // パスワードハッシュを除外
-            let user_without_hash = User {
-                id: user.id,
-                username: user.username,
-                password_hash: "".to_string(),
-                role: user.role,
-                created_at: user.created_at,
-                updated_at: user.updated_at,
-            };
-            
-            Ok(Json(user_without_hash))
-        },
+        // パスワードハッシュはシリアライズされない
+        Ok(Some(user)) => Ok(Json(user)),
         Ok(None) => Err((
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({
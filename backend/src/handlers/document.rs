@@ -4,10 +4,15 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
-use std::fs;
 
-use crate::AppState;
-use crate::git_ops::{GitRepository, CommitInfo};
+use crate::{auth, AppError, AppState};
+use crate::auth::acl::{self, AccessControl, Subject};
+use crate::git_ops::CommitInfo;
+use crate::handlers::metadata::normalize_tag_name;
+use crate::handlers::audit::{content_summary, document_summary, AuditContext, AuditEvent};
+use crate::models::acl::Permission;
+use crate::db::{DbManager, DocumentListQuery, DocumentMeta, DocumentSort};
+use crate::handlers::pagination::{encode_cursor, page_limit, paginate, parse_cursor, Page, PageQuery, SortOrder};
 
 #[derive(Serialize, Deserialize)]
 pub struct Document {
@@ -15,14 +20,41 @@ pub struct Document {
     content: String,
 }
 
+#[derive(Deserialize)]
+pub struct RenameDocumentRequest {
+    new_filename: String,
+}
+
+#[derive(Serialize)]
+pub struct DocumentSummary {
+    filename: String,
+    title: String,
+    created_at: String,
+    updated_at: String,
+    tags: Vec<String>,
+}
+
 #[derive(Serialize)]
 pub struct DocumentList {
-    documents: Vec<String>,
+    documents: Vec<DocumentSummary>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListDocumentsQuery {
+    cursor: Option<String>,
+    limit: Option<usize>,
+    sort: Option<DocumentSort>,
+    order: Option<SortOrder>,
+    tag: Option<String>,
+    folder: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SearchQuery {
     q: String,
+    cursor: Option<String>,
+    limit: Option<usize>,
 }
 
 #[derive(Serialize)]
@@ -37,12 +69,14 @@ pub struct SearchResults {
     results: Vec<SearchResult>,
     query: String,
     total_matches: usize,
+    next_cursor: Option<String>,
 }
 
 #[derive(Serialize)]
 pub struct DocumentHistory {
     filename: String,
     commits: Vec<CommitInfo>,
+    next_cursor: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -52,101 +86,233 @@ pub struct DocumentVersion {
     commit_info: CommitInfo,
 }
 
+// ストアのエラーをレスポンスに変換する
+fn store_error(e: AppError, context: &str) -> (StatusCode, Json<serde_json::Value>) {
+    let status = match e {
+        AppError::NotFound(_) => StatusCode::NOT_FOUND,
+        AppError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+
+    (
+        status,
+        Json(serde_json::json!({
+            "error": format!("{}: {}", context, e)
+        })),
+    )
+}
+
+// 書き込んだドキュメントを一覧の索引（DBのメタデータ）に反映する
+// ファイルは保存済みのため、失敗しても処理は続行する
+async fn touch_index(state: &AppState, filename: &str) {
+    if let Some(db) = &state.db_manager {
+        if let Err(e) = db.touch_document(filename).await {
+            tracing::warn!("Failed to update the document index for {}: {}", filename, e);
+        }
+    }
+}
+
 // Get a specific markdown document
 pub async fn get_document(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
     Path(filename): Path<String>,
 ) -> Result<Json<Document>, (StatusCode, Json<serde_json::Value>)> {
-    let file_path = state.markdown_dir.join(format!("{}.md", filename));
-    
-    match fs::read_to_string(&file_path) {
+    acl::authorize(&state, claims.as_ref(), &filename, Permission::Read).await?;
+
+    match state.document_store.read(&filename).await {
         Ok(content) => Ok(Json(Document {
             filename,
             content,
         })),
-        Err(_) => Err((
+        Err(AppError::NotFound(_)) => Err((
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({
                 "error": format!("Document {} not found", filename)
             })),
         )),
+        Err(e) => Err(store_error(e, "Failed to read document")),
     }
 }
 
 // Save a markdown document
 pub async fn save_document(
     State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
     Path(filename): Path<String>,
     Json(document): Json<Document>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
-    let file_path = state.markdown_dir.join(format!("{}.md", filename));
-    
-    match fs::write(&file_path, &document.content) {
-        Ok(_) => {
-            // Gitコミットを作成
-            let git_repo = match GitRepository::new(&state.markdown_dir) {
-                Ok(ops) => ops,
-                Err(e) => {
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({
-                            "error": format!("Failed to initialize Git operations: {}", e)
-                        })),
-                    ));
-                }
-            };
-            
-            let commit_message = format!("Update {}.md", filename);
-            match git_repo.commit_file(&format!("{}.md", filename), &document.content, &commit_message) {
-                Ok(_) => Ok(StatusCode::OK),
-                Err(e) => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to commit document: {}", e)
-                    })),
-                )),
-            }
-        },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to save document: {}", e)
-            })),
-        )),
-    }
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    let previous = document_summary(&state, &filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to save document"))?;
+    let commit_message = format!("Update {}.md", filename);
+    state.document_store
+        .write(&filename, &document.content, &commit_message)
+        .await
+        .map_err(|e| store_error(e, "Failed to save document"))?;
+    touch_index(&state, &filename).await;
+
+    // 存在しないドキュメントを保存した場合は作成として記録する
+    let event = match previous {
+        Some(previous) => AuditEvent::new("document.update", "document", &filename).before(previous),
+        None => AuditEvent::new("document.create", "document", &filename),
+    };
+    audit.record_document(&state, event.after(content_summary(&document.content))).await;
+
+    Ok(StatusCode::OK)
 }
 
 // List all available markdown documents
 pub async fn list_documents(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
+    Query(query): Query<ListDocumentsQuery>,
 ) -> Result<Json<DocumentList>, (StatusCode, Json<serde_json::Value>)> {
-    let read_dir = match fs::read_dir(&state.markdown_dir) {
-        Ok(dir) => dir,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to read markdown directory: {}", e)
-                })),
-            ));
+    let access = acl::load_all(&state).await?;
+    let subject = Subject::load(state.db_manager.as_ref(), claims.as_ref()).await?;
+
+    let sort = query.sort.unwrap_or_default();
+    let order = query.order.unwrap_or(match sort {
+        DocumentSort::Title => SortOrder::Asc,
+        DocumentSort::UpdatedAt | DocumentSort::CreatedAt => SortOrder::Desc,
+    });
+    let folder = query
+        .folder
+        .as_deref()
+        .map(|f| f.trim_matches('/'))
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string());
+
+    let page = match &state.db_manager {
+        Some(db) => {
+            let list = DocumentListQuery {
+                folder,
+                tag: query.tag.as_deref().map(normalize_tag_name),
+                sort,
+                descending: order == SortOrder::Desc,
+                after: parse_cursor(query.cursor.as_deref())?
+                    .map(|cursor| document_cursor(&cursor))
+                    .transpose()?,
+                limit: page_limit(query.limit),
+            };
+            list_indexed_documents(&state, db, &access, &subject, list).await?
         }
+        None => list_stored_documents(&state, &access, &subject, &query, folder, sort, order).await?,
     };
-    
-    let mut documents = Vec::new();
-    
-    for entry in read_dir {
-        if let Ok(entry) = entry {
-            if let Some(file_name) = entry.file_name().to_str() {
-                if file_name.ends_with(".md") {
-                    documents.push(file_name.trim_end_matches(".md").to_string());
-                }
+
+    Ok(Json(DocumentList {
+        documents: page.items,
+        next_cursor: page.next_cursor,
+    }))
+}
+
+// カーソルを (並び順の値, ファイル名) に分ける
+fn document_cursor(cursor: &str) -> Result<(String, String), AppError> {
+    cursor
+        .rsplit_once('\u{0}')
+        .map(|(value, filename)| (value.to_string(), filename.to_string()))
+        .ok_or_else(|| AppError::InvalidInput("Invalid cursor".to_string()))
+}
+
+// DBの索引から、並び順とカーソルをSQLに任せて1ページ分を集める
+// 読めないドキュメントやストアにないドキュメントを除いて件数が足りなければ、続きを取得する
+async fn list_indexed_documents(
+    state: &AppState,
+    db: &DbManager,
+    access: &AccessControl,
+    subject: &Subject,
+    mut list: DocumentListQuery,
+) -> Result<Page<DocumentSummary>, AppError> {
+    let limit = list.limit;
+    // 次のページがあるか分かるよう、1件多く取得する
+    list.limit = limit + 1;
+
+    let mut documents: Vec<(String, DocumentMeta)> = Vec::new();
+    loop {
+        let batch = db.list_documents_page(&list).await?;
+        let exhausted = batch.len() <= limit;
+        list.after = batch.last().map(|(key, doc)| (key.clone(), doc.filename.clone()));
+
+        for (key, doc) in batch {
+            if !access.allows(subject, &doc.filename, Permission::Read) || !state.document_store.exists(&doc.filename).await? {
+                continue;
             }
+            documents.push((key, doc));
+            if documents.len() > limit {
+                break;
+            }
+        }
+        if documents.len() > limit || exhausted {
+            break;
         }
     }
-    
-    Ok(Json(DocumentList { documents }))
+
+    let next_cursor = if documents.len() > limit {
+        documents.truncate(limit);
+        documents.last().map(|(key, doc)| encode_cursor(&format!("{}\u{0}{}", key, doc.filename)))
+    } else {
+        None
+    };
+
+    let items = documents
+        .into_iter()
+        .map(|(_, meta)| DocumentSummary {
+            title: meta.title.unwrap_or_else(|| meta.filename.clone()),
+            filename: meta.filename,
+            created_at: meta.created_at,
+            updated_at: meta.updated_at,
+            tags: meta.tags,
+        })
+        .collect();
+
+    Ok(Page { items, next_cursor })
+}
+
+// DBがない場合は、ストアの一覧を並べ替えて1ページ分を返す
+async fn list_stored_documents(
+    state: &AppState,
+    access: &AccessControl,
+    subject: &Subject,
+    query: &ListDocumentsQuery,
+    folder: Option<String>,
+    sort: DocumentSort,
+    order: SortOrder,
+) -> Result<Page<DocumentSummary>, AppError> {
+    let stored = state.document_store.list().await?;
+    let folder_prefix = folder.map(|f| format!("{}/", f));
+
+    let documents = stored
+        .into_iter()
+        .filter(|doc| folder_prefix.as_ref().is_none_or(|prefix| doc.name.starts_with(prefix)))
+        .filter(|doc| access.allows(subject, &doc.name, Permission::Read))
+        // タグはDBに保存されるため、タグで絞り込むと何も残らない
+        .filter(|_| query.tag.is_none())
+        .map(|doc| DocumentSummary {
+            title: doc.name.clone(),
+            filename: doc.name,
+            created_at: doc.created_at,
+            updated_at: doc.updated_at,
+            tags: Vec::new(),
+        })
+        .collect();
+
+    paginate(documents, query.cursor.as_deref(), query.limit, order, |doc| {
+        let value = match sort {
+            DocumentSort::Title => doc.title.to_lowercase(),
+            DocumentSort::UpdatedAt => doc.updated_at.clone(),
+            DocumentSort::CreatedAt => doc.created_at.clone(),
+        };
+        // ファイル名を付けてキーを一意にする
+        format!("{}\u{0}{}", value, doc.filename)
+    })
 }
 
+// 最近のドキュメントを読み込むときの1回あたりの件数の下限
+const RECENT_DOCUMENTS_PAGE_SIZE: u32 = 50;
+
 #[derive(Deserialize)]
 pub struct RecentDocumentsQuery {
     limit: Option<u32>,
@@ -170,18 +336,36 @@ pub struct RecentDocumentsResponse {
 
 pub async fn list_recent_documents(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
     Query(query): Query<RecentDocumentsQuery>,
 ) -> Result<Json<RecentDocumentsResponse>, (StatusCode, Json<serde_json::Value>)> {
     let limit = query.limit.unwrap_or(10);
 
-    let db_manager = state.db_manager.ok_or_else(|| {
+    let db_manager = state.db_manager.as_ref().ok_or_else(|| {
         (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({ "error": "Database not initialized" })))
     })?;
 
-    match db_manager.list_recent_documents_meta(limit).await {
-        Ok(docs_meta) => {
-            let documents: Vec<RecentDocument> = docs_meta.into_iter().map(|meta| {
-                RecentDocument {
+    let access = acl::load_all(&state).await?;
+    let subject = Subject::load(Some(db_manager), claims.as_ref()).await?;
+
+    // 読めないドキュメントを除いても件数が足りるよう、足りるまで新しい順に読み進める
+    let page_size = limit.max(RECENT_DOCUMENTS_PAGE_SIZE);
+    let mut documents = Vec::new();
+    let mut offset = 0;
+    while documents.len() < limit as usize {
+        let page = db_manager.list_recent_documents_meta(page_size, offset).await.map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": format!("Failed to fetch recent documents: {}", e)
+                })),
+            )
+        })?;
+        let fetched = page.len();
+        documents.extend(
+            page.into_iter()
+                .filter(|meta| access.allows(&subject, &meta.filename, Permission::Read))
+                .map(|meta| RecentDocument {
                     id: meta.id,
                     filename: meta.filename,
                     title: meta.title.unwrap_or_else(|| "Untitled".to_string()),
@@ -189,22 +373,22 @@ pub async fn list_recent_documents(
                     updated_at: meta.updated_at,
                     view_count: 0, // DBにview_countがないため、0を返す
                     tags: meta.tags,
-                }
-            }).collect();
-            Ok(Json(RecentDocumentsResponse { documents }))
-        },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to fetch recent documents: {}", e)
-            })),
-        )),
+                }),
+        );
+        if fetched < page_size as usize {
+            break;
+        }
+        offset = offset.saturating_add(page_size);
     }
+    documents.truncate(limit as usize);
+
+    Ok(Json(RecentDocumentsResponse { documents }))
 }
 
 // 検索機能の実装
 pub async fn search_documents(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
     Query(query): Query<SearchQuery>,
 ) -> Result<Json<SearchResults>, (StatusCode, Json<serde_json::Value>)> {
     let search_term = query.q.to_lowercase();
@@ -217,127 +401,99 @@ pub async fn search_documents(
         ));
     }
 
-    let read_dir = match fs::read_dir(&state.markdown_dir) {
-        Ok(dir) => dir,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to read markdown directory: {}", e)
-                })),
-            ));
-        }
-    };
+    let stored = state.document_store
+        .list()
+        .await
+        .map_err(|e| store_error(e, "Failed to list documents"))?;
+
+    let access = acl::load_all(&state).await?;
+    let subject = Subject::load(state.db_manager.as_ref(), claims.as_ref()).await?;
 
     let mut results = Vec::new();
     let mut total_matches = 0;
 
-    for entry in read_dir {
-        if let Ok(entry) = entry {
-            let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "md") {
-                if let Some(file_name) = path.file_stem().and_then(|s| s.to_str()) {
-                    match fs::read_to_string(&path) {
-                        Ok(content) => {
-                            let content_lower = content.to_lowercase();
-                            if let Some(pos) = content_lower.find(&search_term) {
-                                // Count occurrences
-                                let matches_count = content_lower.matches(&search_term).count();
-                                total_matches += matches_count;
-                                
-                                // Extract preview
-                                let start = content_lower[..pos].rfind("\n").unwrap_or(0);
-                                let end = content_lower[pos..].find("\n").map_or(content.len(), |p| pos + p);
-                                let preview = content[start..end].trim().to_string();
-                                
-                                results.push(SearchResult {
-                                    filename: file_name.to_string(),
-                                    content_preview: preview,
-                                    matches: matches_count,
-                                });
-                            }
-                        }
-                        Err(_) => continue,
-                    }
-                }
-            }
+    for doc in stored {
+        let file_name = doc.name;
+        // 読めないドキュメントは件数やプレビューにも含めない
+        if !access.allows(&subject, &file_name, Permission::Read) {
+            continue;
+        }
+        let content = match state.document_store.read(&file_name).await {
+            Ok(content) => content,
+            Err(_) => continue,
+        };
+
+        let content_lower = content.to_lowercase();
+        if let Some(pos) = content_lower.find(&search_term) {
+            // Count occurrences
+            let matches_count = content_lower.matches(&search_term).count();
+            total_matches += matches_count;
+
+            // Extract preview
+            let start = content_lower[..pos].rfind("\n").unwrap_or(0);
+            let end = content_lower[pos..].find("\n").map_or(content.len(), |p| pos + p);
+            let preview = content[start..end].trim().to_string();
+
+            results.push(SearchResult {
+                filename: file_name,
+                content_preview: preview,
+                matches: matches_count,
+            });
         }
     }
 
-    // Sort by number of matches (descending)
-    results.sort_by(|a, b| b.matches.cmp(&a.matches));
+    // Sort by number of matches (descending), then paginate
+    let page = paginate(results, query.cursor.as_deref(), query.limit, SortOrder::Desc, |result| {
+        format!("{:020}\u{0}{}", result.matches, result.filename)
+    })?;
 
     Ok(Json(SearchResults {
-        results,
+        results: page.items,
         query: search_term,
         total_matches,
+        next_cursor: page.next_cursor,
     }))
 }
 
 // ドキュメントの変更履歴を取得
 pub async fn get_document_history(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
     Path(filename): Path<String>,
+    Query(page_query): Query<PageQuery>,
 ) -> Result<Json<DocumentHistory>, (StatusCode, Json<serde_json::Value>)> {
-    let file_path = format!("{}.md", filename);
-    
-    let git_repo = match GitRepository::new(&state.markdown_dir) {
-        Ok(ops) => ops,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to initialize Git operations: {}", e)
-                })),
-            ));
-        }
-    };
-    
-    match git_repo.get_file_history(&file_path) {
-        Ok(commits) => Ok(Json(DocumentHistory {
-            filename,
-            commits,
-        })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to get document history: {}", e)
-            })),
-        )),
-    }
+    acl::authorize(&state, claims.as_ref(), &filename, Permission::Read).await?;
+
+    let commits = state.document_store
+        .history(&filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to get document history"))?;
+
+    // 新しいコミットから順に返す
+    let page = paginate(commits, page_query.cursor.as_deref(), page_query.limit, SortOrder::Desc, |commit| {
+        format!("{:020}\u{0}{}", commit.timestamp, commit.id)
+    })?;
+
+    Ok(Json(DocumentHistory {
+        filename,
+        commits: page.items,
+        next_cursor: page.next_cursor,
+    }))
 }
 
 // 特定バージョンのドキュメントを取得
 pub async fn get_document_version(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
     Path((filename, commit_id)): Path<(String, String)>,
 ) -> Result<Json<DocumentVersion>, (StatusCode, Json<serde_json::Value>)> {
-    let file_path = format!("{}.md", filename);
-    
-    let git_repo = match GitRepository::new(&state.markdown_dir) {
-        Ok(ops) => ops,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to initialize Git operations: {}", e)
-                })),
-            ));
-        }
-    };
-    
+    acl::authorize(&state, claims.as_ref(), &filename, Permission::Read).await?;
+
     // 履歴から特定のコミット情報を取得
-    let history = match git_repo.get_file_history(&file_path) {
-        Ok(commits) => commits,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({
-                    "error": format!("Failed to get document history: {}", e)
-                })),
-            ));
-        }
-    };
+    let history = state.document_store
+        .history(&filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to get document history"))?;
     
     let commit_info = match history.iter().find(|commit| commit.id.starts_with(&commit_id)) {
         Some(commit) => commit.clone(),
@@ -352,95 +508,171 @@ pub async fn get_document_version(
     };
     
     // 特定バージョンの内容を取得
-    match git_repo.get_file_content_at_commit(&file_path, &commit_info.id) {
+    match state.document_store.read_version(&filename, &commit_info.id).await {
         Ok(content) => Ok(Json(DocumentVersion {
             filename,
             content,
             commit_info,
         })),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to get document at commit {}: {}", commit_id, e)
-            })),
-        )),
+        Err(e) => Err(store_error(e, &format!("Failed to get document at commit {}", commit_id))),
     }
 }
 
 // ドキュメントを削除する
 pub async fn delete_document(
     State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
     Path(filename): Path<String>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
-    let file_path = state.markdown_dir.join(format!("{}.md", filename));
-    
-    // ファイルが存在するか確認
-    if !file_path.exists() {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(serde_json::json!({
-                "error": format!("Document {} not found", filename)
-            })),
-        ));
-    }
-    
-    // ファイルを削除
-    match fs::remove_file(&file_path) {
-        Ok(_) => {
-            // Gitオペレーションを初期化
-            let git_repo = match GitRepository::new(&state.markdown_dir) {
-                Ok(ops) => ops,
-                Err(e) => {
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({
-                            "error": format!("Failed to initialize Git operations: {}", e)
-                        })),
-                    ));
-                }
-            };
-            
-            // ファイル削除をコミット
-            let commit_message = format!("Delete {}.md", filename);
-            match git_repo.remove_file(&format!("{}.md", filename), &commit_message) {
-                Ok(_) => {
-                    // データベースからメタデータも削除
-                    if let Some(db) = &state.db_manager {
-                        // エラーが発生しても処理は続行（ファイルは削除済み）
-                        if let Err(e) = db.delete_document_metadata(&filename).await {
-                            tracing::warn!("Failed to delete metadata for {}: {}", filename, e);
-                        }
-                    }
-                    
-                    Ok(StatusCode::OK)
-                },
-                Err(e) => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to commit document deletion: {}", e)
-                    })),
-                )),
-            }
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    let previous = document_summary(&state, &filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to delete document"))?;
+    let commit_message = format!("Delete {}.md", filename);
+    match state.document_store.delete(&filename, &commit_message).await {
+        Ok(()) => {},
+        Err(AppError::NotFound(_)) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "error": format!("Document {} not found", filename)
+                })),
+            ));
         },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to delete document: {}", e)
-            })),
-        )),
+        Err(e) => return Err(store_error(e, "Failed to delete document")),
+    }
+
+    // データベースからメタデータも削除
+    if let Some(db) = &state.db_manager {
+        // エラーが発生しても処理は続行（ファイルは削除済み）
+        if let Err(e) = db.delete_document_metadata(&filename).await {
+            tracing::warn!("Failed to delete metadata for {}: {}", filename, e);
+        }
+    }
+
+    let mut event = AuditEvent::new("document.delete", "document", &filename);
+    if let Some(previous) = previous {
+        event = event.before(previous);
+    }
+    audit.record_document(&state, event).await;
+
+    Ok(StatusCode::OK)
+}
+
+// ドキュメントの名前を変更する（フォルダの移動も含む）
+// メタデータ・添付ファイル・ACL・共有リンクも新しい名前に移す
+pub async fn rename_document(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(filename): Path<String>,
+    Json(request): Json<RenameDocumentRequest>,
+) -> Result<Json<Document>, (StatusCode, Json<serde_json::Value>)> {
+    let new_filename = request.new_filename.trim().to_string();
+    if new_filename == filename {
+        return Err(AppError::InvalidInput("The new name is the same as the current name".to_string()).into());
+    }
+
+    // 元の場所と移動先の両方に書き込めること
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+    acl::authorize(&state, Some(&claims), &new_filename, Permission::Write).await?;
+
+    let commit_message = format!("Rename {}.md to {}.md", filename, new_filename);
+    match state.document_store.rename(&filename, &new_filename, &commit_message).await {
+        Ok(()) => {}
+        Err(e @ AppError::Conflict(_)) => return Err(e.into()),
+        Err(e) => return Err(store_error(e, "Failed to rename document")),
+    }
+
+    if let Some(db) = &state.db_manager {
+        db.rename_document_metadata(&filename, &new_filename).await?;
     }
+    touch_index(&state, &new_filename).await;
+
+    let content = state.document_store
+        .read(&new_filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to read document"))?;
+    audit
+        .record_document(
+            &state,
+            AuditEvent::new("document.rename", "document", &filename).before(filename.clone()).after(new_filename.clone()),
+        )
+        .await;
+
+    Ok(Json(Document {
+        filename: new_filename,
+        content,
+    }))
+}
+
+// ドキュメントを指定したコミット時点の内容に戻す
+// 履歴は書き換えず、その内容で新しいコミットを作る（削除したドキュメントも元に戻せる）
+pub async fn revert_document(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path((filename, commit_id)): Path<(String, String)>,
+) -> Result<Json<Document>, (StatusCode, Json<serde_json::Value>)> {
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    let history = state.document_store
+        .history(&filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to get document history"))?;
+    let commit_info = history
+        .into_iter()
+        .find(|commit| commit.id.starts_with(&commit_id))
+        .ok_or_else(|| AppError::NotFound(format!("Commit {} not found for document {}", commit_id, filename)))?;
+
+    let content = state.document_store
+        .read_version(&filename, &commit_info.id)
+        .await
+        .map_err(|e| store_error(e, &format!("Failed to get document at commit {}", commit_id)))?;
+    let previous = document_summary(&state, &filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to revert document"))?;
+
+    let short_id = &commit_info.id[..commit_info.id.len().min(7)];
+    let commit_message = format!("Revert {}.md to {}", filename, short_id);
+    state.document_store
+        .write(&filename, &content, &commit_message)
+        .await
+        .map_err(|e| store_error(e, "Failed to revert document"))?;
+    touch_index(&state, &filename).await;
+
+    audit
+        .record_document(
+            &state,
+            AuditEvent::new("document.revert", "document", &filename)
+                .before(previous.unwrap_or_else(|| "deleted".to_string()))
+                .after(format!("{} (commit {})", content_summary(&content), commit_info.id)),
+        )
+        .await;
+
+    Ok(Json(Document { filename, content }))
 }
 
 // 新しいドキュメントを作成
 pub async fn create_document(
     State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
     Json(document): Json<Document>,
 ) -> Result<Json<Document>, (StatusCode, Json<serde_json::Value>)> {
     let filename = document.filename.clone();
-    let file_path = state.markdown_dir.join(format!("{}.md", filename));
+
+    // 新しいドキュメントは作成先のフォルダのACLに従う
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
     
-    // 同名のファイルが既に存在するか確認
-    if file_path.exists() {
+    // 同名のドキュメントが既に存在するか確認
+    let exists = state.document_store
+        .exists(&filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to create document"))?;
+    if exists {
         return Err((
             StatusCode::CONFLICT,
             Json(serde_json::json!({
@@ -449,92 +681,58 @@ pub async fn create_document(
         ));
     }
     
-    // ファイルを作成
-    match fs::write(&file_path, &document.content) {
-        Ok(_) => {
-            // Gitコミットを作成
-            let git_repo = match GitRepository::new(&state.markdown_dir) {
-                Ok(ops) => ops,
-                Err(e) => {
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({
-                            "error": format!("Failed to initialize Git operations: {}", e)
-                        })),
-                    ));
-                }
-            };
-            
-            let commit_message = format!("Create {}.md", filename);
-            match git_repo.commit_file(&format!("{}.md", filename), &document.content, &commit_message) {
-                Ok(_) => Ok(Json(document)),
-                Err(e) => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to commit document: {}", e)
-                    })),
-                )),
-            }
-        },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to create document: {}", e)
-            })),
-        )),
-    }
+    let commit_message = format!("Create {}.md", filename);
+    state.document_store
+        .write(&filename, &document.content, &commit_message)
+        .await
+        .map_err(|e| store_error(e, "Failed to create document"))?;
+    touch_index(&state, &filename).await;
+    audit
+        .record_document(
+            &state,
+            AuditEvent::new("document.create", "document", &filename).after(content_summary(&document.content)),
+        )
+        .await;
+
+    Ok(Json(document))
 }
 
 // ドキュメントを更新
 pub async fn update_document(
     State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
     Path(filename): Path<String>,
     Json(document): Json<Document>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
-    let file_path = state.markdown_dir.join(format!("{}.md", filename));
-    
-    // ファイルが存在するか確認
-    if !file_path.exists() {
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    // ドキュメントが存在するか確認
+    let previous = document_summary(&state, &filename)
+        .await
+        .map_err(|e| store_error(e, "Failed to update document"))?;
+    let Some(previous) = previous else {
         return Err((
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({
                 "error": format!("Document {} not found", filename)
             })),
         ));
-    }
+    };
     
-    // ファイルを更新
-    match fs::write(&file_path, &document.content) {
-        Ok(_) => {
-            // Gitコミットを作成
-            let git_repo = match GitRepository::new(&state.markdown_dir) {
-                Ok(ops) => ops,
-                Err(e) => {
-                    return Err((
-                        StatusCode::INTERNAL_SERVER_ERROR,
-                        Json(serde_json::json!({
-                            "error": format!("Failed to initialize Git operations: {}", e)
-                        })),
-                    ));
-                }
-            };
-            
-            let commit_message = format!("Update {}.md", filename);
-            match git_repo.commit_file(&format!("{}.md", filename), &document.content, &commit_message) {
-                Ok(_) => Ok(StatusCode::OK),
-                Err(e) => Err((
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({
-                        "error": format!("Failed to commit document: {}", e)
-                    })),
-                )),
-            }
-        },
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({
-                "error": format!("Failed to update document: {}", e)
-            })),
-        )),
-    }
-}
\ No newline at end of file
+    let commit_message = format!("Update {}.md", filename);
+    state.document_store
+        .write(&filename, &document.content, &commit_message)
+        .await
+        .map_err(|e| store_error(e, "Failed to update document"))?;
+    audit
+        .record_document(
+            &state,
+            AuditEvent::new("document.update", "document", &filename)
+                .before(previous)
+                .after(content_summary(&document.content)),
+        )
+        .await;
+
+    Ok(StatusCode::OK)
+}
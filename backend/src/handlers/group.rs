@@ -0,0 +1,189 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppState};
+use crate::db::{DbManager, Group};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::models::user::Role;
+
+#[derive(Deserialize)]
+pub struct CreateGroupRequest {
+    name: String,
+    description: Option<String>,
+    role: Option<Role>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateGroupRequest {
+    description: Option<String>,
+    role: Option<Role>,
+}
+
+#[derive(Serialize)]
+pub struct GroupDetail {
+    #[serde(flatten)]
+    group: Group,
+    members: Vec<String>,
+}
+
+// 監査ログに残すグループのロール
+fn role_summary(role: Option<Role>) -> String {
+    role.map_or_else(|| "no role".to_string(), |role| format!("role {}", role))
+}
+
+async fn find_group(db: &DbManager, name: &str) -> Result<Group, AppError> {
+    db.get_group(name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Group {} not found", name)))
+}
+
+// グループ一覧を取得
+pub async fn list_groups(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<Group>>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    Ok(Json(db.list_groups().await?))
+}
+
+// グループを作成
+pub async fn create_group(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Json(request): Json<CreateGroupRequest>,
+) -> Result<(StatusCode, Json<Group>), (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("Group name cannot be empty".to_string()).into());
+    }
+    if db.get_group(name).await?.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("Group {} already exists", name)
+            })),
+        ));
+    }
+
+    let group = db.create_group(name, request.description.as_deref(), request.role).await?;
+    audit
+        .record(db, AuditEvent::new("group.create", "group", &group.name).after(role_summary(group.role)))
+        .await;
+    Ok((StatusCode::CREATED, Json(group)))
+}
+
+// グループとメンバーを取得
+pub async fn get_group(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<GroupDetail>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let group = find_group(db, &name).await?;
+    let members = db.list_group_members(group.id).await?;
+    Ok(Json(GroupDetail { group, members }))
+}
+
+// グループの説明とロールを更新
+pub async fn update_group(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(name): Path<String>,
+    Json(request): Json<UpdateGroupRequest>,
+) -> Result<Json<Group>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let group = find_group(db, &name).await?;
+
+    if !db.update_group(&name, request.description.as_deref(), request.role).await? {
+        return Err(AppError::NotFound(format!("Group {} not found", name)).into());
+    }
+    if group.role != request.role {
+        audit
+            .record(
+                db,
+                AuditEvent::new("group.role_change", "group", &name)
+                    .before(role_summary(group.role))
+                    .after(role_summary(request.role)),
+            )
+            .await;
+    }
+    Ok(Json(find_group(db, &name).await?))
+}
+
+// グループを削除（ACLのグループ宛ての設定も削除される）
+pub async fn delete_group(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let group = find_group(db, &name).await?;
+    let members = db.list_group_members(group.id).await?;
+    let members = if members.is_empty() {
+        "no members".to_string()
+    } else {
+        format!("members {}", members.join(" "))
+    };
+
+    if !db.delete_group(&name).await? {
+        return Err(AppError::NotFound(format!("Group {} not found", name)).into());
+    }
+    audit
+        .record(
+            db,
+            AuditEvent::new("group.delete", "group", &name)
+                .before(format!("{}, {}", role_summary(group.role), members)),
+        )
+        .await;
+    Ok(StatusCode::OK)
+}
+
+// グループにユーザーを追加
+pub async fn add_group_member(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path((name, username)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let group = find_group(db, &name).await?;
+    let user = db
+        .get_user_by_username(&username)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", username)))?;
+
+    db.add_group_member(group.id, user.id).await?;
+    audit
+        .record(db, AuditEvent::new("group.member_add", "group", &name).after(format!("member {}", user.username)))
+        .await;
+    Ok(StatusCode::OK)
+}
+
+// グループからユーザーを外す
+pub async fn remove_group_member(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path((name, username)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let group = find_group(db, &name).await?;
+    let user = db
+        .get_user_by_username(&username)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User {} not found", username)))?;
+
+    if !db.remove_group_member(group.id, user.id).await? {
+        return Err(AppError::NotFound(format!("User {} is not a member of {}", username, name)).into());
+    }
+    audit
+        .record(db, AuditEvent::new("group.member_remove", "group", &name).before(format!("member {}", user.username)))
+        .await;
+    Ok(StatusCode::OK)
+}
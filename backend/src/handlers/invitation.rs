@@ -0,0 +1,255 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth, AppError, AppState};
+use crate::auth::session::{generate_secret_token, hash_secret_token};
+use crate::db::{DbManager, Invitation, NewInvitation};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::handlers::session::login_response;
+use crate::models::user::{validate_password, Role};
+
+// 招待の有効期間（時間）
+const DEFAULT_INVITATION_HOURS: i64 = 72;
+const MAX_INVITATION_HOURS: i64 = 30 * 24;
+
+#[derive(Deserialize)]
+pub struct CreateInvitationRequest {
+    // どちらか一方は必須
+    username: Option<String>,
+    email: Option<String>,
+    role: Option<Role>,
+    expires_in_hours: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedInvitation {
+    #[serde(flatten)]
+    invitation: Invitation,
+    // 招待された人に渡すトークン（この時だけ返す）
+    token: String,
+}
+
+#[derive(Serialize)]
+pub struct InvitationList {
+    invitations: Vec<Invitation>,
+}
+
+#[derive(Deserialize)]
+pub struct InvitationListQuery {
+    // 使用済み・取り消し済み・期限切れのものも含める
+    #[serde(default)]
+    all: bool,
+}
+
+// 招待を受ける前に表示する内容
+#[derive(Serialize)]
+pub struct InvitationPreview {
+    username: Option<String>,
+    email: Option<String>,
+    role: Role,
+    expires_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInvitationRequest {
+    token: String,
+    // 招待でユーザー名を指定していない場合は必須
+    username: Option<String>,
+    password: String,
+    email: Option<String>,
+}
+
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
+}
+
+// 監査ログの対象にする、招待された人（ユーザー名、なければメールアドレス）
+fn invitee(invitation: &Invitation) -> &str {
+    invitation.username.as_deref().or(invitation.email.as_deref()).unwrap_or_default()
+}
+
+async fn find_active_invitation(db: &DbManager, token: &str) -> Result<Option<Invitation>, AppError> {
+    Ok(db
+        .get_invitation_by_token(&hash_secret_token(token.trim()))
+        .await?
+        .filter(Invitation::is_active))
+}
+
+// 招待を作成する（管理者用）
+pub async fn create_invitation(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Json(request): Json<CreateInvitationRequest>,
+) -> Result<(StatusCode, Json<CreatedInvitation>), (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let username = non_empty(request.username.as_deref());
+    let email = non_empty(request.email.as_deref());
+    if username.is_none() && email.is_none() {
+        return Err(AppError::InvalidInput("Specify a username or an email".to_string()).into());
+    }
+
+    let hours = request.expires_in_hours.unwrap_or(DEFAULT_INVITATION_HOURS);
+    if !(1..=MAX_INVITATION_HOURS).contains(&hours) {
+        return Err(AppError::InvalidInput(format!(
+            "expires_in_hours must be between 1 and {}",
+            MAX_INVITATION_HOURS
+        ))
+        .into());
+    }
+
+    if let Some(username) = username {
+        if db.get_user_by_username(username).await?.is_some() {
+            return Err(AppError::Conflict(format!("User {} already exists", username)).into());
+        }
+    }
+    if let Some(email) = email {
+        if db.get_user_by_email(email).await?.is_some() {
+            return Err(AppError::Conflict("Email is already in use".to_string()).into());
+        }
+    }
+
+    let token = generate_secret_token();
+    let invitation = db
+        .create_invitation(NewInvitation {
+            token_hash: hash_secret_token(&token),
+            username: username.map(str::to_string),
+            email: email.map(str::to_string),
+            role: request.role.unwrap_or(Role::Viewer),
+            created_by: Some(claims.sub),
+            expires_at: chrono::Utc::now() + chrono::Duration::hours(hours),
+        })
+        .await?;
+
+    // 招待を受けると、このロールのユーザーが作られる
+    audit
+        .record(
+            db,
+            AuditEvent::new("user.invite", "user", invitee(&invitation))
+                .after(format!("role {} (invitation {})", invitation.role, invitation.id)),
+        )
+        .await;
+
+    Ok((StatusCode::CREATED, Json(CreatedInvitation { invitation, token })))
+}
+
+// 招待の一覧（管理者用）。既定では使われていない有効なものだけ
+pub async fn list_invitations(
+    State(state): State<AppState>,
+    Query(query): Query<InvitationListQuery>,
+) -> Result<Json<InvitationList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    Ok(Json(InvitationList {
+        invitations: db.list_invitations(!query.all).await?,
+    }))
+}
+
+// 使われていない招待を取り消す（管理者用）
+pub async fn revoke_invitation(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let invitation = db
+        .revoke_invitation(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Pending invitation not found".to_string()))?;
+    audit
+        .record(
+            db,
+            AuditEvent::new("user.invite.revoke", "user", invitee(&invitation))
+                .before(format!("role {} (invitation {})", invitation.role, invitation.id)),
+        )
+        .await;
+    Ok(StatusCode::OK)
+}
+
+// 招待の内容を確認する（ログイン不要。登録画面の表示用）
+pub async fn get_invitation(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<InvitationPreview>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let invitation = find_active_invitation(db, &token)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invalid or expired invitation".to_string()))?;
+    Ok(Json(InvitationPreview {
+        username: invitation.username,
+        email: invitation.email,
+        role: invitation.role,
+        expires_at: invitation.expires_at,
+    }))
+}
+
+// 招待を受けてパスワードを設定し、そのままログインする（ログイン不要）
+// REGISTRATION_MODE に関わらず受け付ける
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Json(request): Json<AcceptInvitationRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<serde_json::Value>)> {
+    if !state.config.password_login {
+        return Err(AppError::Forbidden("Password login is disabled".to_string()).into());
+    }
+    let db = database(&state)?;
+
+    let invitation = find_active_invitation(db, &request.token)
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired invitation".to_string()))?;
+
+    // 招待で指定したユーザー名・メールアドレスは変えられない
+    let username = match (invitation.username.as_deref(), non_empty(request.username.as_deref())) {
+        (Some(invited), Some(requested)) if invited != requested => {
+            return Err(AppError::InvalidInput(format!("This invitation is for user {}", invited)).into());
+        }
+        (Some(invited), _) => invited,
+        (None, Some(requested)) => requested,
+        (None, None) => return Err(AppError::InvalidInput("Username is required".to_string()).into()),
+    };
+    let email = match (invitation.email.as_deref(), non_empty(request.email.as_deref())) {
+        (Some(invited), Some(requested)) if !invited.eq_ignore_ascii_case(requested) => {
+            return Err(AppError::InvalidInput("This invitation is for a different email".to_string()).into());
+        }
+        (Some(invited), _) => Some(invited),
+        (None, requested) => requested,
+    };
+    validate_password(&request.password)?;
+
+    if db.get_user_by_username(username).await?.is_some() {
+        return Err(AppError::Conflict(format!("User {} already exists", username)).into());
+    }
+    if let Some(email) = email {
+        if db.get_user_by_email(email).await?.is_some() {
+            return Err(AppError::Conflict("Email is already in use".to_string()).into());
+        }
+    }
+
+    let user_id = db
+        .accept_invitation(&invitation, username, &request.password, email)
+        .await?
+        .ok_or_else(|| AppError::Auth("Invalid or expired invitation".to_string()))?;
+    let user = db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::Internal("Created user not found".to_string()))?;
+    audit
+        .with_actor(Some(user.id), &user.username)
+        .record(
+            db,
+            AuditEvent::new("user.create", "user", &user.username)
+                .after(format!("role {} (invitation {})", user.role, invitation.id)),
+        )
+        .await;
+
+    let response = login_response(&state, db, &user, user.role, &audit, "invitation").await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
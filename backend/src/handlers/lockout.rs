@@ -0,0 +1,176 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppState};
+use crate::auth::throttle::LoginThrottle;
+use crate::db::{DbManager, FailedLogin, LoginFailure, LoginLockout, ThrottleScope};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::handlers::pagination::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+// ログインを試したリクエストの情報
+pub(crate) struct LoginAttempt {
+    username: String,
+    audit: AuditContext,
+}
+
+impl LoginAttempt {
+    pub(crate) fn new(audit: &AuditContext, username: &str) -> Self {
+        Self {
+            username: username.to_string(),
+            audit: audit.with_actor(None, username),
+        }
+    }
+
+    // ユーザー名かIPアドレスがロック中なら、パスワードを確認せずに 429 を返す
+    pub(crate) async fn check_allowed(&self, db: &DbManager) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+        let mut locked_until = db.login_locked_until(ThrottleScope::Username, &self.username).await?;
+        if let Some(ip_address) = self.audit.ip_address() {
+            locked_until = locked_until.max(db.login_locked_until(ThrottleScope::Ip, ip_address).await?);
+        }
+
+        let locked_until = match locked_until {
+            Some(locked_until) => locked_until,
+            None => return Ok(()),
+        };
+
+        // ロック中の試行も失敗として記録するが、回数は増やさない
+        // 監査ログにはロックした時に1回だけ残す（ロック中の試行ごとには残さない）
+        db.record_login_failure(&self.failure("locked"), None).await?;
+
+        let retry_after = (locked_until - chrono::Utc::now()).num_seconds().max(1);
+        Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            Json(serde_json::json!({
+                "error": "Too many failed login attempts. Try again later.",
+                "retry_after": retry_after
+            })),
+        ))
+    }
+
+    // 失敗を記録し、ユーザー名とIPアドレスの連続失敗の回数を増やす
+    pub(crate) async fn failed(&self, state: &AppState, db: &DbManager, reason: &str) -> Result<(), AppError> {
+        let throttle = LoginThrottle::from_config(&state.config);
+        let locked = db.record_login_failure(&self.failure(reason), Some(&throttle)).await?;
+        self.audit_failure(db, reason).await;
+
+        // ロックしている間は試行を受け付けないため、ロックの記録は1回のロックにつき1件になる
+        for scope in locked {
+            let locked = match scope {
+                ThrottleScope::Username => "username".to_string(),
+                ThrottleScope::Ip => format!("ip {}", self.audit.ip_address().unwrap_or_default()),
+            };
+            self.audit
+                .record(
+                    db,
+                    AuditEvent::new("auth.lockout", "user", &self.username)
+                        .after(format!("{} locked for {} minutes", locked, state.config.login_lockout_minutes)),
+                )
+                .await;
+        }
+        Ok(())
+    }
+
+    // パスワードは正しいが受け付けなかった（無効なアカウントなど）場合は、記録だけして回数は増やさない
+    pub(crate) async fn rejected(&self, db: &DbManager, reason: &str) -> Result<(), AppError> {
+        db.record_login_failure(&self.failure(reason), None).await?;
+        self.audit_failure(db, reason).await;
+        Ok(())
+    }
+
+    async fn audit_failure(&self, db: &DbManager, reason: &str) {
+        self.audit
+            .record(db, AuditEvent::new("auth.login_failed", "user", &self.username).after(reason))
+            .await;
+    }
+
+    // ログインできたら、ユーザー名の連続失敗の回数をリセットする
+    // IPアドレスの回数は意図してリセットしない（自分のアカウントでのログインを挟んで他人のパスワードを試せないように）
+    // IPアドレスの回数は、最後の失敗からロックの時間が経つか、管理者が解除したときに数え直す
+    pub(crate) async fn succeeded(&self, db: &DbManager) -> Result<(), AppError> {
+        db.reset_login_throttle(ThrottleScope::Username, &self.username).await?;
+        Ok(())
+    }
+
+    fn failure<'a>(&'a self, reason: &'a str) -> FailedLogin<'a> {
+        FailedLogin {
+            username: &self.username,
+            ip_address: self.audit.ip_address(),
+            user_agent: self.audit.user_agent(),
+            reason,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginFailureQuery {
+    username: Option<String>,
+    ip: Option<String>,
+    // このIDより前の記録（前のページの最後の ID を指定する）
+    before: Option<i64>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+pub struct LoginFailureList {
+    failures: Vec<LoginFailure>,
+}
+
+#[derive(Serialize)]
+pub struct LoginLockoutList {
+    lockouts: Vec<LoginLockout>,
+}
+
+#[derive(Deserialize)]
+pub struct UnlockQuery {
+    username: Option<String>,
+    ip: Option<String>,
+}
+
+// 失敗したログインの記録（管理者用）
+pub async fn list_login_failures(
+    State(state): State<AppState>,
+    Query(query): Query<LoginFailureQuery>,
+) -> Result<Json<LoginFailureList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+    let failures = db
+        .list_login_failures(query.username.as_deref(), query.ip.as_deref(), query.before, limit as i64)
+        .await?;
+    Ok(Json(LoginFailureList { failures }))
+}
+
+// ロック中のユーザー名・IPアドレスの一覧（管理者用）
+pub async fn list_login_lockouts(
+    State(state): State<AppState>,
+) -> Result<Json<LoginLockoutList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    Ok(Json(LoginLockoutList {
+        lockouts: db.list_login_lockouts().await?,
+    }))
+}
+
+// ユーザー名またはIPアドレスのロックを解除する（管理者用）
+pub async fn unlock_login(
+    State(state): State<AppState>,
+    Query(query): Query<UnlockQuery>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let (scope, identifier) = match (query.username, query.ip) {
+        (Some(username), None) => (ThrottleScope::Username, username),
+        (None, Some(ip)) => (ThrottleScope::Ip, ip),
+        _ => return Err(AppError::InvalidInput("Specify either username or ip".to_string()).into()),
+    };
+
+    if db.reset_login_throttle(scope, &identifier).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(AppError::NotFound("No failed logins recorded".to_string()).into())
+    }
+}
@@ -4,10 +4,15 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
 
-use crate::AppState;
-use crate::db::{DocumentMeta};
+use crate::{auth, storage, AppError, AppState};
+use crate::auth::acl::{self, Subject};
+use crate::db::{DbManager, DocumentMeta, Tag, TagChange};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::models::acl::{Permission, Visibility};
+use crate::handlers::pagination::{encode_cursor, page_limit, parse_cursor, PageQuery};
 
 #[derive(Serialize, Deserialize)]
 pub struct MetadataRequest {
@@ -17,12 +22,88 @@ pub struct MetadataRequest {
 
 #[derive(Serialize, Deserialize)]
 pub struct TagsResponse {
+    tags: Vec<Tag>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TagAssignmentRequest {
     tags: Vec<String>,
 }
 
+#[derive(Deserialize)]
+pub struct VisibilityRequest {
+    // null にするとウィキ全体の設定（WIKI_MODE）に従う
+    visibility: Option<Visibility>,
+}
+
+#[derive(Serialize)]
+pub struct VisibilityResponse {
+    filename: String,
+    visibility: Option<Visibility>,
+    // ウィキ全体の設定を反映した実際の公開設定
+    effective: Visibility,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UpdateTagRequest {
+    description: Option<String>,
+    color: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RenameTagRequest {
+    new_name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MergeTagRequest {
+    into: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SynonymRequest {
+    name: String,
+}
+
+#[derive(Deserialize)]
+pub struct TagDocumentsQuery {
+    #[serde(default)]
+    include_descendants: bool,
+}
+
+// 階層タグ（`infra/k8s`）の各階層の前後の空白と空の階層を取り除く
+pub(crate) fn normalize_tag_name(tag: &str) -> String {
+    tag.split('/')
+        .map(str::trim)
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// タグ名を正規化し、空のタグと重複を取り除く
+fn normalize_tag_names(tags: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = tags
+        .iter()
+        .map(|tag| normalize_tag_name(tag))
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// `#RRGGBB` 形式のカラーコードかどうか
+fn is_valid_color(color: &str) -> bool {
+    color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
 // ドキュメントのメタデータを取得
 pub async fn get_document_metadata(
     State(state): State<AppState>,
+    claims: Option<auth::Claims>,
     Path(filename): Path<String>,
 ) -> Result<Json<DocumentMeta>, (StatusCode, Json<serde_json::Value>)> {
     let db = match &state.db_manager {
@@ -36,6 +117,8 @@ pub async fn get_document_metadata(
             ));
         }
     };
+
+    acl::authorize(&state, claims.as_ref(), &filename, Permission::Read).await?;
     
     // メタデータを取得
     match db.get_document_metadata(&filename).await {
@@ -58,6 +141,8 @@ pub async fn get_document_metadata(
 // ドキュメントのメタデータを更新
 pub async fn update_document_metadata(
     State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
     Path(filename): Path<String>,
     Json(meta_request): Json<MetadataRequest>,
 ) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
@@ -72,19 +157,15 @@ pub async fn update_document_metadata(
             ));
         }
     };
-    
-    let _now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs() as i64;
-    
-    // 既存のメタデータを取得または新規作成
-    match db.get_document_metadata(&filename).await {
-        Ok(Some(_existing_meta)) => {
-            // 既存のメタデータを更新
+
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    // 既存のメタデータを更新するか、なければ新規作成
+    let (document_id, status) = match db.get_document_metadata(&filename).await? {
+        Some(existing_meta) => {
             match db.update_document_metadata(&filename, Some(&meta_request.title)).await {
-                Ok(_) => Ok(StatusCode::OK),
-                Err(e) => Err((
+                Ok(_) => (existing_meta.id, StatusCode::OK),
+                Err(e) => return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(serde_json::json!({
                         "error": format!("Failed to update document metadata: {}", e)
@@ -92,11 +173,10 @@ pub async fn update_document_metadata(
                 )),
             }
         },
-        Ok(None) | Err(_) => {
-            // 新規作成
+        None => {
             match db.create_document_metadata(&filename, Some(&meta_request.title)).await {
-                Ok(_) => Ok(StatusCode::CREATED),
-                Err(e) => Err((
+                Ok(id) => (id, StatusCode::CREATED),
+                Err(e) => return Err((
                     StatusCode::INTERNAL_SERVER_ERROR,
                     Json(serde_json::json!({
                         "error": format!("Failed to create document metadata: {}", e)
@@ -104,13 +184,39 @@ pub async fn update_document_metadata(
                 )),
             }
         }
+    };
+
+    match db.set_document_tags(document_id, normalize_tag_names(meta_request.tags)).await {
+        Ok(created) => {
+            audit_created_tags(&db, &audit, &filename, &created).await;
+            Ok(status)
+        }
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to update document tags: {}", e)
+            })),
+        )),
     }
 }
 
-// すべてのタグを取得
-pub async fn get_all_tags(
+// タグ付けで新しく作成されたタグを監査ログに残す
+async fn audit_created_tags(db: &DbManager, audit: &AuditContext, filename: &str, created: &[String]) {
+    for tag in created {
+        audit
+            .record(db, AuditEvent::new("tag.create", "tag", tag).after(format!("tagged on {}", filename)))
+            .await;
+    }
+}
+
+// ドキュメントのタグを置き換える
+pub async fn set_document_tags(
     State(state): State<AppState>,
-) -> Result<Json<TagsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(filename): Path<String>,
+    Json(request): Json<TagAssignmentRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
     let db = match &state.db_manager {
         Some(db) => db.clone(),
         None => {
@@ -122,22 +228,158 @@ pub async fn get_all_tags(
             ));
         }
     };
-    
-    match db.list_tags().await {
-        Ok(tags) => Ok(Json(TagsResponse { tags: tags.into_iter().map(|tag| tag.name).collect() })),
+
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    // メタデータがまだなければタイトルなしで作成する
+    let document_id = match db.get_document_metadata(&filename).await {
+        Ok(Some(meta)) => meta.id,
+        Ok(None) => db.create_document_metadata(&filename, None).await.map_err(|e| (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to create document metadata: {}", e)
+            })),
+        ))?,
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": format!("Failed to get document metadata: {}", e)
+                })),
+            ));
+        }
+    };
+
+    match db.set_document_tags(document_id, normalize_tag_names(request.tags)).await {
+        Ok(created) => {
+            audit_created_tags(&db, &audit, &filename, &created).await;
+            Ok(StatusCode::OK)
+        }
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({
-                "error": format!("Failed to get tags: {}", e)
+                "error": format!("Failed to update document tags: {}", e)
             })),
         )),
     }
 }
 
+fn visibility_response(state: &AppState, filename: String, visibility: Option<Visibility>) -> VisibilityResponse {
+    VisibilityResponse {
+        filename,
+        visibility,
+        effective: visibility.unwrap_or(state.config.wiki_mode),
+    }
+}
+
+// 監査ログに残す公開設定（未指定の場合は WIKI_MODE に従う）
+fn visibility_summary(visibility: Option<Visibility>) -> String {
+    visibility.map_or_else(|| "default".to_string(), |visibility| visibility.to_string())
+}
+
+// ドキュメントの公開設定を取得
+pub async fn get_document_visibility(
+    State(state): State<AppState>,
+    claims: Option<auth::Claims>,
+    Path(filename): Path<String>,
+) -> Result<Json<VisibilityResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    acl::authorize(&state, claims.as_ref(), &filename, Permission::Read).await?;
+    let visibility = db.get_document_visibility(&filename).await?;
+    Ok(Json(visibility_response(&state, filename, visibility)))
+}
+
+// ドキュメントの公開設定を変更（ドキュメントの管理権限が必要）
+// ACLで制限されたドキュメントは public にしても未ログインのユーザーには見えない
+pub async fn set_document_visibility(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(filename): Path<String>,
+    Json(request): Json<VisibilityRequest>,
+) -> Result<Json<VisibilityResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    storage::validate_name(&filename)?;
+    acl::authorize(&state, Some(&claims), &filename, Permission::Admin).await?;
+    if !state.document_store.exists(&filename).await? {
+        return Err(AppError::NotFound(format!("Document {} not found", filename)).into());
+    }
+
+    let previous = db.get_document_visibility(&filename).await?;
+    db.set_document_visibility(&filename, request.visibility).await?;
+    audit
+        .record(
+            db,
+            AuditEvent::new("permission.visibility", "document", &filename)
+                .before(visibility_summary(previous))
+                .after(visibility_summary(request.visibility)),
+        )
+        .await;
+    Ok(Json(visibility_response(&state, filename, request.visibility)))
+}
+
+// すべてのタグを取得
+pub async fn get_all_tags(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Query(page_query): Query<PageQuery>,
+) -> Result<Json<TagsResponse>, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+    
+    let access = acl::load_all(&state).await?;
+    let subject = Subject::load(Some(&db), Some(&claims)).await?;
+
+    let limit = page_limit(page_query.limit);
+    let after = parse_cursor(page_query.cursor.as_deref())?;
+
+    let mut tags = db.list_tags_page(after.as_deref(), limit + 1).await.map_err(|e| (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(serde_json::json!({
+            "error": format!("Failed to get tags: {}", e)
+        })),
+    ))?;
+    let next_cursor = if tags.len() > limit {
+        tags.truncate(limit);
+        tags.last().map(|tag| encode_cursor(&tag.name))
+    } else {
+        None
+    };
+
+    // 読めないドキュメントがあることが分からないよう、使用数から除く
+    if !access.is_empty() {
+        let ids: Vec<i64> = tags.iter().map(|tag| tag.id).collect();
+        let mut hidden: HashMap<i64, i64> = HashMap::new();
+        for (tag_id, filename) in db.list_tagged_documents(&ids).await? {
+            if !access.allows(&subject, &filename, Permission::Read) {
+                *hidden.entry(tag_id).or_default() += 1;
+            }
+        }
+        for tag in &mut tags {
+            tag.usage_count -= hidden.get(&tag.id).copied().unwrap_or(0);
+        }
+    }
+
+    Ok(Json(TagsResponse { tags, next_cursor }))
+}
+
 // タグでドキュメントを検索
 pub async fn search_documents_by_tag(
     State(state): State<AppState>,
-    Query(params): Query<std::collections::HashMap<String, String>>,
+    claims: auth::Claims,
+    Path(tag): Path<String>,
+    Query(params): Query<TagDocumentsQuery>,
 ) -> Result<Json<Vec<String>>, (StatusCode, Json<serde_json::Value>)> {
     let db = match &state.db_manager {
         Some(db) => db.clone(),
@@ -151,7 +393,7 @@ pub async fn search_documents_by_tag(
         }
     };
     
-    let tag = params.get("tag").cloned().unwrap_or_default();
+    let tag = normalize_tag_name(&tag);
     if tag.is_empty() {
         return Err((
             StatusCode::BAD_REQUEST,
@@ -161,8 +403,16 @@ pub async fn search_documents_by_tag(
         ));
     }
     
-    match db.get_documents_by_tag(&tag).await {
-        Ok(documents) => Ok(Json(documents)),
+    let access = acl::load_all(&state).await?;
+    let subject = Subject::load(Some(&db), Some(&claims)).await?;
+
+    match db.get_documents_by_tag(&tag, params.include_descendants).await {
+        Ok(documents) => Ok(Json(
+            documents
+                .into_iter()
+                .filter(|document| access.allows(&subject, document, Permission::Read))
+                .collect(),
+        )),
         Err(e) => Err((
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({
@@ -170,4 +420,357 @@ pub async fn search_documents_by_tag(
             })),
         )),
     }
-}
\ No newline at end of file
+}
+
+// タグの説明と色を更新
+pub async fn update_tag(
+    State(state): State<AppState>,
+    Path(tag): Path<String>,
+    Json(request): Json<UpdateTagRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    let tag = normalize_tag_name(&tag);
+    if let Some(color) = &request.color {
+        if !is_valid_color(color) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "error": "Color must be in #RRGGBB format"
+                })),
+            ));
+        }
+    }
+
+    match db.update_tag_details(&tag, request.description, request.color).await {
+        Ok(true) => Ok(StatusCode::OK),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("Tag {} not found", tag)
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to update tag: {}", e)
+            })),
+        )),
+    }
+}
+
+// タグ名を変更
+pub async fn rename_tag(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(tag): Path<String>,
+    Json(request): Json<RenameTagRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    let tag = normalize_tag_name(&tag);
+    let new_name = normalize_tag_name(&request.new_name);
+    if new_name.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Tag name cannot be empty"
+            })),
+        ));
+    }
+
+    match db.rename_tag(&tag, &new_name).await {
+        Ok(TagChange::Applied) => {
+            audit
+                .record(&db, AuditEvent::new("tag.rename", "tag", &new_name).before(tag.as_str()).after(new_name.as_str()))
+                .await;
+            Ok(StatusCode::OK)
+        }
+        // 既存のタグと同名にする場合はマージを使う
+        Ok(TagChange::Conflict) => Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("Tag {} already exists or is a descendant of {}", new_name, tag)
+            })),
+        )),
+        Ok(TagChange::NotFound) => Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("Tag {} not found", tag)
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to rename tag: {}", e)
+            })),
+        )),
+    }
+}
+
+// タグを別のタグに統合
+pub async fn merge_tag(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(tag): Path<String>,
+    Json(request): Json<MergeTagRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    let tag = normalize_tag_name(&tag);
+    let target = normalize_tag_name(&request.into);
+    if target.is_empty() || target == tag {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Merge target must be a different, non-empty tag"
+            })),
+        ));
+    }
+
+    match db.merge_tags(&tag, &target).await {
+        Ok(TagChange::Applied) => {
+            audit
+                .record(&db, AuditEvent::new("tag.merge", "tag", &target).before(tag.as_str()).after(target.as_str()))
+                .await;
+            Ok(StatusCode::OK)
+        }
+        Ok(TagChange::Conflict) => Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("Tag {} has child tags or is the same tag as {}", tag, target)
+            })),
+        )),
+        Ok(TagChange::NotFound) => Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("Tag {} not found", tag)
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to merge tags: {}", e)
+            })),
+        )),
+    }
+}
+
+// 未使用のタグを削除
+pub async fn delete_tag(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(tag): Path<String>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    let tag = normalize_tag_name(&tag);
+    match db.get_tag_by_name(&tag).await {
+        Ok(Some(existing)) if existing.usage_count > 0 => {
+            return Err((
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({
+                    "error": format!("Tag {} is used by {} documents", tag, existing.usage_count)
+                })),
+            ));
+        },
+        Ok(Some(_)) => {},
+        Ok(None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                Json(serde_json::json!({
+                    "error": format!("Tag {} not found", tag)
+                })),
+            ));
+        },
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": format!("Failed to get tag: {}", e)
+                })),
+            ));
+        }
+    }
+
+    match db.delete_tag_if_unused(&tag).await {
+        Ok(true) => {
+            audit.record(&db, AuditEvent::new("tag.delete", "tag", &tag).before(tag.as_str())).await;
+            Ok(StatusCode::OK)
+        }
+        // 子タグを持つか、確認後に他のリクエストでタグ付けされた
+        Ok(false) => Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("Tag {} is in use or has child tags", tag)
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to delete tag: {}", e)
+            })),
+        )),
+    }
+}
+
+// どのドキュメントにも使われていないタグをまとめて削除
+pub async fn delete_unused_tags(
+    State(state): State<AppState>,
+    audit: AuditContext,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    match db.delete_unused_tags().await {
+        Ok(deleted) => {
+            for tag in &deleted {
+                audit.record(&db, AuditEvent::new("tag.delete", "tag", tag).before(tag.as_str())).await;
+            }
+            Ok(Json(serde_json::json!({ "deleted": deleted.len() })))
+        }
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to delete unused tags: {}", e)
+            })),
+        )),
+    }
+}
+
+// タグに同義語を追加（同義語でのタグ付けは正規タグに置き換えられる）
+pub async fn add_tag_synonym(
+    State(state): State<AppState>,
+    Path(tag): Path<String>,
+    Json(request): Json<SynonymRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    let tag = normalize_tag_name(&tag);
+    let synonym = normalize_tag_name(&request.name);
+    if synonym.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({
+                "error": "Tag name cannot be empty"
+            })),
+        ));
+    }
+
+    match db.add_tag_synonym(&tag, &synonym).await {
+        Ok(TagChange::Applied) => Ok(StatusCode::CREATED),
+        Ok(TagChange::Conflict) => Err((
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({
+                "error": format!("Tag {} cannot become a synonym of {}", synonym, tag)
+            })),
+        )),
+        Ok(TagChange::NotFound) => Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("Tag {} not found", tag)
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to add tag synonym: {}", e)
+            })),
+        )),
+    }
+}
+
+// タグの同義語を削除
+pub async fn remove_tag_synonym(
+    State(state): State<AppState>,
+    Path((tag, synonym)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = match &state.db_manager {
+        Some(db) => db.clone(),
+        None => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({
+                    "error": "Database not initialized"
+                })),
+            ));
+        }
+    };
+
+    let tag = normalize_tag_name(&tag);
+    let synonym = normalize_tag_name(&synonym);
+    match db.remove_tag_synonym(&tag, &synonym).await {
+        Ok(true) => Ok(StatusCode::OK),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({
+                "error": format!("Synonym {} of tag {} not found", synonym, tag)
+            })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({
+                "error": format!("Failed to remove tag synonym: {}", e)
+            })),
+        )),
+    }
+}
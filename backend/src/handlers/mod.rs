@@ -1,7 +1,69 @@
+pub mod acl;
+pub mod api_token;
+pub mod attachment;
+pub mod audit;
 pub mod auth;
 pub mod document;
+pub mod group;
+pub mod invitation;
+pub mod lockout;
 pub mod metadata;
+pub mod oidc;
+pub mod pagination;
+pub mod password_reset;
+pub mod session;
+pub mod setup;
+pub mod share;
+pub mod totp;
+pub mod user;
 
+pub use acl::*;
+pub use attachment::*;
 pub use auth::*;
 pub use document::*;
-pub use metadata::*; 
\ No newline at end of file
+pub use group::*;
+pub use metadata::*;
+pub use share::*;
+
+use axum::{
+    extract::ConnectInfo,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{db::DbManager, AppState};
+
+// データベースが初期化されていない場合は 500 を返す
+pub(crate) fn database(state: &AppState) -> Result<&DbManager, (StatusCode, Json<serde_json::Value>)> {
+    match &state.db_manager {
+        Some(db) => Ok(db),
+        None => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "Database not initialized" })),
+        )),
+    }
+}
+
+// リクエスト元のIPアドレス
+// TRUST_PROXY_HEADERS を指定した場合は、リバースプロキシが X-Forwarded-For の最後に付けた値を使う
+// （それより前の値はクライアントが自由に書けるため使わない）
+pub(crate) fn client_ip(
+    state: &AppState,
+    headers: &HeaderMap,
+    connect_info: Option<&ConnectInfo<SocketAddr>>,
+) -> Option<String> {
+    if state.config.trust_proxy_headers {
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .last()
+            .and_then(|value| value.trim().parse::<IpAddr>().ok());
+        if let Some(ip) = forwarded {
+            return Some(ip.to_string());
+        }
+    }
+    connect_info.map(|ConnectInfo(addr)| addr.ip().to_string())
+}
\ No newline at end of file
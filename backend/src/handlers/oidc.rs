@@ -0,0 +1,90 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Redirect,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{AppError, AppState};
+use crate::auth::oidc::OidcClient;
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::handlers::session::start_session;
+
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+fn oidc_client(state: &AppState) -> Result<&OidcClient, AppError> {
+    state
+        .oidc
+        .as_deref()
+        .ok_or_else(|| AppError::NotFound("Single sign-on is not configured".to_string()))
+}
+
+// プロバイダのログイン画面へリダイレクトする
+pub async fn oidc_login(
+    State(state): State<AppState>,
+) -> Result<Redirect, (StatusCode, Json<serde_json::Value>)> {
+    let client = oidc_client(&state)?;
+    Ok(Redirect::to(&client.authorization_url().await?))
+}
+
+// プロバイダから戻ってきたときの処理
+// 認可コードを検証してユーザーを作成（または更新）し、パスワードでのログインと同じ形でトークンを返す
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let client = oidc_client(&state)?;
+    let db = database(&state)?;
+
+    if let Some(error) = query.error {
+        let description = query.error_description.unwrap_or_default();
+        return Err(AppError::Auth(format!("Login was rejected by the provider: {} {}", error, description).trim_end().to_string()).into());
+    }
+    let (code, login_state) = match (query.code, query.state) {
+        (Some(code), Some(login_state)) => (code, login_state),
+        _ => return Err(AppError::InvalidInput("code and state are required".to_string()).into()),
+    };
+
+    let identity = client.exchange_code(&code, &login_state).await?;
+    let user = db.provision_external_user(&identity).await?;
+    if user.is_disabled() {
+        audit
+            .with_actor(Some(user.id), &user.username)
+            .record(db, AuditEvent::new("auth.login_failed", "user", &user.username).after("disabled"))
+            .await;
+        return Err(AppError::Forbidden("This account has been disabled".to_string()).into());
+    }
+
+    // 所属グループのロールも含めた、最も強いロールをトークンに入れる
+    let role = db.effective_role(user.id, user.role).await?;
+    let tokens = start_session(db, &state.tokens, &user, role, &audit, "oidc").await?;
+
+    Ok(Json(serde_json::json!({
+        "token": tokens.token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+        "user": user
+    })))
+}
+
+// 使えるログイン方法（ログイン画面の表示の切り替え用）
+pub async fn auth_methods(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let providers = database(&state)?.auth_provider_names();
+    Ok(Json(serde_json::json!({
+        "password": state.config.password_login || !providers.is_empty(),
+        "providers": providers,
+        "oidc": state.oidc.is_some(),
+        "registration": if state.config.password_login { state.config.registration_mode.as_str() } else { "off" }
+    })))
+}
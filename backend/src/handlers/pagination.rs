@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+use crate::AppError;
+
+// 1ページあたりの既定件数と上限
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+// カーソルとページサイズのみを受け取る一覧系APIの共通クエリ
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+// ソートキーを不透明なカーソル文字列にエンコードする
+pub fn encode_cursor(key: &str) -> String {
+    key.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_cursor(cursor: &str) -> Option<String> {
+    if !cursor.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(cursor.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+
+    String::from_utf8(bytes).ok()
+}
+
+// 指定されたページサイズを既定値と上限に合わせる
+pub fn page_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
+
+// クエリの `cursor` をソートキーに戻す（空なら最初のページ）
+pub fn parse_cursor(cursor: Option<&str>) -> Result<Option<String>, AppError> {
+    cursor
+        .filter(|c| !c.is_empty())
+        .map(|c| decode_cursor(c).ok_or_else(|| AppError::InvalidInput("Invalid cursor".to_string())))
+        .transpose()
+}
+
+// ソートキーに基づくカーソルページネーション
+// DBで絞り込めない一覧（検索結果・変更履歴など）に使う
+//
+// `key` は要素ごとに一意で、並び順と同じ順序で比較できる文字列を返す必要がある。
+// カーソルには前ページ最後の要素のキーが入るため、ページ間で要素が追加・削除されても
+// 重複や取りこぼしが起きない。
+// 並べ替えるのはカーソルより後の要素のうち、ページに入る分だけ
+pub fn paginate<T, F>(
+    items: Vec<T>,
+    cursor: Option<&str>,
+    limit: Option<usize>,
+    order: SortOrder,
+    key: F,
+) -> Result<Page<T>, AppError>
+where
+    F: Fn(&T) -> String,
+{
+    let limit = page_limit(limit);
+    let after = parse_cursor(cursor)?;
+
+    let mut keyed: Vec<(String, T)> = items
+        .into_iter()
+        .map(|item| (key(&item), item))
+        .filter(|(k, _)| match (&after, order) {
+            (None, _) => true,
+            (Some(after), SortOrder::Asc) => k > after,
+            (Some(after), SortOrder::Desc) => k < after,
+        })
+        .collect();
+
+    let compare = |a: &(String, T), b: &(String, T)| match order {
+        SortOrder::Asc => a.0.cmp(&b.0),
+        SortOrder::Desc => b.0.cmp(&a.0),
+    };
+    let has_more = keyed.len() > limit;
+    if has_more {
+        keyed.select_nth_unstable_by(limit, compare);
+        keyed.truncate(limit);
+    }
+    keyed.sort_unstable_by(compare);
+
+    let next_cursor = if has_more { keyed.last().map(|(k, _)| encode_cursor(k)) } else { None };
+    let items = keyed.into_iter().map(|(_, item)| item).collect();
+
+    Ok(Page { items, next_cursor })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 同じ並び順の値を持つ要素はファイル名で区別する
+    fn items() -> Vec<(u32, &'static str)> {
+        vec![(3, "c"), (1, "a"), (2, "b2"), (2, "b1"), (5, "e"), (4, "d")]
+    }
+
+    fn key(item: &(u32, &str)) -> String {
+        format!("{:020}\u{0}{}", item.0, item.1)
+    }
+
+    // 最後のページまでたどり、各ページの要素をつなげて返す
+    fn walk(order: SortOrder, limit: usize) -> Vec<Vec<&'static str>> {
+        let mut pages = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = paginate(items(), cursor.as_deref(), Some(limit), order, key).unwrap();
+            pages.push(page.items.iter().map(|item| item.1).collect());
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return pages,
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        for key in ["", "notes", "2024-01-01 00:00:00\u{0}guide/setup", "日本語"] {
+            assert_eq!(decode_cursor(&encode_cursor(key)).as_deref(), Some(key));
+        }
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        assert_eq!(decode_cursor("abc"), None);
+        assert_eq!(decode_cursor("zz"), None);
+        // UTF-8 として不正なバイト列
+        assert_eq!(decode_cursor("ff"), None);
+        assert!(matches!(parse_cursor(Some("not-a-cursor")), Err(AppError::InvalidInput(_))));
+        assert!(matches!(
+            paginate(items(), Some("xyz"), None, SortOrder::Asc, key),
+            Err(AppError::InvalidInput(_))
+        ));
+        // 空のカーソルは最初のページ
+        assert_eq!(parse_cursor(Some("")).unwrap(), None);
+    }
+
+    #[test]
+    fn pages_follow_the_order_without_gaps_or_duplicates() {
+        assert_eq!(walk(SortOrder::Asc, 2), vec![vec!["a", "b1"], vec!["b2", "c"], vec!["d", "e"]]);
+        assert_eq!(walk(SortOrder::Desc, 4), vec![vec!["e", "d", "c", "b2"], vec!["b1", "a"]]);
+    }
+
+    #[test]
+    fn last_page_has_no_next_cursor() {
+        let page = paginate(items(), None, Some(6), SortOrder::Asc, key).unwrap();
+        assert_eq!(page.items.len(), 6);
+        assert_eq!(page.next_cursor, None);
+
+        let page = paginate(items(), None, Some(5), SortOrder::Asc, key).unwrap();
+        let last = paginate(items(), page.next_cursor.as_deref(), Some(5), SortOrder::Asc, key).unwrap();
+        assert_eq!(last.items, vec![(5, "e")]);
+        assert_eq!(last.next_cursor, None);
+    }
+
+    #[test]
+    fn page_size_is_clamped() {
+        assert_eq!(page_limit(None), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_limit(Some(0)), 1);
+        assert_eq!(page_limit(Some(10_000)), MAX_PAGE_SIZE);
+    }
+}
@@ -0,0 +1,123 @@
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+
+use crate::{AppError, AppState};
+use crate::auth::session::{generate_secret_token, hash_secret_token};
+use crate::db::ThrottleScope;
+use crate::handlers::database;
+use crate::mail::MailMessage;
+use crate::models::user::{hash_password, validate_password};
+
+// 再設定のリンクの有効期間（分）
+const PASSWORD_RESET_MINUTES: i64 = 60;
+// 同じユーザーにメールを送り直せるまでの間隔（秒）
+const PASSWORD_RESET_COOLDOWN_SECONDS: i64 = 60;
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmPasswordResetRequest {
+    token: String,
+    password: String,
+}
+
+fn reset_message(state: &AppState, to: &str, username: &str, token: &str) -> MailMessage {
+    let link = format!("{}/reset-password?token={}", state.config.public_url, token);
+    MailMessage {
+        to: to.to_string(),
+        subject: "Reset your MD-Wiki password".to_string(),
+        body: format!(
+            "Hello {},\n\n\
+             A password reset was requested for your account.\n\
+             Open the link below within {} minutes to choose a new password:\n\n\
+             {}\n\n\
+             If you did not request this, you can ignore this email. Your password will not change.\n",
+            username, PASSWORD_RESET_MINUTES, link
+        ),
+    }
+}
+
+// パスワードの再設定を申し込む（ログイン不要）
+// 登録されているメールアドレスかどうかが分からないよう、結果に関わらず同じ応答を返す
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<serde_json::Value>)> {
+    if !state.config.password_login {
+        return Err(AppError::Forbidden("Password login is disabled".to_string()).into());
+    }
+    let db = database(&state)?;
+
+    let email = request.email.trim();
+    if email.is_empty() {
+        return Err(AppError::InvalidInput("Email is required".to_string()).into());
+    }
+
+    // 無効にしたユーザーと、外部の認証基盤（LDAP・OIDC）のユーザーには送らない
+    let user = db
+        .get_user_by_email(email)
+        .await?
+        .filter(|user| !user.is_disabled() && !user.password_hash.is_empty());
+    if let Some(user) = user {
+        let now = chrono::Utc::now();
+        let recently_sent = db
+            .last_password_reset_at(user.id)
+            .await?
+            .is_some_and(|sent_at| now - sent_at < chrono::Duration::seconds(PASSWORD_RESET_COOLDOWN_SECONDS));
+        if !recently_sent {
+            let token = generate_secret_token();
+            let expires_at = now + chrono::Duration::minutes(PASSWORD_RESET_MINUTES);
+            db.create_password_reset(user.id, &hash_secret_token(&token), expires_at).await?;
+
+            // 送信にかかる時間で結果が分からないよう、応答を待たせずに送る
+            let message = reset_message(&state, email, &user.username, &token);
+            let mailer = state.mailer.clone();
+            tokio::spawn(async move {
+                if let Err(e) = mailer.send(&message).await {
+                    tracing::error!("Failed to send password reset email: {}", e);
+                }
+            });
+        }
+    }
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "message": "If the email is registered, a password reset link has been sent"
+        })),
+    ))
+}
+
+// 再設定のトークンで新しいパスワードを設定する（ログイン不要）
+// すべてのセッションを取り消し、ユーザー名のロックも解除する
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<ConfirmPasswordResetRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    if !state.config.password_login {
+        return Err(AppError::Forbidden("Password login is disabled".to_string()).into());
+    }
+    let db = database(&state)?;
+    let invalid_token = || AppError::Auth("Invalid or expired reset token".to_string());
+
+    let token_hash = hash_secret_token(request.token.trim());
+    let user_id = db.password_reset_user(&token_hash).await?.ok_or_else(invalid_token)?;
+    let user = db
+        .get_user_by_id(user_id)
+        .await?
+        .filter(|user| !user.is_disabled() && !user.password_hash.is_empty())
+        .ok_or_else(invalid_token)?;
+    validate_password(&request.password)?;
+
+    if !db
+        .complete_password_reset(&token_hash, user.id, &hash_password(&request.password)?)
+        .await?
+    {
+        return Err(invalid_token().into());
+    }
+    db.reset_login_throttle(ThrottleScope::Username, &user.username).await?;
+    Ok(StatusCode::OK)
+}
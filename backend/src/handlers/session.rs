@@ -0,0 +1,251 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth, AppError, AppState};
+use crate::auth::TokenService;
+use crate::auth::session::{generate_secret_token, hash_secret_token, refresh_token_expiry, ACCESS_TOKEN_MINUTES};
+use crate::db::{DbManager, Session};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::handlers::totp::mfa_challenge;
+use crate::models::user::{Role, User};
+
+#[derive(Deserialize)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Serialize)]
+pub struct TokenPair {
+    pub token: String,
+    pub refresh_token: String,
+    // アクセストークンの有効期間（秒）
+    pub expires_in: i64,
+}
+
+#[derive(Serialize)]
+pub struct SessionInfo {
+    #[serde(flatten)]
+    session: Session,
+    // このリクエストのトークンのセッションか
+    current: bool,
+}
+
+#[derive(Serialize)]
+pub struct SessionList {
+    sessions: Vec<SessionInfo>,
+}
+
+#[derive(Serialize)]
+pub struct RevokedSessions {
+    revoked: u64,
+}
+
+fn token_pair(
+    tokens: &TokenService,
+    user_id: i64,
+    role: Role,
+    session_id: i64,
+    refresh_token: String,
+) -> Result<TokenPair, AppError> {
+    Ok(TokenPair {
+        token: auth::create_token(tokens, user_id, &role.to_string(), session_id)?,
+        refresh_token,
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+    })
+}
+
+// ログイン時にセッションを作り、トークンを発行する
+// ログインは `method`（password・mfa・oidc など）と合わせて監査ログに記録する
+pub(crate) async fn start_session(
+    db: &DbManager,
+    tokens: &TokenService,
+    user: &User,
+    role: Role,
+    audit: &AuditContext,
+    method: &str,
+) -> Result<TokenPair, AppError> {
+    let refresh_token = generate_secret_token();
+    let session = db
+        .create_session(user.id, &hash_secret_token(&refresh_token), audit.user_agent(), refresh_token_expiry())
+        .await?;
+
+    audit
+        .with_actor(Some(user.id), &user.username)
+        .record(
+            db,
+            AuditEvent::new("auth.login", "user", &user.username)
+                .after(format!("{} login as {} (session {})", method, role, session.id)),
+        )
+        .await;
+    token_pair(tokens, user.id, role, session.id, refresh_token)
+}
+
+// 作成したばかりのユーザーでログインしたときのレスポンス（初期設定・招待の受け入れ）
+// 二要素認証が必要なら、パスワードでのログインと同じく2段階目に進ませる
+pub(crate) async fn login_response(
+    state: &AppState,
+    db: &DbManager,
+    user: &User,
+    role: Role,
+    audit: &AuditContext,
+    method: &str,
+) -> Result<serde_json::Value, AppError> {
+    if let Some(challenge) = mfa_challenge(state, db, user, role).await? {
+        return Ok(challenge);
+    }
+
+    let tokens = start_session(db, &state.tokens, user, role, audit, method).await?;
+    Ok(serde_json::json!({
+        "token": tokens.token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+        "user": user
+    }))
+}
+
+// 置き換え済みのリフレッシュトークンが使われたら、漏れたものとみなしてセッションごと取り消す
+// 正しい利用者と漏れたトークンを使う側のどちらが先に更新したかは区別できないため、両方ともログインし直しになる
+async fn revoke_reused_session(db: &DbManager, audit: &AuditContext, token_hash: &str) -> Result<(), AppError> {
+    let Some(session) = db.get_session_by_rotated_token(token_hash).await? else {
+        return Ok(());
+    };
+    if !db.revoke_session(session.id, session.user_id).await? {
+        return Ok(());
+    }
+
+    let username = db
+        .get_user_by_id(session.user_id)
+        .await?
+        .map(|user| user.username)
+        .unwrap_or_default();
+    audit
+        .with_actor(Some(session.user_id), &username)
+        .record(
+            db,
+            AuditEvent::new("auth.refresh_token_reuse", "user", &username)
+                .after(format!("session {} revoked", session.id)),
+        )
+        .await;
+    Ok(())
+}
+
+// リフレッシュトークンを使ってアクセストークンを更新する（ログイン不要）
+// リフレッシュトークンも新しいものに置き換わり、古いものは使えなくなる
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<TokenPair>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let invalid = || AppError::Auth("Invalid or expired refresh token".to_string());
+
+    let old_hash = hash_secret_token(&request.refresh_token);
+    let session = match db.get_session_by_token(&old_hash).await? {
+        Some(session) => session,
+        None => {
+            revoke_reused_session(db, &audit, &old_hash).await?;
+            return Err(invalid().into());
+        }
+    };
+    if !session.is_active() {
+        return Err(invalid().into());
+    }
+
+    let user = db
+        .get_user_by_id(session.user_id)
+        .await?
+        .filter(|user| !user.is_disabled())
+        .ok_or_else(|| AppError::Auth("User no longer exists or has been disabled".to_string()))?;
+    // グループやロールの変更は更新時に反映する
+    let role = db.effective_role(user.id, user.role).await?;
+
+    let refresh_token = generate_secret_token();
+    let rotated = db
+        .rotate_session_token(session.id, &old_hash, &hash_secret_token(&refresh_token), refresh_token_expiry())
+        .await?;
+    if !rotated {
+        // 同じトークンで同時に更新され、先に置き換えられた場合
+        revoke_reused_session(db, &audit, &old_hash).await?;
+        return Err(invalid().into());
+    }
+
+    Ok(Json(token_pair(&state.tokens, user.id, role, session.id, refresh_token)?))
+}
+
+// 現在のセッションからログアウトする
+pub async fn logout(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    if let Some(session_id) = claims.sid {
+        db.revoke_session(session_id, claims.sub).await?;
+    }
+    Ok(StatusCode::OK)
+}
+
+// 自分の有効なセッションの一覧
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<Json<SessionList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let sessions = db
+        .list_user_sessions(claims.sub)
+        .await?
+        .into_iter()
+        .map(|session| SessionInfo {
+            current: claims.sid == Some(session.id),
+            session,
+        })
+        .collect();
+    Ok(Json(SessionList { sessions }))
+}
+
+// 自分のセッションを1つ取り消す
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    if db.revoke_session(id, claims.sub).await? {
+        Ok(StatusCode::OK)
+    } else {
+        Err(AppError::NotFound(format!("Session {} not found", id)).into())
+    }
+}
+
+// 自分のすべてのセッションからログアウトする
+pub async fn revoke_all_sessions(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<Json<RevokedSessions>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    Ok(Json(RevokedSessions {
+        revoked: db.revoke_user_sessions(claims.sub).await?,
+    }))
+}
+
+// ユーザーのすべてのセッションを取り消す（管理者用）
+pub async fn revoke_user_sessions(
+    State(state): State<AppState>,
+    Path(user_id): Path<i64>,
+) -> Result<Json<RevokedSessions>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    if db.get_user_by_id(user_id).await?.is_none() {
+        return Err(AppError::NotFound("User not found".to_string()).into());
+    }
+    Ok(Json(RevokedSessions {
+        revoked: db.revoke_user_sessions(user_id).await?,
+    }))
+}
@@ -0,0 +1,74 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{AppError, AppState};
+use crate::auth::session::hash_secret_token;
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::handlers::session::login_response;
+use crate::models::user::{validate_password, Role};
+
+#[derive(Deserialize)]
+pub struct SetupRequest {
+    setup_token: String,
+    username: String,
+    password: String,
+    email: Option<String>,
+}
+
+// 初期設定（最初の管理者の作成）が済んでいるか（ログイン不要）
+pub async fn get_setup_status(
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    Ok(Json(serde_json::json!({
+        "setup_required": db.count_users().await? == 0
+    })))
+}
+
+// 初期設定のトークンを使って最初の管理者を作成し、そのままログインする（ログイン不要）
+// 管理者に二要素認証を必須にしている場合は、ログインと同じく登録に進ませる
+pub async fn complete_setup(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Json(request): Json<SetupRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), (StatusCode, Json<serde_json::Value>)> {
+    if !state.config.password_login {
+        return Err(AppError::Forbidden("Password login is disabled".to_string()).into());
+    }
+    let db = database(&state)?;
+
+    let username = request.username.trim();
+    if username.is_empty() {
+        return Err(AppError::InvalidInput("Username cannot be empty".to_string()).into());
+    }
+    let email = request.email.as_deref().map(str::trim).filter(|email| !email.is_empty());
+    validate_password(&request.password)?;
+
+    let user_id = db
+        .complete_setup(&hash_secret_token(request.setup_token.trim()), username, &request.password, email)
+        .await?;
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None if db.count_users().await? > 0 => {
+            return Err(AppError::Conflict("Setup has already been completed".to_string()).into());
+        }
+        None => return Err(AppError::Auth("Invalid or expired setup token".to_string()).into()),
+    };
+
+    let user = db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::Internal("Created user not found".to_string()))?;
+    audit
+        .with_actor(Some(user.id), &user.username)
+        .record(db, AuditEvent::new("user.create", "user", &user.username).after(format!("role {} (initial setup)", Role::Admin)))
+        .await;
+
+    let response = login_response(&state, db, &user, Role::Admin, &audit, "setup").await?;
+    Ok((StatusCode::CREATED, Json(response)))
+}
@@ -0,0 +1,313 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::{auth, storage, AppError, AppState};
+use crate::auth::acl::{self, Subject};
+use crate::auth::share::{create_share_token, verify_share_token};
+use crate::db::{NewShareLink, ShareLink};
+use crate::git_ops::CommitInfo;
+use crate::handlers::audit::{content_summary, document_summary, AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::models::acl::Permission;
+use crate::models::user::Role;
+
+// 共有リンクの有効期間（時間）
+const DEFAULT_SHARE_HOURS: i64 = 72;
+const MAX_SHARE_HOURS: i64 = 30 * 24;
+
+#[derive(Deserialize)]
+pub struct CreateShareRequest {
+    expires_in_hours: Option<i64>,
+    // 特定のバージョンを共有する場合のコミットID（前方一致）
+    commit: Option<String>,
+    // `read`（既定）か `write`
+    permission: Option<Permission>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    link: ShareLink,
+    token: String,
+    url: String,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinkList {
+    links: Vec<ShareLink>,
+}
+
+#[derive(Serialize)]
+pub struct SharedDocument {
+    filename: String,
+    content: String,
+    permission: Permission,
+    expires_at: String,
+    commit_info: Option<CommitInfo>,
+}
+
+#[derive(Deserialize)]
+pub struct SharedDocumentUpdate {
+    content: String,
+}
+
+// ドキュメントの共有リンクを作成する（ドキュメントの編集権限が必要）
+pub async fn create_share_link(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(filename): Path<String>,
+    Json(request): Json<CreateShareRequest>,
+) -> Result<(StatusCode, Json<CreatedShareLink>), (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    storage::validate_name(&filename)?;
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+
+    let permission = request.permission.unwrap_or(Permission::Read);
+    if permission == Permission::Admin {
+        return Err(AppError::InvalidInput("Share links can only grant read or write permission".to_string()).into());
+    }
+    if permission == Permission::Write && request.commit.is_some() {
+        return Err(AppError::InvalidInput("Share links to a past version are always read-only".to_string()).into());
+    }
+
+    let hours = request.expires_in_hours.unwrap_or(DEFAULT_SHARE_HOURS);
+    if !(1..=MAX_SHARE_HOURS).contains(&hours) {
+        return Err(AppError::InvalidInput(format!(
+            "expires_in_hours must be between 1 and {}",
+            MAX_SHARE_HOURS
+        ))
+        .into());
+    }
+
+    if !state.document_store.exists(&filename).await? {
+        return Err(AppError::NotFound(format!("Document {} not found", filename)).into());
+    }
+
+    // 短縮したコミットIDは完全なIDに置き換えて保存する
+    let commit_id = match &request.commit {
+        Some(commit) => {
+            let history = state.document_store.history(&filename).await?;
+            let found = history.into_iter().find(|c| c.id.starts_with(commit.as_str()));
+            match found {
+                Some(c) => Some(c.id),
+                None => {
+                    return Err(AppError::NotFound(format!(
+                        "Commit {} not found for document {}",
+                        commit, filename
+                    ))
+                    .into());
+                }
+            }
+        }
+        None => None,
+    };
+
+    let expires_at = chrono::Utc::now() + chrono::Duration::hours(hours);
+    let link = db
+        .create_share_link(NewShareLink {
+            document: filename,
+            commit_id,
+            permission,
+            created_by: Some(claims.sub),
+            expires_at,
+        })
+        .await?;
+
+    audit
+        .record(
+            db,
+            AuditEvent::new("permission.share_create", "document", &link.document).after(format!(
+                "share link {}: {}{} until {}",
+                link.id,
+                link.permission,
+                link.commit_id.as_deref().map(|commit| format!(" at {}", commit)).unwrap_or_default(),
+                link.expires_at
+            )),
+        )
+        .await;
+
+    let token = create_share_token(&state.tokens, &link, expires_at)?;
+    let url = format!("/api/shared/{}", token);
+    Ok((StatusCode::CREATED, Json(CreatedShareLink { link, token, url })))
+}
+
+// ドキュメントの共有リンク一覧
+pub async fn list_document_share_links(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Path(filename): Path<String>,
+) -> Result<Json<ShareLinkList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    acl::authorize(&state, Some(&claims), &filename, Permission::Write).await?;
+    Ok(Json(ShareLinkList {
+        links: db.list_share_links(Some(&filename), None).await?,
+    }))
+}
+
+fn is_admin(claims: &auth::Claims) -> bool {
+    Role::from_str(&claims.role) == Ok(Role::Admin)
+}
+
+// 自分が作成した共有リンクの一覧（管理者はすべて）
+pub async fn list_share_links(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<Json<ShareLinkList>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let created_by = if is_admin(&claims) { None } else { Some(claims.sub) };
+    Ok(Json(ShareLinkList {
+        links: db.list_share_links(None, created_by).await?,
+    }))
+}
+
+// 共有リンクを取り消す（作成者と管理者のみ）
+pub async fn revoke_share_link(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let link = db
+        .get_share_link(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Share link {} not found", id)))?;
+    if link.created_by != Some(claims.sub) && !is_admin(&claims) {
+        return Err(AppError::Forbidden("Only the creator or an admin can revoke a share link".to_string()).into());
+    }
+
+    db.revoke_share_link(id).await?;
+    audit
+        .record(
+            db,
+            AuditEvent::new("permission.share_revoke", "document", &link.document)
+                .before(format!("share link {}: {}", link.id, link.permission)),
+        )
+        .await;
+    Ok(StatusCode::OK)
+}
+
+// トークンを検証し、有効な共有リンクを取得する
+async fn active_share_link(state: &AppState, token: &str) -> Result<ShareLink, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(state)?;
+    let claims = verify_share_token(&state.tokens, token)?;
+
+    // ドキュメントの名前が変わってもリンクを使えるよう、共有先はトークンではなく share_links の行で決める
+    let link = db
+        .get_share_link(claims.sid)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Share link not found".to_string()))?;
+
+    if !link.is_active() {
+        return Err((
+            StatusCode::GONE,
+            Json(serde_json::json!({
+                "error": "Share link has expired or been revoked"
+            })),
+        ));
+    }
+    Ok(link)
+}
+
+// 作成者が無効にされたり、ドキュメントの権限を失ったりしたリンクは使えない
+// 権限は使うたびに作成者の現在のロール・グループ・ACLで確かめる
+async fn ensure_creator_allows(
+    state: &AppState,
+    link: &ShareLink,
+    required: Permission,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    let db = database(state)?;
+
+    let creator = match link.created_by {
+        Some(id) => db.get_user_by_id(id).await?.filter(|user| !user.is_disabled()),
+        None => None,
+    };
+    let permitted = match creator {
+        Some(creator) => acl::load(db, &link.document)
+            .await?
+            .allows(&Subject::for_user(db, &creator).await?, &link.document, required),
+        None => false,
+    };
+    if !permitted {
+        return Err((
+            StatusCode::GONE,
+            Json(serde_json::json!({
+                "error": "The creator of this share link can no longer access the document"
+            })),
+        ));
+    }
+    Ok(())
+}
+
+// 共有リンクからドキュメントを取得する（ログイン不要）
+pub async fn get_shared_document(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Json<SharedDocument>, (StatusCode, Json<serde_json::Value>)> {
+    let link = active_share_link(&state, &token).await?;
+    ensure_creator_allows(&state, &link, Permission::Read).await?;
+
+    let (content, commit_info) = match &link.commit_id {
+        Some(commit_id) => {
+            let history = state.document_store.history(&link.document).await?;
+            let commit_info = history.into_iter().find(|c| &c.id == commit_id);
+            (state.document_store.read_version(&link.document, commit_id).await?, commit_info)
+        }
+        None => (state.document_store.read(&link.document).await?, None),
+    };
+
+    Ok(Json(SharedDocument {
+        filename: link.document,
+        content,
+        permission: link.permission,
+        expires_at: link.expires_at,
+        commit_info,
+    }))
+}
+
+// 編集可能な共有リンクからドキュメントを更新する（ログイン不要）
+pub async fn update_shared_document(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(token): Path<String>,
+    Json(update): Json<SharedDocumentUpdate>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let link = active_share_link(&state, &token).await?;
+
+    if link.permission != Permission::Write {
+        return Err(AppError::Forbidden("This share link is read-only".to_string()).into());
+    }
+    ensure_creator_allows(&state, &link, Permission::Write).await?;
+
+    // 削除されたドキュメントを共有リンクから作り直さない
+    let previous = document_summary(&state, &link.document)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Document {} not found", link.document)))?;
+
+    let commit_message = format!("Update {}.md via share link {}", link.document, link.id);
+    state.document_store
+        .write(&link.document, &update.content, &commit_message)
+        .await?;
+
+    // 共有リンクからの編集はログインしていないため、操作したユーザーは残らない
+    audit
+        .record_document(
+            &state,
+            AuditEvent::new("document.update", "document", &link.document)
+                .before(previous)
+                .after(format!("{} (via share link {})", content_summary(&update.content), link.id)),
+        )
+        .await;
+
+    Ok(StatusCode::OK)
+}
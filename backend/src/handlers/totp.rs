@@ -0,0 +1,348 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{auth, AppError, AppState};
+use crate::auth::totp::{
+    create_mfa_token, generate_recovery_codes, generate_secret, hash_recovery_code, provisioning_uri, verify_code,
+    verify_mfa_token, MfaClaims, MfaPurpose, MFA_TOKEN_MINUTES,
+};
+use crate::db::{DbManager, TotpState};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::handlers::lockout::LoginAttempt;
+use crate::handlers::session::start_session;
+use crate::models::user::{Role, User};
+
+#[derive(Deserialize)]
+pub struct MfaLoginRequest {
+    mfa_token: String,
+    code: Option<String>,
+    recovery_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MfaSetupRequest {
+    mfa_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct EnableTotpRequest {
+    code: String,
+}
+
+// 認証アプリのコードか、リカバリーコードのどちらかで本人であることを確認する
+#[derive(Deserialize)]
+pub struct SecondFactorRequest {
+    code: Option<String>,
+    recovery_code: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct TotpStatus {
+    enabled: bool,
+    recovery_codes_remaining: i64,
+}
+
+#[derive(Serialize)]
+pub struct TotpSetup {
+    // 認証アプリに手入力する場合の秘密鍵（Base32）
+    secret: String,
+    provisioning_uri: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+// APIトークンで二要素認証の設定を変えられないようにする
+fn require_login(claims: &auth::Claims) -> Result<(), AppError> {
+    if claims.api_token.is_some() {
+        return Err(AppError::Forbidden("API tokens cannot be used to manage two-factor authentication".to_string()));
+    }
+    Ok(())
+}
+
+// パスワードを確認した後、2段階目のログインが必要なら、その案内を返す
+// 二要素認証を有効にしているか、管理者に必須にしていてまだ登録していない場合
+pub(crate) async fn mfa_challenge(
+    state: &AppState,
+    db: &DbManager,
+    user: &User,
+    role: Role,
+) -> Result<Option<serde_json::Value>, AppError> {
+    let totp = db.get_totp_state(user.id).await?;
+
+    let purpose = if totp.enabled {
+        MfaPurpose::Verify
+    } else if state.config.totp_required_for_admins && role == Role::Admin {
+        MfaPurpose::Setup
+    } else {
+        return Ok(None);
+    };
+
+    let mfa_token = create_mfa_token(&state.tokens, user.id, purpose)?;
+    let flag = match purpose {
+        MfaPurpose::Verify => "mfa_required",
+        MfaPurpose::Setup => "mfa_setup_required",
+    };
+    Ok(Some(serde_json::json!({
+        flag: true,
+        "mfa_token": mfa_token,
+        "expires_in": MFA_TOKEN_MINUTES * 60
+    })))
+}
+
+// 有効にしている二要素認証のコードかリカバリーコードを確認する
+async fn verify_second_factor(
+    db: &DbManager,
+    user_id: i64,
+    totp: &TotpState,
+    code: Option<&str>,
+    recovery_code: Option<&str>,
+) -> Result<(), AppError> {
+    let secret = match (&totp.secret, totp.enabled) {
+        (Some(secret), true) => secret,
+        _ => return Err(AppError::InvalidInput("Two-factor authentication is not enabled".to_string())),
+    };
+
+    match (code, recovery_code) {
+        (Some(code), _) => match verify_code(secret, code)? {
+            // 同じコードは2回使えない
+            Some(step) if db.record_totp_step(user_id, step).await? => Ok(()),
+            _ => Err(AppError::Auth("Invalid authentication code".to_string())),
+        },
+        (None, Some(recovery_code)) => {
+            if db.use_recovery_code(user_id, &hash_recovery_code(recovery_code)).await? {
+                Ok(())
+            } else {
+                Err(AppError::Auth("Invalid recovery code".to_string()))
+            }
+        }
+        (None, None) => Err(AppError::InvalidInput("code or recovery_code is required".to_string())),
+    }
+}
+
+// 新しい秘密鍵で登録を始める（確認するまでは有効にならない）
+async fn start_enrollment(db: &DbManager, user: &User) -> Result<TotpSetup, AppError> {
+    let secret = generate_secret();
+    let provisioning_uri = provisioning_uri(&secret, &user.username)?;
+
+    if !db.start_totp_enrollment(user.id, &secret).await? {
+        return Err(AppError::InvalidInput("Two-factor authentication is already enabled".to_string()));
+    }
+    Ok(TotpSetup { secret, provisioning_uri })
+}
+
+// 登録中の秘密鍵でコードを確認して有効にし、リカバリーコードを返す
+async fn confirm_enrollment(db: &DbManager, user_id: i64, totp: &TotpState, code: &str) -> Result<Vec<String>, AppError> {
+    if totp.enabled {
+        return Err(AppError::InvalidInput("Two-factor authentication is already enabled".to_string()));
+    }
+    let secret = totp
+        .secret
+        .as_deref()
+        .ok_or_else(|| AppError::InvalidInput("Two-factor authentication setup has not been started".to_string()))?;
+    let step = verify_code(secret, code)?
+        .ok_or_else(|| AppError::Auth("Invalid authentication code".to_string()))?;
+
+    let recovery_codes = generate_recovery_codes();
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+    if !db.enable_totp(user_id, step, &code_hashes).await? {
+        return Err(AppError::InvalidInput("Two-factor authentication setup has not been started".to_string()));
+    }
+    Ok(recovery_codes)
+}
+
+// 2段階目のログイン用のトークンからユーザーを取り出す
+async fn mfa_user(state: &AppState, db: &DbManager, mfa_token: &str) -> Result<(User, MfaClaims), AppError> {
+    let claims = verify_mfa_token(&state.tokens, mfa_token)?;
+    let user = db
+        .get_user_by_id(claims.sub)
+        .await?
+        .ok_or_else(|| AppError::Auth("User no longer exists".to_string()))?;
+    // パスワードを確認した後に無効にされた場合
+    if user.is_disabled() {
+        return Err(AppError::Forbidden("This account has been disabled".to_string()));
+    }
+    Ok((user, claims))
+}
+
+// ログインの2段階目（ログイン不要）
+// 認証アプリのコードかリカバリーコードを確認し、パスワードでのログインと同じ形でトークンを返す
+// 二要素認証が必須でまだ登録していない場合は、登録中の秘密鍵のコードで有効にし、リカバリーコードも返す
+pub async fn login_mfa(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Json(request): Json<MfaLoginRequest>,
+) -> Result<Json<serde_json::Value>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let (user, claims) = mfa_user(&state, db, &request.mfa_token).await?;
+    let purpose = claims.purpose;
+
+    // コードの総当たりも、パスワードの失敗と合わせて制限する
+    let attempt = LoginAttempt::new(&audit, &user.username);
+    attempt.check_allowed(db).await?;
+
+    let totp = db.get_totp_state(user.id).await?;
+    let result = match (purpose, request.code.as_deref()) {
+        (MfaPurpose::Verify, code) => verify_second_factor(db, user.id, &totp, code, request.recovery_code.as_deref())
+            .await
+            .map(|_| None),
+        (MfaPurpose::Setup, Some(code)) => confirm_enrollment(db, user.id, &totp, code).await.map(Some),
+        (MfaPurpose::Setup, None) => Err(AppError::InvalidInput("code is required".to_string())),
+    };
+    let recovery_codes = match result {
+        Ok(recovery_codes) => recovery_codes,
+        Err(e @ AppError::Auth(_)) => {
+            attempt.failed(&state, db, "invalid_mfa_code").await?;
+            return Err(e.into());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    attempt.succeeded(db).await?;
+
+    // 同じトークンでセッションを重ねて始められないよう、使用済みにする
+    let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0).unwrap_or_else(chrono::Utc::now);
+    if !db.use_mfa_token(&claims.nonce, user.id, expires_at).await? {
+        return Err(AppError::Auth("This login has already been completed".to_string()).into());
+    }
+
+    let role = db.effective_role(user.id, user.role).await?;
+    let tokens = start_session(db, &state.tokens, &user, role, &audit, "mfa").await?;
+
+    let mut response = serde_json::json!({
+        "token": tokens.token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+        "user": user
+    });
+    if let Some(recovery_codes) = recovery_codes {
+        response["recovery_codes"] = serde_json::json!(recovery_codes);
+    }
+    Ok(Json(response))
+}
+
+// 二要素認証が必須のユーザーが、ログインの途中で登録を始める（ログイン不要）
+pub async fn login_mfa_setup(
+    State(state): State<AppState>,
+    Json(request): Json<MfaSetupRequest>,
+) -> Result<Json<TotpSetup>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let (user, claims) = mfa_user(&state, db, &request.mfa_token).await?;
+    if claims.purpose != MfaPurpose::Setup {
+        return Err(AppError::InvalidInput("Two-factor authentication is already enabled".to_string()).into());
+    }
+
+    Ok(Json(start_enrollment(db, &user).await?))
+}
+
+// 自分の二要素認証の状態
+pub async fn get_totp_status(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<Json<TotpStatus>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let totp = db.get_totp_state(claims.sub).await?;
+
+    let recovery_codes_remaining = if totp.enabled {
+        db.count_recovery_codes(claims.sub).await?
+    } else {
+        0
+    };
+    Ok(Json(TotpStatus {
+        enabled: totp.enabled,
+        recovery_codes_remaining,
+    }))
+}
+
+// 二要素認証の登録を始める。返した URI を認証アプリに登録し、コードを enable で確認する
+pub async fn setup_totp(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+) -> Result<Json<TotpSetup>, (StatusCode, Json<serde_json::Value>)> {
+    require_login(&claims)?;
+    let db = database(&state)?;
+    let user = db
+        .get_user_by_id(claims.sub)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    Ok(Json(start_enrollment(db, &user).await?))
+}
+
+// 認証アプリのコードを確認して二要素認証を有効にする
+// リカバリーコードはこのときだけ返す
+pub async fn enable_totp(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Json(request): Json<EnableTotpRequest>,
+) -> Result<Json<RecoveryCodes>, (StatusCode, Json<serde_json::Value>)> {
+    require_login(&claims)?;
+    let db = database(&state)?;
+    let totp = db.get_totp_state(claims.sub).await?;
+
+    let recovery_codes = confirm_enrollment(db, claims.sub, &totp, &request.code).await?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+// 二要素認証を解除する（コードかリカバリーコードが必要）
+pub async fn disable_totp(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Json(request): Json<SecondFactorRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    require_login(&claims)?;
+    // 必須にしている場合、管理者は自分では解除できない
+    if state.config.totp_required_for_admins && claims.role == Role::Admin.to_string() {
+        return Err(AppError::Forbidden("Two-factor authentication is required for administrators".to_string()).into());
+    }
+
+    let db = database(&state)?;
+    let totp = db.get_totp_state(claims.sub).await?;
+    verify_second_factor(db, claims.sub, &totp, request.code.as_deref(), request.recovery_code.as_deref()).await?;
+
+    db.disable_totp(claims.sub).await?;
+    Ok(StatusCode::OK)
+}
+
+// リカバリーコードを作り直す（以前のコードは使えなくなる）
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    Json(request): Json<SecondFactorRequest>,
+) -> Result<Json<RecoveryCodes>, (StatusCode, Json<serde_json::Value>)> {
+    require_login(&claims)?;
+    let db = database(&state)?;
+    let totp = db.get_totp_state(claims.sub).await?;
+    verify_second_factor(db, claims.sub, &totp, request.code.as_deref(), request.recovery_code.as_deref()).await?;
+
+    let recovery_codes = generate_recovery_codes();
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| hash_recovery_code(code)).collect();
+    db.replace_recovery_codes(claims.sub, &code_hashes).await?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+// ユーザーの二要素認証を解除する（管理者用。認証アプリをなくした場合など）
+pub async fn reset_user_totp(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(user_id): Path<i64>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let user = db
+        .get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))?;
+
+    let before = if db.get_totp_state(user_id).await?.enabled { "enabled" } else { "disabled" };
+    db.disable_totp(user_id).await?;
+    audit
+        .record(db, AuditEvent::new("user.totp_reset", "user", &user.username).before(before).after("disabled"))
+        .await;
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,227 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{AppError, AppState};
+use crate::auth;
+use crate::db::{DbManager, ThrottleScope};
+use crate::handlers::audit::{AuditContext, AuditEvent};
+use crate::handlers::database;
+use crate::models::user::{hash_password, validate_password, Role, User};
+
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    username: String,
+    password: String,
+    email: Option<String>,
+    role: Option<Role>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRoleRequest {
+    role: Role,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordRequest {
+    password: String,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteUserQuery {
+    // 削除するユーザーのドキュメントなどの作成者を付け替える先（ユーザー名）
+    reassign_to: Option<String>,
+}
+
+async fn find_user(db: &DbManager, user_id: i64) -> Result<User, AppError> {
+    db.get_user_by_id(user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("User not found".to_string()))
+}
+
+// 有効な管理者がいなくなる操作は受け付けない（誰もユーザーを管理できなくなるため）
+async fn ensure_not_last_admin(db: &DbManager, user: &User) -> Result<(), AppError> {
+    if user.role == Role::Admin && !user.is_disabled() && db.count_active_admins().await? <= 1 {
+        return Err(AppError::Conflict("Cannot remove the last active admin".to_string()));
+    }
+    Ok(())
+}
+
+// 自分自身は無効にしたり削除したりできない
+fn ensure_not_self(claims: &auth::Claims, user: &User) -> Result<(), AppError> {
+    if claims.sub == user.id {
+        return Err(AppError::InvalidInput("You cannot do this to your own account".to_string()));
+    }
+    Ok(())
+}
+
+// ユーザーを作成する（管理者用）
+pub async fn create_user(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<(StatusCode, Json<User>), (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+
+    let username = request.username.trim();
+    if username.is_empty() {
+        return Err(AppError::InvalidInput("Username cannot be empty".to_string()).into());
+    }
+    let email = request.email.as_deref().map(str::trim).filter(|email| !email.is_empty());
+    validate_password(&request.password)?;
+
+    if db.get_user_by_username(username).await?.is_some() {
+        return Err(AppError::Conflict(format!("User {} already exists", username)).into());
+    }
+    if let Some(email) = email {
+        if db.get_user_by_email(email).await?.is_some() {
+            return Err(AppError::Conflict("Email is already in use".to_string()).into());
+        }
+    }
+
+    let role = request.role.unwrap_or(Role::Viewer);
+    let user_id = db.create_user(username, &request.password, email, role).await?;
+    audit
+        .record(db, AuditEvent::new("user.create", "user", username).after(format!("role {}", role)))
+        .await;
+    Ok((StatusCode::CREATED, Json(find_user(db, user_id).await?)))
+}
+
+// ユーザーのロールを変更する（管理者用）
+// 新しいロールは発行済みのアクセストークンにも次のリクエストから反映される
+pub async fn update_user_role(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(user_id): Path<i64>,
+    Json(request): Json<UpdateRoleRequest>,
+) -> Result<Json<User>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let user = find_user(db, user_id).await?;
+
+    if request.role != Role::Admin {
+        ensure_not_last_admin(db, &user).await?;
+    }
+    db.update_user_role(&user.username, &request.role.to_string()).await?;
+    if user.role != request.role {
+        audit
+            .record(
+                db,
+                AuditEvent::new("user.role_change", "user", &user.username)
+                    .before(user.role.to_string())
+                    .after(request.role.to_string()),
+            )
+            .await;
+    }
+    Ok(Json(find_user(db, user_id).await?))
+}
+
+// ユーザーを無効にし、すべてのセッションを取り消す（管理者用）
+// 発行済みのアクセストークンは期限が切れるまで使える
+pub async fn disable_user(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(user_id): Path<i64>,
+) -> Result<Json<User>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let user = find_user(db, user_id).await?;
+
+    ensure_not_self(&claims, &user)?;
+    ensure_not_last_admin(db, &user).await?;
+
+    if !user.is_disabled() {
+        db.set_user_disabled(user.id, true).await?;
+        audit
+            .record(db, AuditEvent::new("user.disable", "user", &user.username).before("enabled").after("disabled"))
+            .await;
+    }
+    db.revoke_user_sessions(user.id).await?;
+    Ok(Json(find_user(db, user_id).await?))
+}
+
+// 無効にしたユーザーを元に戻す（管理者用）
+pub async fn enable_user(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(user_id): Path<i64>,
+) -> Result<Json<User>, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let user = find_user(db, user_id).await?;
+
+    if user.is_disabled() {
+        db.set_user_disabled(user.id, false).await?;
+        audit
+            .record(db, AuditEvent::new("user.enable", "user", &user.username).before("disabled").after("enabled"))
+            .await;
+    }
+    Ok(Json(find_user(db, user_id).await?))
+}
+
+// ユーザーを削除する（管理者用）
+// ドキュメントなどは削除せず、`reassign_to` のユーザーに付け替える（指定しなければ作成者を空にする）
+pub async fn delete_user(
+    State(state): State<AppState>,
+    claims: auth::Claims,
+    audit: AuditContext,
+    Path(user_id): Path<i64>,
+    Query(query): Query<DeleteUserQuery>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let user = find_user(db, user_id).await?;
+
+    ensure_not_self(&claims, &user)?;
+    ensure_not_last_admin(db, &user).await?;
+
+    let reassign_to = match query.reassign_to.as_deref() {
+        Some(username) => {
+            let target = db
+                .get_user_by_username(username)
+                .await?
+                .ok_or_else(|| AppError::InvalidInput(format!("User {} not found", username)))?;
+            if target.id == user.id {
+                return Err(AppError::InvalidInput("Cannot reassign to the user being deleted".to_string()).into());
+            }
+            Some(target.id)
+        }
+        None => None,
+    };
+
+    db.delete_user(&user.username, reassign_to).await?;
+    let after = match &query.reassign_to {
+        Some(username) => format!("deleted, content reassigned to {}", username),
+        None => "deleted".to_string(),
+    };
+    audit
+        .record(db, AuditEvent::new("user.delete", "user", &user.username).before(format!("role {}", user.role)).after(after))
+        .await;
+    Ok(StatusCode::OK)
+}
+
+// ユーザーのパスワードを設定し直す（管理者用）
+// すべてのセッションを取り消し、ユーザー名のロックも解除する
+pub async fn reset_user_password(
+    State(state): State<AppState>,
+    audit: AuditContext,
+    Path(user_id): Path<i64>,
+    Json(request): Json<ResetPasswordRequest>,
+) -> Result<StatusCode, (StatusCode, Json<serde_json::Value>)> {
+    let db = database(&state)?;
+    let user = find_user(db, user_id).await?;
+
+    // 外部の認証基盤（LDAP・OIDC）のユーザーにローカルのパスワードを持たせると、そちらより優先されてしまう
+    if user.password_hash.is_empty() {
+        return Err(AppError::InvalidInput("Password is managed by an external provider".to_string()).into());
+    }
+    validate_password(&request.password)?;
+
+    db.update_password(user.id, &hash_password(&request.password)?).await?;
+    db.revoke_user_sessions(user.id).await?;
+    db.reset_login_throttle(ThrottleScope::Username, &user.username).await?;
+    audit
+        .record(db, AuditEvent::new("user.password_reset", "user", &user.username).after("sessions revoked"))
+        .await;
+    Ok(StatusCode::OK)
+}
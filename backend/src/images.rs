@@ -0,0 +1,449 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+
+// サムネイルの幅（ピクセル）
+pub const THUMBNAIL_WIDTH: u32 = 320;
+// `?w=` で指定できる幅の範囲
+pub const MIN_RESIZE_WIDTH: u32 = 16;
+pub const MAX_RESIZE_WIDTH: u32 = 2048;
+
+const JPEG_QUALITY: u8 = 85;
+
+// 縮小・メタデータ除去の対象になる形式かどうか
+pub fn is_resizable(content_type: &str) -> bool {
+    image_format(content_type).is_some()
+}
+
+fn image_format(content_type: &str) -> Option<ImageFormat> {
+    match content_type {
+        "image/png" => Some(ImageFormat::Png),
+        "image/jpeg" => Some(ImageFormat::Jpeg),
+        "image/webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+fn image_error(e: image::ImageError) -> AppError {
+    AppError::InvalidInput(format!("Invalid image: {}", e))
+}
+
+// 巨大な画像でメモリを使い切らないよう、デコード時の上限を設ける
+fn open_decoder(data: &[u8], format: ImageFormat) -> Result<impl ImageDecoder + '_, AppError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(16_384);
+    limits.max_image_height = Some(16_384);
+    limits.max_alloc = Some(256 * 1024 * 1024);
+
+    let mut reader = ImageReader::with_format(Cursor::new(data), format);
+    reader.limits(limits);
+    reader.into_decoder().map_err(image_error)
+}
+
+// 画像をデコードし、EXIFの向き情報を画素に反映する
+fn decode(data: &[u8], format: ImageFormat) -> Result<DynamicImage, AppError> {
+    let mut decoder = open_decoder(data, format)?;
+    let orientation = decoder.orientation().map_err(image_error)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(image_error)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+// 元の形式のまま再エンコードする（メタデータは書き出されない）
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, AppError> {
+    let mut buf = Cursor::new(Vec::new());
+    match format {
+        ImageFormat::Jpeg => {
+            // JPEGはアルファチャンネルを持てない
+            let rgb = DynamicImage::ImageRgb8(image.to_rgb8());
+            rgb.write_with_encoder(JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY))
+                .map_err(image_error)?;
+        }
+        _ => image.write_to(&mut buf, format).map_err(image_error)?,
+    }
+    Ok(buf.into_inner())
+}
+
+// アップロードされた画像から EXIF などのメタデータを取り除く
+// 向き情報がある場合は画素を回転させたうえで再エンコードし、それ以外は画素を変えずにメタデータ部分だけを削除する
+pub fn strip_metadata(content_type: &str, data: &[u8]) -> Result<Vec<u8>, AppError> {
+    let format = match image_format(content_type) {
+        Some(format) => format,
+        None => return Ok(data.to_vec()),
+    };
+
+    let orientation = open_decoder(data, format)?.orientation().map_err(image_error)?;
+    if orientation != image::metadata::Orientation::NoTransforms {
+        return encode(&decode(data, format)?, format);
+    }
+
+    let stripped = match format {
+        ImageFormat::Jpeg => strip_jpeg(data),
+        ImageFormat::Png => strip_png(data),
+        ImageFormat::WebP => strip_webp(data),
+        _ => None,
+    };
+    // 構造を解釈できない場合は再エンコードで確実に取り除く
+    match stripped {
+        Some(stripped) => Ok(stripped),
+        None => encode(&decode(data, format)?, format),
+    }
+}
+
+// JPEGからAPP1（EXIF / XMP）とAPP13（IPTC）セグメントを取り除く
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return None;
+    }
+
+    let mut out = vec![0xff, 0xd8];
+    let mut pos = 2;
+    loop {
+        if *data.get(pos)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(pos + 1)?;
+        match marker {
+            // 詰め物のバイト
+            0xff => {
+                pos += 1;
+                continue;
+            }
+            // スキャン開始以降は画像データなのでそのまま残す
+            0xda => {
+                out.extend_from_slice(&data[pos..]);
+                return Some(out);
+            }
+            // 長さを持たないマーカー
+            0x01 | 0xd0..=0xd7 => {
+                out.extend_from_slice(&data[pos..pos + 2]);
+                pos += 2;
+            }
+            _ => {
+                let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+                let end = pos + 2 + len;
+                if len < 2 || end > data.len() {
+                    return None;
+                }
+                if marker != 0xe1 && marker != 0xed {
+                    out.extend_from_slice(&data[pos..end]);
+                }
+                pos = end;
+            }
+        }
+    }
+}
+
+// PNGから eXIf とテキストチャンクを取り除く
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+    if !data.starts_with(SIGNATURE) {
+        return None;
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    let mut pos = SIGNATURE.len();
+    while pos < data.len() {
+        let len = u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let chunk_type = data.get(pos + 4..pos + 8)?;
+        let end = pos.checked_add(12)?.checked_add(len)?;
+        if end > data.len() {
+            return None;
+        }
+        if !matches!(chunk_type, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt") {
+            out.extend_from_slice(&data[pos..end]);
+        }
+        pos = end;
+    }
+    Some(out)
+}
+
+// WebPから EXIF / XMP チャンクを取り除き、VP8X のフラグも合わせて落とす
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WEBP" {
+        return None;
+    }
+
+    let mut body = b"WEBP".to_vec();
+    let mut pos = 12;
+    while pos < data.len() {
+        let fourcc = data.get(pos..pos + 4)?;
+        let len = u32::from_le_bytes(data.get(pos + 4..pos + 8)?.try_into().ok()?) as usize;
+        if pos.checked_add(8)?.checked_add(len)? > data.len() {
+            return None;
+        }
+        // チャンクは偶数バイト境界に揃えられる
+        let end = (pos + 8 + len + (len & 1)).min(data.len());
+        match fourcc {
+            b"EXIF" | b"XMP " => {}
+            b"VP8X" => {
+                let mut chunk = data[pos..end].to_vec();
+                // フラグ: 0x08 = EXIF, 0x04 = XMP
+                *chunk.get_mut(8)? &= !(0x08 | 0x04);
+                body.extend_from_slice(&chunk);
+            }
+            _ => body.extend_from_slice(&data[pos..end]),
+        }
+        pos = end;
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&u32::try_from(body.len()).ok()?.to_le_bytes());
+    out.extend_from_slice(&body);
+    Some(out)
+}
+
+// 画像を指定した幅に縮小する（縦横比は維持し、拡大はしない）
+pub fn resize(content_type: &str, data: &[u8], width: u32) -> Result<Vec<u8>, AppError> {
+    let format = image_format(content_type)
+        .ok_or_else(|| AppError::InvalidInput(format!("{} attachments cannot be resized", content_type)))?;
+
+    let image = decode(data, format)?;
+    if image.width() <= width {
+        return Ok(data.to_vec());
+    }
+
+    let height = ((image.height() as u64 * width as u64) / image.width() as u64).max(1) as u32;
+    encode(&image.thumbnail(width, height), format)
+}
+
+// 縮小画像のキャッシュファイルのパス（`<cache_dir>/images/ab/<hash>-w320`）
+pub fn cache_path(cache_dir: &Path, hash: &str, width: u32) -> PathBuf {
+    cache_dir
+        .join("images")
+        .join(&hash[..2.min(hash.len())])
+        .join(format!("{}-w{}", hash, width))
+}
+
+pub fn read_cache(path: &Path) -> Option<Vec<u8>> {
+    fs::read(path).ok()
+}
+
+// 書き込み途中のファイルを読まれないよう、一時ファイルに書いてから置き換える
+pub fn write_cache(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("tmp{:016x}", rand::random::<u64>()));
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::webp::WebPEncoder;
+    use image::{Rgb, RgbImage};
+
+    // 画素ごとに色の違う小さな画像（向きの確認に使う）
+    fn sample() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(4, 2, |x, y| Rgb([x as u8 * 60, y as u8 * 120, 30])))
+    }
+
+    // 向き（Orientation タグ）だけを持つビッグエンディアンの TIFF
+    fn exif(orientation: u16) -> Vec<u8> {
+        let mut tiff = b"MM\x00\x2a\x00\x00\x00\x08\x00\x01\x01\x12\x00\x03\x00\x00\x00\x01".to_vec();
+        tiff.extend_from_slice(&orientation.to_be_bytes());
+        tiff.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        tiff
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in bytes {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    fn png_chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&crc32(&chunk[4..]).to_be_bytes());
+        chunk
+    }
+
+    // IHDR の直後にメタデータのチャンクを差し込んだPNG
+    fn png_with(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let png = encode(&sample(), ImageFormat::Png).unwrap();
+        let ihdr_end = 8 + 12 + 13;
+        let mut out = png[..ihdr_end].to_vec();
+        for chunk in chunks {
+            out.extend_from_slice(chunk);
+        }
+        out.extend_from_slice(&png[ihdr_end..]);
+        out
+    }
+
+    // SOI の直後に APP1 / APP13 を差し込んだJPEG
+    fn jpeg_with(orientation: Option<u16>) -> Vec<u8> {
+        let jpeg = encode(&sample(), ImageFormat::Jpeg).unwrap();
+        let mut app1 = b"Exif\x00\x00".to_vec();
+        app1.extend_from_slice(&exif(orientation.unwrap_or(1)));
+        let mut out = jpeg[..2].to_vec();
+        for (marker, payload) in [(0xe1u8, app1.as_slice()), (0xed, b"Photoshop 3.0\x00".as_slice())] {
+            out.extend_from_slice(&[0xff, marker]);
+            out.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+            out.extend_from_slice(payload);
+        }
+        out.extend_from_slice(&jpeg[2..]);
+        out
+    }
+
+    fn riff_chunk(fourcc: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = fourcc.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    // ロスレスの VP8L を VP8X の拡張形式に包み、EXIF と XMP を付けたWebP
+    fn webp_with_metadata() -> Vec<u8> {
+        let image = sample();
+        let mut lossless = Vec::new();
+        image.write_with_encoder(WebPEncoder::new_lossless(&mut lossless)).unwrap();
+        let vp8l = &lossless[12..];
+
+        let mut vp8x = vec![0x08 | 0x04, 0, 0, 0];
+        vp8x.extend_from_slice(&(image.width() - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(image.height() - 1).to_le_bytes()[..3]);
+
+        let mut body = b"WEBP".to_vec();
+        body.extend_from_slice(&riff_chunk(b"VP8X", &vp8x));
+        body.extend_from_slice(vp8l);
+        body.extend_from_slice(&riff_chunk(b"EXIF", &exif(1)));
+        body.extend_from_slice(&riff_chunk(b"XMP ", b"<x:xmpmeta/>"));
+        let mut out = b"RIFF".to_vec();
+        out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|window| window == needle)
+    }
+
+    fn pixels(data: &[u8], format: ImageFormat) -> DynamicImage {
+        image::load_from_memory_with_format(data, format).unwrap()
+    }
+
+    #[test]
+    fn jpeg_app1_and_app13_are_removed_without_touching_pixels() {
+        let data = jpeg_with(None);
+        assert!(contains(&data, b"Exif\x00\x00"));
+
+        let stripped = strip_metadata("image/jpeg", &data).unwrap();
+        assert!(!contains(&stripped, b"Exif\x00\x00"));
+        assert!(!contains(&stripped, b"Photoshop 3.0"));
+        assert_eq!(pixels(&stripped, ImageFormat::Jpeg), pixels(&data, ImageFormat::Jpeg));
+    }
+
+    #[test]
+    fn png_text_and_exif_chunks_are_removed_without_touching_pixels() {
+        let data = png_with(&[
+            png_chunk(b"tEXt", b"Comment\x00secret"),
+            png_chunk(b"iTXt", b"Author\x00\x00\x00\x00\x00someone"),
+            png_chunk(b"eXIf", &exif(1)),
+        ]);
+
+        let stripped = strip_metadata("image/png", &data).unwrap();
+        for chunk_type in [b"tEXt", b"iTXt", b"eXIf"] {
+            assert!(contains(&data, chunk_type));
+            assert!(!contains(&stripped, chunk_type));
+        }
+        assert_eq!(pixels(&stripped, ImageFormat::Png), sample());
+    }
+
+    #[test]
+    fn webp_exif_and_xmp_chunks_are_removed_without_touching_pixels() {
+        let data = webp_with_metadata();
+        let stripped = strip_metadata("image/webp", &data).unwrap();
+
+        assert!(!contains(&stripped, b"EXIF"));
+        assert!(!contains(&stripped, b"XMP "));
+        // VP8X のフラグからも EXIF / XMP が落ちている
+        let flags = stripped[12 + 8];
+        assert_eq!(flags & (0x08 | 0x04), 0);
+        assert_eq!(u32::from_le_bytes(stripped[4..8].try_into().unwrap()) as usize, stripped.len() - 8);
+        assert_eq!(pixels(&stripped, ImageFormat::WebP).to_rgb8(), sample().to_rgb8());
+    }
+
+    #[test]
+    fn orientation_is_applied_before_the_metadata_is_dropped() {
+        // 6 = 時計回りに90度回転
+        let data = png_with(&[png_chunk(b"eXIf", &exif(6))]);
+        let stripped = strip_metadata("image/png", &data).unwrap();
+
+        assert!(!contains(&stripped, b"eXIf"));
+        let mut expected = sample();
+        expected.apply_orientation(image::metadata::Orientation::Rotate90);
+        assert_eq!((expected.width(), expected.height()), (2, 4));
+        assert_eq!(pixels(&stripped, ImageFormat::Png).to_rgb8(), expected.to_rgb8());
+
+        let stripped = strip_metadata("image/jpeg", &jpeg_with(Some(6))).unwrap();
+        assert!(!contains(&stripped, b"Exif\x00\x00"));
+        let image = pixels(&stripped, ImageFormat::Jpeg);
+        assert_eq!((image.width(), image.height()), (2, 4));
+    }
+
+    #[test]
+    fn malformed_images_are_rejected_without_panicking() {
+        let samples = [
+            ("image/jpeg", jpeg_with(None)),
+            ("image/png", png_with(&[png_chunk(b"tEXt", b"a\x00b")])),
+            ("image/webp", webp_with_metadata()),
+        ];
+        for (content_type, data) in &samples {
+            assert!(strip_metadata(content_type, b"not an image").is_err());
+            assert!(resize(content_type, b"not an image", 32).is_err());
+            // 途中で切れたデータは、どこで切れていてもパニックしない
+            for len in 0..data.len() {
+                let truncated = &data[..len];
+                let _ = strip_metadata(content_type, truncated);
+                let _ = strip_jpeg(truncated);
+                let _ = strip_png(truncated);
+                let _ = strip_webp(truncated);
+            }
+        }
+
+        // 構造が壊れたチャンクは解釈せずに None を返す
+        let png = png_with(&[]);
+        assert!(strip_png(&png[..png.len() - 1]).is_none());
+        let mut bad_length = png.clone();
+        bad_length[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(strip_png(&bad_length).is_none());
+        assert!(strip_jpeg(&[0xff, 0xd8, 0x00]).is_none());
+        assert!(strip_jpeg(&[0xff, 0xd8, 0xff, 0xe1, 0x00, 0x01]).is_none());
+        let webp = webp_with_metadata();
+        assert!(strip_webp(&webp[..webp.len() - 3]).is_none());
+        assert!(strip_metadata("image/png", &png[..png.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn resize_keeps_the_aspect_ratio_and_never_upscales() {
+        let image = DynamicImage::ImageRgb8(RgbImage::new(64, 32));
+        let data = encode(&image, ImageFormat::Png).unwrap();
+
+        let resized = pixels(&resize("image/png", &data, 16).unwrap(), ImageFormat::Png);
+        assert_eq!((resized.width(), resized.height()), (16, 8));
+        assert_eq!(resize("image/png", &data, 128).unwrap(), data);
+        assert!(resize("application/pdf", &data, 16).is_err());
+    }
+
+    #[test]
+    fn cache_paths_are_sharded_by_hash() {
+        let path = cache_path(Path::new("/cache"), "abcdef", 320);
+        assert_eq!(path, Path::new("/cache/images/ab/abcdef-w320"));
+    }
+}
@@ -10,12 +10,17 @@ pub mod db;
 pub mod error;
 pub mod git_ops;
 pub mod handlers;
+pub mod images;
+pub mod mail;
 pub mod models;
 pub mod routes;
 pub mod config;
+pub mod storage;
+
+use std::sync::Arc;
 
 use db::DbManager;
-use git_ops::GitRepository;
+use storage::DocumentStore;
 
 // エラー型のエクスポート
 pub use error::{AppError, AppResult};
@@ -24,7 +29,12 @@ pub use error::{AppError, AppResult};
 #[derive(Clone, Debug)]
 pub struct AppState {
     pub db_manager: Option<DbManager>,
-    pub git_repo: Option<GitRepository>,
+    pub document_store: Arc<dyn DocumentStore>,
     pub markdown_dir: PathBuf,
     pub config: config::Config,
+    pub tokens: Arc<auth::TokenService>,
+    // シングルサインオンを設定していなければ None
+    pub oidc: Option<Arc<auth::oidc::OidcClient>>,
+    // パスワードの再設定などのメールの送信先（MAIL_TRANSPORT）
+    pub mailer: Arc<dyn mail::Mailer>,
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use rand::RngCore;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+use crate::mail::{MailMessage, Mailer};
+
+// メールを送らずにログに出す（開発用）
+#[derive(Debug)]
+pub struct LogMailer {
+    from: String,
+}
+
+impl LogMailer {
+    pub fn new(from: &str) -> Self {
+        Self { from: from.to_string() }
+    }
+}
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, message: &MailMessage) -> Result<(), AppError> {
+        tracing::info!(
+            "Mail from {} to {}: {}\n{}",
+            self.from,
+            message.to,
+            message.subject,
+            message.body
+        );
+        Ok(())
+    }
+}
+
+// メールを送らずに、1通ずつ .eml ファイルとしてディレクトリに保存する（開発・テスト用）
+#[derive(Debug)]
+pub struct FileMailer {
+    dir: PathBuf,
+    from: String,
+}
+
+impl FileMailer {
+    pub fn new(dir: &Path, from: &str) -> Result<Self, AppError> {
+        std::fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            from: from.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &MailMessage) -> Result<(), AppError> {
+        let now = chrono::Utc::now();
+        // 同じ時刻に送っても上書きしないよう、ファイル名に乱数を付ける
+        let mut suffix = [0u8; 4];
+        rand::rngs::OsRng.fill_bytes(&mut suffix);
+        let suffix: String = suffix.iter().map(|b| format!("{:02x}", b)).collect();
+        let path = self.dir.join(format!("{}-{}.eml", now.format("%Y%m%d%H%M%S"), suffix));
+
+        let content = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            self.from,
+            message.to,
+            message.subject,
+            now.to_rfc2822(),
+            message.body.replace('\n', "\r\n")
+        );
+        tokio::fs::write(&path, content).await?;
+        tracing::info!("Mail to {} saved to {}", message.to, path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use async_trait::async_trait;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::config::{MailConfig, MailTransport};
+use crate::error::AppError;
+
+pub mod local;
+pub mod smtp;
+
+// 送信するメール（本文はプレーンテキスト）
+#[derive(Debug, Clone)]
+pub struct MailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// メールの送信先を抽象化するトレイト
+// 差出人などの設定は実装ごとに持つ
+#[async_trait]
+pub trait Mailer: Send + Sync + fmt::Debug {
+    async fn send(&self, message: &MailMessage) -> Result<(), AppError>;
+}
+
+// MAIL_TRANSPORT に応じた送信方法を作る
+pub fn from_config(config: &MailConfig) -> Result<Arc<dyn Mailer>, AppError> {
+    Ok(match &config.transport {
+        MailTransport::Log => Arc::new(local::LogMailer::new(&config.from)),
+        MailTransport::File(dir) => Arc::new(local::FileMailer::new(dir, &config.from)?),
+        MailTransport::Smtp(smtp) => Arc::new(smtp::SmtpMailer::from_config(smtp, &config.from)?),
+    })
+}
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+use crate::config::{SmtpConfig, SmtpTls};
+use crate::error::AppError;
+use crate::mail::{MailMessage, Mailer};
+
+// SMTPサーバーの応答を待つ時間
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn config_error(message: String) -> AppError {
+    AppError::Internal(format!("Invalid SMTP configuration: {}", message))
+}
+
+fn smtp_error(error: impl std::fmt::Display) -> AppError {
+    AppError::Internal(format!("Failed to send mail: {}", error))
+}
+
+// SMTPサーバーを通してメールを送る
+#[derive(Debug)]
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn from_config(config: &SmtpConfig, from: &str) -> Result<Self, AppError> {
+        if config.host.is_empty() {
+            return Err(config_error("SMTP_HOST is required".to_string()));
+        }
+        if config.username.is_some() != config.password.is_some() {
+            return Err(config_error("SMTP_USERNAME and SMTP_PASSWORD must be set together".to_string()));
+        }
+        let from: Mailbox = from
+            .parse()
+            .map_err(|e| config_error(format!("MAIL_FROM: {}", e)))?;
+
+        let builder = match config.tls {
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                .map_err(|e| config_error(e.to_string()))?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+                .map_err(|e| config_error(e.to_string()))?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port).timeout(Some(SMTP_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &MailMessage) -> Result<(), AppError> {
+        let to: Mailbox = message
+            .to
+            .parse()
+            .map_err(|e| AppError::InvalidInput(format!("Invalid email address {}: {}", message.to, e)))?;
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(message.body.clone())
+            .map_err(smtp_error)?;
+
+        self.transport.send(email).await.map_err(smtp_error)?;
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
 use axum::{
     routing::get,
     Router,
@@ -7,7 +8,7 @@ use axum::{
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::services::ServeDir;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use md_wiki_backend::{AppState, routes, config, db::DbManager};
+use md_wiki_backend::{AppState, auth, mail, routes, config, db::DbManager, storage::{DocumentStore, GitDocumentStore}};
 
 async fn health_check() -> &'static str {
     "OK"
@@ -15,37 +16,101 @@ async fn health_check() -> &'static str {
 
 #[tokio::main]
 async fn main() {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::EnvFilter::new(
-            std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
-        ))
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // `md-wiki-backend setup-token` は初期設定のトークンを発行して終了する（起動中のサーバーにも使える）
+    let setup_token_command = std::env::args().nth(1).as_deref() == Some("setup-token");
+
+    // Initialize logging（コマンドの出力にログが混ざらないよう、サーバーとして起動する場合だけ）
+    if !setup_token_command {
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::EnvFilter::new(
+                std::env::var("RUST_LOG").unwrap_or_else(|_| "info".into()),
+            ))
+            .with(tracing_subscriber::fmt::layer())
+            .init();
+    }
 
     tracing::info!("Environment loaded");
 
     // アプリケーション設定の読み込み
     let config = config::Config::from_env();
     
-    // ストレージディレクトリの作成
+    // ストレージディレクトリの作成とGitリポジトリの初期化
     let markdown_dir = PathBuf::from(&config.markdown_dir);
-    std::fs::create_dir_all(&markdown_dir).unwrap_or_else(|e| {
-        tracing::warn!("Failed to create markdown directory: {}", e);
-    });
+    let document_store = GitDocumentStore::new(&markdown_dir)
+        .expect("Failed to initialize document store");
+
+    // パスワードでのログインに使う外部の認証基盤（LDAP）
+    let mut auth_providers: Vec<Arc<dyn auth::provider::AuthProvider>> = Vec::new();
+    if let Some(ldap) = &config.ldap {
+        let provider = auth::ldap::LdapProvider::from_config(ldap).expect("Failed to initialize LDAP");
+        auth_providers.push(Arc::new(provider));
+    }
 
     // アプリケーション状態の初期化
-    // アプリケーション状態の初期化
-    let db_manager = DbManager::new(std::path::Path::new(&config.database_url))
+    let db_manager = DbManager::new(&config.database_url, config.database_backend)
         .await
-        .expect("Failed to initialize database manager");
+        .expect("Failed to initialize database manager")
+        .with_auth_providers(config.password_login, auth_providers);
     db_manager.init().await.expect("Failed to initialize database schema");
 
+    if setup_token_command {
+        match auth::setup::issue_setup_token(&db_manager).await.expect("Failed to issue setup token") {
+            Some(token) => println!("{}", token),
+            None => {
+                eprintln!("Setup has already been completed");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    // APIを通さずに追加されたドキュメントも一覧に出るよう、索引にないものを加える
+    let stored = document_store.list().await.expect("Failed to list documents");
+    let added = db_manager.sync_documents(&stored).await.expect("Failed to index documents");
+    if added > 0 {
+        tracing::info!("Added {} documents to the document index", added);
+    }
+
+    // ユーザーがいなければ、最初の管理者を作成するためのトークンを表示する
+    // ログにトークンを何度も残さないよう、期限内のトークンがあれば再起動しても発行しない
+    let setup_pending = db_manager.count_users().await.expect("Failed to count users") == 0;
+    if config.password_login && setup_pending {
+        if db_manager.has_setup_token().await.expect("Failed to check setup tokens") {
+            tracing::warn!(
+                "No users exist yet. Use the setup token logged when it was issued, or run `md-wiki-backend setup-token` to issue a new one"
+            );
+        } else if let Some(token) = auth::setup::issue_setup_token(&db_manager).await.expect("Failed to issue setup token") {
+            tracing::warn!(
+                "No users exist yet. Create the first admin with POST /api/auth/setup using this setup token (valid for {} hours): {}",
+                auth::setup::SETUP_TOKEN_HOURS,
+                token
+            );
+        }
+    }
+
+    // トークンの署名鍵の読み込み（本番環境で既定の秘密鍵のままなら起動しない）
+    let tokens = auth::TokenService::from_config(&config)
+        .expect("Failed to initialize token signing keys");
+
+    // シングルサインオン（OpenID Connect）の設定
+    let oidc = config.oidc.as_ref().map(|oidc| {
+        auth::oidc::OidcClient::from_config(oidc).expect("Failed to initialize OpenID Connect")
+    });
+    if !config.password_login && oidc.is_none() && config.ldap.is_none() {
+        tracing::warn!("PASSWORD_LOGIN is disabled but neither OpenID Connect nor LDAP is configured; nobody can log in");
+    }
+
+    // メールの送信方法（SMTP、またはファイルやログへの出力）
+    let mailer = mail::from_config(&config.mail).expect("Failed to initialize mailer");
+
     let state = AppState {
         db_manager: Some(db_manager),
-        git_repo: None,   // 必要に応じて初期化
+        document_store: Arc::new(document_store),
         markdown_dir,
         config: config.clone(),
+        tokens: Arc::new(tokens),
+        oidc: oidc.map(Arc::new),
+        mailer,
     };
 
     // CORS設定
@@ -73,5 +138,6 @@ async fn main() {
     
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
     tracing::info!("Server started, listening on {}", addr);
-    axum::serve(listener, app).await.unwrap();
+    // ログインの制限にクライアントのIPアドレスを使うため、接続元のアドレスを渡す
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
 }
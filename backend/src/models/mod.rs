@@ -1,6 +1,8 @@
 pub use self::user::*;
+pub mod acl;
 pub mod user;
 pub mod document;
 
+pub use acl::{Permission, PrincipalType};
 pub use document::Document;
 pub use user::{User, Role}; 
\ No newline at end of file
@@ -17,6 +17,9 @@ pub struct User {
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub role: Role,
+    pub email: Option<String>,
+    // 無効にした日時（無効なユーザーはログインできない）
+    pub disabled_at: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -29,6 +32,8 @@ impl User {
             username,
             password_hash,
             role,
+            email: None,
+            disabled_at: None,
             created_at: "".to_string(),
             updated_at: "".to_string(),
         })
@@ -37,6 +42,10 @@ impl User {
     pub fn verify_password(&self, password: &str) -> bool {
         verify_password(&self.password_hash, password)
     }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,6 +74,19 @@ pub struct ChangePasswordRequest {
     pub new_password: String,
 }
 
+// パスワードの最小の長さ（文字数）
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+
+pub fn validate_password(password: &str) -> crate::AppResult<()> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(crate::AppError::InvalidInput(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    Ok(())
+}
+
 pub fn hash_password(password: &str) -> crate::AppResult<String> {
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
@@ -86,21 +108,28 @@ pub fn verify_password(hash: &str, password: &str) -> bool {
         .is_ok()
 }
 
+// ロールは 閲覧者 < 編集者 < 管理者 の順に強く、上位のロールは下位のロールの操作をすべて行える
+// 比較（`>=`）はこの宣言順で決まるため、並びを変えないこと
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
-    Admin,
-    Editor,
     Viewer,
+    Editor,
+    Admin,
 }
 
 impl Role {
+    // 指定したロール以上の権限を持つか
+    pub fn includes(&self, required: Role) -> bool {
+        *self >= required
+    }
+
     pub fn can_edit(&self) -> bool {
-        matches!(self, Role::Admin | Role::Editor)
+        self.includes(Role::Editor)
     }
 
     pub fn can_manage_users(&self) -> bool {
-        matches!(self, Role::Admin)
+        self.includes(Role::Admin)
     }
 }
 
@@ -127,6 +156,49 @@ impl FromStr for Role {
     }
 }
 
+// 個人用APIトークンのスコープ（read < write < admin）
+// トークンで操作できるロールの上限になる
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl TokenScope {
+    pub fn max_role(self) -> Role {
+        match self {
+            TokenScope::Read => Role::Viewer,
+            TokenScope::Write => Role::Editor,
+            TokenScope::Admin => Role::Admin,
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenScope::Read => write!(f, "read"),
+            TokenScope::Write => write!(f, "write"),
+            TokenScope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for TokenScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(TokenScope::Read),
+            "write" => Ok(TokenScope::Write),
+            "admin" => Ok(TokenScope::Admin),
+            _ => Err(format!("Invalid token scope: {}", s)),
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RegistrationData {
     pub username: String,
@@ -138,4 +210,33 @@ pub struct RegistrationData {
 pub struct PasswordChangeRequest {
     pub current_password: String,
     pub new_password: String,
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_include_lower_roles() {
+        assert!(Role::Admin.includes(Role::Admin));
+        assert!(Role::Admin.includes(Role::Editor));
+        assert!(Role::Admin.includes(Role::Viewer));
+        assert!(Role::Editor.includes(Role::Editor));
+        assert!(Role::Editor.includes(Role::Viewer));
+        assert!(Role::Viewer.includes(Role::Viewer));
+    }
+
+    #[test]
+    fn roles_do_not_include_higher_roles() {
+        assert!(!Role::Editor.includes(Role::Admin));
+        assert!(!Role::Viewer.includes(Role::Admin));
+        assert!(!Role::Viewer.includes(Role::Editor));
+    }
+
+    #[test]
+    fn role_helpers_follow_the_order() {
+        assert!(Role::Editor.can_edit());
+        assert!(!Role::Viewer.can_edit());
+        assert!(Role::Admin.can_manage_users());
+        assert!(!Role::Editor.can_manage_users());
+    }
+}
@@ -1,59 +1,245 @@
 use axum::{
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    routing::{get, post, put, delete},
     Router,
     middleware,
 };
 
 use crate::handlers::{
-    auth::{login, register_user, get_current_user},
+    acl::{get_document_acl, set_document_acl, get_folder_acl, set_folder_acl},
+    api_token::{list_api_tokens, create_api_token, revoke_api_token},
+    attachment::{upload_attachment, get_attachment, get_attachment_thumbnail, list_document_attachments},
+    auth::{login, register_user, get_current_user, change_password, get_all_users, get_user},
     document::{
         get_document,
         save_document,
+        create_document,
         list_documents,
         search_documents,
         get_document_history,
         get_document_version,
         delete_document,
+        revert_document,
+        rename_document,
         list_recent_documents, // この行を追加
     },
+    audit::{list_audit_log, export_audit_log},
+    invitation::{create_invitation, list_invitations, revoke_invitation, get_invitation, accept_invitation},
+    lockout::{list_login_failures, list_login_lockouts, unlock_login},
+    password_reset::{request_password_reset, confirm_password_reset},
+    group::{
+        list_groups,
+        create_group,
+        get_group,
+        update_group,
+        delete_group,
+        add_group_member,
+        remove_group_member,
+    },
     metadata::{
         get_document_metadata,
         update_document_metadata,
         get_all_tags,
         search_documents_by_tag,
+        set_document_tags,
+        get_document_visibility,
+        set_document_visibility,
+        update_tag,
+        rename_tag,
+        merge_tag,
+        delete_tag,
+        delete_unused_tags,
+        add_tag_synonym,
+        remove_tag_synonym,
+    },
+    setup::{get_setup_status, complete_setup},
+    session::{
+        refresh_token,
+        logout,
+        list_sessions,
+        revoke_session,
+        revoke_all_sessions,
+        revoke_user_sessions,
+    },
+    oidc::{oidc_login, oidc_callback, auth_methods},
+    totp::{
+        login_mfa,
+        login_mfa_setup,
+        get_totp_status,
+        setup_totp,
+        enable_totp,
+        disable_totp,
+        regenerate_recovery_codes,
+        reset_user_totp,
+    },
+    user::{
+        create_user,
+        update_user_role,
+        disable_user,
+        enable_user,
+        delete_user,
+        reset_user_password,
+    },
+    share::{
+        create_share_link,
+        list_document_share_links,
+        list_share_links,
+        revoke_share_link,
+        get_shared_document,
+        update_shared_document,
     },
 };
-use crate::auth::middleware::{require_auth};
+use crate::auth::middleware::{require_auth, optional_auth, require_editor, require_admin};
 use crate::AppState;
 
 pub fn create_router(state: AppState) -> Router {
+    let session_routes = Router::new()
+        .route("/me", get(get_current_user))
+        .route("/me/password", put(change_password))
+        .route("/logout", post(logout))
+        .route("/sessions", get(list_sessions).delete(revoke_all_sessions))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/tokens", get(list_api_tokens).post(create_api_token))
+        .route("/tokens/:id", delete(revoke_api_token))
+        .route("/totp", get(get_totp_status))
+        .route("/totp/setup", post(setup_totp))
+        .route("/totp/enable", post(enable_totp))
+        .route("/totp/disable", post(disable_totp))
+        .route("/totp/recovery-codes", post(regenerate_recovery_codes))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth));
+
     let auth_routes = Router::new()
+        .route("/setup", get(get_setup_status).post(complete_setup))
         .route("/register", post(register_user))
+        .route("/accept-invite", post(accept_invitation))
+        .route("/invitations/:token", get(get_invitation))
+        .route("/password-reset", post(request_password_reset))
+        .route("/password-reset/confirm", post(confirm_password_reset))
         .route("/login", post(login))
-        .route("/me", get(get_current_user).route_layer(middleware::from_fn_with_state(state.clone(), require_auth)))
+        .route("/login/mfa", post(login_mfa))
+        .route("/login/mfa/setup", post(login_mfa_setup))
+        .route("/refresh", post(refresh_token))
+        .route("/methods", get(auth_methods))
+        .route("/oidc/login", get(oidc_login))
+        .route("/oidc/callback", get(oidc_callback))
+        .merge(session_routes)
         .with_state(state.clone());
 
+    // 閲覧はすべてのロール、変更は編集者以上に許可する（ドキュメントごとの権限はACLで確認する）
+    let document_edit_routes = Router::new()
+        .route("/", post(create_document))
+        .route("/:filename", put(save_document).delete(delete_document))
+        .route("/:filename/revert/:commit_id", post(revert_document))
+        .route("/:filename/rename", post(rename_document))
+        .route("/:filename/metadata", put(update_document_metadata))
+        .route("/:filename/tags", put(set_document_tags))
+        .route("/:filename/visibility", put(set_document_visibility))
+        .route("/:filename/acl", put(set_document_acl))
+        .route("/:filename/shares", get(list_document_share_links).post(create_share_link))
+        .route_layer(middleware::from_fn(require_editor));
+
+    let document_auth_routes = Router::new()
+        .route("/:filename/acl", get(get_document_acl))
+        .merge(document_edit_routes)
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth));
+
+    // 閲覧はログインしていなくてもよい（公開されたドキュメントだけが見える）
     let document_routes = Router::new()
-        .route("/", get(list_documents).post(save_document))
+        .route("/", get(list_documents))
         .route("/search", get(search_documents))
         .route("/recent", get(list_recent_documents))
-        .route("/:filename", 
-            get(get_document)
-                .put(save_document)
-                .delete(delete_document)
-        )
+        .route("/:filename", get(get_document))
         .route("/:filename/history", get(get_document_history))
         .route("/:filename/version/:commit_id", get(get_document_version))
-        .route("/:filename/metadata", 
-            get(get_document_metadata)
-                .put(update_document_metadata)
-        )
-        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .route("/:filename/metadata", get(get_document_metadata))
+        .route("/:filename/visibility", get(get_document_visibility))
+        .route("/:filename/attachments", get(list_document_attachments))
+        .route_layer(middleware::from_fn_with_state(state.clone(), optional_auth))
+        .merge(document_auth_routes)
         .with_state(state.clone());
 
+    let tag_edit_routes = Router::new()
+        .route("/unused", delete(delete_unused_tags))
+        .route("/:tag", put(update_tag).delete(delete_tag))
+        .route("/:tag/rename", post(rename_tag))
+        .route("/:tag/merge", post(merge_tag))
+        .route("/:tag/synonyms", post(add_tag_synonym))
+        .route("/:tag/synonyms/:synonym", delete(remove_tag_synonym))
+        .route_layer(middleware::from_fn(require_editor));
+
     let tag_routes = Router::new()
         .route("/", get(get_all_tags))
         .route("/:tag/documents", get(search_documents_by_tag))
+        .merge(tag_edit_routes)
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .with_state(state.clone());
+
+    let folder_edit_routes = Router::new()
+        .route("/:folder/acl", put(set_folder_acl))
+        .route_layer(middleware::from_fn(require_editor));
+
+    let folder_routes = Router::new()
+        .route("/:folder/acl", get(get_folder_acl))
+        .merge(folder_edit_routes)
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .with_state(state.clone());
+
+    // multipart のヘッダ分を見込んで上限に余裕を持たせる
+    let upload_limit = state.config.max_attachment_size + 64 * 1024;
+    let attachment_edit_routes = Router::new()
+        .route("/", post(upload_attachment).layer(DefaultBodyLimit::max(upload_limit)))
+        .route_layer(middleware::from_fn(require_editor));
+
+    let attachment_routes = Router::new()
+        .route("/:id", get(get_attachment))
+        .route("/:id/thumbnail", get(get_attachment_thumbnail))
+        .route_layer(middleware::from_fn_with_state(state.clone(), optional_auth))
+        .merge(attachment_edit_routes.route_layer(middleware::from_fn_with_state(state.clone(), require_auth)))
+        .with_state(state.clone());
+
+    let share_routes = Router::new()
+        .route("/", get(list_share_links))
+        .route("/:id", delete(revoke_share_link))
+        .route_layer(middleware::from_fn(require_editor))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .with_state(state.clone());
+
+    // 共有リンクはトークン自体で認可するため、ログインを求めない
+    let shared_routes = Router::new()
+        .route("/:token", get(get_shared_document).put(update_shared_document))
+        .with_state(state.clone());
+
+    // ユーザー管理は管理者のみ
+    let user_routes = Router::new()
+        .route("/", get(get_all_users).post(create_user))
+        .route("/login-failures", get(list_login_failures))
+        .route("/lockouts", get(list_login_lockouts).delete(unlock_login))
+        .route("/invitations", get(list_invitations).post(create_invitation))
+        .route("/invitations/:id", delete(revoke_invitation))
+        .route("/:id", get(get_user).delete(delete_user))
+        .route("/:id/role", put(update_user_role))
+        .route("/:id/password", put(reset_user_password))
+        .route("/:id/disable", post(disable_user))
+        .route("/:id/enable", post(enable_user))
+        .route("/:id/sessions", delete(revoke_user_sessions))
+        .route("/:id/totp", delete(reset_user_totp))
+        .route_layer(middleware::from_fn(require_admin))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .with_state(state.clone());
+
+    // 監査ログは管理者のみ
+    let audit_routes = Router::new()
+        .route("/", get(list_audit_log))
+        .route("/export", get(export_audit_log))
+        .route_layer(middleware::from_fn(require_admin))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
+        .with_state(state.clone());
+
+    let group_routes = Router::new()
+        .route("/", get(list_groups).post(create_group))
+        .route("/:name", get(get_group).put(update_group).delete(delete_group))
+        .route("/:name/members/:username", put(add_group_member).delete(remove_group_member))
+        .route_layer(middleware::from_fn(require_admin))
         .route_layer(middleware::from_fn_with_state(state.clone(), require_auth))
         .with_state(state.clone());
 
@@ -61,4 +247,11 @@ pub fn create_router(state: AppState) -> Router {
         .nest("/auth", auth_routes)
         .nest("/documents", document_routes)
         .nest("/tags", tag_routes)
+        .nest("/folders", folder_routes)
+        .nest("/shares", share_routes)
+        .nest("/shared", shared_routes)
+        .nest("/users", user_routes)
+        .nest("/audit", audit_routes)
+        .nest("/groups", group_routes)
+        .nest("/attachments", attachment_routes)
 } 
\ No newline at end of file
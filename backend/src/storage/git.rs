@@ -0,0 +1,192 @@
+use async_trait::async_trait;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+use crate::git_ops::{CommitInfo, GitRepository};
+use super::{attachment_path, validate_name, DocumentStore, StoredDocument};
+
+const COMMIT_AUTHOR: &str = "MD Wiki User";
+
+// Markdownファイルをディスクに保存し、変更をGitリポジトリにコミットするストア
+#[derive(Clone, Debug)]
+pub struct GitDocumentStore {
+    root: PathBuf,
+    repo: GitRepository,
+}
+
+impl GitDocumentStore {
+    // ディレクトリを作成し、Gitリポジトリとして初期化（既存なら開く）する
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self, AppError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        let repo = GitRepository::new(&root)?;
+        Ok(Self { root, repo })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // リポジトリ内の相対パス（`infra/setup.md`）
+    fn relative_path(name: &str) -> Result<String, AppError> {
+        validate_name(name)?;
+        Ok(format!("{}.md", name))
+    }
+
+    // ルート以下の .md ファイルを再帰的に収集する
+    fn collect_markdown_files(&self) -> std::io::Result<Vec<(String, PathBuf)>> {
+        let mut files = Vec::new();
+        let mut dirs = vec![self.root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)?.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    // Gitの管理ディレクトリなどの隠しディレクトリは対象外
+                    if !entry.file_name().to_string_lossy().starts_with('.') {
+                        dirs.push(path);
+                    }
+                } else if path.extension().is_some_and(|ext| ext == "md") {
+                    if let Ok(relative) = path.strip_prefix(&self.root) {
+                        let name = relative
+                            .with_extension("")
+                            .components()
+                            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                            .collect::<Vec<_>>()
+                            .join("/");
+                        files.push((name, path));
+                    }
+                }
+            }
+        }
+
+        Ok(files)
+    }
+}
+
+// ファイルシステムの時刻をSQLiteの CURRENT_TIMESTAMP と同じ形式に変換する
+fn format_system_time(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Utc>::from(time).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+#[async_trait]
+impl DocumentStore for GitDocumentStore {
+    async fn read(&self, name: &str) -> Result<String, AppError> {
+        let path = self.root.join(Self::relative_path(name)?);
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(AppError::NotFound(format!("Document {} not found", name)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn write(&self, name: &str, content: &str, message: &str) -> Result<(), AppError> {
+        let relative = Self::relative_path(name)?;
+        let path = self.root.join(&relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&path, content)?;
+        self.repo.commit_file(&relative, message, COMMIT_AUTHOR)?;
+        Ok(())
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, AppError> {
+        Ok(self.root.join(Self::relative_path(name)?).is_file())
+    }
+
+    async fn list(&self) -> Result<Vec<StoredDocument>, AppError> {
+        let files = self.collect_markdown_files()?;
+
+        Ok(files
+            .into_iter()
+            .map(|(name, path)| {
+                let meta = fs::metadata(&path).ok();
+                StoredDocument {
+                    name,
+                    created_at: meta
+                        .as_ref()
+                        .and_then(|m| m.created().ok())
+                        .map(format_system_time)
+                        .unwrap_or_default(),
+                    updated_at: meta
+                        .as_ref()
+                        .and_then(|m| m.modified().ok())
+                        .map(format_system_time)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect())
+    }
+
+    async fn delete(&self, name: &str, message: &str) -> Result<(), AppError> {
+        let relative = Self::relative_path(name)?;
+        let path = self.root.join(&relative);
+        if !path.is_file() {
+            return Err(AppError::NotFound(format!("Document {} not found", name)));
+        }
+
+        // インデックスから削除してコミットした後、作業ツリーのファイルを削除する
+        self.repo.remove_file(&relative, message)?;
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    async fn rename(&self, from: &str, to: &str, message: &str) -> Result<(), AppError> {
+        let from_relative = Self::relative_path(from)?;
+        let to_relative = Self::relative_path(to)?;
+        let from_path = self.root.join(&from_relative);
+        let to_path = self.root.join(&to_relative);
+        if !from_path.is_file() {
+            return Err(AppError::NotFound(format!("Document {} not found", from)));
+        }
+        if to_path.is_file() {
+            return Err(AppError::Conflict(format!("Document {} already exists", to)));
+        }
+        if let Some(parent) = to_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::rename(&from_path, &to_path)?;
+        self.repo.rename_file(&from_relative, &to_relative, message, COMMIT_AUTHOR)?;
+        Ok(())
+    }
+
+    async fn history(&self, name: &str) -> Result<Vec<CommitInfo>, AppError> {
+        Ok(self.repo.get_file_history(Self::relative_path(name)?)?)
+    }
+
+    async fn read_version(&self, name: &str, commit_id: &str) -> Result<String, AppError> {
+        Ok(self.repo.get_file_content_at_commit(Self::relative_path(name)?, commit_id)?)
+    }
+
+    async fn write_attachment(&self, hash: &str, content: &[u8], message: &str) -> Result<(), AppError> {
+        let relative = attachment_path(hash)?;
+        let path = self.root.join(&relative);
+        if path.is_file() {
+            return Ok(());
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(&path, content)?;
+        self.repo.commit_file(&relative, message, COMMIT_AUTHOR)?;
+        Ok(())
+    }
+
+    async fn read_attachment(&self, hash: &str) -> Result<Vec<u8>, AppError> {
+        let path = self.root.join(attachment_path(hash)?);
+        match fs::read(&path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                Err(AppError::NotFound(format!("Attachment {} not found", hash)))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::error::AppError;
+use crate::git_ops::CommitInfo;
+use super::{attachment_path, validate_name, DocumentStore, StoredDocument};
+
+// 1回の変更（削除の場合 content は None）
+#[derive(Debug, Clone)]
+struct Revision {
+    commit: CommitInfo,
+    content: Option<String>,
+    recorded_at: String,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    documents: BTreeMap<String, Vec<Revision>>,
+    attachments: BTreeMap<String, Vec<u8>>,
+    next_commit: u64,
+}
+
+// ファイルシステムを使わずにメモリ上で履歴付きのドキュメントを保持するストア
+// テストや一時的な環境向けで、プロセス終了時に内容は失われる
+#[derive(Clone, Debug, Default)]
+pub struct MemoryDocumentStore {
+    state: Arc<RwLock<MemoryState>>,
+}
+
+impl MemoryDocumentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn record(&self, name: &str, content: Option<String>, message: &str) {
+        let mut state = self.state.write();
+        let revision = state.revision(content, message);
+        state.documents.entry(name.to_string()).or_default().push(revision);
+    }
+}
+
+impl MemoryState {
+    // 新しいコミットとしての版を作る
+    fn revision(&mut self, content: Option<String>, message: &str) -> Revision {
+        self.next_commit += 1;
+
+        let now = chrono::Utc::now();
+        Revision {
+            commit: CommitInfo {
+                id: format!("{:040x}", self.next_commit),
+                author: "MD Wiki User".to_string(),
+                message: message.to_string(),
+                timestamp: now.timestamp(),
+            },
+            content,
+            recorded_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+
+    fn latest(&self, name: &str) -> Option<&String> {
+        self.documents
+            .get(name)
+            .and_then(|revisions| revisions.last())
+            .and_then(|revision| revision.content.as_ref())
+    }
+}
+
+#[async_trait]
+impl DocumentStore for MemoryDocumentStore {
+    async fn read(&self, name: &str) -> Result<String, AppError> {
+        validate_name(name)?;
+        self.state
+            .read()
+            .latest(name)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Document {} not found", name)))
+    }
+
+    async fn write(&self, name: &str, content: &str, message: &str) -> Result<(), AppError> {
+        validate_name(name)?;
+        self.record(name, Some(content.to_string()), message);
+        Ok(())
+    }
+
+    async fn exists(&self, name: &str) -> Result<bool, AppError> {
+        validate_name(name)?;
+        Ok(self.state.read().latest(name).is_some())
+    }
+
+    async fn list(&self) -> Result<Vec<StoredDocument>, AppError> {
+        let state = self.state.read();
+        Ok(state
+            .documents
+            .iter()
+            .filter_map(|(name, revisions)| {
+                let latest = revisions.last().filter(|r| r.content.is_some())?;
+                // 最後に削除された後の最初の版を作成日時とする
+                let created = revisions
+                    .iter()
+                    .rev()
+                    .take_while(|r| r.content.is_some())
+                    .last()
+                    .unwrap_or(latest);
+                Some(StoredDocument {
+                    name: name.clone(),
+                    created_at: created.recorded_at.clone(),
+                    updated_at: latest.recorded_at.clone(),
+                })
+            })
+            .collect())
+    }
+
+    async fn delete(&self, name: &str, message: &str) -> Result<(), AppError> {
+        if !self.exists(name).await? {
+            return Err(AppError::NotFound(format!("Document {} not found", name)));
+        }
+        self.record(name, None, message);
+        Ok(())
+    }
+
+    async fn rename(&self, from: &str, to: &str, message: &str) -> Result<(), AppError> {
+        validate_name(from)?;
+        validate_name(to)?;
+        let mut state = self.state.write();
+        let content = state
+            .latest(from)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Document {} not found", from)))?;
+        if state.latest(to).is_some() {
+            return Err(AppError::Conflict(format!("Document {} already exists", to)));
+        }
+
+        // Git と同じく、1つのコミットで元の名前の削除と新しい名前の追加を記録する
+        let added = state.revision(Some(content), message);
+        let removed = Revision {
+            content: None,
+            ..added.clone()
+        };
+        state.documents.entry(from.to_string()).or_default().push(removed);
+        state.documents.entry(to.to_string()).or_default().push(added);
+        Ok(())
+    }
+
+    async fn history(&self, name: &str) -> Result<Vec<CommitInfo>, AppError> {
+        validate_name(name)?;
+        Ok(self
+            .state
+            .read()
+            .documents
+            .get(name)
+            .map(|revisions| revisions.iter().rev().map(|r| r.commit.clone()).collect())
+            .unwrap_or_default())
+    }
+
+    async fn read_version(&self, name: &str, commit_id: &str) -> Result<String, AppError> {
+        validate_name(name)?;
+        self.state
+            .read()
+            .documents
+            .get(name)
+            .and_then(|revisions| revisions.iter().find(|r| r.commit.id == commit_id))
+            .and_then(|revision| revision.content.clone())
+            .ok_or_else(|| AppError::NotFound(format!("Document {} not found at commit {}", name, commit_id)))
+    }
+
+    async fn write_attachment(&self, hash: &str, content: &[u8], _message: &str) -> Result<(), AppError> {
+        attachment_path(hash)?;
+        self.state
+            .write()
+            .attachments
+            .entry(hash.to_string())
+            .or_insert_with(|| content.to_vec());
+        Ok(())
+    }
+
+    async fn read_attachment(&self, hash: &str) -> Result<Vec<u8>, AppError> {
+        attachment_path(hash)?;
+        self.state
+            .read()
+            .attachments
+            .get(hash)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Attachment {} not found", hash)))
+    }
+}
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use serde::Serialize;
+
+use crate::error::AppError;
+use crate::git_ops::CommitInfo;
+
+pub mod git;
+pub mod memory;
+
+pub use git::GitDocumentStore;
+pub use memory::MemoryDocumentStore;
+
+// ストア内のドキュメント情報
+// `name` は拡張子なしの相対パス（フォルダの区切りは `/`）
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredDocument {
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// ドキュメント本文の保存先を抽象化するトレイト
+// ハンドラはこのトレイトだけを通してドキュメントを読み書きする
+#[async_trait]
+pub trait DocumentStore: Send + Sync + std::fmt::Debug {
+    // ドキュメントの本文を取得する（存在しなければ NotFound）
+    async fn read(&self, name: &str) -> Result<String, AppError>;
+
+    // ドキュメントを作成または更新し、変更を履歴に記録する
+    async fn write(&self, name: &str, content: &str, message: &str) -> Result<(), AppError>;
+
+    async fn exists(&self, name: &str) -> Result<bool, AppError>;
+
+    async fn list(&self) -> Result<Vec<StoredDocument>, AppError>;
+
+    // ドキュメントを削除し、削除を履歴に記録する（存在しなければ NotFound）
+    async fn delete(&self, name: &str, message: &str) -> Result<(), AppError>;
+
+    // ドキュメントの名前を変更し、変更を履歴に記録する
+    // 元のドキュメントがなければ NotFound、変更先が既にあれば Conflict
+    async fn rename(&self, from: &str, to: &str, message: &str) -> Result<(), AppError>;
+
+    // ドキュメントの変更履歴を新しい順に取得する
+    async fn history(&self, name: &str) -> Result<Vec<CommitInfo>, AppError>;
+
+    // 指定したコミット時点のドキュメント本文を取得する
+    async fn read_version(&self, name: &str, commit_id: &str) -> Result<String, AppError>;
+
+    // 添付ファイルを内容のハッシュ（SHA-256の16進表記）をキーに保存する
+    // 同じ内容のファイルは一度だけ保存される
+    async fn write_attachment(&self, hash: &str, content: &[u8], message: &str) -> Result<(), AppError>;
+
+    // 添付ファイルの内容を取得する（存在しなければ NotFound）
+    async fn read_attachment(&self, hash: &str) -> Result<Vec<u8>, AppError>;
+}
+
+// 添付ファイルのストア内パス（`attachments/ab/abcdef...`）
+// 添付ファイルはMarkdownではないため、ドキュメント一覧には現れない
+pub fn attachment_path(hash: &str) -> Result<String, AppError> {
+    let valid = hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+    if !valid {
+        return Err(AppError::InvalidInput(format!("Invalid attachment hash: {}", hash)));
+    }
+
+    Ok(format!("attachments/{}/{}", &hash[..2], hash))
+}
+
+// ドキュメント名を検証する
+// ストアのルートの外を指す名前（`..`、絶対パスなど）や `.git` などの隠しパスは受け付けない
+pub fn validate_name(name: &str) -> Result<(), AppError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('/')
+        && !name.contains('\\')
+        && !name.contains('\0')
+        && name.split('/').all(|segment| !segment.is_empty() && !segment.starts_with('.'));
+
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidInput(format!("Invalid document name: {}", name)))
+    }
+}
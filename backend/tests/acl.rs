@@ -0,0 +1,74 @@
+// ACLが設定されていないドキュメントの権限と、ACLを変更できるユーザーを確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use md_wiki_backend::models::user::Role;
+use serde_json::json;
+
+async fn create_document(app: &TestApp, token: &str, name: &str) {
+    let (status, _) = app.post("/api/documents", Some(token), json!({ "filename": name, "content": "x" })).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn editors_can_write_but_not_change_acls_without_an_acl() {
+    let app = TestApp::new().await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    let editor = editor.as_str();
+    app.create_user("bob", Role::Viewer).await;
+    create_document(&app, editor, "notes").await;
+
+    let (status, _) = app.put("/api/documents/notes", Some(editor), json!({ "filename": "notes", "content": "y" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let entries = json!({ "entries": [{ "user": "editor", "permission": "admin" }] });
+    let (status, _) = app.put("/api/documents/notes/acl", Some(editor), entries.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.put("/api/folders/guide/acl", Some(editor), entries).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.put("/api/documents/notes/visibility", Some(editor), json!({ "visibility": "public" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, body) = app.get("/api/documents/notes/acl", Some(editor)).await;
+    assert!(body["entries"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn explicit_admin_entry_allows_editors_to_change_the_acl() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    app.create_user("bob", Role::Editor).await;
+    create_document(&app, &admin, "notes").await;
+
+    // 管理者はACLがなくても変更できる
+    let (status, _) = app
+        .put("/api/documents/notes/acl", Some(&admin), json!({ "entries": [{ "user": "editor", "permission": "admin" }] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let entries = json!({ "entries": [
+        { "user": "editor", "permission": "admin" },
+        { "user": "bob", "permission": "read" }
+    ] });
+    let (status, _) = app.put("/api/documents/notes/acl", Some(&editor), entries).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.put("/api/documents/notes/visibility", Some(&editor), json!({ "visibility": "public" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // write 権限では変更できない
+    let bob = app.token_for(app.db().get_user_by_username("bob").await.unwrap().unwrap().id, Role::Editor).await;
+    let (status, _) = app
+        .put("/api/documents/notes/acl", Some(&admin), json!({ "entries": [
+            { "user": "editor", "permission": "admin" },
+            { "user": "bob", "permission": "write" }
+        ] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.put("/api/documents/notes", Some(&bob), json!({ "filename": "notes", "content": "y" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app
+        .put("/api/documents/notes/acl", Some(&bob), json!({ "entries": [{ "user": "bob", "permission": "admin" }] }))
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
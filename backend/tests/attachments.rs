@@ -0,0 +1,114 @@
+// ログインのトークンを送れない <img> 向けの、添付ファイルの署名付きURLを確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::{test_config, TestApp};
+use md_wiki_backend::auth::attachment::create_attachment_token;
+use md_wiki_backend::db::NewAttachment;
+use md_wiki_backend::images;
+use md_wiki_backend::models::user::Role;
+use md_wiki_backend::storage::DocumentStore;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+
+const CONTENT: &[u8] = b"%PDF-1.4 test";
+
+async fn attach(app: &TestApp, document: &str, filename: &str, content_type: &str, content: &[u8]) -> i64 {
+    let hash = format!("{:x}", Sha256::digest(content));
+    app.store.write_attachment(&hash, content, "test").await.unwrap();
+    let attachment = app
+        .db()
+        .create_attachment(NewAttachment {
+            document: document.to_string(),
+            filename: filename.to_string(),
+            content_type: content_type.to_string(),
+            size: content.len() as i64,
+            hash,
+            uploaded_by: None,
+        })
+        .await
+        .unwrap();
+    attachment.id
+}
+
+#[tokio::test]
+async fn signed_urls_serve_attachments_of_private_documents_without_a_login() {
+    let app = TestApp::new().await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    let (status, _) = app.post("/api/documents", Some(&editor), json!({ "filename": "notes", "content": "x" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let id = attach(&app, "notes", "spec.pdf", "application/pdf", CONTENT).await;
+
+    let (status, _) = app.get(&format!("/api/attachments/{}", id), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = app.get("/api/documents/notes/attachments", Some(&editor)).await;
+    assert_eq!(status, StatusCode::OK);
+    let url = body["attachments"][0]["url"].as_str().unwrap().to_string();
+    assert_eq!(body["attachments"][0]["id"], id);
+
+    let (status, content) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content, String::from_utf8_lossy(CONTENT).as_ref());
+}
+
+#[tokio::test]
+async fn signed_urls_are_only_valid_for_their_attachment() {
+    let app = TestApp::new().await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    for name in ["notes", "secret"] {
+        let (status, _) = app.post("/api/documents", Some(&editor), json!({ "filename": name, "content": "x" })).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let notes = attach(&app, "notes", "a.pdf", "application/pdf", CONTENT).await;
+    let secret = attach(&app, "secret", "b.pdf", "application/pdf", b"%PDF-1.4 secret").await;
+
+    // 別の添付ファイルのトークンは使えない
+    let token = create_attachment_token(&app.state.tokens, notes).unwrap();
+    let (status, _) = app.get(&format!("/api/attachments/{}?token={}", secret, token), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get(&format!("/api/attachments/{}?token=garbage", notes), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // ログインのトークンは添付ファイルのトークンとして使えない
+    let (status, _) = app.get(&format!("/api/attachments/{}?token={}", notes, editor), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn resized_images_are_limited_in_width_and_served_from_the_cache() {
+    let mut config = test_config();
+    config.cache_dir = std::env::temp_dir().join(format!("md-wiki-cache-{:016x}", rand::random::<u64>()));
+    let app = TestApp::with_config(config).await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    let (status, _) = app.post("/api/documents", Some(&editor), json!({ "filename": "notes", "content": "x" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut png = Cursor::new(Vec::new());
+    image::DynamicImage::new_rgb8(64, 32).write_to(&mut png, image::ImageFormat::Png).unwrap();
+    let png = png.into_inner();
+    let id = attach(&app, "notes", "wide.png", "image/png", &png).await;
+
+    for width in [images::MIN_RESIZE_WIDTH - 1, images::MAX_RESIZE_WIDTH + 1] {
+        let (status, _) = app.get(&format!("/api/attachments/{}?w={}", id, width), Some(&editor)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+    let (status, _) = app.get(&format!("/api/attachments/{}?w={}", id, images::MIN_RESIZE_WIDTH), Some(&editor)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 一度縮小した画像はキャッシュに保存され、次からはそれが返される
+    let hash = format!("{:x}", Sha256::digest(&png));
+    let path = images::cache_path(&app.state.config.cache_dir, &hash, 32);
+    assert!(!path.exists());
+    let (status, _) = app.get(&format!("/api/attachments/{}?w=32", id), Some(&editor)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(path.exists());
+
+    std::fs::write(&path, b"cached").unwrap();
+    let (status, body) = app.get(&format!("/api/attachments/{}?w=32", id), Some(&editor)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "cached");
+
+    let _ = std::fs::remove_dir_all(&app.state.config.cache_dir);
+}
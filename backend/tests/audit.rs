@@ -0,0 +1,144 @@
+// 管理操作・ロックアウト・外部の認証基盤によるロールの変更が監査ログに残ることを確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::{test_config, TestApp, PASSWORD};
+use md_wiki_backend::auth::provider::ExternalIdentity;
+use md_wiki_backend::db::{AuditEntry, AuditFilter};
+use md_wiki_backend::models::user::Role;
+use serde_json::json;
+
+async fn audit_entries(app: &TestApp, action: &str) -> Vec<AuditEntry> {
+    let filter = AuditFilter {
+        action: Some(action.to_string()),
+        ..AuditFilter::default()
+    };
+    app.db().list_audit_entries(&filter, None, 100).await.unwrap()
+}
+
+#[tokio::test]
+async fn admin_actions_are_recorded() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let admin = Some(admin.as_str());
+    let alice = app.create_user("alice", Role::Viewer).await;
+
+    let (status, _) = app.post("/api/groups", admin, json!({ "name": "writers", "role": "editor" })).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let entries = audit_entries(&app, "group.create").await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].target, "writers");
+    assert_eq!(entries[0].actor.as_deref(), Some("admin"));
+    assert_eq!(entries[0].after.as_deref(), Some("role editor"));
+
+    let (status, _) = app.post("/api/users/invitations", admin, json!({ "username": "bob", "role": "admin" })).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let entries = audit_entries(&app, "user.invite").await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].target, "bob");
+    assert!(entries[0].after.as_deref().unwrap().starts_with("role admin"));
+
+    let (status, _) = app.put(&format!("/api/users/{}/password", alice), admin, json!({ "password": "a-brand-new-password" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = audit_entries(&app, "user.password_reset").await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].target, "alice");
+
+    let (status, _) = app.delete(&format!("/api/users/{}/totp", alice), admin).await;
+    assert_eq!(status, StatusCode::OK);
+    let entries = audit_entries(&app, "user.totp_reset").await;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].target, "alice");
+    assert_eq!(entries[0].after.as_deref(), Some("disabled"));
+}
+
+#[tokio::test]
+async fn lockout_is_recorded_once_per_lock() {
+    let mut config = test_config();
+    config.login_max_failures = 3;
+    let app = TestApp::with_config(config).await;
+    app.create_user("alice", Role::Viewer).await;
+
+    // 上限に達するまでは失敗するたびに1秒、2秒と待たせるため、待ってから試す
+    let wrong = json!({ "username": "alice", "password": "wrong-password" });
+    for wait in [1, 2] {
+        let (status, _) = app.post("/api/auth/login", None, wrong.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        tokio::time::sleep(std::time::Duration::from_millis(wait * 1000 + 100)).await;
+    }
+    let (status, _) = app.post("/api/auth/login", None, wrong.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    for _ in 0..5 {
+        let (status, _) = app.post("/api/auth/login", None, wrong.clone()).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    // ロック中の試行は監査ログに残らない
+    let lockouts = audit_entries(&app, "auth.lockout").await;
+    assert_eq!(lockouts.len(), 1);
+    assert_eq!(lockouts[0].target, "alice");
+    assert!(lockouts[0].after.as_deref().unwrap().starts_with("username locked"));
+    assert_eq!(audit_entries(&app, "auth.login_failed").await.len(), 3);
+    // 失敗の記録にはロック中の試行も残る
+    assert_eq!(app.db().list_login_failures(Some("alice"), None, None, 100).await.unwrap().len(), 8);
+
+    let (status, _) = app.post("/api/auth/login", None, json!({ "username": "alice", "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn external_provisioning_is_recorded() {
+    let app = TestApp::new().await;
+    let identity = |role| ExternalIdentity {
+        issuer: "https://idp.example.com".to_string(),
+        subject: "u-1".to_string(),
+        username: "carol".to_string(),
+        email: None,
+        role,
+        sync_role: true,
+    };
+
+    let user = app.db().provision_external_user(&identity(Role::Viewer)).await.unwrap();
+    let created = audit_entries(&app, "user.create").await;
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].target, "carol");
+    assert_eq!(created[0].after.as_deref(), Some("role viewer (from https://idp.example.com)"));
+
+    // ロールが変わらなければ記録しない
+    app.db().provision_external_user(&identity(Role::Viewer)).await.unwrap();
+    assert!(audit_entries(&app, "user.role_change").await.is_empty());
+
+    app.db().provision_external_user(&identity(Role::Editor)).await.unwrap();
+    let changes = audit_entries(&app, "user.role_change").await;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].actor_id, Some(user.id));
+    assert_eq!(changes[0].before.as_deref(), Some("viewer"));
+    assert_eq!(changes[0].after.as_deref(), Some("editor (from https://idp.example.com)"));
+}
+
+#[tokio::test]
+async fn audit_failures_do_not_fail_committed_changes() {
+    let path = std::env::temp_dir().join(format!("md-wiki-{:016x}.db", rand::random::<u64>()));
+    let mut config = test_config();
+    config.database_url = format!("sqlite://{}", path.display());
+    let app = TestApp::with_config(config.clone()).await;
+    let admin = app.login_as("admin", Role::Admin).await;
+
+    // 監査ログへの書き込みだけを失敗させる
+    sqlx::any::install_default_drivers();
+    let mut conn = <sqlx::AnyConnection as sqlx::Connection>::connect(&config.database_url).await.unwrap();
+    sqlx::raw_sql("CREATE TRIGGER reject_audit BEFORE INSERT ON audit_log BEGIN SELECT RAISE(ABORT, 'audit log unavailable'); END")
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let (status, _) = app.post("/api/groups", Some(&admin), json!({ "name": "writers", "role": "editor" })).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = app.get("/api/groups/writers", Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(audit_entries(&app, "group.create").await.is_empty());
+
+    drop(conn);
+    drop(app);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,139 @@
+// 外部の認証基盤（LDAP など）でのパスワードログインを、テスト用の AuthProvider で確認する
+mod common;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use common::{test_config, test_db, TestApp, PASSWORD};
+use md_wiki_backend::auth::provider::{map_role, AuthProvider, ExternalIdentity};
+use md_wiki_backend::error::AppError;
+use md_wiki_backend::models::user::Role;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const EXTERNAL_PASSWORD: &str = "directory-password";
+
+// パスワードとグループを覚えておき、LDAP と同じ形でユーザーを返す
+#[derive(Debug, Default)]
+struct FakeDirectory {
+    groups: Mutex<HashMap<String, Vec<String>>>,
+    calls: AtomicUsize,
+}
+
+impl FakeDirectory {
+    fn set_groups(&self, username: &str, groups: &[&str]) {
+        self.groups
+            .lock()
+            .insert(username.to_string(), groups.iter().map(|group| group.to_string()).collect());
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl AuthProvider for FakeDirectory {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn authenticate(&self, username: &str, password: &str) -> Result<Option<ExternalIdentity>, AppError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let groups = match self.groups.lock().get(username) {
+            Some(groups) if password == EXTERNAL_PASSWORD => groups.clone(),
+            _ => return Ok(None),
+        };
+        let mapping = [("wiki-editors".to_string(), Role::Editor), ("wiki-admins".to_string(), Role::Admin)];
+
+        Ok(Some(ExternalIdentity {
+            issuer: "fake://directory".to_string(),
+            subject: format!("uid={}", username),
+            username: username.to_string(),
+            email: None,
+            role: map_role(&mapping, &groups, Role::Viewer),
+            sync_role: true,
+        }))
+    }
+}
+
+async fn directory_app() -> (TestApp, Arc<FakeDirectory>) {
+    let config = test_config();
+    let directory = Arc::new(FakeDirectory::default());
+    let db = test_db(&config.database_url)
+        .await
+        .with_auth_providers(true, vec![directory.clone() as Arc<dyn AuthProvider>]);
+    (TestApp::with_db(config, db), directory)
+}
+
+async fn login(app: &TestApp, username: &str, password: &str) -> (StatusCode, Value) {
+    app.post("/api/auth/login", None, json!({ "username": username, "password": password })).await
+}
+
+#[tokio::test]
+async fn local_users_are_checked_before_falling_through_to_the_provider() {
+    let (app, directory) = directory_app().await;
+    app.create_user("alice", Role::Editor).await;
+    directory.set_groups("erin", &["staff"]);
+
+    let (status, _) = login(&app, "alice", PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    // ローカルのパスワードを持つユーザーは外部に問い合わせない
+    let (status, _) = login(&app, "alice", EXTERNAL_PASSWORD).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(directory.calls(), 0);
+
+    let (status, body) = login(&app, "erin", EXTERNAL_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(directory.calls(), 1);
+    let erin = app.db().get_user_by_username("erin").await.unwrap().unwrap();
+    assert!(erin.password_hash.is_empty());
+    assert_eq!(erin.role, Role::Viewer);
+
+    let (status, _) = login(&app, "erin", "wrong-password").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = login(&app, "nobody", EXTERNAL_PASSWORD).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn disabled_external_users_are_rejected() {
+    let (app, directory) = directory_app().await;
+    directory.set_groups("erin", &["wiki-editors"]);
+
+    let (status, body) = login(&app, "erin", EXTERNAL_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = body["token"].as_str().unwrap().to_string();
+    let erin = app.db().get_user_by_username("erin").await.unwrap().unwrap();
+
+    // 認証基盤ではパスワードが正しくても、無効にしたユーザーはログインできない
+    app.db().set_user_disabled(erin.id, true).await.unwrap();
+    let (status, _) = login(&app, "erin", EXTERNAL_PASSWORD).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.get("/api/auth/me", Some(&token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn group_mapping_is_applied_on_every_login() {
+    let (app, directory) = directory_app().await;
+
+    for (groups, role) in [
+        (vec!["wiki-editors"], Role::Editor),
+        (vec!["wiki-editors", "wiki-admins"], Role::Admin),
+        (vec!["staff"], Role::Viewer),
+    ] {
+        directory.set_groups("erin", &groups);
+        let (status, body) = login(&app, "erin", EXTERNAL_PASSWORD).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+
+        let erin = app.db().get_user_by_username("erin").await.unwrap().unwrap();
+        assert_eq!(erin.role, role, "{:?}", groups);
+        // 発行したトークンのロールも同じになる
+        let (_, me) = app.get("/api/auth/me", body["token"].as_str()).await;
+        assert_eq!(me["role"], role.to_string());
+    }
+    assert_eq!(app.db().list_users().await.unwrap().len(), 1);
+}
@@ -0,0 +1,190 @@
+// 結合テストの共通部分
+// ドキュメントはメモリ上のストア、メタデータはメモリ上の SQLite に保存する
+#![allow(dead_code)]
+
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::Router;
+use http_body_util::BodyExt;
+use md_wiki_backend::config::{Config, DatabaseBackend, Environment, MailTransport, RegistrationMode};
+use md_wiki_backend::db::DbManager;
+use md_wiki_backend::mail::Mailer;
+use md_wiki_backend::models::acl::Visibility;
+use md_wiki_backend::models::user::Role;
+use md_wiki_backend::storage::MemoryDocumentStore;
+use md_wiki_backend::{auth, mail, routes, AppState};
+use serde_json::Value;
+use tower::ServiceExt;
+
+pub const PASSWORD: &str = "correct-horse-battery";
+
+// 環境変数に左右されないテスト用の設定
+pub fn test_config() -> Config {
+    let mut config = Config::from_env();
+    config.environment = Environment::Development;
+    config.database_url = "sqlite::memory:".to_string();
+    config.database_backend = DatabaseBackend::Sqlite;
+    config.jwt_secret = "test-secret".to_string();
+    config.jwt_algorithm = "HS256".to_string();
+    config.jwt_key_id = "test".to_string();
+    config.jwt_previous_keys = Vec::new();
+    config.wiki_mode = Visibility::Private;
+    config.password_login = true;
+    config.registration_mode = RegistrationMode::Off;
+    config.totp_required_for_admins = false;
+    config.trust_proxy_headers = false;
+    config.mail.transport = MailTransport::Log;
+    config.oidc = None;
+    config.ldap = None;
+    config
+}
+
+pub async fn test_db(database_url: &str) -> DbManager {
+    let db = DbManager::new(database_url, DatabaseBackend::from_url(database_url))
+        .await
+        .expect("open database");
+    db.init().await.expect("migrate database");
+    db
+}
+
+// 共通のクエリを確かめるデータベース
+// メモリ上の SQLite に加えて、DATABASE_URL に PostgreSQL を指定した場合はそちらでも確かめる
+// PostgreSQL ではテストごとのスキーマを作り直して使う
+pub async fn test_databases(name: &str) -> Vec<DbManager> {
+    let mut databases = vec![test_db("sqlite::memory:").await];
+
+    let url = match std::env::var("DATABASE_URL") {
+        Ok(url) if DatabaseBackend::from_url(&url) == DatabaseBackend::Postgres => url,
+        _ => return databases,
+    };
+    let schema = format!("md_wiki_test_{}", name);
+    sqlx::any::install_default_drivers();
+    let mut conn = <sqlx::AnyConnection as sqlx::Connection>::connect(&url).await.expect("connect to PostgreSQL");
+    sqlx::raw_sql(&format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}", schema))
+        .execute(&mut conn)
+        .await
+        .expect("create test schema");
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    databases.push(test_db(&format!("{}{}options=-c%20search_path%3D{}", url, separator, schema)).await);
+    databases
+}
+
+pub struct TestApp {
+    pub state: AppState,
+    pub store: MemoryDocumentStore,
+    router: Router,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_config(test_config()).await
+    }
+
+    pub async fn with_config(config: Config) -> Self {
+        let db = test_db(&config.database_url).await;
+        Self::with_db(config, db)
+    }
+
+    pub fn with_db(config: Config, db: DbManager) -> Self {
+        let mailer = mail::from_config(&config.mail).expect("mailer");
+        Self::with_parts(config, db, mailer)
+    }
+
+    // 送ったメールを確かめるテスト用の送信方法を使う
+    pub async fn with_mailer(config: Config, mailer: Arc<dyn Mailer>) -> Self {
+        let db = test_db(&config.database_url).await;
+        Self::with_parts(config, db, mailer)
+    }
+
+    fn with_parts(config: Config, db: DbManager, mailer: Arc<dyn Mailer>) -> Self {
+        let store = MemoryDocumentStore::new();
+        let state = AppState {
+            db_manager: Some(db),
+            document_store: Arc::new(store.clone()),
+            markdown_dir: config.markdown_dir.clone(),
+            tokens: Arc::new(auth::TokenService::from_config(&config).expect("token service")),
+            oidc: config
+                .oidc
+                .as_ref()
+                .map(|oidc| Arc::new(auth::oidc::OidcClient::from_config(oidc).expect("OpenID Connect client"))),
+            mailer,
+            config,
+        };
+        let router = Router::new().nest("/api", routes::create_router(state.clone()));
+        Self { state, store, router }
+    }
+
+    pub fn db(&self) -> &DbManager {
+        self.state.db_manager.as_ref().expect("database")
+    }
+
+    pub async fn create_user(&self, username: &str, role: Role) -> i64 {
+        self.db()
+            .create_user(username, PASSWORD, Some(&format!("{}@example.com", username)), role)
+            .await
+            .expect("create user")
+    }
+
+    // ログインした場合と同じく、セッションに結び付いたアクセストークンを発行する
+    pub async fn token_for(&self, user_id: i64, role: Role) -> String {
+        let session = self
+            .db()
+            .create_session(user_id, &format!("test-refresh-{}", rand_suffix()), None, chrono::Utc::now() + chrono::Duration::days(1))
+            .await
+            .expect("create session");
+        auth::create_token(&self.state.tokens, user_id, &role.to_string(), session.id).expect("create token")
+    }
+
+    // ユーザーを作成してトークンを発行する
+    pub async fn login_as(&self, username: &str, role: Role) -> String {
+        let user_id = self.create_user(username, role).await;
+        self.token_for(user_id, role).await
+    }
+
+    pub async fn request(&self, method: Method, uri: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        let mut builder = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            builder = builder.header("Authorization", format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => builder
+                .header("Content-Type", "application/json")
+                .body(Body::from(body.to_string()))
+                .expect("request"),
+            None => builder.body(Body::empty()).expect("request"),
+        };
+
+        let response = self.router.clone().oneshot(request).await.expect("response");
+        let status = response.status();
+        let bytes = response.into_body().collect().await.expect("body").to_bytes();
+        let json = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+        };
+        (status, json)
+    }
+
+    pub async fn get(&self, uri: &str, token: Option<&str>) -> (StatusCode, Value) {
+        self.request(Method::GET, uri, token, None).await
+    }
+
+    pub async fn post(&self, uri: &str, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+        self.request(Method::POST, uri, token, Some(body)).await
+    }
+
+    pub async fn put(&self, uri: &str, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+        self.request(Method::PUT, uri, token, Some(body)).await
+    }
+
+    pub async fn delete(&self, uri: &str, token: Option<&str>) -> (StatusCode, Value) {
+        self.request(Method::DELETE, uri, token, None).await
+    }
+}
+
+fn rand_suffix() -> u64 {
+    rand::random()
+}
@@ -0,0 +1,320 @@
+// メタデータのクエリが SQLite と PostgreSQL の両方で動くことを確かめる
+// PostgreSQL では `DATABASE_URL=postgres://... cargo test --test database` のように実行する
+mod common;
+
+use common::{test_databases, test_db, PASSWORD};
+use md_wiki_backend::auth::throttle::{LoginThrottle, ThrottlePolicy};
+use md_wiki_backend::db::migrations::latest_version;
+use md_wiki_backend::db::{
+    AuditFilter, DocumentListQuery, DocumentMeta, DocumentSort, FailedLogin, NewAttachment, NewAuditEntry, NewInvitation,
+    TagChange, ThrottleScope,
+};
+use md_wiki_backend::models::acl::{Permission, PrincipalType, Visibility};
+use md_wiki_backend::models::user::{Role, TokenScope};
+use md_wiki_backend::storage::StoredDocument;
+
+#[tokio::test]
+async fn migrations_reach_the_latest_version() {
+    for db in test_databases("migrations").await {
+        assert_eq!(db.schema_version().await.unwrap(), latest_version(), "{:?}", db.backend());
+        // 適用済みのマイグレーションは繰り返さない
+        db.init().await.unwrap();
+        assert_eq!(db.schema_version().await.unwrap(), latest_version());
+    }
+}
+
+#[tokio::test]
+async fn users() {
+    for db in test_databases("users").await {
+        let id = db.create_user("alice", PASSWORD, Some("alice@example.com"), Role::Editor).await.unwrap();
+        assert!(db.create_user("alice", PASSWORD, None, Role::Viewer).await.is_err());
+
+        let user = db.get_user_by_username("alice").await.unwrap().unwrap();
+        assert_eq!(user.id, id);
+        assert_eq!(user.role, Role::Editor);
+        assert_eq!(db.get_user_by_email("alice@example.com").await.unwrap().unwrap().id, id);
+        assert!(db.authenticate_user("alice", PASSWORD).await.unwrap().is_some());
+        assert!(db.authenticate_user("alice", "wrong-password").await.unwrap().is_none());
+
+        assert!(db.update_user_role("alice", "admin").await.unwrap());
+        assert_eq!(db.count_active_admins().await.unwrap(), 1);
+        assert!(db.set_user_disabled(id, true).await.unwrap());
+        assert!(db.get_user_by_id(id).await.unwrap().unwrap().is_disabled());
+        assert_eq!(db.count_active_admins().await.unwrap(), 0);
+
+        // グループのロールも含めたロール（メンバーの追加は重複しても1件）
+        let bob = db.create_user("bob", PASSWORD, None, Role::Viewer).await.unwrap();
+        let group = db.create_group("writers", None, Some(Role::Editor)).await.unwrap();
+        db.add_group_member(group.id, bob).await.unwrap();
+        db.add_group_member(group.id, bob).await.unwrap();
+        assert_eq!(db.list_group_members(group.id).await.unwrap(), vec!["bob".to_string()]);
+        assert_eq!(db.effective_role(bob, Role::Viewer).await.unwrap(), Role::Editor);
+    }
+}
+
+#[tokio::test]
+async fn documents_and_tags() {
+    for db in test_databases("tags").await {
+        let id = db.create_document_metadata("guide/setup", Some("Setup")).await.unwrap();
+        db.set_document_tags(id, vec!["infra/k8s".to_string(), "ops".to_string()]).await.unwrap();
+        // 同じタグを付け直しても重複しない
+        assert!(db.add_tag_to_document(id, "ops").await.unwrap());
+
+        let meta = db.get_document_metadata("guide/setup").await.unwrap().unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Setup"));
+        assert_eq!(meta.tags, vec!["infra/k8s".to_string(), "ops".to_string()]);
+
+        // 階層タグは親タグも作られる
+        let infra = db.get_tag_by_name("infra").await.unwrap().unwrap();
+        assert_eq!(infra.usage_count, 0);
+        assert_eq!(db.get_tag_by_name("infra/k8s").await.unwrap().unwrap().parent.as_deref(), Some("infra"));
+        assert!(db.get_documents_by_tag("infra", false).await.unwrap().is_empty());
+        assert_eq!(db.get_documents_by_tag("infra", true).await.unwrap(), vec!["guide/setup".to_string()]);
+
+        assert_eq!(db.add_tag_synonym("infra/k8s", "kubernetes").await.unwrap(), TagChange::Applied);
+        assert_eq!(db.get_documents_by_tag("kubernetes", false).await.unwrap(), vec!["guide/setup".to_string()]);
+
+        assert_eq!(db.rename_tag("infra", "platform").await.unwrap(), TagChange::Applied);
+        assert!(db.get_tag_by_name("platform/k8s").await.unwrap().is_some());
+        assert_eq!(db.merge_tags("ops", "platform/k8s").await.unwrap(), TagChange::Applied);
+        assert_eq!(db.merge_tags("platform/k8s", "kubernetes").await.unwrap(), TagChange::Conflict);
+        let meta = db.get_document_metadata("guide/setup").await.unwrap().unwrap();
+        assert_eq!(meta.tags, vec!["platform/k8s".to_string()]);
+
+        // 公開設定はメタデータがなければ作る
+        db.set_document_visibility("guide/setup", Some(Visibility::Public)).await.unwrap();
+        db.set_document_visibility("other", Some(Visibility::Private)).await.unwrap();
+        let visibility = db.list_document_visibility().await.unwrap();
+        assert_eq!(visibility.get("guide/setup"), Some(&Visibility::Public));
+        assert_eq!(visibility.get("other"), Some(&Visibility::Private));
+
+        db.rename_document_metadata("guide/setup", "guide/install").await.unwrap();
+        assert!(db.get_document_metadata("guide/setup").await.unwrap().is_none());
+        assert!(db.delete_document_metadata("guide/install").await.unwrap());
+        assert_eq!(db.list_documents().await.unwrap().len(), 1);
+    }
+}
+
+// 削除の連鎖は外部キーに任せるため、SQLite でも PostgreSQL と同じ結果になる
+#[tokio::test]
+async fn deletes_cascade_through_foreign_keys() {
+    for db in test_databases("cascade").await {
+        let document = db.create_document_metadata("notes", None).await.unwrap();
+        db.set_document_tags(document, vec!["ops".to_string()]).await.unwrap();
+        assert_eq!(db.get_tag_by_name("ops").await.unwrap().unwrap().usage_count, 1);
+        assert!(db.delete_document_metadata("notes").await.unwrap());
+        // タグ自体は残り、付与だけが消える
+        assert_eq!(db.get_tag_by_name("ops").await.unwrap().unwrap().usage_count, 0);
+
+        let alice = db.create_user("alice", PASSWORD, None, Role::Editor).await.unwrap();
+        let expires = chrono::Utc::now() + chrono::Duration::days(1);
+        let session = db.create_session(alice, "refresh-hash", None, expires).await.unwrap();
+        assert!(db.rotate_session_token(session.id, "refresh-hash", "refresh-hash-2", expires).await.unwrap());
+        db.create_api_token(alice, "ci", "api-token-hash", TokenScope::Read, expires).await.unwrap();
+        let group = db.create_group("writers", None, None).await.unwrap();
+        db.add_group_member(group.id, alice).await.unwrap();
+        assert!(db.start_totp_enrollment(alice, "JBSWY3DPEHPK3PXP").await.unwrap());
+        assert!(db.enable_totp(alice, 1, &["recovery-hash".to_string()]).await.unwrap());
+        assert!(db.use_mfa_token("nonce", alice, expires).await.unwrap());
+        let attachment = db
+            .create_attachment(NewAttachment {
+                document: "notes".to_string(),
+                filename: "a.pdf".to_string(),
+                content_type: "application/pdf".to_string(),
+                size: 1,
+                hash: "attachment-hash".to_string(),
+                uploaded_by: Some(alice),
+            })
+            .await
+            .unwrap();
+
+        assert!(db.delete_user("alice", None).await.unwrap());
+        assert!(db.get_session(session.id).await.unwrap().is_none());
+        assert!(db.get_session_by_rotated_token("refresh-hash").await.unwrap().is_none());
+        assert!(db.list_user_api_tokens(alice).await.unwrap().is_empty());
+        assert!(db.list_group_members(group.id).await.unwrap().is_empty());
+        assert_eq!(db.count_recovery_codes(alice).await.unwrap(), 0);
+        // 作成者の記録は空になるだけで、添付ファイルは残る
+        assert_eq!(db.get_attachment(attachment.id).await.unwrap().unwrap().uploaded_by, None);
+    }
+}
+
+// マイグレーションを適用済みのファイルを開き直した場合も、外部キー制約が有効になる
+#[tokio::test]
+async fn sqlite_enforces_foreign_keys_after_reopening() {
+    let path = std::env::temp_dir().join(format!("md-wiki-{:016x}.db", rand::random::<u64>()));
+    let url = format!("sqlite://{}", path.display());
+    let alice = {
+        let db = test_db(&url).await;
+        db.create_user("alice", PASSWORD, None, Role::Viewer).await.unwrap()
+    };
+
+    let db = test_db(&url).await;
+    let expires = chrono::Utc::now() + chrono::Duration::days(1);
+    let session = db.create_session(alice, "refresh-hash", None, expires).await.unwrap();
+    assert!(db.delete_user("alice", None).await.unwrap());
+    assert!(db.get_session(session.id).await.unwrap().is_none());
+    // 存在しないユーザーを参照する行は作れない
+    assert!(db.create_session(alice, "other-hash", None, expires).await.is_err());
+
+    drop(db);
+    let _ = std::fs::remove_file(&path);
+}
+
+// 一覧の並び順・カーソル・フォルダ・タグの絞り込みはSQLで行う
+#[tokio::test]
+async fn document_list_pages() {
+    for db in test_databases("document_pages").await {
+        let stored = [("guide/setup", "2024-01-03 00:00:00"), ("guide/install", "2024-01-01 00:00:00"), ("notes", "2024-01-02 00:00:00")]
+            .map(|(name, at)| StoredDocument { name: name.to_string(), created_at: at.to_string(), updated_at: at.to_string() });
+        assert_eq!(db.sync_documents(&stored).await.unwrap(), 3);
+        // 索引にあるドキュメントは追加しない
+        assert_eq!(db.sync_documents(&stored).await.unwrap(), 0);
+        let id = db.get_document_metadata("notes").await.unwrap().unwrap().id;
+        db.set_document_tags(id, vec!["infra/k8s".to_string()]).await.unwrap();
+
+        let names = |page: &[(String, DocumentMeta)]| -> Vec<String> { page.iter().map(|(_, doc)| doc.filename.clone()).collect() };
+        let mut by_date = DocumentListQuery { descending: true, limit: 2, ..Default::default() };
+        let mut pages = Vec::new();
+        loop {
+            let page = db.list_documents_page(&by_date).await.unwrap();
+            if page.is_empty() {
+                break;
+            }
+            by_date.after = page.last().map(|(key, doc)| (key.clone(), doc.filename.clone()));
+            pages.push(names(&page));
+        }
+        assert_eq!(pages, vec![vec!["guide/setup", "notes"], vec!["guide/install"]]);
+
+        // タイトルがなければファイル名で並べる
+        db.update_document_metadata("guide/install", Some("Zebra")).await.unwrap();
+        let by_title = DocumentListQuery { sort: DocumentSort::Title, limit: 10, ..Default::default() };
+        assert_eq!(names(&db.list_documents_page(&by_title).await.unwrap()), vec!["guide/setup", "notes", "guide/install"]);
+        let folder = DocumentListQuery { folder: Some("guide".to_string()), ..by_title.clone() };
+        assert_eq!(names(&db.list_documents_page(&folder).await.unwrap()), vec!["guide/setup", "guide/install"]);
+        let tagged = DocumentListQuery { tag: Some("infra".to_string()), ..by_title.clone() };
+        assert_eq!(names(&db.list_documents_page(&tagged).await.unwrap()), vec!["notes"]);
+        let unknown = DocumentListQuery { tag: Some("unknown".to_string()), ..by_title };
+        assert!(db.list_documents_page(&unknown).await.unwrap().is_empty());
+
+        // 書き込んだドキュメントは索引に加わり、更新日時が新しくなる
+        db.touch_document("new").await.unwrap();
+        db.touch_document("notes").await.unwrap();
+        let oldest = DocumentListQuery { limit: 1, ..Default::default() };
+        assert_eq!(names(&db.list_documents_page(&oldest).await.unwrap()), vec!["guide/setup"]);
+        assert!(db.get_document_metadata("new").await.unwrap().is_some());
+
+        let tags = db.list_tags_page(None, 1).await.unwrap();
+        assert_eq!(tags[0].name, "infra");
+        let tags = db.list_tags_page(Some("infra"), 10).await.unwrap();
+        assert_eq!(tags.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>(), vec!["infra/k8s"]);
+        assert_eq!(db.list_tagged_documents(&[tags[0].id]).await.unwrap(), vec![(tags[0].id, "notes".to_string())]);
+    }
+}
+
+#[tokio::test]
+async fn acl_entries() {
+    for db in test_databases("acl").await {
+        let alice = db.create_user("alice", PASSWORD, None, Role::Editor).await.unwrap();
+        db.set_acl_entries("guide/", &[(PrincipalType::User, alice, Permission::Write)]).await.unwrap();
+
+        let entries = db.get_acl_entries(&["guide/setup".to_string(), "guide/".to_string()]).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].principal.as_deref(), Some("alice"));
+        assert_eq!(entries[0].permission, Permission::Write);
+
+        db.set_acl_entries("guide/", &[]).await.unwrap();
+        assert!(db.list_acl_entries().await.unwrap().is_empty());
+    }
+}
+
+#[tokio::test]
+async fn sessions_invitations_and_throttles() {
+    for db in test_databases("sessions").await {
+        let alice = db.create_user("alice", PASSWORD, None, Role::Viewer).await.unwrap();
+        let expires = chrono::Utc::now() + chrono::Duration::days(1);
+        let session = db.create_session(alice, "refresh-hash", Some("test"), expires).await.unwrap();
+        assert!(session.is_active());
+        assert_eq!(db.get_session_by_token("refresh-hash").await.unwrap().unwrap().id, session.id);
+        // 置き換えたトークンは、どのセッションのものだったかを覚えておく
+        assert!(db.rotate_session_token(session.id, "refresh-hash", "refresh-hash-2", expires).await.unwrap());
+        assert!(!db.rotate_session_token(session.id, "refresh-hash", "refresh-hash-3", expires).await.unwrap());
+        assert!(db.get_session_by_token("refresh-hash").await.unwrap().is_none());
+        assert_eq!(db.get_session_by_rotated_token("refresh-hash").await.unwrap().unwrap().id, session.id);
+        assert!(db.get_session_by_rotated_token("refresh-hash-2").await.unwrap().is_none());
+
+        let invitation = db
+            .create_invitation(NewInvitation {
+                token_hash: "invitation-hash".to_string(),
+                username: Some("bob".to_string()),
+                email: None,
+                role: Role::Editor,
+                created_by: Some(alice),
+                expires_at: expires,
+            })
+            .await
+            .unwrap();
+        assert!(invitation.is_active());
+        let bob = db.accept_invitation(&invitation, "bob", PASSWORD, None).await.unwrap().unwrap();
+        assert_eq!(db.get_user_by_id(bob).await.unwrap().unwrap().role, Role::Editor);
+        // 2回目は使えない
+        assert!(db.accept_invitation(&invitation, "carol", PASSWORD, None).await.unwrap().is_none());
+
+        // 連続失敗の回数は同じ行を更新して数える
+        let throttle = LoginThrottle {
+            username: ThrottlePolicy::new(2, 15, false),
+            ip: ThrottlePolicy::new(0, 15, false),
+        };
+        let failure = FailedLogin {
+            username: "alice",
+            ip_address: Some("192.0.2.1"),
+            user_agent: None,
+            reason: "invalid_credentials",
+        };
+        db.record_login_failure(&failure, Some(&throttle)).await.unwrap();
+        assert!(db.login_locked_until(ThrottleScope::Username, "alice").await.unwrap().is_none());
+        db.record_login_failure(&failure, Some(&throttle)).await.unwrap();
+        assert!(db.login_locked_until(ThrottleScope::Username, "alice").await.unwrap().is_some());
+        let lockouts = db.list_login_lockouts().await.unwrap();
+        assert_eq!(lockouts.len(), 1);
+        assert_eq!(lockouts[0].failures, 2);
+        assert_eq!(db.list_login_failures(Some("alice"), None, None, 10).await.unwrap().len(), 2);
+    }
+}
+
+#[tokio::test]
+async fn audit_log() {
+    for db in test_databases("audit").await {
+        for action in ["document.create", "document.update", "user.create"] {
+            db.record_audit(&NewAuditEntry {
+                action,
+                actor_id: None,
+                actor: Some("alice"),
+                ip_address: Some("192.0.2.1"),
+                user_agent: None,
+                target_type: "document",
+                target: "notes",
+                before: None,
+                after: Some("after"),
+            })
+            .await
+            .unwrap();
+        }
+
+        let filter = AuditFilter {
+            action: Some("document.*".to_string()),
+            ..AuditFilter::default()
+        };
+        let entries = db.list_audit_entries(&filter, None, 10).await.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, "document.update");
+        let older = db.list_audit_entries(&filter, Some(entries[0].id), 10).await.unwrap();
+        assert_eq!(older.len(), 1);
+
+        let filter = AuditFilter {
+            since: Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+            ..AuditFilter::default()
+        };
+        assert!(db.list_audit_entries(&filter, None, 10).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+// DocumentStore の動作をメモリ上のストアで確認する
+use md_wiki_backend::storage::{DocumentStore, MemoryDocumentStore};
+use md_wiki_backend::AppError;
+
+#[tokio::test]
+async fn write_and_read() {
+    let store = MemoryDocumentStore::new();
+    store.write("guide/setup", "# Setup", "Create guide/setup.md").await.unwrap();
+
+    assert_eq!(store.read("guide/setup").await.unwrap(), "# Setup");
+    assert!(store.exists("guide/setup").await.unwrap());
+    assert!(matches!(store.read("missing").await, Err(AppError::NotFound(_))));
+}
+
+#[tokio::test]
+async fn update_keeps_history() {
+    let store = MemoryDocumentStore::new();
+    store.write("notes", "v1", "Create notes.md").await.unwrap();
+    store.write("notes", "v2", "Update notes.md").await.unwrap();
+
+    assert_eq!(store.read("notes").await.unwrap(), "v2");
+    let history = store.history("notes").await.unwrap();
+    assert_eq!(history.len(), 2);
+    // 新しい順
+    assert_eq!(history[0].message, "Update notes.md");
+    assert_eq!(store.read_version("notes", &history[1].id).await.unwrap(), "v1");
+}
+
+#[tokio::test]
+async fn delete_hides_document_but_keeps_history() {
+    let store = MemoryDocumentStore::new();
+    store.write("notes", "v1", "Create notes.md").await.unwrap();
+    store.delete("notes", "Delete notes.md").await.unwrap();
+
+    assert!(!store.exists("notes").await.unwrap());
+    assert!(store.list().await.unwrap().is_empty());
+    assert!(matches!(store.read("notes").await, Err(AppError::NotFound(_))));
+    assert!(matches!(store.delete("notes", "Delete notes.md").await, Err(AppError::NotFound(_))));
+
+    let history = store.history("notes").await.unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(store.read_version("notes", &history[1].id).await.unwrap(), "v1");
+}
+
+#[tokio::test]
+async fn list_returns_current_documents() {
+    let store = MemoryDocumentStore::new();
+    store.write("a", "a", "Create a.md").await.unwrap();
+    store.write("folder/b", "b", "Create folder/b.md").await.unwrap();
+
+    let names: Vec<String> = store.list().await.unwrap().into_iter().map(|doc| doc.name).collect();
+    assert_eq!(names, vec!["a".to_string(), "folder/b".to_string()]);
+}
+
+#[tokio::test]
+async fn rename_moves_content() {
+    let store = MemoryDocumentStore::new();
+    store.write("old", "content", "Create old.md").await.unwrap();
+    store.write("taken", "other", "Create taken.md").await.unwrap();
+
+    assert!(matches!(store.rename("old", "taken", "Rename").await, Err(AppError::Conflict(_))));
+    assert!(matches!(store.rename("missing", "new", "Rename").await, Err(AppError::NotFound(_))));
+
+    store.rename("old", "folder/new", "Rename old.md to folder/new.md").await.unwrap();
+    assert!(!store.exists("old").await.unwrap());
+    assert_eq!(store.read("folder/new").await.unwrap(), "content");
+
+    // 元の名前の削除と新しい名前の追加は同じコミットになる
+    let old_history = store.history("old").await.unwrap();
+    let new_history = store.history("folder/new").await.unwrap();
+    assert_eq!(old_history[0].id, new_history[0].id);
+    assert_eq!(new_history[0].message, "Rename old.md to folder/new.md");
+}
+
+#[tokio::test]
+async fn rejects_invalid_names() {
+    let store = MemoryDocumentStore::new();
+    for name in ["../escape", "/absolute", ".git/config", "a//b", ""] {
+        assert!(
+            matches!(store.write(name, "x", "Create").await, Err(AppError::InvalidInput(_))),
+            "{} should be rejected",
+            name
+        );
+    }
+}
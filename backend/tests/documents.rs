@@ -0,0 +1,201 @@
+// ドキュメントのAPIをメモリ上のストアで確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use md_wiki_backend::models::user::Role;
+use md_wiki_backend::storage::DocumentStore;
+use serde_json::json;
+
+#[tokio::test]
+async fn create_read_update_delete() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+
+    let (status, _) = app.post("/api/documents", token, json!({ "filename": "notes", "content": "# v1" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(app.store.read("notes").await.unwrap(), "# v1");
+
+    // 同じ名前では作成できない
+    let (status, _) = app.post("/api/documents", token, json!({ "filename": "notes", "content": "# again" })).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, body) = app.get("/api/documents/notes", token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], "# v1");
+
+    let (status, _) = app.put("/api/documents/notes", token, json!({ "filename": "notes", "content": "# v2" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.get("/api/documents/notes", token).await;
+    assert_eq!(body["content"], "# v2");
+
+    let (status, _) = app.delete("/api/documents/notes", token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get("/api/documents/notes", token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.delete("/api/documents/notes", token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn list_documents() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+
+    for name in ["alpha", "guide%2Fsetup", "guide%2Finstall"] {
+        let (status, _) = app.put(&format!("/api/documents/{}", name), token, json!({ "filename": name, "content": "x" })).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (_, body) = app.get("/api/documents?sort=title", token).await;
+    let names: Vec<&str> = body["documents"].as_array().unwrap().iter().map(|doc| doc["filename"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["alpha", "guide/install", "guide/setup"]);
+
+    let (_, body) = app.get("/api/documents?folder=guide&sort=title&limit=1", token).await;
+    assert_eq!(body["documents"][0]["filename"], "guide/install");
+    let cursor = body["next_cursor"].as_str().unwrap().to_string();
+    let (_, body) = app.get(&format!("/api/documents?folder=guide&sort=title&limit=1&cursor={}", cursor), token).await;
+    assert_eq!(body["documents"][0]["filename"], "guide/setup");
+}
+
+// ページをたどると、並び順どおりに重複も取りこぼしもなく全件が返る
+#[tokio::test]
+async fn list_documents_in_pages() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    for name in ["e", "d", "c", "b", "a", "hidden"] {
+        let (status, _) = app.post("/api/documents", Some(&admin), json!({ "filename": name, "content": "x" })).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    // 読めないドキュメントはページの件数に数えない
+    let (status, _) = app
+        .put("/api/documents/hidden/acl", Some(&admin), json!({ "entries": [{ "user": "admin", "permission": "admin" }] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, first) = app.get("/api/documents?sort=title&limit=3", Some(&editor)).await;
+    assert_eq!(status, StatusCode::OK);
+    let names = |body: &serde_json::Value| -> Vec<String> {
+        body["documents"].as_array().unwrap().iter().map(|doc| doc["filename"].as_str().unwrap().to_string()).collect()
+    };
+    assert_eq!(names(&first), vec!["a", "b", "c"]);
+
+    let cursor = first["next_cursor"].as_str().unwrap();
+    let (status, second) = app.get(&format!("/api/documents?sort=title&limit=3&cursor={}", cursor), Some(&editor)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(names(&second), vec!["d", "e"]);
+    assert!(second["next_cursor"].is_null());
+
+    // 逆順と、同じ日時のドキュメントが並ぶ更新日時順
+    let (_, body) = app.get("/api/documents?sort=title&order=desc&limit=4", Some(&editor)).await;
+    assert_eq!(names(&body), vec!["e", "d", "c", "b"]);
+    let mut walked = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let (status, body) = app.get(&format!("/api/documents?limit=2&cursor={}", cursor), Some(&editor)).await;
+        assert_eq!(status, StatusCode::OK);
+        walked.extend(names(&body));
+        match body["next_cursor"].as_str() {
+            Some(next) => cursor = next.to_string(),
+            None => break,
+        }
+    }
+    walked.sort();
+    assert_eq!(walked, vec!["a", "b", "c", "d", "e"]);
+
+    let (status, _) = app.get("/api/documents?cursor=not-a-cursor", Some(&editor)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.get(&format!("/api/documents?cursor={}", "6e6f746573"), Some(&editor)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn history_and_versions() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+
+    app.post("/api/documents", token, json!({ "filename": "notes", "content": "# v1" })).await;
+    app.put("/api/documents/notes", token, json!({ "filename": "notes", "content": "# v2" })).await;
+
+    let (status, body) = app.get("/api/documents/notes/history", token).await;
+    assert_eq!(status, StatusCode::OK);
+    let commits = body["commits"].as_array().unwrap();
+    assert_eq!(commits.len(), 2);
+    assert_eq!(commits[0]["message"], "Update notes.md");
+    let first = commits[1]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app.get(&format!("/api/documents/notes/version/{}", first), token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], "# v1");
+
+    let (status, _) = app.get("/api/documents/notes/version/ffffffff", token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // 以前の版に戻すと、新しいコミットとして記録される
+    let (status, body) = app.post(&format!("/api/documents/notes/revert/{}", first), token, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], "# v1");
+    assert_eq!(app.store.read("notes").await.unwrap(), "# v1");
+    assert_eq!(app.store.history("notes").await.unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn rename_moves_document_and_metadata() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+
+    app.post("/api/documents", token, json!({ "filename": "draft", "content": "# Draft" })).await;
+    app.post("/api/documents", token, json!({ "filename": "taken", "content": "# Taken" })).await;
+    let (status, _) = app.put("/api/documents/draft/metadata", token, json!({ "title": "Draft", "tags": ["plan"] })).await;
+    assert!(status.is_success());
+    let (_, body) = app.post("/api/documents/draft/shares", token, json!({})).await;
+    let share_url = body["url"].as_str().unwrap().to_string();
+
+    let (status, _) = app.post("/api/documents/draft/rename", token, json!({ "new_filename": "taken" })).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (status, _) = app.post("/api/documents/missing/rename", token, json!({ "new_filename": "other" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, body) = app.post("/api/documents/draft/rename", token, json!({ "new_filename": "plans/final" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["filename"], "plans/final");
+    assert_eq!(body["content"], "# Draft");
+
+    let (status, _) = app.get("/api/documents/draft", token).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = app.get("/api/documents/plans%2Ffinal/metadata", token).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["title"], "Draft");
+    assert_eq!(body["tags"], json!(["plan"]));
+    assert_eq!(app.store.history("plans/final").await.unwrap()[0].message, "Rename draft.md to plans/final.md");
+
+    // 名前を変える前の共有リンクも新しい名前のドキュメントを指す
+    let (status, body) = app.get(&share_url, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["filename"], "plans/final");
+}
+
+#[tokio::test]
+async fn viewers_cannot_change_documents() {
+    let app = TestApp::new().await;
+    let viewer = app.login_as("viewer", Role::Viewer).await;
+    let viewer = Some(viewer.as_str());
+    app.store.write("notes", "# v1", "Create notes.md").await.unwrap();
+
+    let (status, body) = app.get("/api/documents/notes", viewer).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], "# v1");
+
+    let (status, _) = app.put("/api/documents/notes", viewer, json!({ "filename": "notes", "content": "# v2" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.delete("/api/documents/notes", viewer).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.post("/api/documents/notes/rename", viewer, json!({ "new_filename": "moved" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(app.store.read("notes").await.unwrap(), "# v1");
+}
@@ -0,0 +1,111 @@
+// 招待による登録
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, PASSWORD};
+use md_wiki_backend::auth::session::{generate_secret_token, hash_secret_token};
+use md_wiki_backend::db::{AuditFilter, NewInvitation};
+use md_wiki_backend::models::user::Role;
+use serde_json::{json, Value};
+
+// 招待を作成し、id とトークンを返す
+async fn invite(app: &TestApp, admin: &str, request: Value) -> (i64, String) {
+    let (status, body) = app.post("/api/users/invitations", Some(admin), request).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    (body["id"].as_i64().unwrap(), body["token"].as_str().unwrap().to_string())
+}
+
+async fn accept(app: &TestApp, request: Value) -> (StatusCode, Value) {
+    app.post("/api/auth/accept-invite", None, request).await
+}
+
+#[tokio::test]
+async fn accepted_invitation_assigns_the_invited_role_once() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let (_, token) = invite(&app, &admin, json!({ "username": "bob", "role": "editor" })).await;
+
+    let (status, preview) = app.get(&format!("/api/auth/invitations/{}", token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["role"], "editor");
+
+    let (status, body) = accept(&app, json!({ "token": token, "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", body);
+    let (_, me) = app.get("/api/auth/me", body["token"].as_str()).await;
+    assert_eq!(me["username"], "bob");
+    assert_eq!(me["role"], "editor");
+    let bob = app.db().get_user_by_username("bob").await.unwrap().unwrap();
+    assert_eq!(bob.role, Role::Editor);
+
+    // 一度使った招待は使えない
+    let (status, _) = accept(&app, json!({ "token": token, "username": "carol", "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(app.db().get_user_by_username("carol").await.unwrap().is_none());
+    let (status, _) = app.get(&format!("/api/auth/invitations/{}", token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn expired_and_revoked_invitations_are_rejected() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+
+    let expired = generate_secret_token();
+    app.db()
+        .create_invitation(NewInvitation {
+            token_hash: hash_secret_token(&expired),
+            username: Some("late".to_string()),
+            email: None,
+            role: Role::Viewer,
+            created_by: None,
+            expires_at: chrono::Utc::now() - chrono::Duration::minutes(1),
+        })
+        .await
+        .unwrap();
+    let (status, _) = accept(&app, json!({ "token": expired, "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(app.db().get_user_by_username("late").await.unwrap().is_none());
+
+    let (id, token) = invite(&app, &admin, json!({ "username": "bob" })).await;
+    let (status, _) = app.delete(&format!("/api/users/invitations/{}", id), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = accept(&app, json!({ "token": token, "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(app.db().get_user_by_username("bob").await.unwrap().is_none());
+
+    // 取り消しは監査ログに残り、二度目は見つからない
+    let filter = AuditFilter {
+        action: Some("user.invite.revoke".to_string()),
+        ..AuditFilter::default()
+    };
+    let entries = app.db().list_audit_entries(&filter, None, 100).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].actor.as_deref(), Some("admin"));
+    assert_eq!(entries[0].target, "bob");
+    let (status, _) = app.delete(&format!("/api/users/invitations/{}", id), Some(&admin)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn invited_username_and_email_cannot_be_changed() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+
+    let (_, token) = invite(&app, &admin, json!({ "username": "bob" })).await;
+    let (status, _) = accept(&app, json!({ "token": token, "username": "mallory", "password": PASSWORD })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, token) = invite(&app, &admin, json!({ "email": "carol@example.com" })).await;
+    let request = json!({ "token": token, "username": "carol", "email": "mallory@example.com", "password": PASSWORD });
+    let (status, _) = accept(&app, request).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(app.db().get_user_by_username("mallory").await.unwrap().is_none());
+    assert!(app.db().get_user_by_username("carol").await.unwrap().is_none());
+
+    // 失敗しても招待は使える。メールアドレスの大文字と小文字は区別しない
+    let request = json!({ "token": token, "username": "carol", "email": "Carol@Example.com", "password": PASSWORD });
+    let (status, _) = accept(&app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let carol = app.db().get_user_by_username("carol").await.unwrap().unwrap();
+    assert_eq!(carol.email.as_deref(), Some("carol@example.com"));
+}
@@ -0,0 +1,68 @@
+// 続けて失敗したログインの待ち時間とロック、管理者によるロックの解除を確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::{test_config, TestApp, PASSWORD};
+use md_wiki_backend::models::user::Role;
+use serde_json::json;
+
+#[tokio::test]
+async fn failed_logins_are_delayed_then_locked_until_an_admin_unlocks() {
+    let mut config = test_config();
+    config.login_max_failures = 2;
+    config.login_lockout_minutes = 15;
+    let app = TestApp::with_config(config).await;
+    let admin = app.login_as("root", Role::Admin).await;
+    app.create_user("alice", Role::Viewer).await;
+    let wrong = json!({ "username": "alice", "password": "wrong-password" });
+    let right = json!({ "username": "alice", "password": PASSWORD });
+
+    let (status, _) = app.post("/api/auth/login", None, wrong.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 1回目の失敗の後は1秒待たせる（正しいパスワードでも確認しない）
+    let (status, body) = app.post("/api/auth/login", None, right.clone()).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["retry_after"], 1);
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    // 上限に達するとロックの時間だけ待たせる
+    let (status, _) = app.post("/api/auth/login", None, wrong.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = app.post("/api/auth/login", None, right.clone()).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    let retry_after = body["retry_after"].as_i64().unwrap();
+    assert!((14 * 60..=15 * 60).contains(&retry_after), "retry_after = {}", retry_after);
+
+    let (_, body) = app.get("/api/users/lockouts", Some(&admin)).await;
+    assert_eq!(body["lockouts"][0]["scope"], "username");
+    assert_eq!(body["lockouts"][0]["identifier"], "alice");
+    assert_eq!(body["lockouts"][0]["failures"], 2);
+
+    // 管理者がロックを解除すると、すぐにログインできる
+    let (status, _) = app.delete("/api/users/lockouts?username=alice&ip=127.0.0.1", Some(&admin)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.delete("/api/users/lockouts?username=alice", Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.get("/api/users/lockouts", Some(&admin)).await;
+    assert_eq!(body["lockouts"].as_array().unwrap().len(), 0);
+
+    let (status, body) = app.post("/api/auth/login", None, right.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+
+    // 成功すると失敗の回数もリセットされるため、解除する記録は残らない
+    let (status, _) = app.delete("/api/users/lockouts?username=alice", Some(&admin)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn only_admins_can_unlock() {
+    let app = TestApp::new().await;
+    let editor = app.login_as("editor", Role::Editor).await;
+
+    let (status, _) = app.get("/api/users/lockouts", Some(&editor)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.delete("/api/users/lockouts?username=alice", Some(&editor)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
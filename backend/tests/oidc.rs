@@ -0,0 +1,238 @@
+// OpenID Connect のログインを、ローカルで動かした発行者（ディスカバリ・JWKS・トークンエンドポイント）で確認する
+mod common;
+
+use axum::extract::{Form, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use common::{test_config, TestApp};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use md_wiki_backend::config::OidcConfig;
+use md_wiki_backend::models::user::Role;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const CLIENT_ID: &str = "md-wiki";
+const REDIRECT_URL: &str = "http://localhost:8080/api/auth/oidc/callback";
+
+// 認可コードと、それを発行したときの認可リクエストの内容
+struct IssuedCode {
+    code_challenge: String,
+    nonce: String,
+    claims: Value,
+}
+
+#[derive(Clone)]
+struct MockIssuer {
+    url: String,
+    codes: Arc<Mutex<HashMap<String, IssuedCode>>>,
+}
+
+impl MockIssuer {
+    async fn start() -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = Self {
+            url: format!("http://{}", listener.local_addr().unwrap()),
+            codes: Arc::default(),
+        };
+
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(issuer.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        issuer
+    }
+
+    // 認可エンドポイントでユーザーがログインしたことにして、認可コードを発行する
+    fn issue_code(&self, code_challenge: &str, nonce: &str, claims: Value) -> String {
+        let code = format!("code-{}", self.codes.lock().len());
+        self.codes.lock().insert(
+            code.clone(),
+            IssuedCode {
+                code_challenge: code_challenge.to_string(),
+                nonce: nonce.to_string(),
+                claims,
+            },
+        );
+        code
+    }
+}
+
+async fn discovery(State(issuer): State<MockIssuer>) -> Json<Value> {
+    Json(json!({
+        "issuer": issuer.url,
+        "authorization_endpoint": format!("{}/authorize", issuer.url),
+        "token_endpoint": format!("{}/token", issuer.url),
+        "jwks_uri": format!("{}/jwks", issuer.url),
+    }))
+}
+
+async fn jwks() -> Json<Value> {
+    Json(serde_json::from_str(include_str!("fixtures/oidc_jwks.json")).unwrap())
+}
+
+// PKCE の code_verifier が認可リクエストの code_challenge と合う場合だけ ID トークンを返す
+async fn token(
+    State(issuer): State<MockIssuer>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let invalid_grant = || (StatusCode::BAD_REQUEST, Json(json!({ "error": "invalid_grant" })));
+
+    let issued = issuer.codes.lock().remove(&form["code"]).ok_or_else(invalid_grant)?;
+    let verifier = form.get("code_verifier").ok_or_else(invalid_grant)?;
+    if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) != issued.code_challenge
+        || form["grant_type"] != "authorization_code"
+        || form["client_id"] != CLIENT_ID
+        || form["redirect_uri"] != REDIRECT_URL
+    {
+        return Err(invalid_grant());
+    }
+
+    let mut claims = issued.claims;
+    claims["iss"] = json!(issuer.url);
+    claims["aud"] = json!(CLIENT_ID);
+    claims["exp"] = json!(chrono::Utc::now().timestamp() + 300);
+    claims["nonce"] = json!(issued.nonce);
+
+    let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some("test-key".to_string());
+    let key = EncodingKey::from_rsa_pem(include_bytes!("fixtures/oidc_rsa.pem")).unwrap();
+    Ok(Json(json!({
+        "access_token": "unused",
+        "token_type": "Bearer",
+        "id_token": encode(&header, &claims, &key).unwrap(),
+    })))
+}
+
+async fn oidc_app() -> (TestApp, MockIssuer) {
+    let issuer = MockIssuer::start().await;
+    let mut config = test_config();
+    config.oidc = Some(OidcConfig {
+        issuer_url: issuer.url.clone(),
+        client_id: CLIENT_ID.to_string(),
+        client_secret: None,
+        redirect_url: REDIRECT_URL.to_string(),
+        scopes: "openid email profile".to_string(),
+        username_claim: "preferred_username".to_string(),
+        role_claim: "groups".to_string(),
+        role_mapping: vec!["wiki-editors=editor".to_string(), "wiki-admins=admin".to_string()],
+        default_role: "viewer".to_string(),
+    });
+    (TestApp::with_config(config).await, issuer)
+}
+
+// ログインを始め、プロバイダへのリダイレクト先から state・nonce・code_challenge を取り出す
+struct AuthorizationRequest {
+    state: String,
+    nonce: String,
+    code_challenge: String,
+}
+
+async fn start_login(app: &TestApp) -> AuthorizationRequest {
+    let url = app.state.oidc.as_ref().unwrap().authorization_url().await.unwrap();
+    let url = reqwest::Url::parse(&url).unwrap();
+    let params: HashMap<String, String> = url.query_pairs().into_owned().collect();
+    assert_eq!(params["code_challenge_method"], "S256");
+    assert_eq!(params["redirect_uri"], REDIRECT_URL);
+    AuthorizationRequest {
+        state: params["state"].clone(),
+        nonce: params["nonce"].clone(),
+        code_challenge: params["code_challenge"].clone(),
+    }
+}
+
+async fn callback(app: &TestApp, code: &str, state: &str) -> (StatusCode, Value) {
+    app.get(&format!("/api/auth/oidc/callback?code={}&state={}", code, state), None).await
+}
+
+fn claims(subject: &str, groups: &[&str]) -> Value {
+    json!({
+        "sub": subject,
+        "preferred_username": "dana",
+        "email": "dana@example.com",
+        "groups": groups,
+    })
+}
+
+#[tokio::test]
+async fn first_login_provisions_the_user_and_maps_groups_to_roles() {
+    let (app, issuer) = oidc_app().await;
+
+    let login = start_login(&app).await;
+    let code = issuer.issue_code(&login.code_challenge, &login.nonce, claims("u-1", &["staff", "wiki-editors"]));
+    let (status, body) = callback(&app, &code, &login.state).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    assert_eq!(body["user"]["username"], "dana");
+    assert_eq!(body["user"]["role"], "editor");
+    let user_id = body["user"]["id"].as_i64().unwrap();
+
+    // 日時はほかのユーザーと同じ形式で保存する
+    let user = app.db().get_user_by_id(user_id).await.unwrap().unwrap();
+    assert!(chrono::NaiveDateTime::parse_from_str(&user.created_at, "%Y-%m-%d %H:%M:%S").is_ok(), "{}", user.created_at);
+    assert_eq!(user.email.as_deref(), Some("dana@example.com"));
+
+    // 発行されたトークンでAPIを使える
+    let (status, _) = app.get("/api/auth/me", body["token"].as_str()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // ログインのたびにグループからロールを合わせる
+    for (groups, role) in [(vec!["wiki-admins"], Role::Admin), (vec![], Role::Viewer)] {
+        let login = start_login(&app).await;
+        let code = issuer.issue_code(&login.code_challenge, &login.nonce, claims("u-1", &groups));
+        let (status, body) = callback(&app, &code, &login.state).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+        assert_eq!(body["user"]["id"], user_id);
+        assert_eq!(app.db().get_user_by_id(user_id).await.unwrap().unwrap().role, role);
+    }
+}
+
+#[tokio::test]
+async fn state_must_match_a_pending_login() {
+    let (app, issuer) = oidc_app().await;
+
+    let login = start_login(&app).await;
+    let code = issuer.issue_code(&login.code_challenge, &login.nonce, claims("u-1", &[]));
+    let (status, _) = callback(&app, &code, "not-the-state").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // state は1回しか使えない
+    let (status, _) = callback(&app, &code, &login.state).await;
+    assert_eq!(status, StatusCode::OK);
+    let code = issuer.issue_code(&login.code_challenge, &login.nonce, claims("u-1", &[]));
+    let (status, _) = callback(&app, &code, &login.state).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn nonce_must_match_the_authorization_request() {
+    let (app, issuer) = oidc_app().await;
+
+    let login = start_login(&app).await;
+    let code = issuer.issue_code(&login.code_challenge, "another-nonce", claims("u-1", &[]));
+    let (status, _) = callback(&app, &code, &login.state).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(app.db().get_user_by_username("dana").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn code_verifier_must_match_the_code_challenge() {
+    let (app, issuer) = oidc_app().await;
+
+    // 別のログインの code_challenge で発行されたコードは、このログインの code_verifier では交換できない
+    let other = start_login(&app).await;
+    let login = start_login(&app).await;
+    let code = issuer.issue_code(&other.code_challenge, &login.nonce, claims("u-1", &[]));
+    let (status, _) = callback(&app, &code, &login.state).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let code = issuer.issue_code(&other.code_challenge, &other.nonce, claims("u-1", &[]));
+    let (status, _) = callback(&app, &code, &other.state).await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,124 @@
+// メールによるパスワードの再設定
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::http::StatusCode;
+use common::{test_config, TestApp, PASSWORD};
+use md_wiki_backend::auth::session::{generate_secret_token, hash_secret_token};
+use md_wiki_backend::error::AppError;
+use md_wiki_backend::mail::{MailMessage, Mailer};
+use md_wiki_backend::models::user::Role;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+
+const NEW_PASSWORD: &str = "another-long-password";
+
+// 送ったメールを覚えておく
+#[derive(Debug, Default)]
+struct FakeMailer {
+    sent: Mutex<Vec<MailMessage>>,
+}
+
+#[async_trait]
+impl Mailer for FakeMailer {
+    async fn send(&self, message: &MailMessage) -> Result<(), AppError> {
+        self.sent.lock().push(message.clone());
+        Ok(())
+    }
+}
+
+impl FakeMailer {
+    // メールは応答の後に送られるので、届くまで少し待つ
+    async fn wait_for(&self, count: usize) -> Vec<MailMessage> {
+        for _ in 0..100 {
+            if self.sent.lock().len() >= count {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        self.sent.lock().clone()
+    }
+}
+
+async fn reset_app() -> (TestApp, Arc<FakeMailer>) {
+    let mailer = Arc::new(FakeMailer::default());
+    let app = TestApp::with_mailer(test_config(), mailer.clone()).await;
+    (app, mailer)
+}
+
+fn reset_token(message: &MailMessage) -> String {
+    let start = message.body.find("token=").expect("reset link") + "token=".len();
+    message.body[start..].split_whitespace().next().unwrap().to_string()
+}
+
+async fn request_reset(app: &TestApp, email: &str) -> (StatusCode, Value) {
+    app.post("/api/auth/password-reset", None, json!({ "email": email })).await
+}
+
+async fn confirm_reset(app: &TestApp, token: &str) -> StatusCode {
+    app.post("/api/auth/password-reset/confirm", None, json!({ "token": token, "password": NEW_PASSWORD }))
+        .await
+        .0
+}
+
+async fn login(app: &TestApp, password: &str) -> StatusCode {
+    app.post("/api/auth/login", None, json!({ "username": "alice", "password": password })).await.0
+}
+
+#[tokio::test]
+async fn reset_email_is_sent_and_the_token_works_once() {
+    let (app, mailer) = reset_app().await;
+    app.create_user("alice", Role::Editor).await;
+
+    let (status, _) = request_reset(&app, "alice@example.com").await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let sent = mailer.wait_for(1).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, "alice@example.com");
+    assert!(sent[0].body.contains("Hello alice"));
+    let token = reset_token(&sent[0]);
+
+    assert_eq!(confirm_reset(&app, &token).await, StatusCode::OK);
+    assert_eq!(login(&app, PASSWORD).await, StatusCode::UNAUTHORIZED);
+    // 1回失敗した後のログインは1秒待つ
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    assert_eq!(login(&app, NEW_PASSWORD).await, StatusCode::OK);
+
+    // 一度使ったトークンは使えない
+    assert_eq!(confirm_reset(&app, &token).await, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn response_is_the_same_for_known_and_unknown_addresses() {
+    let (app, mailer) = reset_app().await;
+    app.create_user("alice", Role::Editor).await;
+
+    let known = request_reset(&app, "alice@example.com").await;
+    let unknown = request_reset(&app, "nobody@example.com").await;
+    assert_eq!(known, unknown);
+    assert_eq!(known.0, StatusCode::ACCEPTED);
+
+    // 登録されているアドレスにだけ送る
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let sent = mailer.wait_for(1).await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, "alice@example.com");
+}
+
+#[tokio::test]
+async fn expired_reset_tokens_are_rejected() {
+    let (app, _) = reset_app().await;
+    let user_id = app.create_user("alice", Role::Editor).await;
+
+    let token = generate_secret_token();
+    app.db()
+        .create_password_reset(user_id, &hash_secret_token(&token), chrono::Utc::now() - chrono::Duration::minutes(1))
+        .await
+        .unwrap();
+    assert_eq!(confirm_reset(&app, &token).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(confirm_reset(&app, "unknown").await, StatusCode::UNAUTHORIZED);
+    assert_eq!(login(&app, PASSWORD).await, StatusCode::OK);
+}
@@ -0,0 +1,89 @@
+// ロールによるルートの制限（require_editor / require_admin）を確認する
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use md_wiki_backend::models::user::Role;
+use serde_json::json;
+
+// 編集者以上に限るルート
+fn editor_routes() -> Vec<(Method, &'static str, serde_json::Value)> {
+    vec![
+        (Method::POST, "/api/documents", json!({ "filename": "notes", "content": "x" })),
+        (Method::PUT, "/api/documents/notes", json!({ "filename": "notes", "content": "x" })),
+        (Method::DELETE, "/api/documents/notes", json!({})),
+        (Method::PUT, "/api/documents/notes/tags", json!({ "tags": ["a"] })),
+        (Method::PUT, "/api/tags/a", json!({ "description": "x" })),
+        (Method::GET, "/api/shares", json!({})),
+    ]
+}
+
+// 管理者に限るルート
+fn admin_routes() -> Vec<(Method, &'static str, serde_json::Value)> {
+    vec![
+        (Method::GET, "/api/users", json!({})),
+        (Method::POST, "/api/users", json!({ "username": "new", "password": "correct-horse-battery", "role": "viewer" })),
+        (Method::PUT, "/api/users/1/role", json!({ "role": "admin" })),
+        (Method::GET, "/api/users/invitations", json!({})),
+        (Method::GET, "/api/users/lockouts", json!({})),
+        (Method::GET, "/api/audit", json!({})),
+        (Method::GET, "/api/groups", json!({})),
+        (Method::POST, "/api/groups", json!({ "name": "writers" })),
+    ]
+}
+
+async fn status(app: &TestApp, method: Method, uri: &str, token: &str, body: serde_json::Value) -> StatusCode {
+    let body = if method == Method::GET || method == Method::DELETE { None } else { Some(body) };
+    app.request(method, uri, Some(token), body).await.0
+}
+
+#[tokio::test]
+async fn viewer_is_forbidden_from_editor_and_admin_routes() {
+    let app = TestApp::new().await;
+    let token = app.login_as("viewer", Role::Viewer).await;
+
+    for (method, uri, body) in editor_routes().into_iter().chain(admin_routes()) {
+        assert_eq!(status(&app, method.clone(), uri, &token, body).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+    }
+
+    // 閲覧は許される
+    let (status, _) = app.get("/api/documents", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn editor_is_forbidden_from_admin_routes() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+
+    for (method, uri, body) in admin_routes() {
+        assert_eq!(status(&app, method.clone(), uri, &token, body).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+    }
+
+    // 編集者のルートはロールでは拒まれない
+    for (method, uri, body) in editor_routes() {
+        assert_ne!(status(&app, method.clone(), uri, &token, body).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn admin_passes_admin_routes() {
+    let app = TestApp::new().await;
+    let token = app.login_as("admin", Role::Admin).await;
+
+    for (method, uri, body) in admin_routes() {
+        let status = status(&app, method.clone(), uri, &token, body).await;
+        assert_ne!(status, StatusCode::FORBIDDEN, "{} {}", method, uri);
+        assert_ne!(status, StatusCode::UNAUTHORIZED, "{} {}", method, uri);
+    }
+}
+
+#[tokio::test]
+async fn missing_token_is_unauthorized() {
+    let app = TestApp::new().await;
+
+    let (status, _) = app.get("/api/users", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post("/api/documents", None, json!({ "filename": "notes", "content": "x" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,167 @@
+// セッションの取り消しがアクセストークンにすぐ反映されることを確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::{TestApp, PASSWORD};
+use md_wiki_backend::auth::session::hash_secret_token;
+use md_wiki_backend::db::AuditFilter;
+use md_wiki_backend::models::user::Role;
+use serde_json::json;
+
+#[tokio::test]
+async fn logout_revokes_the_access_token() {
+    let app = TestApp::new().await;
+    let token = app.login_as("alice", Role::Viewer).await;
+    let token = Some(token.as_str());
+
+    let (status, _) = app.get("/api/auth/me", token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.post("/api/auth/logout", token, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get("/api/auth/me", token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn revoking_a_session_revokes_only_its_access_token() {
+    let app = TestApp::new().await;
+    let alice = app.create_user("alice", Role::Viewer).await;
+    let current = app.token_for(alice, Role::Viewer).await;
+    let other = app.token_for(alice, Role::Viewer).await;
+
+    let sessions = app.db().list_user_sessions(alice).await.unwrap();
+    let other_id = sessions.iter().map(|session| session.id).max().unwrap();
+    let (status, _) = app.delete(&format!("/api/auth/sessions/{}", other_id), Some(&current)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.get("/api/auth/me", Some(&other)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get("/api/auth/me", Some(&current)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn admin_can_sign_out_a_user_everywhere() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+
+    let (status, _) = app.delete(&format!("/api/users/{}/sessions", alice), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.get("/api/auth/me", Some(&token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post("/api/documents", Some(&token), json!({ "filename": "notes", "content": "x" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn disabled_users_are_rejected_with_access_and_api_tokens() {
+    let app = TestApp::new().await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    let (status, body) = app.post("/api/auth/tokens", Some(&token), json!({ "name": "ci", "scope": "read" })).await;
+    assert!(status.is_success());
+    let api_token = body["token"].as_str().unwrap().to_string();
+
+    // セッションを残したまま無効にしても、どちらのトークンも使えない
+    app.db().set_user_disabled(alice, true).await.unwrap();
+    for token in [&token, &api_token] {
+        let (status, _) = app.get("/api/auth/me", Some(token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    app.db().set_user_disabled(alice, false).await.unwrap();
+    for token in [&token, &api_token] {
+        let (status, _) = app.get("/api/auth/me", Some(token)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn password_reset_revokes_sessions_and_api_tokens() {
+    let app = TestApp::new().await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    let (_, body) = app.post("/api/auth/tokens", Some(&token), json!({ "name": "ci", "scope": "write" })).await;
+    let api_token = body["token"].as_str().unwrap().to_string();
+
+    let reset = "reset-token";
+    app.db()
+        .create_password_reset(alice, &hash_secret_token(reset), chrono::Utc::now() + chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    let (status, _) = app
+        .post("/api/auth/password-reset/confirm", None, json!({ "token": reset, "password": "a-brand-new-password" }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    for token in [&token, &api_token] {
+        let (status, _) = app.get("/api/auth/me", Some(token)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+    assert!(app.db().list_user_api_tokens(alice).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn reusing_a_rotated_refresh_token_revokes_the_session() {
+    let app = TestApp::new().await;
+    app.create_user("alice", Role::Editor).await;
+    let (status, body) = app
+        .post("/api/auth/login", None, json!({ "username": "alice", "password": PASSWORD }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let first = body["refresh_token"].as_str().unwrap().to_string();
+
+    let (status, body) = app.post("/api/auth/refresh", None, json!({ "refresh_token": first })).await;
+    assert_eq!(status, StatusCode::OK);
+    let second = body["refresh_token"].as_str().unwrap().to_string();
+    let token = body["token"].as_str().unwrap().to_string();
+    let (status, _) = app.get("/api/auth/me", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 置き換え済みのトークンが使われたら、新しいトークンも含めてセッションごと使えなくなる
+    let (status, _) = app.post("/api/auth/refresh", None, json!({ "refresh_token": first })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post("/api/auth/refresh", None, json!({ "refresh_token": second })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get("/api/auth/me", Some(&token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let filter = AuditFilter {
+        action: Some("auth.refresh_token_reuse".to_string()),
+        ..AuditFilter::default()
+    };
+    let entries = app.db().list_audit_entries(&filter, None, 100).await.unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].target, "alice");
+
+    // 知らないトークンはセッションに影響しない
+    let (status, _) = app.post("/api/auth/refresh", None, json!({ "refresh_token": "unknown" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn role_changes_apply_to_issued_access_tokens() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let alice = app.create_user("alice", Role::Admin).await;
+    let token = app.token_for(alice, Role::Admin).await;
+
+    let (status, _) = app.get("/api/users", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.put(&format!("/api/users/{}/role", alice), Some(&admin), json!({ "role": "viewer" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // トークンには admin と入っているが、現在のロールで判断される
+    let (status, _) = app.get("/api/users", Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.post("/api/documents", Some(&token), json!({ "filename": "notes", "content": "x" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = app.get("/api/auth/me", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["role"], "viewer");
+}
@@ -0,0 +1,85 @@
+// 初期設定（最初の管理者の作成）
+mod common;
+
+use axum::http::StatusCode;
+use common::{test_config, TestApp, PASSWORD};
+use md_wiki_backend::auth::setup::issue_setup_token;
+use md_wiki_backend::config::RegistrationMode;
+use md_wiki_backend::models::user::Role;
+use serde_json::{json, Value};
+
+async fn viewer_registration_app() -> TestApp {
+    let mut config = test_config();
+    config.registration_mode = RegistrationMode::Viewer;
+    TestApp::with_config(config).await
+}
+
+fn setup_request(token: &str, username: &str) -> Value {
+    json!({ "setup_token": token, "username": username, "password": PASSWORD })
+}
+
+#[tokio::test]
+async fn setup_token_creates_the_first_admin_once() {
+    let app = viewer_registration_app().await;
+    let (_, body) = app.get("/api/auth/setup", None).await;
+    assert_eq!(body["setup_required"], true);
+    // 起動時は期限内のトークンがあれば発行し直さない
+    assert!(!app.db().has_setup_token().await.unwrap());
+    let token = issue_setup_token(app.db()).await.unwrap().expect("setup token");
+    assert!(app.db().has_setup_token().await.unwrap());
+
+    let (status, _) = app.post("/api/auth/setup", None, setup_request("wrong", "admin")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = app.post("/api/auth/setup", None, setup_request(&token, "admin")).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert!(body["token"].is_string());
+    let admin = app.db().get_user_by_username("admin").await.unwrap().unwrap();
+    assert_eq!(admin.role, Role::Admin);
+    assert!(!app.db().has_setup_token().await.unwrap());
+
+    // 同じトークンは二度使えない
+    let (status, _) = app.post("/api/auth/setup", None, setup_request(&token, "second")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(app.db().get_user_by_username("second").await.unwrap().is_none());
+    let (_, body) = app.get("/api/auth/setup", None).await;
+    assert_eq!(body["setup_required"], false);
+}
+
+#[tokio::test]
+async fn register_and_login_are_refused_until_setup_is_done() {
+    let app = viewer_registration_app().await;
+    let registration = json!({ "username": "alice", "password": PASSWORD, "email": "alice@example.com" });
+    let credentials = json!({ "username": "alice", "password": PASSWORD });
+
+    let (status, _) = app.post("/api/auth/register", None, registration.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.post("/api/auth/login", None, credentials.clone()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(app.db().list_users().await.unwrap().is_empty());
+
+    let token = issue_setup_token(app.db()).await.unwrap().expect("setup token");
+    let (status, _) = app.post("/api/auth/setup", None, setup_request(&token, "admin")).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _) = app.post("/api/auth/register", None, registration).await;
+    assert!(status.is_success(), "register returned {}", status);
+    let (status, body) = app.post("/api/auth/login", None, credentials).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+}
+
+#[tokio::test]
+async fn setup_is_refused_once_any_user_exists() {
+    let app = TestApp::new().await;
+    // ユーザーがいない間に発行したトークンも、ユーザーができた後は使えない
+    let token = issue_setup_token(app.db()).await.unwrap().expect("setup token");
+    app.create_user("viewer", Role::Viewer).await;
+
+    assert!(issue_setup_token(app.db()).await.unwrap().is_none());
+    let (status, _) = app.post("/api/auth/setup", None, setup_request(&token, "admin")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert!(app.db().get_user_by_username("admin").await.unwrap().is_none());
+    let (_, body) = app.get("/api/auth/setup", None).await;
+    assert_eq!(body["setup_required"], false);
+}
@@ -0,0 +1,79 @@
+// 共有リンクを使うたびに作成者の状態と権限を確かめることを確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use md_wiki_backend::models::user::Role;
+use serde_json::json;
+
+// ドキュメントを作り、作成者として共有リンクを発行する
+async fn share(app: &TestApp, token: &str, permission: &str) -> String {
+    let (status, _) = app.post("/api/documents", Some(token), json!({ "filename": "notes", "content": "x" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.post("/api/documents/notes/shares", Some(token), json!({ "permission": permission })).await;
+    assert!(status.is_success());
+    body["url"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn links_stop_working_while_the_creator_is_disabled() {
+    let app = TestApp::new().await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    let url = share(&app, &token, "read").await;
+
+    let (status, body) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], "x");
+
+    app.db().set_user_disabled(alice, true).await.unwrap();
+    let (status, _) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::GONE);
+
+    app.db().set_user_disabled(alice, false).await.unwrap();
+    let (status, _) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn links_follow_the_creators_current_permission() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    app.create_user("bob", Role::Editor).await;
+    let url = share(&app, &token, "write").await;
+
+    let (status, _) = app.put(&url, None, json!({ "content": "y" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 閲覧者になると書き込めないが、閲覧はできる
+    assert!(app.db().update_user_role("alice", "viewer").await.unwrap());
+    let (status, _) = app.put(&url, None, json!({ "content": "z" })).await;
+    assert_eq!(status, StatusCode::GONE);
+    let (status, body) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["content"], "y");
+
+    // ACLで閲覧できなくなると使えない
+    let (status, _) = app
+        .put("/api/documents/notes/acl", Some(&admin), json!({ "entries": [{ "user": "bob", "permission": "admin" }] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::GONE);
+}
+
+#[tokio::test]
+async fn links_of_deleted_users_stop_working() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    let url = share(&app, &token, "read").await;
+
+    let (status, _) = app.delete(&format!("/api/users/{}", alice), Some(&admin)).await;
+    assert!(status.is_success());
+    let (status, _) = app.get(&url, None).await;
+    assert_eq!(status, StatusCode::GONE);
+}
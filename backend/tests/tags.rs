@@ -0,0 +1,180 @@
+// タグのAPI
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use md_wiki_backend::db::{AuditEntry, AuditFilter};
+use md_wiki_backend::models::user::Role;
+use md_wiki_backend::storage::DocumentStore;
+use serde_json::{json, Value};
+
+async fn tag_document(app: &TestApp, token: Option<&str>, filename: &str, tags: Value) {
+    app.store.write(filename, "# doc", "Create").await.unwrap();
+    let (status, _) = app.put(&format!("/api/documents/{}/tags", filename), token, json!({ "tags": tags })).await;
+    assert!(status.is_success(), "tagging {} failed: {}", filename, status);
+}
+
+async fn audit_entries(app: &TestApp, action: &str) -> Vec<AuditEntry> {
+    let filter = AuditFilter {
+        action: Some(action.to_string()),
+        ..AuditFilter::default()
+    };
+    app.db().list_audit_entries(&filter, None, 100).await.unwrap()
+}
+
+fn usage_count(tags: &Value, name: &str) -> i64 {
+    tags["tags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|tag| tag["name"] == name)
+        .map(|tag| tag["usage_count"].as_i64().unwrap())
+        .unwrap_or_else(|| panic!("tag {} not found", name))
+}
+
+#[tokio::test]
+async fn merge_rejects_the_same_tag_after_normalization() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+    tag_document(&app, token, "notes", json!(["foo"])).await;
+
+    for source in ["foo", "%20foo%20", "foo%2F"] {
+        let (status, _) = app.post(&format!("/api/tags/{}/merge", source), token, json!({ "into": " foo " })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "merging {} into foo", source);
+    }
+
+    // 別のタグへの統合はできる
+    tag_document(&app, token, "other", json!(["bar"])).await;
+    let (status, _) = app.post("/api/tags/%20bar%20/merge", token, json!({ "into": "foo" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, tags) = app.get("/api/tags", token).await;
+    assert_eq!(usage_count(&tags, "foo"), 2);
+}
+
+#[tokio::test]
+async fn usage_count_only_includes_readable_documents() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    let editor = app.login_as("editor", Role::Editor).await;
+    let admin = Some(admin.as_str());
+    let editor = Some(editor.as_str());
+
+    tag_document(&app, admin, "public", json!(["plan"])).await;
+    tag_document(&app, admin, "secret", json!(["plan", "hidden"])).await;
+    let (status, _) = app
+        .put("/api/documents/secret/acl", admin, json!({ "entries": [{ "user": "admin", "permission": "admin" }] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, tags) = app.get("/api/tags", admin).await;
+    assert_eq!(usage_count(&tags, "plan"), 2);
+    assert_eq!(usage_count(&tags, "hidden"), 1);
+
+    let (_, tags) = app.get("/api/tags", editor).await;
+    assert_eq!(usage_count(&tags, "plan"), 1);
+    assert_eq!(usage_count(&tags, "hidden"), 0);
+}
+
+#[tokio::test]
+async fn document_list_tag_filter_uses_synonyms_and_descendants() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+
+    tag_document(&app, token, "cluster", json!(["infra/kubernetes"])).await;
+    tag_document(&app, token, "network", json!(["infra"])).await;
+    tag_document(&app, token, "recipes", json!(["cooking"])).await;
+    let (status, _) = app.post("/api/tags/infra%2Fkubernetes/synonyms", token, json!({ "name": "k8s" })).await;
+    assert!(status.is_success());
+
+    let filenames = |body: Value| -> Vec<String> {
+        let mut names: Vec<String> = body["documents"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|doc| doc["filename"].as_str().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+
+    let (_, body) = app.get("/api/documents?tag=infra", token).await;
+    assert_eq!(filenames(body), vec!["cluster", "network"]);
+    let (_, body) = app.get("/api/documents?tag=k8s", token).await;
+    assert_eq!(filenames(body), vec!["cluster"]);
+    let (_, body) = app.get("/api/documents?tag=infra%2Fkubernetes", token).await;
+    assert_eq!(filenames(body), vec!["cluster"]);
+    let (_, body) = app.get("/api/documents?tag=unknown", token).await;
+    assert!(filenames(body).is_empty());
+}
+
+#[tokio::test]
+async fn tags_are_listed_in_pages() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+    tag_document(&app, token, "notes", json!(["delta", "alpha", "charlie", "bravo", "echo"])).await;
+
+    let mut names = Vec::new();
+    let mut cursor = String::new();
+    loop {
+        let (status, body) = app.get(&format!("/api/tags?limit=2&cursor={}", cursor), token).await;
+        assert_eq!(status, StatusCode::OK);
+        let page: Vec<String> =
+            body["tags"].as_array().unwrap().iter().map(|tag| tag["name"].as_str().unwrap().to_string()).collect();
+        assert!(page.len() <= 2);
+        names.extend(page);
+        match body["next_cursor"].as_str() {
+            Some(next) => cursor = next.to_string(),
+            None => break,
+        }
+    }
+    assert_eq!(names, vec!["alpha", "bravo", "charlie", "delta", "echo"]);
+
+    let (status, _) = app.get("/api/tags?cursor=zz", token).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn tag_paths_are_normalized_and_changes_are_audited() {
+    let app = TestApp::new().await;
+    let token = app.login_as("editor", Role::Editor).await;
+    let token = Some(token.as_str());
+    tag_document(&app, token, "notes", json!(["infra/k8s", "ops"])).await;
+
+    // 前後や階層の区切りに空白があっても同じタグになる
+    let (status, _) = app.put("/api/tags/%20infra%20%2F%20k8s%20", token, json!({ "color": "#336699" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post("/api/tags/infra%2Fk8s%2F/synonyms", token, json!({ "name": "kube" })).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = app.delete("/api/tags/%20infra%2Fk8s/synonyms/%20kube%20", token).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post("/api/tags/%20ops%20/rename", token, json!({ "new_name": "operations" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.put("/api/documents/notes/tags", token, json!({ "tags": ["infra/k8s"] })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.delete("/api/tags/operations%20", token).await;
+    assert_eq!(status, StatusCode::OK);
+
+    tag_document(&app, token, "other", json!(["k8s"])).await;
+    let (status, _) = app.post("/api/tags/%20k8s/merge", token, json!({ "into": "infra/k8s" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, tags) = app.get("/api/tags", token).await;
+    let names: Vec<&str> = tags["tags"].as_array().unwrap().iter().map(|tag| tag["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["infra", "infra/k8s"]);
+    assert_eq!(tags["tags"][1]["color"], "#336699");
+
+    let mut created: Vec<String> = audit_entries(&app, "tag.create").await.into_iter().map(|entry| entry.target).collect();
+    created.sort();
+    assert_eq!(created, vec!["infra", "infra/k8s", "k8s", "ops"]);
+    let renamed = audit_entries(&app, "tag.rename").await;
+    assert_eq!((renamed[0].before.as_deref(), renamed[0].after.as_deref()), (Some("ops"), Some("operations")));
+    let merged = audit_entries(&app, "tag.merge").await;
+    assert_eq!((merged[0].before.as_deref(), merged[0].after.as_deref()), (Some("k8s"), Some("infra/k8s")));
+    let deleted = audit_entries(&app, "tag.delete").await;
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].target, "operations");
+}
@@ -0,0 +1,189 @@
+// 二要素認証（TOTP）の登録と、ログインの2段階目を確認する
+mod common;
+
+use axum::http::StatusCode;
+use common::{test_config, TestApp, PASSWORD};
+use md_wiki_backend::models::user::Role;
+use serde_json::{json, Value};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+// 認証アプリと同じく、秘密鍵から現在（に `offset` ステップ足した時刻）のコードを作る
+fn code(secret: &str, offset: i64) -> String {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    let totp = TOTP::new(Algorithm::SHA1, 6, 0, 30, secret, None, "test".to_string()).unwrap();
+    let time = chrono::Utc::now().timestamp() + offset * 30;
+    totp.generate(time as u64)
+}
+
+async fn login(app: &TestApp, username: &str) -> Value {
+    let (status, body) = app
+        .post("/api/auth/login", None, json!({ "username": username, "password": PASSWORD }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.get("token").is_none());
+    body
+}
+
+// 続けて失敗したログインの後は、失敗の回数と同じ秒数だけ待たないと次の試行を受け付けない
+async fn wait_for_throttle(failures: u64) {
+    tokio::time::sleep(std::time::Duration::from_millis(failures * 1000 + 100)).await;
+}
+
+// 二要素認証を有効にして、秘密鍵とリカバリーコードを返す
+async fn enroll(app: &TestApp, token: &str) -> (String, Vec<String>) {
+    let (status, body) = app.post("/api/auth/totp/setup", Some(token), json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    let secret = body["secret"].as_str().unwrap().to_string();
+    assert!(body["provisioning_uri"].as_str().unwrap().starts_with("otpauth://totp/"));
+
+    let (status, _) = app.post("/api/auth/totp/enable", Some(token), json!({ "code": "000000x" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = app.post("/api/auth/totp/enable", Some(token), json!({ "code": code(&secret, 0) })).await;
+    assert_eq!(status, StatusCode::OK);
+    let recovery_codes: Vec<String> = body["recovery_codes"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|code| code.as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(recovery_codes.len(), 10);
+    (secret, recovery_codes)
+}
+
+#[tokio::test]
+async fn enrolled_users_finish_login_with_a_code() {
+    let app = TestApp::new().await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    let (secret, recovery_codes) = enroll(&app, &token).await;
+
+    let (_, status) = app.get("/api/auth/totp", Some(&token)).await;
+    assert_eq!(status["enabled"], true);
+    assert_eq!(status["recovery_codes_remaining"], 10);
+
+    let challenge = login(&app, "alice").await;
+    assert_eq!(challenge["mfa_required"], true);
+    let mfa_token = challenge["mfa_token"].as_str().unwrap().to_string();
+
+    // 2段階目のトークンは通常のAPIには使えない
+    let (status, _) = app.get("/api/auth/totp", Some(&mfa_token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 登録で使ったコードは、同じ時間ステップなのでもう使えない
+    let (status, _) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "code": code(&secret, 0) }))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    wait_for_throttle(1).await;
+
+    let (status, body) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "code": code(&secret, 1) }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["user"]["username"], "alice");
+    let session_token = body["token"].as_str().unwrap().to_string();
+    let (status, _) = app.get("/api/auth/totp", Some(&session_token)).await;
+    assert_eq!(status, StatusCode::OK);
+    let sessions = app.db().list_user_sessions(alice).await.unwrap().len();
+
+    // 同じ2段階目のトークンでは、正しいリカバリーコードを添えてもセッションを始められない
+    let (status, _) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "recovery_code": recovery_codes[0] }))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.db().list_user_sessions(alice).await.unwrap().len(), sessions);
+}
+
+#[tokio::test]
+async fn recovery_codes_work_once_and_can_be_regenerated() {
+    let app = TestApp::new().await;
+    let alice = app.create_user("alice", Role::Editor).await;
+    let token = app.token_for(alice, Role::Editor).await;
+    let (_, recovery_codes) = enroll(&app, &token).await;
+
+    // 区切りや大文字小文字の違いは無視される
+    let mfa_token = login(&app, "alice").await["mfa_token"].clone();
+    let typed = recovery_codes[0].replace('-', "").to_uppercase();
+    let (status, body) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "recovery_code": typed }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+
+    let mfa_token = login(&app, "alice").await["mfa_token"].clone();
+    let (status, _) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "recovery_code": recovery_codes[0] }))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (_, status) = app.get("/api/auth/totp", Some(&token)).await;
+    assert_eq!(status["recovery_codes_remaining"], 9);
+
+    // 作り直すと以前のコードは使えなくなる
+    let (status, _) = app.post("/api/auth/totp/recovery-codes", Some(&token), json!({})).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = app
+        .post("/api/auth/totp/recovery-codes", Some(&token), json!({ "recovery_code": recovery_codes[1] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let regenerated = body["recovery_codes"].as_array().unwrap().clone();
+    assert_eq!(regenerated.len(), 10);
+    let (_, status) = app.get("/api/auth/totp", Some(&token)).await;
+    assert_eq!(status["recovery_codes_remaining"], 10);
+
+    wait_for_throttle(1).await;
+    let mfa_token = login(&app, "alice").await["mfa_token"].clone();
+    let (status, _) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "recovery_code": recovery_codes[2] }))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    wait_for_throttle(2).await;
+    let (status, _) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "recovery_code": regenerated[0] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn admins_without_totp_must_enroll_while_logging_in() {
+    let mut config = test_config();
+    config.totp_required_for_admins = true;
+    let app = TestApp::with_config(config).await;
+    app.create_user("root", Role::Admin).await;
+    app.create_user("alice", Role::Editor).await;
+
+    // 必須になっていないロールは、そのままログインできる
+    let (status, body) = app
+        .post("/api/auth/login", None, json!({ "username": "alice", "password": PASSWORD }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+
+    let challenge = login(&app, "root").await;
+    assert_eq!(challenge["mfa_setup_required"], true);
+    let mfa_token = challenge["mfa_token"].as_str().unwrap().to_string();
+
+    // 登録しないままではログインできない
+    let (status, _) = app.post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.post("/api/auth/totp/setup", Some(&mfa_token), json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, body) = app.post("/api/auth/login/mfa/setup", None, json!({ "mfa_token": mfa_token })).await;
+    assert_eq!(status, StatusCode::OK);
+    let secret = body["secret"].as_str().unwrap().to_string();
+
+    let (status, body) = app
+        .post("/api/auth/login/mfa", None, json!({ "mfa_token": mfa_token, "code": code(&secret, 0) }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["token"].is_string());
+    assert_eq!(body["recovery_codes"].as_array().unwrap().len(), 10);
+
+    // 次からは登録したコードの確認になる
+    let challenge = login(&app, "root").await;
+    assert_eq!(challenge["mfa_required"], true);
+    let (status, _) = app
+        .post("/api/auth/login/mfa/setup", None, json!({ "mfa_token": challenge["mfa_token"] }))
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,114 @@
+// 未ログインのユーザーに見えるドキュメント（WIKI_MODE、公開設定、ACL）
+mod common;
+
+use axum::http::StatusCode;
+use common::{test_config, TestApp};
+use md_wiki_backend::models::acl::Visibility;
+use md_wiki_backend::models::user::Role;
+use serde_json::{json, Value};
+
+async fn create_document(app: &TestApp, token: &str, name: &str) {
+    let (status, _) =
+        app.post("/api/documents", Some(token), json!({ "filename": name, "content": "shared keyword" })).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+fn filenames(body: &Value, key: &str) -> Vec<String> {
+    let mut names: Vec<String> =
+        body[key].as_array().unwrap().iter().map(|doc| doc["filename"].as_str().unwrap().to_string()).collect();
+    names.sort();
+    names
+}
+
+// 一覧・検索・最近のドキュメント・取得のそれぞれで、未ログインで見えるドキュメント
+async fn anonymous_view(app: &TestApp, names: &[&str]) -> Vec<Vec<String>> {
+    let (status, list) = app.get("/api/documents", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, search) = app.get("/api/documents/search?q=keyword", None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, recent) = app.get("/api/documents/recent", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let mut readable = Vec::new();
+    for name in names {
+        let (status, _) = app.get(&format!("/api/documents/{}", name), None).await;
+        match status {
+            StatusCode::OK => readable.push(name.to_string()),
+            StatusCode::UNAUTHORIZED => {}
+            other => panic!("unexpected status {} for {}", other, name),
+        }
+    }
+    vec![filenames(&list, "documents"), filenames(&search, "results"), filenames(&recent, "documents"), readable]
+}
+
+#[tokio::test]
+async fn anonymous_users_see_nothing_in_a_private_wiki() {
+    let app = TestApp::new().await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    create_document(&app, &admin, "notes").await;
+    create_document(&app, &admin, "guide").await;
+
+    for names in anonymous_view(&app, &["notes", "guide"]).await {
+        assert!(names.is_empty(), "visible to anonymous users: {:?}", names);
+    }
+
+    // 公開を明示したドキュメントだけは見える
+    let (status, _) = app.put("/api/documents/guide/visibility", Some(&admin), json!({ "visibility": "public" })).await;
+    assert_eq!(status, StatusCode::OK);
+    for names in anonymous_view(&app, &["notes", "guide"]).await {
+        assert_eq!(names, vec!["guide"]);
+    }
+}
+
+#[tokio::test]
+async fn anonymous_users_cannot_see_private_or_acl_documents_in_a_public_wiki() {
+    let mut config = test_config();
+    config.wiki_mode = Visibility::Public;
+    let app = TestApp::with_config(config).await;
+    let admin = app.login_as("admin", Role::Admin).await;
+    for name in ["open", "private", "restricted"] {
+        create_document(&app, &admin, name).await;
+    }
+    let names = ["open", "private", "restricted"];
+
+    for visible in anonymous_view(&app, &names).await {
+        assert_eq!(visible, vec!["open", "private", "restricted"]);
+    }
+
+    let (status, _) = app.put("/api/documents/private/visibility", Some(&admin), json!({ "visibility": "private" })).await;
+    assert_eq!(status, StatusCode::OK);
+    // 閲覧を許可するエントリでも、ACLがあれば未ログインのユーザーには見えない
+    let entries = json!({ "entries": [{ "user": "admin", "permission": "read" }] });
+    let (status, _) = app.put("/api/documents/restricted/acl", Some(&admin), entries).await;
+    assert_eq!(status, StatusCode::OK);
+
+    for visible in anonymous_view(&app, &names).await {
+        assert_eq!(visible, vec!["open"]);
+    }
+}
+
+#[tokio::test]
+async fn recent_documents_are_filled_past_unreadable_ones() {
+    let mut config = test_config();
+    config.wiki_mode = Visibility::Public;
+    let app = TestApp::with_config(config).await;
+    let admin = app.login_as("admin", Role::Admin).await;
+
+    create_document(&app, &admin, "open").await;
+    // 読めないドキュメントが1ページ分より多く新しい側にあっても、古い公開ドキュメントまで読み進める
+    for i in 0..60 {
+        let name = format!("hidden-{}", i);
+        create_document(&app, &admin, &name).await;
+        let (status, _) = app
+            .put(&format!("/api/documents/{}/visibility", name), Some(&admin), json!({ "visibility": "private" }))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, body) = app.get("/api/documents/recent?limit=1", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(filenames(&body, "documents"), vec!["open"]);
+
+    let (_, body) = app.get("/api/documents/recent?limit=5", Some(&admin)).await;
+    assert_eq!(body["documents"].as_array().unwrap().len(), 5);
+}
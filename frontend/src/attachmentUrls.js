@@ -0,0 +1,41 @@
+import { uriTransformer } from 'react-markdown';
+
+const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
+
+// `/api/attachments/{id}`、`/api/attachments/{id}/thumbnail`（`?w=` つきも含む）
+const ATTACHMENT_PATH = /^\/api\/attachments\/(\d+)(\/thumbnail)?$/;
+
+// ドキュメントの添付ファイルの署名付きURLのトークンを ID ごとに取得する
+// <img> は Authorization ヘッダを送れないため、画像はこのトークンをつけたURLで読み込む
+export async function fetchAttachmentTokens(filename) {
+  try {
+    const response = await fetch(`${API_BASE_URL}/api/documents/${filename}/attachments`);
+    if (!response.ok) {
+      return {};
+    }
+    const data = await response.json();
+    const tokens = {};
+    for (const attachment of data.attachments) {
+      tokens[attachment.id] = new URL(attachment.url, API_BASE_URL).searchParams.get('token');
+    }
+    return tokens;
+  } catch (err) {
+    console.error('Error fetching attachments:', err);
+    return {};
+  }
+}
+
+// ReactMarkdown の transformImageUri に渡す関数を作る
+// 添付ファイルへのリンクは署名付きURLに書き換え、それ以外は既定の変換だけを行う
+export function attachmentImageUri(tokens) {
+  return (uri) => {
+    const safe = uriTransformer(uri);
+    const url = new URL(safe, API_BASE_URL);
+    const match = url.origin === new URL(API_BASE_URL).origin && url.pathname.match(ATTACHMENT_PATH);
+    if (!match || !tokens[match[1]]) {
+      return safe;
+    }
+    url.searchParams.set('token', tokens[match[1]]);
+    return url.toString();
+  };
+}
@@ -1,10 +1,13 @@
 import React, { useState, useEffect } from 'react';
 import { Link } from 'react-router-dom';
+import { fetchPage } from '../pagination';
 
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
 
 function DocumentList() {
   const [documents, setDocuments] = useState([]);
+  const [nextCursor, setNextCursor] = useState(null);
+  const [loadingMore, setLoadingMore] = useState(false);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState(null);
   const [newDocName, setNewDocName] = useState('');
@@ -16,12 +19,9 @@ function DocumentList() {
   const fetchDocuments = async () => {
     try {
       setLoading(true);
-      const response = await fetch(`${API_BASE_URL}/api/documents`);
-      if (!response.ok) {
-        throw new Error(`HTTP error! status: ${response.status}`);
-      }
-      const data = await response.json();
-      setDocuments(data.documents);
+      const page = await fetchPage('/api/documents', 'documents');
+      setDocuments(page.items);
+      setNextCursor(page.nextCursor);
       setError(null);
     } catch (err) {
       setError(`Failed to fetch documents: ${err.message}`);
@@ -31,6 +31,22 @@ function DocumentList() {
     }
   };
 
+  // 次のページを取得して一覧の末尾に加える
+  const fetchMoreDocuments = async () => {
+    try {
+      setLoadingMore(true);
+      const page = await fetchPage('/api/documents', 'documents', nextCursor);
+      setDocuments((current) => [...current, ...page.items]);
+      setNextCursor(page.nextCursor);
+      setError(null);
+    } catch (err) {
+      setError(`Failed to fetch documents: ${err.message}`);
+      console.error('Error fetching documents:', err);
+    } finally {
+      setLoadingMore(false);
+    }
+  };
+
   const handleCreateDocument = async (e) => {
     e.preventDefault();
     if (!newDocName.trim()) return;
@@ -84,16 +100,22 @@ function DocumentList() {
       ) : (
         <ul className="documents">
           {documents.map((doc) => (
-            <li key={doc} className="document-item">
-              <span className="document-name">{doc}</span>
+            <li key={doc.filename} className="document-item">
+              <span className="document-name">{doc.title}</span>
               <div className="document-actions">
-                <Link to={`/view/${doc}`} className="view-btn">View</Link>
-                <Link to={`/edit/${doc}`} className="edit-btn">Edit</Link>
+                <Link to={`/view/${doc.filename}`} className="view-btn">View</Link>
+                <Link to={`/edit/${doc.filename}`} className="edit-btn">Edit</Link>
               </div>
             </li>
           ))}
         </ul>
       )}
+
+      {!loading && nextCursor && (
+        <button onClick={fetchMoreDocuments} disabled={loadingMore} className="load-more-btn">
+          {loadingMore ? 'Loading...' : 'Load more'}
+        </button>
+      )}
     </div>
   );
 }
@@ -1,6 +1,7 @@
 import React, { useState, useEffect } from 'react';
 import { useParams, Link } from 'react-router-dom';
 import ReactMarkdown from 'react-markdown';
+import { attachmentImageUri, fetchAttachmentTokens } from '../attachmentUrls';
 import '../styles.css';
 
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
@@ -8,6 +9,7 @@ const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000
 function DocumentVersionViewer() {
   const { filename, commitId } = useParams();
   const [document, setDocument] = useState(null);
+  const [attachmentTokens, setAttachmentTokens] = useState({});
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState(null);
 
@@ -23,6 +25,7 @@ function DocumentVersionViewer() {
         
         const data = await response.json();
         setDocument(data);
+        setAttachmentTokens(await fetchAttachmentTokens(filename));
         setLoading(false);
       } catch (err) {
         setError(`ドキュメントの読み込みに失敗しました: ${err.message}`);
@@ -65,7 +68,7 @@ function DocumentVersionViewer() {
       </div>
       
       <div className="markdown-content">
-        <ReactMarkdown>{document.content}</ReactMarkdown>
+        <ReactMarkdown transformImageUri={attachmentImageUri(attachmentTokens)}>{document.content}</ReactMarkdown>
       </div>
     </div>
   );
@@ -1,6 +1,7 @@
 import React, { useState, useEffect } from 'react';
 import { useParams, useNavigate, Link } from 'react-router-dom';
 import ReactMarkdown from 'react-markdown';
+import { attachmentImageUri, fetchAttachmentTokens } from '../attachmentUrls';
 
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
 
@@ -8,6 +9,7 @@ function DocumentViewer() {
   const { filename } = useParams();
   const navigate = useNavigate();
   const [content, setContent] = useState('');
+  const [attachmentTokens, setAttachmentTokens] = useState({});
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState(null);
 
@@ -26,6 +28,7 @@ function DocumentViewer() {
       
       const data = await response.json();
       setContent(data.content);
+      setAttachmentTokens(await fetchAttachmentTokens(filename));
       setError(null);
     } catch (err) {
       setError(`Failed to fetch document: ${err.message}`);
@@ -67,7 +70,7 @@ function DocumentViewer() {
       </div>
       
       <div className="markdown-content">
-        <ReactMarkdown>{content}</ReactMarkdown>
+        <ReactMarkdown transformImageUri={attachmentImageUri(attachmentTokens)}>{content}</ReactMarkdown>
       </div>
     </div>
   );
@@ -1,5 +1,6 @@
 import React, { useState, useEffect } from 'react';
 import { useParams, useNavigate } from 'react-router-dom';
+import { fetchAllPages } from '../pagination';
 import '../styles.css';
 
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
@@ -27,15 +28,9 @@ function MetadataEditor() {
         // メタデータを取得
         const metaResponse = await fetch(`${API_BASE_URL}/api/documents/${filename}/metadata`);
         
-        // タグ一覧を取得
-        const tagsResponse = await fetch(`${API_BASE_URL}/api/tags`);
-        
-        if (!tagsResponse.ok) {
-          throw new Error(`Failed to fetch tags: ${tagsResponse.status}`);
-        }
-        
-        const tagsData = await tagsResponse.json();
-        setAvailableTags(tagsData.tags || []);
+        // タグ一覧を取得（すべてのページ）
+        const tags = await fetchAllPages('/api/tags', 'tags');
+        setAvailableTags(tags.map((tag) => tag.name));
         
         if (metaResponse.ok) {
           const metaData = await metaResponse.json();
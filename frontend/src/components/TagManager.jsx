@@ -1,5 +1,6 @@
 import React, { useState, useEffect } from 'react';
 import { Link } from 'react-router-dom';
+import { fetchAllPages } from '../pagination';
 import '../styles.css';
 
 const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
@@ -17,14 +18,8 @@ function TagManager() {
     const fetchTags = async () => {
       try {
         setLoading(true);
-        const response = await fetch(`${API_BASE_URL}/api/tags`);
-        
-        if (!response.ok) {
-          throw new Error(`Error: ${response.status}`);
-        }
-        
-        const data = await response.json();
-        setTags(data.tags || []);
+        const tags = await fetchAllPages('/api/tags', 'tags');
+        setTags(tags.map((tag) => tag.name));
         setLoading(false);
       } catch (err) {
         setError(`タグの取得に失敗しました: ${err.message}`);
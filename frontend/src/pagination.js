@@ -0,0 +1,29 @@
+const API_BASE_URL = import.meta.env.VITE_API_BASE_URL || 'http://localhost:3000';
+
+// 一覧APIの1ページ分を取得する
+// `key` はレスポンスで要素の配列が入っている項目（`documents` や `tags`）
+export async function fetchPage(path, key, cursor) {
+  const url = new URL(`${API_BASE_URL}${path}`);
+  if (cursor) {
+    url.searchParams.set('cursor', cursor);
+  }
+
+  const response = await fetch(url);
+  if (!response.ok) {
+    throw new Error(`HTTP error! status: ${response.status}`);
+  }
+  const data = await response.json();
+  return { items: data[key] || [], nextCursor: data.next_cursor || null };
+}
+
+// `next_cursor` がなくなるまでたどり、すべてのページの要素を集める
+export async function fetchAllPages(path, key) {
+  const items = [];
+  let cursor = null;
+  do {
+    const page = await fetchPage(path, key, cursor);
+    items.push(...page.items);
+    cursor = page.nextCursor;
+  } while (cursor);
+  return items;
+}